@@ -0,0 +1,12 @@
+-- Migration 013: IRC SASL authentication
+-- Binds TLS client certificate fingerprints to users for SASL EXTERNAL
+
+CREATE TABLE IF NOT EXISTS irc_cert_fingerprints (
+    id          TEXT PRIMARY KEY,
+    user_id     TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    fingerprint TEXT NOT NULL UNIQUE,
+    label       TEXT,
+    last_used   TEXT,
+    created_at  TEXT NOT NULL DEFAULT (datetime('now'))
+);
+CREATE INDEX IF NOT EXISTS idx_irc_cert_fingerprints_user ON irc_cert_fingerprints(user_id);
@@ -0,0 +1,4 @@
+-- Migration 014: Per-server message of the day
+-- Overrides the instance MOTD for IRC clients asking about this server
+
+ALTER TABLE servers ADD COLUMN motd TEXT;
@@ -0,0 +1,4 @@
+-- Migration 015: Message kinds
+-- Distinguishes /me actions and notices from ordinary messages
+
+ALTER TABLE messages ADD COLUMN kind TEXT NOT NULL DEFAULT 'normal';
@@ -0,0 +1,22 @@
+-- Migration 016: Always-on IRC presence
+-- Accounts can stay connected while none of their clients are, and each IRC
+-- client keeps its own read markers so it can be replayed what it missed
+
+ALTER TABLE users ADD COLUMN irc_always_on INTEGER NOT NULL DEFAULT 0;
+
+-- Read markers are kept per client; '' is the account's own marker (MarkRead)
+CREATE TABLE read_states_v2 (
+    user_id              TEXT NOT NULL,
+    client               TEXT NOT NULL DEFAULT '',
+    channel_id           TEXT NOT NULL,
+    last_read_message_id TEXT,
+    last_read_at         TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (user_id, client, channel_id)
+);
+
+INSERT INTO read_states_v2 (user_id, client, channel_id, last_read_message_id, last_read_at)
+    SELECT user_id, '', channel_id, last_read_message_id, last_read_at FROM read_states;
+
+DROP TABLE read_states;
+
+ALTER TABLE read_states_v2 RENAME TO read_states;
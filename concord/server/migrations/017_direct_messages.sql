@@ -0,0 +1,59 @@
+-- Migration 017: Direct message conversations
+-- DMs between accounts belong to a conversation keyed by the two user IDs, so
+-- they can be listed, read back and counted unread whether or not either side
+-- is online. DMs involving guests are still stored, without a conversation.
+
+CREATE TABLE IF NOT EXISTS dm_conversations (
+    id              TEXT PRIMARY KEY,
+    -- The two user IDs, sorted and joined with a space: one conversation per pair
+    pair_key        TEXT NOT NULL UNIQUE,
+    created_at      TEXT NOT NULL DEFAULT (datetime('now')),
+    last_message_at TEXT
+);
+
+-- Each participant's read position in a conversation
+CREATE TABLE IF NOT EXISTS dm_participants (
+    conversation_id      TEXT NOT NULL REFERENCES dm_conversations(id) ON DELETE CASCADE,
+    user_id              TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    last_read_message_id TEXT,
+    PRIMARY KEY (conversation_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_dm_participants_user ON dm_participants(user_id);
+
+ALTER TABLE messages ADD COLUMN dm_conversation_id TEXT REFERENCES dm_conversations(id) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS idx_messages_dm_conversation ON messages(dm_conversation_id, created_at)
+    WHERE dm_conversation_id IS NOT NULL;
+
+-- Gather earlier DMs between accounts into conversations
+INSERT INTO dm_conversations (id, pair_key, created_at, last_message_at)
+    SELECT lower(hex(randomblob(16))), pair_key, MIN(created_at), MAX(created_at)
+    FROM (
+        SELECT min(sender_id, target_user_id) || ' ' || max(sender_id, target_user_id) AS pair_key,
+               created_at
+        FROM messages
+        WHERE channel_id IS NULL AND target_user_id IS NOT NULL
+          AND sender_id IN (SELECT id FROM users)
+          AND target_user_id IN (SELECT id FROM users)
+    )
+    GROUP BY pair_key;
+
+UPDATE messages SET dm_conversation_id = (
+    SELECT id FROM dm_conversations
+    WHERE pair_key = min(messages.sender_id, messages.target_user_id) || ' ' ||
+                     max(messages.sender_id, messages.target_user_id)
+)
+WHERE channel_id IS NULL AND target_user_id IS NOT NULL;
+
+-- Both sides start out having read everything sent before the upgrade
+INSERT OR IGNORE INTO dm_participants (conversation_id, user_id)
+    SELECT dm_conversation_id, sender_id FROM messages WHERE dm_conversation_id IS NOT NULL
+    UNION
+    SELECT dm_conversation_id, target_user_id FROM messages WHERE dm_conversation_id IS NOT NULL;
+
+UPDATE dm_participants SET last_read_message_id = (
+    SELECT id FROM messages
+    WHERE dm_conversation_id = dm_participants.conversation_id
+    ORDER BY created_at DESC, rowid DESC LIMIT 1
+);
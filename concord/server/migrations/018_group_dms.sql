@@ -0,0 +1,8 @@
+-- Migration 018: Group direct messages
+-- A group conversation has an owner, an optional name and a changing set of
+-- participants. Its pair_key is `group:<id>`, which no pair of user IDs can
+-- produce. Group messages are stored without a target_user_id.
+
+ALTER TABLE dm_conversations ADD COLUMN is_group INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE dm_conversations ADD COLUMN name TEXT;
+ALTER TABLE dm_conversations ADD COLUMN owner_id TEXT REFERENCES users(id) ON DELETE SET NULL;
@@ -0,0 +1,18 @@
+-- Migration 019: Mentions inbox
+-- One row per user per message that mentions them, by nickname, by one of
+-- their roles or with @everyone. Rows are only written when the user's
+-- notification settings let the mention through.
+
+CREATE TABLE IF NOT EXISTS mentions (
+    id          TEXT PRIMARY KEY,
+    user_id     TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    message_id  TEXT NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    server_id   TEXT NOT NULL REFERENCES servers(id) ON DELETE CASCADE,
+    channel_id  TEXT NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    kind        TEXT NOT NULL CHECK(kind IN ('user', 'role', 'everyone')),
+    created_at  TEXT NOT NULL DEFAULT (datetime('now')),
+    read_at     TEXT,
+    UNIQUE(user_id, message_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_mentions_user ON mentions(user_id, created_at);
@@ -0,0 +1,14 @@
+-- Migration 020: Message edit history
+-- Editing a message keeps what it said before: each row is one earlier
+-- version of a message, with when it was written and when it was replaced.
+-- The current version stays in messages.content.
+
+CREATE TABLE IF NOT EXISTS message_revisions (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    message_id  TEXT NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    content     TEXT NOT NULL,
+    written_at  TEXT NOT NULL,
+    replaced_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_message_revisions_message ON message_revisions(message_id, id);
@@ -74,6 +74,13 @@ pub fn verify_irc_token(token: &str, hash: &str) -> bool {
         .is_ok()
 }
 
+/// SHA-256 fingerprint of a DER-encoded certificate as lowercase hex,
+/// the form stored for SASL EXTERNAL bindings.
+pub fn cert_fingerprint(der: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    hex_encode(&Sha256::digest(der))
+}
+
 fn hex_encode(bytes: &[u8]) -> String {
     bytes.iter().map(|b| format!("{:02x}", b)).collect()
 }
@@ -111,6 +118,14 @@ mod tests {
         assert!(!verify_irc_token("wrong-token", &hash));
     }
 
+    #[test]
+    fn test_cert_fingerprint_is_sha256_hex() {
+        let fp = cert_fingerprint(b"not really a certificate");
+        assert_eq!(fp.len(), 64);
+        assert!(fp.chars().all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase()));
+        assert_eq!(fp, cert_fingerprint(b"not really a certificate"));
+    }
+
     // ── Additional JWT tests ──
 
     #[test]
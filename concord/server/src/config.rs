@@ -1,5 +1,6 @@
 use serde::Deserialize;
 use std::path::Path;
+use std::time::Duration;
 use tracing::info;
 
 use crate::auth::config::AuthConfig;
@@ -13,6 +14,7 @@ pub struct ServerConfig {
     pub auth: AuthSection,
     pub storage: StorageSection,
     pub admin: AdminSection,
+    pub irc: IrcSection,
 }
 
 #[derive(Deserialize, Default)]
@@ -31,6 +33,11 @@ pub struct ServerSection {
     pub irc_tls_cert: Option<String>,
     /// Path to TLS private key file (PEM) for IRC.
     pub irc_tls_key: Option<String>,
+    /// Message of the day shown to IRC clients. Servers can set their own.
+    pub motd: Option<String>,
+    /// Reverse proxies (IP addresses or CIDR ranges) whose `Forwarded` and
+    /// `X-Forwarded-For` headers are believed for the client's address.
+    pub trusted_proxies: Vec<String>,
 }
 
 impl Default for ServerSection {
@@ -40,10 +47,91 @@ impl Default for ServerSection {
             irc_address: "0.0.0.0:6667".into(),
             irc_tls_cert: None,
             irc_tls_key: None,
+            motd: None,
+            trusted_proxies: vec!["127.0.0.1".into(), "::1".into()],
         }
     }
 }
 
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct IrcSection {
+    /// Network name shown in the welcome message and RPL_ISUPPORT.
+    pub network_name: String,
+    /// Hostname the server sends its own messages from.
+    pub server_name: String,
+    /// Most connections one IP address may hold open at once (0 for no limit).
+    pub max_connections_per_ip: usize,
+    /// Seconds a new connection has to finish registering.
+    pub registration_timeout_secs: u64,
+    /// Most channels an IRC client may be in at once (0 for no limit).
+    pub max_channels_per_user: usize,
+    /// Seconds without hearing from a client before it is sent a PING.
+    pub ping_interval_secs: u64,
+    /// Seconds a client has to answer a PING before it is disconnected.
+    pub ping_timeout_secs: u64,
+    /// Longest line a client may send, in bytes (at least 512, as RFC 1459
+    /// allows any client to send lines that long).
+    pub max_line_length: usize,
+    /// Whether every connection starts with a HAProxy PROXY protocol header
+    /// (v1 or v2) giving the client's real address.
+    pub proxy_protocol: bool,
+}
+
+impl Default for IrcSection {
+    fn default() -> Self {
+        Self {
+            network_name: "Concord".into(),
+            server_name: "concord".into(),
+            max_connections_per_ip: 10,
+            registration_timeout_secs: 60,
+            max_channels_per_user: 100,
+            ping_interval_secs: 120,
+            ping_timeout_secs: 60,
+            max_line_length: 4096,
+            proxy_protocol: false,
+        }
+    }
+}
+
+/// The shortest `max_line_length` allowed.
+const MIN_LINE_LENGTH: usize = 512;
+
+impl IrcSection {
+    /// Check the settings the IRC listener can't run with: a zero timeout or
+    /// keepalive interval would drop or PING every client at once, and a
+    /// short line limit would refuse registration itself.
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, secs) in [
+            ("registration_timeout_secs", self.registration_timeout_secs),
+            ("ping_interval_secs", self.ping_interval_secs),
+            ("ping_timeout_secs", self.ping_timeout_secs),
+        ] {
+            if secs == 0 {
+                return Err(format!("irc.{name} must be at least 1"));
+            }
+        }
+        if self.max_line_length < MIN_LINE_LENGTH {
+            return Err(format!(
+                "irc.max_line_length must be at least {MIN_LINE_LENGTH}"
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn registration_timeout(&self) -> Duration {
+        Duration::from_secs(self.registration_timeout_secs)
+    }
+
+    pub fn ping_interval(&self) -> Duration {
+        Duration::from_secs(self.ping_interval_secs)
+    }
+
+    pub fn ping_timeout(&self) -> Duration {
+        Duration::from_secs(self.ping_timeout_secs)
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(default)]
 pub struct DatabaseSection {
@@ -105,6 +193,9 @@ impl ServerConfig {
         };
 
         config.apply_env_overrides();
+        if let Err(e) = config.irc.validate() {
+            panic!("invalid config in {}: {}", path, e);
+        }
         config
     }
 
@@ -121,6 +212,32 @@ impl ServerConfig {
         if let Ok(v) = std::env::var("IRC_TLS_KEY") {
             self.server.irc_tls_key = Some(v);
         }
+        if let Ok(v) = std::env::var("MOTD") {
+            self.server.motd = Some(v);
+        }
+        if let Ok(v) = std::env::var("TRUSTED_PROXIES") {
+            self.server.trusted_proxies = v
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect();
+        }
+        if let Ok(v) = std::env::var("IRC_NETWORK_NAME") {
+            self.irc.network_name = v;
+        }
+        if let Ok(v) = std::env::var("IRC_SERVER_NAME") {
+            self.irc.server_name = v;
+        }
+        if let Ok(v) = std::env::var("IRC_MAX_CONNECTIONS_PER_IP")
+            && let Ok(max) = v.parse()
+        {
+            self.irc.max_connections_per_ip = max;
+        }
+        if let Ok(v) = std::env::var("IRC_PROXY_PROTOCOL")
+            && let Ok(enabled) = v.parse()
+        {
+            self.irc.proxy_protocol = enabled;
+        }
         if let Ok(v) = std::env::var("DATABASE_URL") {
             self.database.url = v;
         }
@@ -158,3 +275,33 @@ impl ServerConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn irc(toml: &str) -> IrcSection {
+        toml::from_str::<ServerConfig>(toml).unwrap().irc
+    }
+
+    #[test]
+    fn test_default_irc_settings_are_valid() {
+        assert!(IrcSection::default().validate().is_ok());
+        assert!(irc("[irc]\nmax_connections_per_ip = 0").validate().is_ok());
+    }
+
+    #[test]
+    fn test_irc_settings_reject_zero_timeouts_and_short_lines() {
+        for (toml, field) in [
+            ("ping_interval_secs = 0", "ping_interval_secs"),
+            ("ping_timeout_secs = 0", "ping_timeout_secs"),
+            ("registration_timeout_secs = 0", "registration_timeout_secs"),
+            ("max_line_length = 0", "max_line_length"),
+            ("max_line_length = 511", "max_line_length"),
+        ] {
+            let err = irc(&format!("[irc]\n{toml}")).validate().unwrap_err();
+            assert!(err.contains(field), "{err}");
+        }
+        assert!(irc("[irc]\nmax_line_length = 512").validate().is_ok());
+    }
+}
@@ -14,6 +14,7 @@ pub struct ServerRow {
     pub welcome_message: Option<String>,
     pub rules_text: Option<String>,
     pub category: Option<String>,
+    pub motd: Option<String>,
 }
 
 /// A server membership record.
@@ -39,6 +40,8 @@ pub struct MessageRow {
     pub edited_at: Option<String>,
     pub deleted_at: Option<String>,
     pub reply_to_id: Option<String>,
+    /// "normal", "action" (`/me`) or "notice".
+    pub kind: String,
 }
 
 /// A stored channel from the database.
@@ -198,6 +201,14 @@ pub struct ThreadTagRow {
     pub tag_id: String,
 }
 
+/// An earlier version of an edited message.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct MessageRevisionRow {
+    pub content: String,
+    pub written_at: String,
+    pub replaced_at: String,
+}
+
 /// A personal bookmark on a message.
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct BookmarkRow {
@@ -208,6 +219,50 @@ pub struct BookmarkRow {
     pub created_at: String,
 }
 
+/// A direct message conversation, with how much of it a participant hasn't read.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct DmConversationRow {
+    pub id: String,
+    pub is_group: bool,
+    pub name: Option<String>,
+    pub owner_id: Option<String>,
+    pub created_at: String,
+    pub last_message_at: Option<String>,
+    pub unread_count: i64,
+}
+
+/// A group direct message conversation.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct GroupDmRow {
+    pub id: String,
+    pub name: Option<String>,
+    pub owner_id: Option<String>,
+}
+
+/// Someone in a direct message conversation.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct DmParticipantRow {
+    pub conversation_id: String,
+    pub user_id: String,
+    pub username: String,
+    pub avatar_url: Option<String>,
+}
+
+/// A mention of a user in their inbox, with the message it came from.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct MentionRow {
+    pub id: String,
+    pub message_id: String,
+    pub server_id: String,
+    pub channel_id: String,
+    pub channel_name: String,
+    pub sender_nick: String,
+    pub content: String,
+    pub kind: String,
+    pub created_at: String,
+    pub read_at: Option<String>,
+}
+
 /// A server ban record.
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct BanRow {
@@ -118,6 +118,14 @@ pub async fn run_migrations(pool: &SqlitePool) -> Result<(), sqlx::Error> {
         (10, include_str!("../../migrations/010_moderation.sql")),
         (11, include_str!("../../migrations/011_community.sql")),
         (12, include_str!("../../migrations/012_integrations.sql")),
+        (13, include_str!("../../migrations/013_irc_sasl.sql")),
+        (14, include_str!("../../migrations/014_server_motd.sql")),
+        (15, include_str!("../../migrations/015_message_kind.sql")),
+        (16, include_str!("../../migrations/016_always_on.sql")),
+        (17, include_str!("../../migrations/017_direct_messages.sql")),
+        (18, include_str!("../../migrations/018_group_dms.sql")),
+        (19, include_str!("../../migrations/019_mentions.sql")),
+        (20, include_str!("../../migrations/020_message_revisions.sql")),
     ];
 
     for &(version, sql) in migrations {
@@ -259,7 +267,7 @@ END;";
             .fetch_one(&pool)
             .await
             .unwrap();
-        assert_eq!(count, 12);
+        assert_eq!(count, 20);
 
         // Running again should not duplicate (INSERT OR IGNORE)
         run_migrations(&pool).await.unwrap();
@@ -268,7 +276,7 @@ END;";
             .fetch_one(&pool)
             .await
             .unwrap();
-        assert_eq!(count_after, 12, "No duplicate version rows after re-run");
+        assert_eq!(count_after, 20, "No duplicate version rows after re-run");
     }
 
     #[tokio::test]
@@ -320,10 +328,10 @@ END;";
                 .fetch_all(&pool)
                 .await
                 .unwrap();
-        let expected: Vec<i64> = (1..=12).collect();
+        let expected: Vec<i64> = (1..=20).collect();
         assert_eq!(
             versions, expected,
-            "Migration versions should be 1 through 12"
+            "Migration versions should be 1 through 20"
         );
     }
 }
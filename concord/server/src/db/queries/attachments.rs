@@ -266,6 +266,7 @@ mod tests {
                 sender_nick: "alice",
                 content: "See attachments",
                 reply_to_id: None,
+                kind: "normal",
             },
         )
         .await
@@ -296,6 +297,7 @@ mod tests {
                 sender_nick: "alice",
                 content: "Test",
                 reply_to_id: None,
+                kind: "normal",
             },
         )
         .await
@@ -349,6 +351,7 @@ mod tests {
                 sender_nick: "alice",
                 content: "Test",
                 reply_to_id: None,
+                kind: "normal",
             },
         )
         .await
@@ -412,6 +415,7 @@ mod tests {
                 sender_nick: "alice",
                 content: "Test",
                 reply_to_id: None,
+                kind: "normal",
             },
         )
         .await
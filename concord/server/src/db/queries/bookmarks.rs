@@ -110,6 +110,7 @@ mod tests {
                 sender_nick: "alice",
                 content: "Bookmark me",
                 reply_to_id: None,
+                kind: "normal",
             },
         )
         .await
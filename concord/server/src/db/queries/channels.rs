@@ -227,6 +227,21 @@ pub async fn get_channel_overrides(
     .await
 }
 
+/// Get the overrides a server's channels set for its @everyone role.
+pub async fn get_default_role_overrides(
+    pool: &SqlitePool,
+    server_id: &str,
+) -> Result<Vec<crate::db::models::ChannelPermissionOverrideRow>, sqlx::Error> {
+    sqlx::query_as::<_, crate::db::models::ChannelPermissionOverrideRow>(
+        "SELECT o.* FROM channel_permission_overrides o \
+         JOIN roles r ON r.id = o.target_id \
+         WHERE o.target_type = 'role' AND r.server_id = ? AND r.is_default = 1",
+    )
+    .bind(server_id)
+    .fetch_all(pool)
+    .await
+}
+
 /// Set (upsert) a channel permission override.
 pub async fn set_channel_override(
     pool: &SqlitePool,
@@ -544,6 +559,47 @@ mod tests {
         assert!(overrides.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_default_role_overrides() {
+        use crate::db::queries::roles::{self, CreateRoleParams};
+
+        let pool = setup_db().await;
+        create_test_user(&pool, "u1", "alice").await;
+        create_test_server(&pool, "s1", "u1").await;
+        ensure_channel(&pool, "c1", "s1", "#general").await.unwrap();
+        for (id, name, is_default) in [("r-everyone", "@everyone", true), ("r-mod", "Mod", false)] {
+            roles::create_role(
+                &pool,
+                &CreateRoleParams {
+                    id,
+                    server_id: "s1",
+                    name,
+                    color: None,
+                    icon_url: None,
+                    position: 0,
+                    permissions: 0,
+                    is_default,
+                },
+            )
+            .await
+            .unwrap();
+        }
+        set_channel_override(&pool, "o1", "c1", "role", "r-everyone", 0, 0x2)
+            .await
+            .unwrap();
+        set_channel_override(&pool, "o2", "c1", "role", "r-mod", 0x2, 0)
+            .await
+            .unwrap();
+        set_channel_override(&pool, "o3", "c1", "user", "u1", 0x2, 0)
+            .await
+            .unwrap();
+
+        let overrides = get_default_role_overrides(&pool, "s1").await.unwrap();
+        assert_eq!(overrides.len(), 1);
+        assert_eq!(overrides[0].target_id, "r-everyone");
+        assert!(get_default_role_overrides(&pool, "s2").await.unwrap().is_empty());
+    }
+
     #[tokio::test]
     async fn test_get_user_channels() {
         let pool = setup_db().await;
@@ -0,0 +1,598 @@
+use sqlx::SqlitePool;
+
+use crate::db::models::{DmConversationRow, DmParticipantRow, GroupDmRow};
+
+/// The key naming the one conversation between two users, whichever way round
+/// they're given.
+pub fn pair_key(user_a: &str, user_b: &str) -> String {
+    if user_a <= user_b {
+        format!("{user_a} {user_b}")
+    } else {
+        format!("{user_b} {user_a}")
+    }
+}
+
+/// Find the conversation between two users, creating it (as `new_id`) if
+/// they've never exchanged messages. Returns the conversation's ID.
+pub async fn get_or_create_direct_conversation(
+    pool: &SqlitePool,
+    new_id: &str,
+    user_a: &str,
+    user_b: &str,
+) -> Result<String, sqlx::Error> {
+    let key = pair_key(user_a, user_b);
+    sqlx::query("INSERT OR IGNORE INTO dm_conversations (id, pair_key) VALUES (?, ?)")
+        .bind(new_id)
+        .bind(&key)
+        .execute(pool)
+        .await?;
+    let id: String = sqlx::query_scalar("SELECT id FROM dm_conversations WHERE pair_key = ?")
+        .bind(&key)
+        .fetch_one(pool)
+        .await?;
+    for user_id in [user_a, user_b] {
+        sqlx::query(
+            "INSERT OR IGNORE INTO dm_participants (conversation_id, user_id) VALUES (?, ?)",
+        )
+        .bind(&id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    }
+    Ok(id)
+}
+
+/// The conversation between two users, if there is one.
+pub async fn find_direct_conversation(
+    pool: &SqlitePool,
+    user_a: &str,
+    user_b: &str,
+) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT id FROM dm_conversations WHERE pair_key = ?")
+        .bind(pair_key(user_a, user_b))
+        .fetch_optional(pool)
+        .await
+}
+
+/// Parameters for storing a message in a conversation (avoids too-many-arguments).
+pub struct InsertDirectMessageParams<'a> {
+    pub id: &'a str,
+    pub conversation_id: &'a str,
+    pub sender_id: &'a str,
+    pub sender_nick: &'a str,
+    /// The other side of a one-to-one conversation; `None` in a group.
+    pub target_user_id: Option<&'a str>,
+    pub content: &'a str,
+    pub kind: &'a str,
+}
+
+/// Store a message in a conversation. The conversation's activity time moves
+/// up to it, and the sender has read everything up to their own message; all
+/// three happen together or not at all.
+pub async fn insert_direct_message(
+    pool: &SqlitePool,
+    p: &InsertDirectMessageParams<'_>,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "INSERT INTO messages \
+         (id, sender_id, sender_nick, target_user_id, content, kind, dm_conversation_id) \
+         VALUES (?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(p.id)
+    .bind(p.sender_id)
+    .bind(p.sender_nick)
+    .bind(p.target_user_id)
+    .bind(p.content)
+    .bind(p.kind)
+    .bind(p.conversation_id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        "UPDATE dm_conversations SET last_message_at = \
+         (SELECT created_at FROM messages WHERE id = ?) WHERE id = ?",
+    )
+    .bind(p.id)
+    .bind(p.conversation_id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        "UPDATE dm_participants SET last_read_message_id = ? \
+         WHERE conversation_id = ? AND user_id = ?",
+    )
+    .bind(p.id)
+    .bind(p.conversation_id)
+    .bind(p.sender_id)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await
+}
+
+/// Record that a participant has read a conversation up to a message. A
+/// marker already past the message stays where it is. Returns false if the
+/// message isn't in the conversation.
+pub async fn mark_conversation_read(
+    pool: &SqlitePool,
+    conversation_id: &str,
+    user_id: &str,
+    message_id: &str,
+) -> Result<bool, sqlx::Error> {
+    let Some(rowid) = sqlx::query_scalar::<_, i64>(
+        "SELECT rowid FROM messages WHERE id = ? AND dm_conversation_id = ?",
+    )
+    .bind(message_id)
+    .bind(conversation_id)
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(false);
+    };
+    sqlx::query(
+        "UPDATE dm_participants SET last_read_message_id = ? \
+         WHERE conversation_id = ? AND user_id = ? \
+           AND ? > COALESCE((SELECT rowid FROM messages WHERE id = last_read_message_id), 0)",
+    )
+    .bind(message_id)
+    .bind(conversation_id)
+    .bind(user_id)
+    .bind(rowid)
+    .execute(pool)
+    .await?;
+    Ok(true)
+}
+
+/// A user's conversations, most recently active first, each with how many
+/// messages from the others in it the user hasn't read.
+pub async fn list_conversations(
+    pool: &SqlitePool,
+    user_id: &str,
+) -> Result<Vec<DmConversationRow>, sqlx::Error> {
+    sqlx::query_as::<_, DmConversationRow>(
+        "SELECT c.id, c.is_group, c.name, c.owner_id, c.created_at, c.last_message_at, \
+         (SELECT COUNT(*) FROM messages m \
+          WHERE m.dm_conversation_id = c.id AND m.deleted_at IS NULL \
+            AND m.sender_id <> p.user_id \
+            AND (p.last_read_message_id IS NULL OR m.rowid > COALESCE( \
+              (SELECT rowid FROM messages WHERE id = p.last_read_message_id), 0))) \
+         AS unread_count \
+         FROM dm_conversations c \
+         JOIN dm_participants p ON p.conversation_id = c.id AND p.user_id = ? \
+         ORDER BY COALESCE(c.last_message_at, c.created_at) DESC",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Start a group conversation owned by `owner_id`. The owner and every member
+/// become participants, in that order.
+pub async fn create_group_conversation(
+    pool: &SqlitePool,
+    id: &str,
+    owner_id: &str,
+    name: Option<&str>,
+    member_ids: &[String],
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "INSERT INTO dm_conversations (id, pair_key, is_group, name, owner_id) \
+         VALUES (?, 'group:' || ?, 1, ?, ?)",
+    )
+    .bind(id)
+    .bind(id)
+    .bind(name)
+    .bind(owner_id)
+    .execute(&mut *tx)
+    .await?;
+    for user_id in std::iter::once(owner_id).chain(member_ids.iter().map(String::as_str)) {
+        sqlx::query(
+            "INSERT OR IGNORE INTO dm_participants (conversation_id, user_id) VALUES (?, ?)",
+        )
+        .bind(id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await
+}
+
+/// A group conversation by ID. One-to-one conversations aren't returned.
+pub async fn get_group_conversation(
+    pool: &SqlitePool,
+    id: &str,
+) -> Result<Option<GroupDmRow>, sqlx::Error> {
+    sqlx::query_as::<_, GroupDmRow>(
+        "SELECT id, name, owner_id FROM dm_conversations WHERE id = ? AND is_group = 1",
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// The user IDs in a conversation, in the order they joined it.
+pub async fn participant_ids(
+    pool: &SqlitePool,
+    conversation_id: &str,
+) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT user_id FROM dm_participants WHERE conversation_id = ? ORDER BY rowid",
+    )
+    .bind(conversation_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Add someone to a conversation holding fewer than `max_participants`
+/// people. They start out having read everything already in it. Returns
+/// false if they were already there or it was full.
+pub async fn add_participant(
+    pool: &SqlitePool,
+    conversation_id: &str,
+    user_id: &str,
+    max_participants: usize,
+) -> Result<bool, sqlx::Error> {
+    // One statement, so concurrent adds can't both see room for one more
+    let result = sqlx::query(
+        "INSERT OR IGNORE INTO dm_participants (conversation_id, user_id, last_read_message_id) \
+         SELECT ?, ?, (SELECT id FROM messages WHERE dm_conversation_id = ? \
+                       ORDER BY created_at DESC, rowid DESC LIMIT 1) \
+         WHERE (SELECT COUNT(*) FROM dm_participants WHERE conversation_id = ?) < ?",
+    )
+    .bind(conversation_id)
+    .bind(user_id)
+    .bind(conversation_id)
+    .bind(conversation_id)
+    .bind(max_participants as i64)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Take someone out of a conversation. Returns false if they weren't in it.
+pub async fn remove_participant(
+    pool: &SqlitePool,
+    conversation_id: &str,
+    user_id: &str,
+) -> Result<bool, sqlx::Error> {
+    let result =
+        sqlx::query("DELETE FROM dm_participants WHERE conversation_id = ? AND user_id = ?")
+            .bind(conversation_id)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Set or clear a group conversation's name.
+pub async fn rename_conversation(
+    pool: &SqlitePool,
+    conversation_id: &str,
+    name: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE dm_conversations SET name = ? WHERE id = ?")
+        .bind(name)
+        .bind(conversation_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Hand a group conversation to a new owner.
+pub async fn set_owner(
+    pool: &SqlitePool,
+    conversation_id: &str,
+    owner_id: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE dm_conversations SET owner_id = ? WHERE id = ?")
+        .bind(owner_id)
+        .bind(conversation_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Delete a conversation along with its messages.
+pub async fn delete_conversation(
+    pool: &SqlitePool,
+    conversation_id: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM dm_conversations WHERE id = ?")
+        .bind(conversation_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// The group conversations a user is in that saw messages strictly between
+/// `after` and `before`, with the time of the latest one.
+pub async fn fetch_group_activity(
+    pool: &SqlitePool,
+    user_id: &str,
+    after: &str,
+    before: &str,
+) -> Result<Vec<(String, String)>, sqlx::Error> {
+    sqlx::query_as::<_, (String, String)>(
+        "SELECT m.dm_conversation_id, MAX(m.created_at) FROM messages m \
+         JOIN dm_conversations c ON c.id = m.dm_conversation_id AND c.is_group = 1 \
+         JOIN dm_participants p ON p.conversation_id = c.id AND p.user_id = ? \
+         WHERE m.deleted_at IS NULL AND m.created_at > ? AND m.created_at < ? \
+         GROUP BY m.dm_conversation_id",
+    )
+    .bind(user_id)
+    .bind(after)
+    .bind(before)
+    .fetch_all(pool)
+    .await
+}
+
+/// Everyone in the given conversations, with their usernames and avatars.
+pub async fn get_participants(
+    pool: &SqlitePool,
+    conversation_ids: &[String],
+) -> Result<Vec<DmParticipantRow>, sqlx::Error> {
+    if conversation_ids.is_empty() {
+        return Ok(vec![]);
+    }
+    let placeholders: Vec<&str> = conversation_ids.iter().map(|_| "?").collect();
+    let sql = format!(
+        "SELECT p.conversation_id, p.user_id, u.username, u.avatar_url \
+         FROM dm_participants p JOIN users u ON u.id = p.user_id \
+         WHERE p.conversation_id IN ({}) ORDER BY u.username",
+        placeholders.join(", ")
+    );
+    let mut query = sqlx::query_as::<_, DmParticipantRow>(&sql);
+    for id in conversation_ids {
+        query = query.bind(id);
+    }
+    query.fetch_all(pool).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::pool::{create_pool, run_migrations};
+    use crate::db::queries::users::{self, CreateOAuthUser};
+
+    async fn setup_db() -> SqlitePool {
+        let pool = create_pool("sqlite::memory:").await.unwrap();
+        run_migrations(&pool).await.unwrap();
+        for (id, name) in [("u1", "alice"), ("u2", "bob"), ("u3", "carol")] {
+            users::create_with_oauth(
+                &pool,
+                &CreateOAuthUser {
+                    user_id: id,
+                    username: name,
+                    email: None,
+                    avatar_url: None,
+                    oauth_id: &format!("oauth-{id}"),
+                    provider: "github",
+                    provider_id: &format!("gh-{id}"),
+                },
+            )
+            .await
+            .unwrap();
+        }
+        pool
+    }
+
+    async fn send(pool: &SqlitePool, conversation_id: &str, id: &str, from: &str, to: &str) {
+        insert_direct_message(
+            pool,
+            &InsertDirectMessageParams {
+                id,
+                conversation_id,
+                sender_id: from,
+                sender_nick: from,
+                target_user_id: Some(to),
+                content: "hi",
+                kind: "normal",
+            },
+        )
+        .await
+        .unwrap();
+    }
+
+    #[test]
+    fn test_pair_key_is_symmetric() {
+        assert_eq!(pair_key("u1", "u2"), pair_key("u2", "u1"));
+        assert_ne!(pair_key("u1", "u2"), pair_key("u1", "u3"));
+    }
+
+    #[tokio::test]
+    async fn test_one_conversation_per_pair() {
+        let pool = setup_db().await;
+        let id = get_or_create_direct_conversation(&pool, "c1", "u1", "u2")
+            .await
+            .unwrap();
+        assert_eq!(id, "c1");
+        let again = get_or_create_direct_conversation(&pool, "c2", "u2", "u1")
+            .await
+            .unwrap();
+        assert_eq!(again, "c1");
+        assert_eq!(
+            find_direct_conversation(&pool, "u2", "u1").await.unwrap(),
+            Some("c1".into())
+        );
+        assert_eq!(
+            find_direct_conversation(&pool, "u1", "u3").await.unwrap(),
+            None
+        );
+
+        let participants = get_participants(&pool, &["c1".into()]).await.unwrap();
+        let names: Vec<&str> = participants.iter().map(|p| p.username.as_str()).collect();
+        assert_eq!(names, vec!["alice", "bob"]);
+    }
+
+    #[tokio::test]
+    async fn test_unread_counts() {
+        let pool = setup_db().await;
+        let c1 = get_or_create_direct_conversation(&pool, "c1", "u1", "u2")
+            .await
+            .unwrap();
+        let c2 = get_or_create_direct_conversation(&pool, "c2", "u1", "u3")
+            .await
+            .unwrap();
+        send(&pool, &c1, "m1", "u2", "u1").await;
+        send(&pool, &c1, "m2", "u2", "u1").await;
+        send(&pool, &c2, "m3", "u1", "u3").await;
+
+        let unread = |rows: Vec<DmConversationRow>, id: &str| {
+            rows.into_iter()
+                .find(|r| r.id == id)
+                .map(|r| r.unread_count)
+                .unwrap()
+        };
+        let alice = list_conversations(&pool, "u1").await.unwrap();
+        assert_eq!(alice.len(), 2);
+        assert_eq!(unread(alice.clone(), "c1"), 2);
+        // Your own messages are never unread
+        assert_eq!(unread(alice, "c2"), 0);
+        assert_eq!(
+            unread(list_conversations(&pool, "u3").await.unwrap(), "c2"),
+            1
+        );
+
+        mark_conversation_read(&pool, &c1, "u1", "m2")
+            .await
+            .unwrap();
+        assert_eq!(
+            unread(list_conversations(&pool, "u1").await.unwrap(), "c1"),
+            0
+        );
+        // The marker never moves back, nor onto another conversation's message
+        assert!(
+            mark_conversation_read(&pool, &c1, "u1", "m1")
+                .await
+                .unwrap()
+        );
+        assert!(
+            !mark_conversation_read(&pool, &c1, "u1", "m3")
+                .await
+                .unwrap()
+        );
+        assert!(
+            !mark_conversation_read(&pool, &c1, "u1", "nope")
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            unread(list_conversations(&pool, "u1").await.unwrap(), "c1"),
+            0
+        );
+
+        // Replying reads everything before the reply
+        send(&pool, &c1, "m4", "u1", "u2").await;
+        assert_eq!(
+            unread(list_conversations(&pool, "u1").await.unwrap(), "c1"),
+            0
+        );
+        assert_eq!(
+            unread(list_conversations(&pool, "u2").await.unwrap(), "c1"),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_group_conversation_membership() {
+        let pool = setup_db().await;
+        create_group_conversation(&pool, "g1", "u1", Some("plans"), &["u2".into()])
+            .await
+            .unwrap();
+        // A group never stands in for the pair's one-to-one conversation
+        assert_eq!(
+            find_direct_conversation(&pool, "u1", "u2").await.unwrap(),
+            None
+        );
+        assert!(
+            get_group_conversation(&pool, "g1")
+                .await
+                .unwrap()
+                .is_some_and(
+                    |g| g.name.as_deref() == Some("plans") && g.owner_id.as_deref() == Some("u1")
+                )
+        );
+
+        insert_direct_message(
+            &pool,
+            &InsertDirectMessageParams {
+                id: "m1",
+                conversation_id: "g1",
+                sender_id: "u2",
+                sender_nick: "bob",
+                target_user_id: None,
+                content: "hi",
+                kind: "normal",
+            },
+        )
+        .await
+        .unwrap();
+
+        // Someone added later hasn't missed anything
+        assert!(!add_participant(&pool, "g1", "u3", 2).await.unwrap());
+        assert!(add_participant(&pool, "g1", "u3", 3).await.unwrap());
+        assert!(!add_participant(&pool, "g1", "u3", 10).await.unwrap());
+        assert_eq!(
+            participant_ids(&pool, "g1").await.unwrap(),
+            vec!["u1", "u2", "u3"]
+        );
+        let carol = list_conversations(&pool, "u3").await.unwrap();
+        assert!(carol[0].is_group);
+        assert_eq!(carol[0].unread_count, 0);
+        assert_eq!(
+            list_conversations(&pool, "u1").await.unwrap()[0].unread_count,
+            1
+        );
+
+        assert!(remove_participant(&pool, "g1", "u2").await.unwrap());
+        assert!(!remove_participant(&pool, "g1", "u2").await.unwrap());
+        assert!(list_conversations(&pool, "u2").await.unwrap().is_empty());
+
+        delete_conversation(&pool, "g1").await.unwrap();
+        assert!(get_group_conversation(&pool, "g1").await.unwrap().is_none());
+        let stored: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM messages")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(stored, 0);
+    }
+
+    #[tokio::test]
+    async fn test_failed_send_leaves_conversation_untouched() {
+        let pool = setup_db().await;
+        let c1 = get_or_create_direct_conversation(&pool, "c1", "u1", "u2")
+            .await
+            .unwrap();
+        send(&pool, &c1, "m1", "u2", "u1").await;
+        sqlx::query(
+            "CREATE TRIGGER refuse_reads BEFORE UPDATE ON dm_participants \
+             BEGIN SELECT RAISE(ABORT, 'disk full'); END",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let result = insert_direct_message(
+            &pool,
+            &InsertDirectMessageParams {
+                id: "m2",
+                conversation_id: &c1,
+                sender_id: "u1",
+                sender_nick: "alice",
+                target_user_id: Some("u2"),
+                content: "hi",
+                kind: "normal",
+            },
+        )
+        .await;
+        assert!(result.is_err());
+        let stored: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM messages WHERE dm_conversation_id = ?")
+                .bind(&c1)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(stored, 1);
+        let alice = list_conversations(&pool, "u1").await.unwrap();
+        assert_eq!(alice[0].unread_count, 1);
+    }
+}
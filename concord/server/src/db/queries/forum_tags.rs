@@ -219,6 +219,7 @@ mod tests {
                 sender_nick: "alice",
                 content: "Parent",
                 reply_to_id: None,
+                kind: "normal",
             },
         )
         .await
@@ -267,6 +268,7 @@ mod tests {
                 sender_nick: "alice",
                 content: "Parent",
                 reply_to_id: None,
+                kind: "normal",
             },
         )
         .await
@@ -0,0 +1,282 @@
+use sqlx::SqlitePool;
+
+use crate::db::models::MentionRow;
+
+/// A mention to record: who was mentioned, and how.
+pub struct NewMention<'a> {
+    pub id: &'a str,
+    pub user_id: &'a str,
+    pub kind: &'a str,
+}
+
+/// Record the mentions in a stored channel message. A user already
+/// mentioned by the message is left alone.
+pub async fn insert_mentions(
+    pool: &SqlitePool,
+    message_id: &str,
+    server_id: &str,
+    channel_id: &str,
+    mentions: &[NewMention<'_>],
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    for mention in mentions {
+        sqlx::query(
+            "INSERT OR IGNORE INTO mentions (id, user_id, message_id, server_id, channel_id, kind) \
+             VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(mention.id)
+        .bind(mention.user_id)
+        .bind(message_id)
+        .bind(server_id)
+        .bind(channel_id)
+        .bind(mention.kind)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await
+}
+
+/// A page of a user's mentions, newest first. `before` is the ID of the last
+/// mention on the previous page.
+pub async fn list_mentions(
+    pool: &SqlitePool,
+    user_id: &str,
+    before: Option<&str>,
+    unread_only: bool,
+    limit: i64,
+) -> Result<Vec<MentionRow>, sqlx::Error> {
+    let mut sql = String::from(
+        "SELECT mn.id, mn.message_id, mn.server_id, mn.channel_id, c.name AS channel_name, \
+         m.sender_nick, m.content, mn.kind, mn.created_at, mn.read_at \
+         FROM mentions mn \
+         JOIN messages m ON m.id = mn.message_id \
+         JOIN channels c ON c.id = mn.channel_id \
+         WHERE mn.user_id = ? AND m.deleted_at IS NULL",
+    );
+    if unread_only {
+        sql.push_str(" AND mn.read_at IS NULL");
+    }
+    if before.is_some() {
+        sql.push_str(
+            " AND (mn.created_at, mn.rowid) < \
+             (SELECT created_at, rowid FROM mentions WHERE id = ? AND user_id = mn.user_id)",
+        );
+    }
+    sql.push_str(" ORDER BY mn.created_at DESC, mn.rowid DESC LIMIT ?");
+
+    let mut query = sqlx::query_as::<_, MentionRow>(&sql).bind(user_id);
+    if let Some(before) = before {
+        query = query.bind(before);
+    }
+    query.bind(limit).fetch_all(pool).await
+}
+
+/// Mark one of a user's mentions as read. Returns false if they have no
+/// such mention.
+pub async fn mark_mention_read(
+    pool: &SqlitePool,
+    user_id: &str,
+    mention_id: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE mentions SET read_at = COALESCE(read_at, datetime('now')) \
+         WHERE id = ? AND user_id = ?",
+    )
+    .bind(mention_id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Mark all of a user's unread mentions as read, or only those in one server.
+pub async fn mark_all_mentions_read(
+    pool: &SqlitePool,
+    user_id: &str,
+    server_id: Option<&str>,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE mentions SET read_at = datetime('now') \
+         WHERE user_id = ? AND read_at IS NULL AND (? IS NULL OR server_id = ?)",
+    )
+    .bind(user_id)
+    .bind(server_id)
+    .bind(server_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// The names each member of a server can be mentioned by: their username
+/// and any IRC nicknames they've registered.
+pub async fn member_names(
+    pool: &SqlitePool,
+    server_id: &str,
+) -> Result<Vec<(String, String)>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT sm.user_id, u.username FROM server_members sm \
+         JOIN users u ON u.id = sm.user_id WHERE sm.server_id = ? \
+         UNION \
+         SELECT sm.user_id, un.nickname FROM server_members sm \
+         JOIN user_nicknames un ON un.user_id = sm.user_id WHERE sm.server_id = ?",
+    )
+    .bind(server_id)
+    .bind(server_id)
+    .fetch_all(pool)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::pool::{create_pool, run_migrations};
+    use crate::db::queries::messages::{self, InsertMessageParams};
+    use crate::db::queries::users::{self, CreateOAuthUser};
+    use crate::db::queries::{channels, servers};
+
+    async fn setup_db() -> SqlitePool {
+        let pool = create_pool("sqlite::memory:").await.unwrap();
+        run_migrations(&pool).await.unwrap();
+        for (id, name) in [("u1", "alice"), ("u2", "bob")] {
+            users::create_with_oauth(
+                &pool,
+                &CreateOAuthUser {
+                    user_id: id,
+                    username: name,
+                    email: None,
+                    avatar_url: None,
+                    oauth_id: &format!("oauth-{id}"),
+                    provider: "github",
+                    provider_id: &format!("gh-{id}"),
+                },
+            )
+            .await
+            .unwrap();
+        }
+        servers::create_server(&pool, "s1", "Test", "u1", None)
+            .await
+            .unwrap();
+        servers::add_server_member(&pool, "s1", "u2", "member")
+            .await
+            .unwrap();
+        channels::ensure_channel(&pool, "c1", "s1", "#general")
+            .await
+            .unwrap();
+        pool
+    }
+
+    async fn mention_bob(pool: &SqlitePool, n: usize) {
+        let message_id = format!("m{n}");
+        messages::insert_message(
+            pool,
+            &InsertMessageParams {
+                id: &message_id,
+                server_id: "s1",
+                channel_id: "c1",
+                sender_id: "u1",
+                sender_nick: "alice",
+                content: &format!("@bob {n}"),
+                reply_to_id: None,
+                kind: "normal",
+            },
+        )
+        .await
+        .unwrap();
+        let id = format!("mn{n}");
+        insert_mentions(
+            pool,
+            &message_id,
+            "s1",
+            "c1",
+            &[NewMention {
+                id: &id,
+                user_id: "u2",
+                kind: "user",
+            }],
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_list_mentions_pages_newest_first() {
+        let pool = setup_db().await;
+        for n in 1..=5 {
+            mention_bob(&pool, n).await;
+        }
+
+        let page = list_mentions(&pool, "u2", None, false, 2).await.unwrap();
+        let ids: Vec<&str> = page.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["mn5", "mn4"]);
+        assert_eq!(page[0].channel_name, "#general");
+        assert_eq!(page[0].content, "@bob 5");
+
+        let page = list_mentions(&pool, "u2", Some("mn4"), false, 10)
+            .await
+            .unwrap();
+        let ids: Vec<&str> = page.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["mn3", "mn2", "mn1"]);
+
+        // Mentioning someone doesn't put anything in your own inbox
+        assert!(
+            list_mentions(&pool, "u1", None, false, 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mark_mentions_read() {
+        let pool = setup_db().await;
+        for n in 1..=3 {
+            mention_bob(&pool, n).await;
+        }
+
+        assert!(mark_mention_read(&pool, "u2", "mn2").await.unwrap());
+        assert!(!mark_mention_read(&pool, "u1", "mn1").await.unwrap());
+        let unread = list_mentions(&pool, "u2", None, true, 10).await.unwrap();
+        let ids: Vec<&str> = unread.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["mn3", "mn1"]);
+
+        assert_eq!(
+            mark_all_mentions_read(&pool, "u2", Some("other"))
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            mark_all_mentions_read(&pool, "u2", Some("s1"))
+                .await
+                .unwrap(),
+            2
+        );
+        assert!(
+            list_mentions(&pool, "u2", None, true, 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            list_mentions(&pool, "u2", None, false, 10)
+                .await
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+
+    #[tokio::test]
+    async fn test_member_names() {
+        let pool = setup_db().await;
+        let mut names = member_names(&pool, "s1").await.unwrap();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                ("u1".to_string(), "alice".to_string()),
+                ("u2".to_string(), "bob".to_string())
+            ]
+        );
+    }
+}
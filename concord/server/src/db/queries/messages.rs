@@ -1,6 +1,6 @@
 use sqlx::SqlitePool;
 
-use crate::db::models::MessageRow;
+use crate::db::models::{MessageRevisionRow, MessageRow};
 
 /// Parameters for inserting a channel message.
 pub struct InsertMessageParams<'a> {
@@ -11,6 +11,8 @@ pub struct InsertMessageParams<'a> {
     pub sender_nick: &'a str,
     pub content: &'a str,
     pub reply_to_id: Option<&'a str>,
+    /// "normal", "action" or "notice".
+    pub kind: &'a str,
 }
 
 /// Insert a new channel message, optionally replying to another message.
@@ -19,8 +21,8 @@ pub async fn insert_message(
     params: &InsertMessageParams<'_>,
 ) -> Result<(), sqlx::Error> {
     sqlx::query(
-        "INSERT INTO messages (id, server_id, channel_id, sender_id, sender_nick, content, reply_to_id) \
-         VALUES (?, ?, ?, ?, ?, ?, ?)",
+        "INSERT INTO messages (id, server_id, channel_id, sender_id, sender_nick, content, reply_to_id, kind) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
     )
     .bind(params.id)
     .bind(params.server_id)
@@ -29,6 +31,7 @@ pub async fn insert_message(
     .bind(params.sender_nick)
     .bind(params.content)
     .bind(params.reply_to_id)
+    .bind(params.kind)
     .execute(pool)
     .await?;
     Ok(())
@@ -42,16 +45,18 @@ pub async fn insert_dm(
     sender_nick: &str,
     target_user_id: &str,
     content: &str,
+    kind: &str,
 ) -> Result<(), sqlx::Error> {
     sqlx::query(
-        "INSERT INTO messages (id, sender_id, sender_nick, target_user_id, content) \
-         VALUES (?, ?, ?, ?, ?)",
+        "INSERT INTO messages (id, sender_id, sender_nick, target_user_id, content, kind) \
+         VALUES (?, ?, ?, ?, ?, ?)",
     )
     .bind(id)
     .bind(sender_id)
     .bind(sender_nick)
     .bind(target_user_id)
     .bind(content)
+    .bind(kind)
     .execute(pool)
     .await?;
     Ok(())
@@ -64,7 +69,7 @@ pub async fn get_message_by_id(
 ) -> Result<Option<MessageRow>, sqlx::Error> {
     sqlx::query_as::<_, MessageRow>(
         "SELECT id, server_id, channel_id, sender_id, sender_nick, content, \
-         created_at, target_user_id, edited_at, deleted_at, reply_to_id \
+         created_at, target_user_id, edited_at, deleted_at, reply_to_id, kind \
          FROM messages WHERE id = ?",
     )
     .bind(id)
@@ -72,23 +77,49 @@ pub async fn get_message_by_id(
     .await
 }
 
-/// Update message content (edit). Sets edited_at to current time.
+/// Update message content (edit). Sets edited_at to current time and keeps
+/// the content being replaced as a revision.
 pub async fn update_message_content(
     pool: &SqlitePool,
     id: &str,
     new_content: &str,
 ) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "INSERT INTO message_revisions (message_id, content, written_at) \
+         SELECT id, content, COALESCE(edited_at, created_at) FROM messages \
+         WHERE id = ? AND deleted_at IS NULL",
+    )
+    .bind(id)
+    .execute(&mut *tx)
+    .await?;
     let result = sqlx::query(
         "UPDATE messages SET content = ?, edited_at = datetime('now') \
          WHERE id = ? AND deleted_at IS NULL",
     )
     .bind(new_content)
     .bind(id)
-    .execute(pool)
+    .execute(&mut *tx)
     .await?;
+    tx.commit().await?;
     Ok(result.rows_affected() > 0)
 }
 
+/// Earlier versions of a message, oldest first. The current version is the
+/// message's own content.
+pub async fn get_message_revisions(
+    pool: &SqlitePool,
+    message_id: &str,
+) -> Result<Vec<MessageRevisionRow>, sqlx::Error> {
+    sqlx::query_as::<_, MessageRevisionRow>(
+        "SELECT content, written_at, replaced_at FROM message_revisions \
+         WHERE message_id = ? ORDER BY id",
+    )
+    .bind(message_id)
+    .fetch_all(pool)
+    .await
+}
+
 /// Soft-delete a message. Sets deleted_at to current time.
 pub async fn soft_delete_message(pool: &SqlitePool, id: &str) -> Result<bool, sqlx::Error> {
     let result = sqlx::query(
@@ -170,15 +201,28 @@ pub async fn mark_channel_read(
     user_id: &str,
     channel_id: &str,
     last_read_message_id: &str,
+) -> Result<(), sqlx::Error> {
+    mark_client_read(pool, user_id, "", channel_id, last_read_message_id).await
+}
+
+/// Upsert one client's read marker for a conversation. The account's own
+/// marker, set by MarkRead, is the one with an empty client name.
+pub async fn mark_client_read(
+    pool: &SqlitePool,
+    user_id: &str,
+    client: &str,
+    channel_id: &str,
+    last_read_message_id: &str,
 ) -> Result<(), sqlx::Error> {
     sqlx::query(
-        "INSERT INTO read_states (user_id, channel_id, last_read_message_id, last_read_at) \
-         VALUES (?, ?, ?, datetime('now')) \
-         ON CONFLICT(user_id, channel_id) DO UPDATE SET \
+        "INSERT INTO read_states (user_id, client, channel_id, last_read_message_id, last_read_at) \
+         VALUES (?, ?, ?, ?, datetime('now')) \
+         ON CONFLICT(user_id, client, channel_id) DO UPDATE SET \
          last_read_message_id = excluded.last_read_message_id, \
          last_read_at = excluded.last_read_at",
     )
     .bind(user_id)
+    .bind(client)
     .bind(channel_id)
     .bind(last_read_message_id)
     .execute(pool)
@@ -186,6 +230,45 @@ pub async fn mark_channel_read(
     Ok(())
 }
 
+/// Give a client a marker at the current time for a conversation it has no
+/// marker for yet. Existing markers are left alone.
+pub async fn touch_client_read(
+    pool: &SqlitePool,
+    user_id: &str,
+    client: &str,
+    channel_id: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT OR IGNORE INTO read_states (user_id, client, channel_id, last_read_message_id) \
+         VALUES (?, ?, ?, NULL)",
+    )
+    .bind(user_id)
+    .bind(client)
+    .bind(channel_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// A client's read marker for a conversation, as (last_read_message_id,
+/// last_read_at).
+pub async fn get_client_read(
+    pool: &SqlitePool,
+    user_id: &str,
+    client: &str,
+    channel_id: &str,
+) -> Result<Option<(Option<String>, String)>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT last_read_message_id, last_read_at FROM read_states \
+         WHERE user_id = ? AND client = ? AND channel_id = ?",
+    )
+    .bind(user_id)
+    .bind(client)
+    .bind(channel_id)
+    .fetch_optional(pool)
+    .await
+}
+
 /// Row for unread count results.
 #[derive(Debug, Clone, sqlx::FromRow)]
 pub struct UnreadCountRow {
@@ -203,7 +286,8 @@ pub async fn get_unread_counts(
     sqlx::query_as::<_, UnreadCountRow>(
         "SELECT m.channel_id, COUNT(*) as unread_count \
          FROM messages m \
-         LEFT JOIN read_states rs ON rs.user_id = ? AND rs.channel_id = m.channel_id \
+         LEFT JOIN read_states rs \
+         ON rs.user_id = ? AND rs.client = '' AND rs.channel_id = m.channel_id \
          WHERE m.server_id = ? AND m.deleted_at IS NULL \
            AND (rs.last_read_message_id IS NULL OR m.created_at > ( \
              SELECT created_at FROM messages WHERE id = rs.last_read_message_id \
@@ -230,7 +314,7 @@ pub async fn fetch_channel_history(
         Some(before) => {
             sqlx::query_as::<_, MessageRow>(
                 "SELECT id, server_id, channel_id, sender_id, sender_nick, content, \
-                 created_at, target_user_id, edited_at, deleted_at, reply_to_id \
+                 created_at, target_user_id, edited_at, deleted_at, reply_to_id, kind \
                  FROM messages \
                  WHERE channel_id = ? AND created_at < ? AND deleted_at IS NULL \
                  ORDER BY created_at DESC \
@@ -245,7 +329,7 @@ pub async fn fetch_channel_history(
         None => {
             sqlx::query_as::<_, MessageRow>(
                 "SELECT id, server_id, channel_id, sender_id, sender_nick, content, \
-                 created_at, target_user_id, edited_at, deleted_at, reply_to_id \
+                 created_at, target_user_id, edited_at, deleted_at, reply_to_id, kind \
                  FROM messages \
                  WHERE channel_id = ? AND deleted_at IS NULL \
                  ORDER BY created_at DESC \
@@ -277,6 +361,137 @@ pub async fn get_last_user_message_time(
     Ok(result.map(|r| r.0))
 }
 
+/// Which conversation an anchored history query reads from.
+pub enum HistoryScope<'a> {
+    /// Messages in a channel, by channel ID.
+    Channel(&'a str),
+    /// Direct messages exchanged between two participant IDs, in either direction.
+    Direct(&'a str, &'a str),
+    /// Messages in a group direct message conversation, by conversation ID.
+    Group(&'a str),
+}
+
+/// A position in message order: `created_at` with the rowid as a tiebreak
+/// for messages stored within the same second.
+pub type HistoryCursor = (String, i64);
+
+/// Look up the position of a message for use as a history anchor.
+pub async fn get_message_cursor(
+    pool: &SqlitePool,
+    id: &str,
+) -> Result<Option<HistoryCursor>, sqlx::Error> {
+    sqlx::query_as::<_, (String, i64)>("SELECT created_at, rowid FROM messages WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Fetch messages strictly between two cursors (either bound may be open).
+/// `newest_first` picks which end of the range `limit` counts from; rows are
+/// always returned oldest first. Excludes soft-deleted messages.
+pub async fn fetch_history_range(
+    pool: &SqlitePool,
+    scope: &HistoryScope<'_>,
+    after: Option<&HistoryCursor>,
+    before: Option<&HistoryCursor>,
+    newest_first: bool,
+    limit: i64,
+) -> Result<Vec<MessageRow>, sqlx::Error> {
+    let mut sql = String::from(
+        "SELECT id, server_id, channel_id, sender_id, sender_nick, content, \
+         created_at, target_user_id, edited_at, deleted_at, reply_to_id, kind \
+         FROM messages WHERE deleted_at IS NULL",
+    );
+    match scope {
+        HistoryScope::Channel(_) => sql.push_str(" AND channel_id = ?"),
+        HistoryScope::Direct(..) => sql.push_str(
+            " AND channel_id IS NULL AND ((sender_id = ? AND target_user_id = ?) \
+             OR (sender_id = ? AND target_user_id = ?))",
+        ),
+        HistoryScope::Group(_) => sql.push_str(" AND dm_conversation_id = ?"),
+    }
+    if after.is_some() {
+        sql.push_str(" AND (created_at, rowid) > (?, ?)");
+    }
+    if before.is_some() {
+        sql.push_str(" AND (created_at, rowid) < (?, ?)");
+    }
+    let order = if newest_first { "DESC" } else { "ASC" };
+    sql.push_str(&format!(
+        " ORDER BY created_at {order}, rowid {order} LIMIT ?"
+    ));
+
+    let mut query = sqlx::query_as::<_, MessageRow>(&sql);
+    match scope {
+        HistoryScope::Channel(channel_id) => query = query.bind(*channel_id),
+        HistoryScope::Direct(a, b) => {
+            query = query.bind(*a).bind(*b).bind(*b).bind(*a);
+        }
+        HistoryScope::Group(conversation_id) => query = query.bind(*conversation_id),
+    }
+    if let Some((time, rowid)) = after {
+        query = query.bind(time).bind(rowid);
+    }
+    if let Some((time, rowid)) = before {
+        query = query.bind(time).bind(rowid);
+    }
+    let mut rows = query.bind(limit).fetch_all(pool).await?;
+    if newest_first {
+        rows.reverse();
+    }
+    Ok(rows)
+}
+
+/// Latest message time per channel, for channels with activity strictly
+/// between `after` and `before`. Returns (channel_id, created_at).
+pub async fn fetch_channel_activity(
+    pool: &SqlitePool,
+    channel_ids: &[String],
+    after: &str,
+    before: &str,
+) -> Result<Vec<(String, String)>, sqlx::Error> {
+    if channel_ids.is_empty() {
+        return Ok(vec![]);
+    }
+    let placeholders: Vec<&str> = channel_ids.iter().map(|_| "?").collect();
+    let sql = format!(
+        "SELECT channel_id, MAX(created_at) FROM messages \
+         WHERE channel_id IN ({}) AND deleted_at IS NULL AND created_at > ? AND created_at < ? \
+         GROUP BY channel_id",
+        placeholders.join(", ")
+    );
+    let mut query = sqlx::query_as::<_, (String, String)>(&sql);
+    for id in channel_ids {
+        query = query.bind(id);
+    }
+    query.bind(after).bind(before).fetch_all(pool).await
+}
+
+/// Latest direct message time per conversation partner of `participant_id`,
+/// for conversations with activity strictly between `after` and `before`.
+/// Returns (partner_id, created_at).
+pub async fn fetch_dm_activity(
+    pool: &SqlitePool,
+    participant_id: &str,
+    after: &str,
+    before: &str,
+) -> Result<Vec<(String, String)>, sqlx::Error> {
+    sqlx::query_as::<_, (String, String)>(
+        "SELECT CASE WHEN sender_id = ? THEN target_user_id ELSE sender_id END AS peer, \
+         MAX(created_at) FROM messages \
+         WHERE channel_id IS NULL AND target_user_id IS NOT NULL AND deleted_at IS NULL \
+         AND (sender_id = ? OR target_user_id = ?) AND created_at > ? AND created_at < ? \
+         GROUP BY peer",
+    )
+    .bind(participant_id)
+    .bind(participant_id)
+    .bind(participant_id)
+    .bind(after)
+    .bind(before)
+    .fetch_all(pool)
+    .await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -323,6 +538,7 @@ mod tests {
             sender_nick: "alice",
             content,
             reply_to_id: None,
+            kind: "normal",
         }
     }
 
@@ -369,6 +585,29 @@ mod tests {
         assert!(msg.edited_at.is_some());
     }
 
+    #[tokio::test]
+    async fn test_edits_keep_earlier_revisions() {
+        let pool = setup_db().await;
+        setup_server_and_channel(&pool).await;
+        insert_message(&pool, &msg_params("m1", "First"))
+            .await
+            .unwrap();
+        assert!(get_message_revisions(&pool, "m1").await.unwrap().is_empty());
+
+        update_message_content(&pool, "m1", "Second").await.unwrap();
+        update_message_content(&pool, "m1", "Third").await.unwrap();
+        let original = get_message_by_id(&pool, "m1").await.unwrap().unwrap();
+        let revisions = get_message_revisions(&pool, "m1").await.unwrap();
+        let contents: Vec<&str> = revisions.iter().map(|r| r.content.as_str()).collect();
+        assert_eq!(contents, ["First", "Second"]);
+        assert_eq!(revisions[0].written_at, original.created_at);
+
+        // A failed edit of a deleted message records nothing
+        soft_delete_message(&pool, "m1").await.unwrap();
+        update_message_content(&pool, "m1", "Fourth").await.unwrap();
+        assert_eq!(get_message_revisions(&pool, "m1").await.unwrap().len(), 2);
+    }
+
     #[tokio::test]
     async fn test_edit_deleted_message_fails() {
         let pool = setup_db().await;
@@ -422,6 +661,7 @@ mod tests {
             sender_nick: "alice",
             content: "Reply!",
             reply_to_id: Some("m1"),
+            kind: "normal",
         };
         insert_message(&pool, &reply_params).await.unwrap();
 
@@ -554,7 +794,7 @@ mod tests {
         .await
         .unwrap();
 
-        insert_dm(&pool, "dm1", "u1", "alice", "u2", "Hey Bob!")
+        insert_dm(&pool, "dm1", "u1", "alice", "u2", "Hey Bob!", "normal")
             .await
             .unwrap();
 
@@ -564,6 +804,92 @@ mod tests {
         assert!(msg.server_id.is_none());
     }
 
+    #[tokio::test]
+    async fn test_fetch_history_range_cursors() {
+        let pool = setup_db().await;
+        setup_server_and_channel(&pool).await;
+        for i in 0..5 {
+            insert_message(&pool, &msg_params(&format!("m{i}"), &format!("Msg {i}")))
+                .await
+                .unwrap();
+        }
+        let scope = HistoryScope::Channel("c1");
+        let anchor = get_message_cursor(&pool, "m2").await.unwrap().unwrap();
+
+        // Same-second inserts are still ordered by rowid
+        let before = fetch_history_range(&pool, &scope, None, Some(&anchor), true, 10)
+            .await
+            .unwrap();
+        let ids: Vec<&str> = before.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["m0", "m1"]);
+
+        let after = fetch_history_range(&pool, &scope, Some(&anchor), None, false, 1)
+            .await
+            .unwrap();
+        assert_eq!(after[0].id, "m3");
+
+        // Newest-first limits count from the end but still return oldest first
+        let latest = fetch_history_range(&pool, &scope, None, None, true, 2)
+            .await
+            .unwrap();
+        let ids: Vec<&str> = latest.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["m3", "m4"]);
+
+        assert!(get_message_cursor(&pool, "nope").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_history_range_direct() {
+        let pool = setup_db().await;
+        insert_dm(&pool, "d1", "u1", "alice", "u2", "hi bob", "normal")
+            .await
+            .unwrap();
+        insert_dm(&pool, "d2", "u2", "bob", "u1", "hi alice", "normal")
+            .await
+            .unwrap();
+        insert_dm(&pool, "d3", "u1", "alice", "u3", "hi carol", "normal")
+            .await
+            .unwrap();
+
+        let scope = HistoryScope::Direct("u1", "u2");
+        let rows = fetch_history_range(&pool, &scope, None, None, true, 10)
+            .await
+            .unwrap();
+        let ids: Vec<&str> = rows.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["d1", "d2"]);
+
+        let mut peers =
+            fetch_dm_activity(&pool, "u1", "2000-01-01 00:00:00", "9999-01-01 00:00:00")
+                .await
+                .unwrap();
+        peers.sort();
+        let peers: Vec<&str> = peers.iter().map(|(p, _)| p.as_str()).collect();
+        assert_eq!(peers, ["u2", "u3"]);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_channel_activity() {
+        let pool = setup_db().await;
+        setup_server_and_channel(&pool).await;
+        insert_message(&pool, &msg_params("m1", "Hello"))
+            .await
+            .unwrap();
+
+        let ids = vec!["c1".to_string(), "c-empty".to_string()];
+        let active =
+            fetch_channel_activity(&pool, &ids, "2000-01-01 00:00:00", "9999-01-01 00:00:00")
+                .await
+                .unwrap();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].0, "c1");
+
+        let none =
+            fetch_channel_activity(&pool, &ids, "9000-01-01 00:00:00", "9999-01-01 00:00:00")
+                .await
+                .unwrap();
+        assert!(none.is_empty());
+    }
+
     #[tokio::test]
     async fn test_mark_channel_read() {
         let pool = setup_db().await;
@@ -581,4 +907,56 @@ mod tests {
             .unwrap();
         mark_channel_read(&pool, "u1", "c1", "m2").await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_client_read_markers() {
+        let pool = setup_db().await;
+        setup_server_and_channel(&pool).await;
+        insert_message(&pool, &msg_params("m1", "Hello"))
+            .await
+            .unwrap();
+
+        // Each client's marker is separate from the account's own
+        mark_channel_read(&pool, "u1", "c1", "m1").await.unwrap();
+        assert!(
+            get_client_read(&pool, "u1", "laptop", "c1")
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        touch_client_read(&pool, "u1", "laptop", "c1")
+            .await
+            .unwrap();
+        let (id, _) = get_client_read(&pool, "u1", "laptop", "c1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(id.is_none());
+
+        mark_client_read(&pool, "u1", "laptop", "c1", "m1")
+            .await
+            .unwrap();
+        touch_client_read(&pool, "u1", "laptop", "c1")
+            .await
+            .unwrap();
+        let (id, _) = get_client_read(&pool, "u1", "laptop", "c1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(id.as_deref(), Some("m1"));
+
+        // Moving a client's marker leaves the account's alone
+        insert_message(&pool, &msg_params("m2", "World"))
+            .await
+            .unwrap();
+        mark_client_read(&pool, "u1", "laptop", "c1", "m2")
+            .await
+            .unwrap();
+        let (id, _) = get_client_read(&pool, "u1", "", "c1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(id.as_deref(), Some("m1"));
+    }
 }
@@ -7,11 +7,13 @@ pub mod bots;
 pub mod categories;
 pub mod channels;
 pub mod community;
+pub mod direct_messages;
 pub mod embeds;
 pub mod emoji;
 pub mod events;
 pub mod forum_tags;
 pub mod invites;
+pub mod mentions;
 pub mod messages;
 pub mod moderation;
 pub mod notifications;
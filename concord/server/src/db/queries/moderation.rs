@@ -55,6 +55,20 @@ pub async fn get_member_timeout(
     Ok(result.and_then(|r| r.0))
 }
 
+/// List members of a server that have a timeout set, as (user_id, timeout_until).
+pub async fn list_member_timeouts(
+    pool: &SqlitePool,
+    server_id: &str,
+) -> Result<Vec<(String, String)>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT user_id, timeout_until FROM server_members \
+         WHERE server_id = ? AND timeout_until IS NOT NULL ORDER BY timeout_until",
+    )
+    .bind(server_id)
+    .fetch_all(pool)
+    .await
+}
+
 /// Set slow mode seconds on a channel.
 pub async fn set_slowmode(
     pool: &SqlitePool,
@@ -250,6 +264,23 @@ mod tests {
         assert!(timeout.is_none());
     }
 
+    #[tokio::test]
+    async fn test_list_member_timeouts() {
+        let pool = setup_db().await;
+        setup_env(&pool).await;
+
+        assert!(list_member_timeouts(&pool, "s1").await.unwrap().is_empty());
+
+        set_member_timeout(&pool, "s1", "u2", Some("2027-01-01 00:00:00"))
+            .await
+            .unwrap();
+        let timeouts = list_member_timeouts(&pool, "s1").await.unwrap();
+        assert_eq!(
+            timeouts,
+            vec![("u2".to_string(), "2027-01-01 00:00:00".to_string())]
+        );
+    }
+
     #[tokio::test]
     async fn test_set_slowmode() {
         let pool = setup_db().await;
@@ -299,6 +330,7 @@ mod tests {
                     sender_nick: "alice",
                     content: &format!("Message {i}"),
                     reply_to_id: None,
+                    kind: "normal",
                 },
             )
             .await
@@ -350,6 +382,7 @@ mod tests {
                 sender_nick: "bob",
                 content: "Bob's message",
                 reply_to_id: None,
+                kind: "normal",
             },
         )
         .await
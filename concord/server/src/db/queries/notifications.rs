@@ -48,6 +48,26 @@ pub async fn get_notification_settings(
     .await
 }
 
+/// Get the notification settings every member of a server has for it, in
+/// one query (for bulk loading).
+pub async fn get_member_notification_settings(
+    pool: &SqlitePool,
+    server_id: &str,
+) -> Result<Vec<NotificationSettingRow>, sqlx::Error> {
+    sqlx::query_as::<_, NotificationSettingRow>(
+        "SELECT id, user_id, server_id, channel_id, level, suppress_everyone, \
+         suppress_roles, muted, mute_until, created_at, updated_at \
+         FROM notification_settings \
+         WHERE user_id IN (SELECT user_id FROM server_members WHERE server_id = ?) \
+         AND (server_id = ? OR server_id IS NULL) \
+         ORDER BY user_id, channel_id NULLS FIRST",
+    )
+    .bind(server_id)
+    .bind(server_id)
+    .fetch_all(pool)
+    .await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -214,6 +234,44 @@ mod tests {
         assert!(settings[1].channel_id.is_some());
     }
 
+    #[tokio::test]
+    async fn test_member_notification_settings() {
+        let pool = setup_db().await;
+        setup_server(&pool).await;
+        servers::create_server(&pool, "s2", "Other", "u1", None)
+            .await
+            .unwrap();
+
+        for (id, server_id) in [("ns1", Some("s1")), ("ns2", None), ("ns3", Some("s2"))] {
+            upsert_notification_setting(
+                &pool,
+                &UpsertNotificationParams {
+                    id,
+                    user_id: "u1",
+                    server_id,
+                    channel_id: None,
+                    level: "mentions",
+                    suppress_everyone: false,
+                    suppress_roles: false,
+                    muted: false,
+                    mute_until: None,
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        // Settings for the server and global ones, but not another server's
+        let mut ids: Vec<String> = get_member_notification_settings(&pool, "s1")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|s| s.id)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, ["ns1", "ns2"]);
+    }
+
     #[tokio::test]
     async fn test_empty_settings() {
         let pool = setup_db().await;
@@ -123,6 +123,7 @@ mod tests {
                 sender_nick: "alice",
                 content: "Test message",
                 reply_to_id: None,
+                kind: "normal",
             },
         )
         .await
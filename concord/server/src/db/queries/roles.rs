@@ -168,6 +168,27 @@ pub async fn get_all_user_roles(
         .await
 }
 
+/// Get the role assignments in a server of just the given users.
+pub async fn get_user_roles_for_users(
+    pool: &SqlitePool,
+    server_id: &str,
+    user_ids: &[String],
+) -> Result<Vec<UserRoleRow>, sqlx::Error> {
+    if user_ids.is_empty() {
+        return Ok(vec![]);
+    }
+    let placeholders: Vec<&str> = user_ids.iter().map(|_| "?").collect();
+    let sql = format!(
+        "SELECT * FROM user_roles WHERE server_id = ? AND user_id IN ({})",
+        placeholders.join(", ")
+    );
+    let mut query = sqlx::query_as::<_, UserRoleRow>(&sql).bind(server_id);
+    for id in user_ids {
+        query = query.bind(id);
+    }
+    query.fetch_all(pool).await
+}
+
 /// Check if a server has any roles defined.
 pub async fn server_has_roles(pool: &SqlitePool, server_id: &str) -> Result<bool, sqlx::Error> {
     let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM roles WHERE server_id = ?")
@@ -422,6 +443,18 @@ mod tests {
 
         let all = get_all_user_roles(&pool, "s1").await.unwrap();
         assert_eq!(all.len(), 2);
+
+        let some = get_user_roles_for_users(&pool, "s1", &["u2".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(some.len(), 1);
+        assert_eq!(some[0].user_id, "u2");
+        assert!(
+            get_user_roles_for_users(&pool, "s1", &[])
+                .await
+                .unwrap()
+                .is_empty()
+        );
     }
 
     #[tokio::test]
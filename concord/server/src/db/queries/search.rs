@@ -32,7 +32,7 @@ pub async fn search_messages(
     let (rows, total) = if let Some(ch_id) = channel_id {
         let rows = sqlx::query_as::<_, MessageRow>(
             "SELECT m.id, m.server_id, m.channel_id, m.sender_id, m.sender_nick, m.content, \
-             m.created_at, m.target_user_id, m.edited_at, m.deleted_at, m.reply_to_id \
+             m.created_at, m.target_user_id, m.edited_at, m.deleted_at, m.reply_to_id, m.kind \
              FROM messages m \
              JOIN messages_fts f ON m.rowid = f.rowid \
              WHERE f.content MATCH ? AND m.server_id = ? AND m.channel_id = ? AND m.deleted_at IS NULL \
@@ -61,7 +61,7 @@ pub async fn search_messages(
     } else {
         let rows = sqlx::query_as::<_, MessageRow>(
             "SELECT m.id, m.server_id, m.channel_id, m.sender_id, m.sender_nick, m.content, \
-             m.created_at, m.target_user_id, m.edited_at, m.deleted_at, m.reply_to_id \
+             m.created_at, m.target_user_id, m.edited_at, m.deleted_at, m.reply_to_id, m.kind \
              FROM messages m \
              JOIN messages_fts f ON m.rowid = f.rowid \
              WHERE f.content MATCH ? AND m.server_id = ? AND m.deleted_at IS NULL \
@@ -142,6 +142,7 @@ mod tests {
                 sender_nick: "alice",
                 content,
                 reply_to_id: None,
+                kind: "normal",
             },
         )
         .await
@@ -80,6 +80,20 @@ pub async fn update_server(
     Ok(())
 }
 
+/// Set or clear a server's message of the day.
+pub async fn set_server_motd(
+    pool: &SqlitePool,
+    server_id: &str,
+    motd: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE servers SET motd = ?, updated_at = datetime('now') WHERE id = ?")
+        .bind(motd)
+        .bind(server_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
 /// Delete a server and all associated data (cascades).
 pub async fn delete_server(pool: &SqlitePool, server_id: &str) -> Result<(), sqlx::Error> {
     sqlx::query("DELETE FROM servers WHERE id = ?")
@@ -377,6 +391,37 @@ mod tests {
         assert_eq!(server.icon_url, Some("https://icon.png".to_string()));
     }
 
+    #[tokio::test]
+    async fn test_set_server_motd() {
+        let pool = setup_db().await;
+        create_test_user(&pool, "u1", "alice").await;
+        create_server(&pool, "s1", "Test", "u1", None)
+            .await
+            .unwrap();
+        assert!(
+            get_server(&pool, "s1")
+                .await
+                .unwrap()
+                .unwrap()
+                .motd
+                .is_none()
+        );
+
+        set_server_motd(&pool, "s1", Some("Be nice")).await.unwrap();
+        let server = get_server(&pool, "s1").await.unwrap().unwrap();
+        assert_eq!(server.motd.as_deref(), Some("Be nice"));
+
+        set_server_motd(&pool, "s1", None).await.unwrap();
+        assert!(
+            get_server(&pool, "s1")
+                .await
+                .unwrap()
+                .unwrap()
+                .motd
+                .is_none()
+        );
+    }
+
     #[tokio::test]
     async fn test_delete_server() {
         let pool = setup_db().await;
@@ -63,6 +63,22 @@ pub async fn get_threads_for_channel(
     .await
 }
 
+/// The channel each thread on a server was started in, as (thread id, parent channel id).
+pub async fn get_thread_parent_channels(
+    pool: &SqlitePool,
+    server_id: &str,
+) -> Result<Vec<(String, String)>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT c.id, m.channel_id FROM channels c \
+         JOIN messages m ON c.thread_parent_message_id = m.id \
+         WHERE c.server_id = ? \
+         AND c.channel_type IN ('public_thread', 'private_thread')",
+    )
+    .bind(server_id)
+    .fetch_all(pool)
+    .await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -110,6 +126,7 @@ mod tests {
                 sender_nick: "alice",
                 content: "Parent message",
                 reply_to_id: None,
+                kind: "normal",
             },
         )
         .await
@@ -168,6 +185,25 @@ mod tests {
         assert_eq!(threads.len(), 2);
     }
 
+    #[tokio::test]
+    async fn test_get_thread_parent_channels() {
+        let pool = setup_db().await;
+        setup_env(&pool).await;
+        assert!(
+            get_thread_parent_channels(&pool, "s1")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        create_thread(&pool, "t1", "s1", "Thread 1", "public_thread", "m1", 60)
+            .await
+            .unwrap();
+
+        let parents = get_thread_parent_channels(&pool, "s1").await.unwrap();
+        assert_eq!(parents, vec![("t1".to_string(), "c1".to_string())]);
+    }
+
     #[tokio::test]
     async fn test_no_threads_for_channel() {
         let pool = setup_db().await;
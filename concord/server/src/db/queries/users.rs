@@ -273,6 +273,119 @@ pub async fn touch_irc_token(
     Ok(())
 }
 
+/// Bind a TLS client certificate fingerprint to a user (for SASL EXTERNAL).
+pub async fn create_irc_cert(
+    pool: &SqlitePool,
+    cert_id: &str,
+    user_id: &str,
+    fingerprint: &str,
+    label: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO irc_cert_fingerprints (id, user_id, fingerprint, label) VALUES (?, ?, ?, ?)",
+    )
+    .bind(cert_id)
+    .bind(user_id)
+    .bind(fingerprint)
+    .bind(label)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// List certificate fingerprints bound to a user (id, fingerprint, label, last_used, created_at).
+pub async fn list_irc_certs(
+    pool: &SqlitePool,
+    user_id: &str,
+) -> Result<Vec<(String, String, Option<String>, Option<String>, String)>, sqlx::Error> {
+    sqlx::query_as::<_, (String, String, Option<String>, Option<String>, String)>(
+        "SELECT id, fingerprint, label, last_used, created_at FROM irc_cert_fingerprints \
+         WHERE user_id = ? ORDER BY created_at DESC",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Remove a certificate fingerprint binding (must belong to the user).
+pub async fn delete_irc_cert(
+    pool: &SqlitePool,
+    cert_id: &str,
+    user_id: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM irc_cert_fingerprints WHERE id = ? AND user_id = ?")
+        .bind(cert_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Find the user a certificate fingerprint is bound to and mark it used.
+/// Returns (user_id, username).
+pub async fn get_user_by_cert_fingerprint(
+    pool: &SqlitePool,
+    fingerprint: &str,
+) -> Result<Option<(String, String)>, sqlx::Error> {
+    let row = sqlx::query_as::<_, (String, String)>(
+        "SELECT u.id, u.username FROM irc_cert_fingerprints c \
+         JOIN users u ON c.user_id = u.id WHERE c.fingerprint = ?",
+    )
+    .bind(fingerprint)
+    .fetch_optional(pool)
+    .await?;
+
+    if row.is_some() {
+        sqlx::query(
+            "UPDATE irc_cert_fingerprints SET last_used = datetime('now') WHERE fingerprint = ?",
+        )
+        .bind(fingerprint)
+        .execute(pool)
+        .await?;
+    }
+    Ok(row)
+}
+
+/// Whether the account stays connected while none of its clients are.
+pub async fn get_irc_always_on(pool: &SqlitePool, user_id: &str) -> Result<bool, sqlx::Error> {
+    let enabled: Option<bool> = sqlx::query_scalar("SELECT irc_always_on FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(enabled.unwrap_or(false))
+}
+
+/// Turn the always-on option on or off for an account.
+pub async fn set_irc_always_on(
+    pool: &SqlitePool,
+    user_id: &str,
+    enabled: bool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE users SET irc_always_on = ?, updated_at = datetime('now') WHERE id = ?")
+        .bind(enabled)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// The name an account is known by on IRC: its Bluesky DID if it signed in
+/// with one, else its username.
+pub async fn get_account_name(
+    pool: &SqlitePool,
+    user_id: &str,
+) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT COALESCE(\
+            (SELECT provider_id FROM oauth_accounts WHERE user_id = u.id AND provider = 'atproto'), \
+            u.username) \
+         FROM users u WHERE u.id = ?",
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -426,6 +539,61 @@ mod tests {
         assert_eq!(tokens.len(), 2);
     }
 
+    #[tokio::test]
+    async fn test_irc_cert_crud() {
+        let pool = setup_db().await;
+        create_test_user(&pool, "u1", "alice").await;
+
+        create_irc_cert(&pool, "c1", "u1", "ab12", Some("laptop"))
+            .await
+            .unwrap();
+
+        let certs = list_irc_certs(&pool, "u1").await.unwrap();
+        assert_eq!(certs.len(), 1);
+        assert_eq!(certs[0].1, "ab12");
+        assert!(certs[0].3.is_none());
+
+        let found = get_user_by_cert_fingerprint(&pool, "ab12").await.unwrap();
+        assert_eq!(found, Some(("u1".to_string(), "alice".to_string())));
+        let certs = list_irc_certs(&pool, "u1").await.unwrap();
+        assert!(certs[0].3.is_some()); // last_used set on lookup
+
+        assert!(
+            get_user_by_cert_fingerprint(&pool, "ffff")
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // Wrong owner cannot delete
+        assert!(!delete_irc_cert(&pool, "c1", "u2").await.unwrap());
+        assert!(delete_irc_cert(&pool, "c1", "u1").await.unwrap());
+        assert!(list_irc_certs(&pool, "u1").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_irc_cert_fingerprint_unique() {
+        let pool = setup_db().await;
+        create_test_user(&pool, "u1", "alice").await;
+        create_test_user(&pool, "u2", "bob").await;
+
+        create_irc_cert(&pool, "c1", "u1", "ab12", None).await.unwrap();
+        assert!(create_irc_cert(&pool, "c2", "u2", "ab12", None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_irc_always_on() {
+        let pool = setup_db().await;
+        create_test_user(&pool, "u1", "alice").await;
+
+        assert!(!get_irc_always_on(&pool, "u1").await.unwrap());
+        set_irc_always_on(&pool, "u1", true).await.unwrap();
+        assert!(get_irc_always_on(&pool, "u1").await.unwrap());
+        set_irc_always_on(&pool, "u1", false).await.unwrap();
+        assert!(!get_irc_always_on(&pool, "u1").await.unwrap());
+        assert!(!get_irc_always_on(&pool, "nobody").await.unwrap());
+    }
+
     #[tokio::test]
     async fn test_atproto_credentials() {
         let pool = setup_db().await;
@@ -471,6 +639,32 @@ mod tests {
         assert_eq!(c.pds_url, "https://pds.example.com");
     }
 
+    #[tokio::test]
+    async fn test_get_account_name() {
+        let pool = setup_db().await;
+        create_test_user(&pool, "u1", "alice").await;
+        create_with_oauth(
+            &pool,
+            &CreateOAuthUser {
+                user_id: "u2",
+                username: "bob",
+                email: None,
+                avatar_url: None,
+                oauth_id: "oauth-at2",
+                provider: "atproto",
+                provider_id: "did:plc:bob",
+            },
+        )
+        .await
+        .unwrap();
+
+        let name = get_account_name(&pool, "u1").await.unwrap();
+        assert_eq!(name.as_deref(), Some("alice"));
+        let name = get_account_name(&pool, "u2").await.unwrap();
+        assert_eq!(name.as_deref(), Some("did:plc:bob"));
+        assert!(get_account_name(&pool, "nobody").await.unwrap().is_none());
+    }
+
     #[tokio::test]
     async fn test_irc_token_no_label() {
         let pool = setup_db().await;
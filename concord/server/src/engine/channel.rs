@@ -26,10 +26,14 @@ pub struct ChannelState {
     pub channel_type: String,
     /// For threads: the message ID this thread was created from.
     pub thread_parent_message_id: Option<String>,
+    /// For threads: the channel the parent message is in.
+    pub thread_parent_channel_id: Option<String>,
     /// Auto-archive duration in minutes (default 1440 = 24h).
     pub auto_archive_minutes: i32,
     /// Whether this channel/thread is archived.
     pub archived: bool,
+    /// Whether @everyone is denied SEND_MESSAGES here (IRC mode +m).
+    pub moderated: bool,
 }
 
 impl ChannelState {
@@ -48,8 +52,10 @@ impl ChannelState {
             is_private: false,
             channel_type: "text".to_string(),
             thread_parent_message_id: None,
+            thread_parent_channel_id: None,
             auto_archive_minutes: 1440,
             archived: false,
+            moderated: false,
         }
     }
 
@@ -1,4 +1,7 @@
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
 use chrono::Utc;
 use dashmap::DashMap;
@@ -10,15 +13,16 @@ use uuid::Uuid;
 use super::channel::ChannelState;
 use super::events::{
     AuditLogEntry, AutomodRuleInfo, BanInfo, BookmarkInfo, BotTokenInfo, CategoryInfo,
-    ChannelFollowInfo, ChannelInfo, ChannelPositionInfo, ChatEvent, EventInfo, HistoryMessage,
-    InteractionInfo, InteractionResponseData, InviteInfo, MemberInfo, OAuth2AppInfo,
-    PinnedMessageInfo, ReactionGroup, ReplyInfo, RoleInfo, RsvpInfo, ServerCommunityInfo,
-    ServerInfo, SessionId, SlashCommandInfo, SlashCommandOption, TemplateInfo, ThreadInfo,
-    WebhookInfo,
+    ChannelFollowInfo, ChannelInfo, ChannelPositionInfo, ChatEvent, DmConversationInfo,
+    DmParticipant, EventInfo, GroupDmChange, GroupDmInfo, HistoryMessage, InteractionInfo,
+    InteractionResponseData, InviteInfo, MemberInfo, MentionInfo, MentionKind, MessageKind,
+    MessageRevisionInfo, OAuth2AppInfo, PinnedMessageInfo, ReactionGroup, ReplyInfo, RoleInfo,
+    RsvpInfo, ServerCommunityInfo, ServerInfo, SessionId, SlashCommandInfo, SlashCommandOption,
+    TemplateInfo, ThreadInfo, WebhookInfo,
 };
 use super::permissions::{
     self, ChannelOverride, DEFAULT_ADMIN, DEFAULT_EVERYONE, DEFAULT_MODERATOR, OverrideTargetType,
-    Permissions, ServerRole,
+    Permissions, ServerRole, member_prefixes,
 };
 use super::rate_limiter::RateLimiter;
 use super::server::ServerState;
@@ -30,6 +34,15 @@ use super::validation;
 /// IRC bare-channel operations will fail unless one is created by a user.
 pub const DEFAULT_SERVER_ID: &str = "default";
 
+/// How long a user must wait before knocking on the same channel again.
+const KNOCK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Most nicknames a session may monitor for coming online and going offline.
+pub const MONITOR_LIMIT: usize = 100;
+
+/// Most people a group DM can hold, its owner included.
+pub const MAX_GROUP_DM_PARTICIPANTS: usize = 10;
+
 /// Parameters for updating notification settings (avoids too-many-arguments).
 pub struct UpdateNotificationSettingsParams<'a> {
     pub server_id: &'a str,
@@ -41,6 +54,128 @@ pub struct UpdateNotificationSettingsParams<'a> {
     pub mute_until: Option<&'a str>,
 }
 
+/// What became of a knock on a channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KnockOutcome {
+    /// The channel's moderators were asked to let the user in.
+    Delivered,
+    /// The user already knocked on this channel within `KNOCK_INTERVAL`.
+    TooSoon,
+    /// The user may already join the channel.
+    Open,
+    /// The user is already in the channel.
+    Joined,
+}
+
+/// A point in a conversation that a history query is anchored to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HistoryAnchor {
+    MessageId(String),
+    Timestamp(chrono::DateTime<Utc>),
+}
+
+/// Which slice of a conversation to fetch, relative to anchors
+/// (mirrors the IRCv3 CHATHISTORY subcommands).
+#[derive(Debug, Clone, PartialEq)]
+pub enum HistoryQuery {
+    /// The newest messages, optionally only those after an anchor.
+    Latest(Option<HistoryAnchor>),
+    /// The newest messages before an anchor.
+    Before(HistoryAnchor),
+    /// The oldest messages after an anchor.
+    After(HistoryAnchor),
+    /// Messages on both sides of an anchor, including it.
+    Around(HistoryAnchor),
+    /// Messages between two anchors, counted from the first.
+    Between(HistoryAnchor, HistoryAnchor),
+}
+
+/// A conversation an IRC client keeps a read marker for.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MarkerTarget {
+    /// A channel, by server ID and channel name.
+    Channel(String, String),
+    /// The account's direct messages, which share one marker.
+    Direct,
+}
+
+/// The `read_states` conversation key of the shared direct-message marker.
+const DIRECT_READ_MARKER: &str = "@direct";
+
+/// A conversation with recent activity, as listed by CHATHISTORY TARGETS.
+#[derive(Debug, Clone)]
+pub struct HistoryTarget {
+    /// Server of a channel target; None for direct messages.
+    pub server_id: Option<String>,
+    /// Channel name, or the other user's nickname for direct messages.
+    pub target: String,
+    pub latest: chrono::DateTime<Utc>,
+}
+
+/// Moderation state of a channel, as reported to IRC clients through channel modes.
+#[derive(Debug, Clone)]
+pub struct ChannelModeration {
+    pub is_private: bool,
+    /// @everyone is denied SEND_MESSAGES, so only members holding a role that grants it may speak.
+    pub moderated: bool,
+    pub created_at: chrono::DateTime<Utc>,
+}
+
+/// Connection and channel counts reported by IRC LUSERS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LuserCounts {
+    /// Distinct people online (a user connected twice counts once).
+    pub users: usize,
+    /// Open sessions across all protocols.
+    pub clients: usize,
+    pub channels: usize,
+    pub servers: usize,
+}
+
+/// Counters for IRC operators' `STATS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServerStats {
+    pub irc_sessions: usize,
+    pub web_sessions: usize,
+    /// Always-on sessions with no client attached.
+    pub detached_sessions: usize,
+    pub channels: usize,
+    pub servers: usize,
+    /// Messages sent in the last minute.
+    pub messages_last_minute: usize,
+    pub uptime_secs: i64,
+}
+
+/// Everything needed to work out members' status prefixes in one channel,
+/// loaded once so a whole member list costs a handful of queries.
+struct PrefixContext {
+    everyone_role_id: String,
+    base: Permissions,
+    overrides: Vec<ChannelOverride>,
+    role_permissions: HashMap<String, Permissions>,
+    user_roles: HashMap<String, Vec<String>>,
+}
+
+impl Default for PrefixContext {
+    fn default() -> Self {
+        Self {
+            everyone_role_id: String::new(),
+            base: DEFAULT_EVERYONE,
+            overrides: Vec::new(),
+            role_permissions: HashMap::new(),
+            user_roles: HashMap::new(),
+        }
+    }
+}
+
+/// A live member's status prefixes in one channel, for spotting rank changes.
+struct PrefixSnapshot {
+    channel_id: String,
+    channel: String,
+    nickname: String,
+    prefixes: String,
+}
+
 /// The central hub that manages all chat state. Protocol-agnostic —
 /// both IRC and WebSocket adapters call into this.
 pub struct ChatEngine {
@@ -52,7 +187,7 @@ pub struct ChatEngine {
     channels: DashMap<String, ChannelState>,
     /// Index: (server_id, channel_name) -> channel_id for name-based lookups.
     channel_name_index: DashMap<(String, String), String>,
-    /// Reverse lookup: nickname -> session ID (for DMs and WHOIS).
+    /// Reverse lookup: case-folded nickname -> session ID (for DMs and WHOIS).
     nick_to_session: DashMap<String, SessionId>,
     /// Optional database pool. When present, messages and channels are persisted.
     db: Option<SqlitePool>,
@@ -60,6 +195,17 @@ pub struct ChatEngine {
     message_limiter: RateLimiter,
     /// HTTP client for outbound requests (link embed unfurling).
     http_client: reqwest::Client,
+    /// Instance-wide message of the day, shown to IRC clients unless a server overrides it.
+    motd: Option<String>,
+    /// Most channels an IRC client may be in at once, if limited.
+    irc_channel_limit: Option<usize>,
+    /// When each (user ID, channel ID) last knocked, to limit knocks per `KNOCK_INTERVAL`.
+    knocks: DashMap<(String, String), Instant>,
+    /// Case-folded nicknames each session monitors for coming online and going offline.
+    monitors: DashMap<SessionId, HashSet<String>>,
+    /// Messages sent in each of the last 60 seconds, for the message rate in `STATS`.
+    message_rate: MessageRate,
+    started_at: chrono::DateTime<Utc>,
 }
 
 impl ChatEngine {
@@ -73,9 +219,32 @@ impl ChatEngine {
             db,
             message_limiter: RateLimiter::new(10, 1.0),
             http_client: reqwest::Client::new(),
+            motd: None,
+            irc_channel_limit: None,
+            knocks: DashMap::new(),
+            monitors: DashMap::new(),
+            message_rate: MessageRate::new(),
+            started_at: Utc::now(),
         }
     }
 
+    /// Set the instance-wide message of the day.
+    pub fn with_motd(mut self, motd: Option<String>) -> Self {
+        self.motd = motd.filter(|m| !m.trim().is_empty());
+        self
+    }
+
+    /// Limit how many channels an IRC client may be in at once (0 for no limit).
+    pub fn with_irc_channel_limit(mut self, limit: usize) -> Self {
+        self.irc_channel_limit = (limit > 0).then_some(limit);
+        self
+    }
+
+    /// Most channels an IRC client may be in at once, if limited.
+    pub fn irc_channel_limit(&self) -> Option<usize> {
+        self.irc_channel_limit
+    }
+
     // ── Startup loading ─────────────────────────────────────────────
 
     /// Load servers from the database into memory on startup.
@@ -168,6 +337,10 @@ impl ChatEngine {
                     ChannelState::new(row.id.clone(), row.server_id.clone(), row.name.clone());
                 ch.topic = row.topic;
                 ch.topic_set_by = row.topic_set_by;
+                ch.topic_set_at = row.topic_set_at.as_deref().and_then(parse_db_timestamp);
+                if let Some(created_at) = parse_db_timestamp(&row.created_at) {
+                    ch.created_at = created_at;
+                }
                 ch.category_id = row.category_id;
                 ch.position = row.position;
                 ch.is_private = row.is_private != 0;
@@ -185,6 +358,28 @@ impl ChatEngine {
 
                 self.channels.insert(row.id, ch);
             }
+
+            // Threads only record their parent message; find the channel it's in
+            let parents = crate::db::queries::threads::get_thread_parent_channels(pool, server_id)
+                .await
+                .map_err(|e| format!("Failed to load thread parents: {e}"))?;
+            for (thread_id, parent_id) in parents {
+                if let Some(mut ch) = self.channels.get_mut(&thread_id) {
+                    ch.thread_parent_channel_id = Some(parent_id);
+                }
+            }
+
+            let overrides = crate::db::queries::channels::get_default_role_overrides(pool, server_id)
+                .await
+                .map_err(|e| format!("Failed to load channel overrides: {e}"))?;
+            for o in overrides {
+                if Permissions::from_bits_truncate(o.deny_bits as u64)
+                    .contains(Permissions::SEND_MESSAGES)
+                    && let Some(mut ch) = self.channels.get_mut(&o.channel_id)
+                {
+                    ch.moderated = true;
+                }
+            }
         }
 
         info!(count = self.channels.len(), "loaded channels from database");
@@ -203,11 +398,29 @@ impl ChatEngine {
     ) -> Result<(SessionId, mpsc::Receiver<ChatEvent>), String> {
         validation::validate_nickname(&nickname)?;
 
-        // If nickname is already in use, disconnect the stale session.
-        if let Some(old_session_id) = self.nick_to_session.get(&nickname).map(|r| *r) {
-            info!(%nickname, "replacing stale session for reconnecting user");
-            self.disconnect(old_session_id);
-        }
+        // An account may be connected from several clients at once; they all
+        // share the nick of its first session. Anyone else holding the nick is
+        // a stale session and is replaced.
+        let siblings = user_id
+            .as_deref()
+            .map(|uid| self.user_session_ids(uid))
+            .unwrap_or_default();
+        let nickname = match siblings.first().and_then(|sid| self.get_session(*sid)) {
+            Some(sibling) => sibling.nickname.clone(),
+            None => {
+                if let Some(old_session_id) =
+                    self.nick_to_session.get(&nick_key(&nickname)).map(|r| *r)
+                {
+                    info!(%nickname, "replacing stale session for reconnecting user");
+                    // Marked detached so an always-on account doesn't keep it
+                    if let Some(old) = self.get_session(old_session_id) {
+                        old.detach();
+                    }
+                    self.disconnect(old_session_id);
+                }
+                nickname
+            }
+        };
 
         let session_id = Uuid::new_v4();
         let (tx, rx) = mpsc::channel(crate::engine::user_session::MAX_OUTBOUND_QUEUE);
@@ -221,14 +434,20 @@ impl ChatEngine {
             avatar_url,
         ));
 
-        // Capture user_id before moving session into the map
         let session_user_id = session.user_id.clone();
 
-        self.sessions.insert(session_id, session);
-        self.nick_to_session.insert(nickname.clone(), session_id);
+        self.sessions.insert(session_id, session.clone());
+        self.nick_to_session.insert(nick_key(&nickname), session_id);
 
-        // Update presence to online
-        if let (Some(uid), Some(pool)) = (&session_user_id, &self.db) {
+        if siblings.is_empty() {
+            self.notify_monitors(&nickname, true);
+        } else {
+            self.inherit_channels(&session, &siblings);
+            self.drop_detached(&siblings);
+        }
+
+        // Update presence to online (another client may already have set it)
+        if let (Some(uid), Some(pool), true) = (&session_user_id, &self.db, siblings.is_empty()) {
             let pool = pool.clone();
             let uid = uid.clone();
             tokio::spawn(async move {
@@ -244,14 +463,205 @@ impl ChatEngine {
         Ok((session_id, rx))
     }
 
-    /// Disconnect a session and clean up all state.
+    /// Live sessions belonging to a user account.
+    fn user_session_ids(&self, user_id: &str) -> Vec<SessionId> {
+        self.sessions
+            .iter()
+            .filter(|s| s.user_id.as_deref() == Some(user_id))
+            .map(|s| s.id)
+            .collect()
+    }
+
+    /// A session together with the other sessions of the same account. Guests
+    /// have only the one.
+    fn sibling_session_ids(&self, session: &UserSession) -> Vec<SessionId> {
+        match &session.user_id {
+            Some(uid) => self.user_session_ids(uid),
+            None => vec![session.id],
+        }
+    }
+
+    /// The nickname an account's live sessions share, if it's connected.
+    pub fn nick_of_user(&self, user_id: &str) -> Option<String> {
+        self.sessions
+            .iter()
+            .find(|s| s.user_id.as_deref() == Some(user_id))
+            .map(|s| s.nickname.clone())
+    }
+
+    /// Put a newly connected session in every channel its account's other
+    /// sessions are in, as if it had joined them itself. The channels don't
+    /// see a join: the user was already there.
+    fn inherit_channels(&self, session: &UserSession, siblings: &[SessionId]) {
+        let joined: Vec<(String, String, String)> = self
+            .channels
+            .iter()
+            .filter(|ch| siblings.iter().any(|sid| ch.members.contains(sid)))
+            .map(|ch| (ch.id.clone(), ch.server_id.clone(), ch.name.clone()))
+            .collect();
+
+        for (channel_id, server_id, channel_name) in joined {
+            if let Some(mut channel) = self.channels.get_mut(&channel_id) {
+                channel.members.insert(session.id);
+            }
+            let _ = session.send(ChatEvent::Join {
+                nickname: session.nickname.clone(),
+                server_id: server_id.clone(),
+                channel: channel_name.clone(),
+                avatar_url: session.avatar_url.clone(),
+            });
+            self.send_channel_state(session, &server_id, &channel_id, &channel_name);
+        }
+    }
+
+    /// Quietly remove the detached session an always-on account kept while
+    /// no client was connected; a client has taken over its place.
+    fn drop_detached(&self, session_ids: &[SessionId]) {
+        for sid in session_ids {
+            if self
+                .sessions
+                .remove_if(sid, |_, s| s.is_detached())
+                .is_none()
+            {
+                continue;
+            }
+            for mut channel in self.channels.iter_mut() {
+                channel.members.remove(sid);
+            }
+            self.monitors.remove(sid);
+            info!(session_id = %sid, "detached session taken over");
+        }
+    }
+
+    /// Send a session that just joined a channel its topic and member list.
+    fn send_channel_state(
+        &self,
+        session: &UserSession,
+        server_id: &str,
+        channel_id: &str,
+        channel_name: &str,
+    ) {
+        let Some(topic) = self.channels.get(channel_id).map(|ch| ch.topic.clone()) else {
+            return;
+        };
+        if !topic.is_empty() {
+            let _ = session.send(ChatEvent::Topic {
+                server_id: server_id.to_string(),
+                channel: channel_name.to_string(),
+                topic,
+            });
+        }
+
+        let members = self.channel_member_infos(server_id, channel_id);
+        let _ = session.send(ChatEvent::Names {
+            server_id: server_id.to_string(),
+            channel: channel_name.to_string(),
+            members,
+        });
+    }
+
+    /// Record the account name a logged-in session goes by.
+    pub fn set_account(&self, session_id: SessionId, account: String) {
+        if let Some(session) = self.sessions.get(&session_id) {
+            session.set_account(account);
+        }
+    }
+
+    /// Ask for (or stop) echoes of a session's own messages.
+    pub fn set_echo_message(&self, session_id: SessionId, enabled: bool) {
+        if let Some(session) = self.sessions.get(&session_id) {
+            session.set_echo_message(enabled);
+        }
+    }
+
+    /// Whether an account asked to stay connected while none of its clients are.
+    fn is_always_on(&self, user_id: &str) -> bool {
+        let Some(pool) = &self.db else {
+            return false;
+        };
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current()
+                .block_on(crate::db::queries::users::get_irc_always_on(pool, user_id))
+        })
+        .unwrap_or_else(|e| {
+            warn!(error = %e, %user_id, "failed to read always-on setting");
+            false
+        })
+    }
+
+    /// Turn an account's always-on option on or off. Turning it off ends a
+    /// detached session the account may have left behind.
+    pub async fn set_always_on(&self, user_id: &str, enabled: bool) -> Result<(), String> {
+        let pool = self.db.as_ref().ok_or("No database configured")?;
+        crate::db::queries::users::set_irc_always_on(pool, user_id, enabled)
+            .await
+            .map_err(|e| format!("DB error: {e}"))?;
+
+        if !enabled {
+            let detached: Vec<SessionId> = self
+                .sessions
+                .iter()
+                .filter(|s| s.user_id.as_deref() == Some(user_id) && s.is_detached())
+                .map(|s| s.id)
+                .collect();
+            for sid in detached {
+                self.disconnect(sid);
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether an account has the always-on option turned on.
+    pub async fn always_on(&self, user_id: &str) -> Result<bool, String> {
+        let pool = self.db.as_ref().ok_or("No database configured")?;
+        crate::db::queries::users::get_irc_always_on(pool, user_id)
+            .await
+            .map_err(|e| format!("DB error: {e}"))
+    }
+
+    /// Disconnect a session and clean up all state. The last session of an
+    /// always-on account is detached instead: it keeps the account's nick,
+    /// channels and presence until a client takes it over.
     pub fn disconnect(&self, session_id: SessionId) {
+        self.end_session(session_id, None);
+    }
+
+    /// Disconnect a session, giving its channels `reason` if it was the
+    /// account's last.
+    fn end_session(&self, session_id: SessionId, reason: Option<String>) {
         let Some((_, session)) = self.sessions.remove(&session_id) else {
             return;
         };
+        self.monitors.remove(&session_id);
 
         let nickname = session.nickname.clone();
-        self.nick_to_session.remove(&nickname);
+        // The account's other clients, which keep its nick, channels and presence
+        let remaining = session
+            .user_id
+            .as_deref()
+            .map(|uid| self.user_session_ids(uid))
+            .unwrap_or_default();
+
+        // The last client of an always-on account leaves its session behind
+        if remaining.is_empty()
+            && !session.is_detached()
+            && let Some(uid) = &session.user_id
+            && self.is_always_on(uid)
+        {
+            session.detach();
+            self.sessions.insert(session_id, session);
+            info!(%session_id, %nickname, "session detached (always-on)");
+            return;
+        }
+        match remaining.first() {
+            Some(&other) => {
+                self.nick_to_session.insert(nick_key(&nickname), other);
+            }
+            None => {
+                self.nick_to_session
+                    .remove_if(&nick_key(&nickname), |_, sid| *sid == session_id);
+            }
+        }
 
         // Collect channels this session was in
         let channels_to_leave: Vec<String> = self
@@ -267,46 +677,46 @@ impl ChatEngine {
             }
         }
 
+        if !remaining.is_empty() {
+            info!(%session_id, %nickname, "session disconnected (account still connected)");
+            return;
+        }
+
         // Broadcast quit to all channels this user was in
         let quit_event = ChatEvent::Quit {
             nickname: nickname.clone(),
-            reason: None,
+            reason,
         };
 
         for channel_id in &channels_to_leave {
             self.broadcast_to_channel(channel_id, &quit_event, Some(session_id));
         }
+        self.notify_monitors(&nickname, false);
 
-        // Update presence if this was the last session for this user
+        // This was the user's last session, so they're now offline
         if let Some(ref uid) = session.user_id {
-            let other_sessions = self
-                .sessions
-                .iter()
-                .any(|s| s.key() != &session_id && s.user_id.as_deref() == Some(uid));
-            if !other_sessions {
-                if let Some(pool) = &self.db {
-                    let _ = tokio::task::block_in_place(|| {
-                        tokio::runtime::Handle::current()
-                            .block_on(crate::db::queries::presence::set_offline(pool, uid))
-                    });
-                }
-                // Broadcast offline to shared servers
-                for server in self.servers.iter() {
-                    if server.member_user_ids.contains(uid) {
-                        let event = ChatEvent::PresenceUpdate {
-                            server_id: server.id.clone(),
-                            presence: super::events::PresenceInfo {
-                                user_id: uid.clone(),
-                                nickname: session.nickname.clone(),
-                                avatar_url: session.avatar_url.clone(),
-                                status: "offline".into(),
-                                custom_status: None,
-                                status_emoji: None,
-                            },
-                        };
-                        for channel_id in server.channel_ids.iter() {
-                            self.broadcast_to_channel(channel_id, &event, Some(session_id));
-                        }
+            if let Some(pool) = &self.db {
+                let _ = tokio::task::block_in_place(|| {
+                    tokio::runtime::Handle::current()
+                        .block_on(crate::db::queries::presence::set_offline(pool, uid))
+                });
+            }
+            // Broadcast offline to shared servers
+            for server in self.servers.iter() {
+                if server.member_user_ids.contains(uid) {
+                    let event = ChatEvent::PresenceUpdate {
+                        server_id: server.id.clone(),
+                        presence: super::events::PresenceInfo {
+                            user_id: uid.clone(),
+                            nickname: session.nickname.clone(),
+                            avatar_url: session.avatar_url.clone(),
+                            status: "offline".into(),
+                            custom_status: None,
+                            status_emoji: None,
+                        },
+                    };
+                    for channel_id in server.channel_ids.iter() {
+                        self.broadcast_to_channel(channel_id, &event, Some(session_id));
                     }
                 }
             }
@@ -418,6 +828,15 @@ impl ChatEngine {
         if let Some(server) = self.servers.get(server_id) {
             for ch_id in &server.channel_ids {
                 if let Some((_, ch)) = self.channels.remove(ch_id) {
+                    // Members' clients leave the channel along with it
+                    for member in ch.members.iter().filter_map(|sid| self.get_session(*sid)) {
+                        let _ = member.send(ChatEvent::Part {
+                            nickname: member.nickname.clone(),
+                            server_id: server_id.to_string(),
+                            channel: ch.name.clone(),
+                            reason: Some("Server deleted".into()),
+                        });
+                    }
                     self.channel_name_index
                         .remove(&(server_id.to_string(), ch.name));
                 }
@@ -683,7 +1102,10 @@ impl ChatEngine {
         channel_name: &str,
     ) -> Result<(), String> {
         let channel_name = normalize_channel_name(channel_name);
-        validation::validate_channel_name(&channel_name)?;
+        // Thread names needn't be valid channel names, so only new channels are checked
+        if self.resolve_channel_id(server_id, &channel_name).is_err() {
+            validation::validate_channel_name(&channel_name)?;
+        }
 
         let session = self
             .sessions
@@ -691,6 +1113,8 @@ impl ChatEngine {
             .ok_or("Session not found")?
             .clone();
 
+        self.check_server_access(&session, server_id)?;
+
         // Check private channel access control
         if let Some(id) = self
             .channel_name_index
@@ -715,17 +1139,24 @@ impl ChatEngine {
                 });
                 if !has_view {
                     return Err(
-                        "You do not have permission to join this private channel"
-                            .into(),
+                        "FORBIDDEN: you do not have permission to join this private channel".into(),
                     );
                 }
             } else {
-                return Err(
-                    "Authentication required to join private channels".into(),
-                );
+                return Err("AUTH_REQUIRED".into());
             }
         }
 
+        self.enter_channel(&session, server_id, &channel_name);
+        Ok(())
+    }
+
+    /// Put a session and its account's other sessions in a channel, creating
+    /// the channel if needed. Access has already been checked.
+    fn enter_channel(&self, session: &UserSession, server_id: &str, channel_name: &str) {
+        let session_id = session.id;
+        let channel_name = channel_name.to_string();
+
         // Get or create channel
         let channel_id = if let Some(id) = self
             .channel_name_index
@@ -765,122 +1196,332 @@ impl ChatEngine {
             new_id
         };
 
-        // Add session to channel
+        // Membership belongs to the user, so their other sessions join too
+        let siblings = self.sibling_session_ids(session);
+        let mut already_present = false;
+        let mut joiners = vec![session_id];
         if let Some(mut channel) = self.channels.get_mut(&channel_id) {
-            channel.members.insert(session_id);
+            already_present = siblings.iter().any(|sid| channel.members.contains(sid));
+            for &sid in &siblings {
+                if channel.members.insert(sid) && sid != session_id {
+                    joiners.push(sid);
+                }
+            }
         }
 
-        // Broadcast join event
         let join_event = ChatEvent::Join {
             nickname: session.nickname.clone(),
             server_id: server_id.to_string(),
             channel: channel_name.clone(),
             avatar_url: session.avatar_url.clone(),
         };
-        self.broadcast_to_channel(&channel_id, &join_event, None);
-
-        // Send current topic to the joiner
-        if let Some(channel) = self.channels.get(&channel_id) {
-            if !channel.topic.is_empty() {
-                let _ = session.send(ChatEvent::Topic {
-                    server_id: server_id.to_string(),
-                    channel: channel_name.clone(),
-                    topic: channel.topic.clone(),
-                });
+        if already_present {
+            // The channel already sees this user; only their own sessions hear about it
+            for sid in &joiners {
+                if let Some(joiner) = self.get_session(*sid) {
+                    let _ = joiner.send(join_event.clone());
+                }
             }
+        } else {
+            self.broadcast_to_channel(&channel_id, &join_event, None);
+        }
 
-            // Send member list to the joiner
-            let members: Vec<MemberInfo> = channel
-                .members
-                .iter()
-                .filter_map(|sid| {
-                    self.sessions.get(sid).map(|s| MemberInfo {
-                        nickname: s.nickname.clone(),
-                        avatar_url: s.avatar_url.clone(),
-                        status: None,
-                        custom_status: None,
-                        status_emoji: None,
-                        user_id: s.user_id.clone(),
-                    })
-                })
-                .collect();
-
-            let _ = session.send(ChatEvent::Names {
-                server_id: server_id.to_string(),
-                channel: channel_name.clone(),
-                members,
-            });
+        for sid in &joiners {
+            if let Some(joiner) = self.get_session(*sid) {
+                self.send_channel_state(&joiner, server_id, &channel_id, &channel_name);
+            }
         }
 
         info!(nickname = %session.nickname, %server_id, %channel_name, "joined channel");
+    }
+
+    /// Registered users may only enter the channels of servers they belong to.
+    /// Anyone may use the default server, and guests aren't tracked as members.
+    /// Refusals start with `NOT_MEMBER` so protocols can offer a way in.
+    fn check_server_access(&self, session: &UserSession, server_id: &str) -> Result<(), String> {
+        if server_id != DEFAULT_SERVER_ID
+            && let Some(uid) = &session.user_id
+            && !self.user_is_server_member(server_id, uid)
+        {
+            return Err("NOT_MEMBER: you are not a member of this server".into());
+        }
         Ok(())
     }
 
-    /// Leave a channel.
-    pub fn part_channel(
+    /// How many channels a session is in.
+    pub fn channel_count(&self, session_id: SessionId) -> usize {
+        self.channels
+            .iter()
+            .filter(|ch| ch.members.contains(&session_id))
+            .count()
+    }
+
+    /// Whether a session is in a channel.
+    pub fn in_channel(&self, session_id: SessionId, server_id: &str, channel_name: &str) -> bool {
+        let channel_name = normalize_channel_name(channel_name);
+        self.resolve_channel_id(server_id, &channel_name)
+            .ok()
+            .and_then(|id| {
+                self.channels
+                    .get(&id)
+                    .map(|ch| ch.members.contains(&session_id))
+            })
+            .unwrap_or(false)
+    }
+
+    /// Invite a user into a channel. A user who isn't a member of the channel's
+    /// server yet is given a single-use invite code for it (CREATE_INVITES), and
+    /// a private channel is opened to them with a user override allowing
+    /// VIEW_CHANNELS (KICK_MEMBERS). Every session of the target is told.
+    /// Returns the invite code, if one was created.
+    pub async fn invite_to_channel(
         &self,
         session_id: SessionId,
         server_id: &str,
         channel_name: &str,
-        reason: Option<String>,
-    ) -> Result<(), String> {
+        target_user_id: &str,
+    ) -> Result<Option<String>, String> {
         let channel_name = normalize_channel_name(channel_name);
         let channel_id = self.resolve_channel_id(server_id, &channel_name)?;
+        let session = self.get_session(session_id).ok_or("Session not found")?;
 
-        let session = self
-            .sessions
-            .get(&session_id)
-            .ok_or("Session not found")?
-            .clone();
+        let is_private = self
+            .channels
+            .get(&channel_id)
+            .is_some_and(|ch| ch.is_private);
+        let needs_code = server_id != DEFAULT_SERVER_ID
+            && !self.user_is_server_member(server_id, target_user_id);
 
-        let mut found = false;
-        if let Some(mut channel) = self.channels.get_mut(&channel_id) {
-            found = channel.members.remove(&session_id);
+        // Check every permission before changing anything
+        if is_private {
+            self.require_permission(
+                session_id,
+                server_id,
+                Some(&channel_id),
+                Permissions::KICK_MEMBERS,
+            )
+            .await?;
         }
+        let inviter_id = if needs_code {
+            Some(
+                self.require_permission(session_id, server_id, None, Permissions::CREATE_INVITES)
+                    .await?,
+            )
+        } else {
+            None
+        };
 
-        if !found {
-            return Err(format!("Not in channel {channel_name}"));
+        if is_private {
+            let Some(pool) = &self.db else {
+                return Err("No database configured".into());
+            };
+            allow_user_view(pool, &channel_id, target_user_id)
+                .await
+                .map_err(|e| format!("Failed to update channel override: {e}"))?;
         }
 
-        let part_event = ChatEvent::Part {
-            nickname: session.nickname.clone(),
+        let code = match inviter_id {
+            Some(inviter_id) => Some(
+                self.store_invite(server_id, &inviter_id, Some(1), None, Some(&channel_id))
+                    .await?
+                    .code,
+            ),
+            None => None,
+        };
+
+        let event = ChatEvent::ChannelInvite {
             server_id: server_id.to_string(),
             channel: channel_name.clone(),
-            reason,
+            invited_by: session.nickname.clone(),
+            code: code.clone(),
         };
-        let _ = session.send(part_event.clone());
-        self.broadcast_to_channel(&channel_id, &part_event, Some(session_id));
-
-        // Remove empty channels from memory (but not from DB)
-        self.channels
-            .remove_if(&channel_id, |_, ch| ch.members.is_empty());
+        for sid in self.user_session_ids(target_user_id) {
+            if let Some(target) = self.get_session(sid) {
+                let _ = target.send(event.clone());
+            }
+        }
 
-        info!(nickname = %session.nickname, %server_id, %channel_name, "parted channel");
-        Ok(())
+        info!(inviter = %session.nickname, %server_id, %channel_name, "invited user to channel");
+        Ok(code)
     }
 
-    /// Send a message to a channel or user (DM), with optional reply and attachments.
-    pub fn send_message(
+    /// Ask to be let into a channel the user can't join. Its live members who
+    /// may kick are sent a `ChannelKnock`. A user may knock on a channel once
+    /// per `KNOCK_INTERVAL`.
+    pub async fn knock(
         &self,
         session_id: SessionId,
         server_id: &str,
-        target: &str,
-        content: &str,
-        reply_to_id: Option<&str>,
-        attachment_ids: Option<&[String]>,
-    ) -> Result<(), String> {
-        validation::validate_message(content)?;
-        let content = &validation::sanitize_html(content);
-
-        let session = self
-            .sessions
-            .get(&session_id)
-            .ok_or("Session not found")?
-            .clone();
-
-        if !self.message_limiter.check(&session.nickname) {
-            return Err("Rate limit exceeded. Please slow down.".into());
-        }
+        channel_name: &str,
+        message: Option<&str>,
+    ) -> Result<KnockOutcome, String> {
+        let channel_name = normalize_channel_name(channel_name);
+        let channel_id = self.resolve_channel_id(server_id, &channel_name)?;
+        let session = self.get_session(session_id).ok_or("Session not found")?;
+        let user_id = session.user_id.clone().ok_or("AUTH_REQUIRED")?;
+
+        let (is_private, members) = self
+            .channels
+            .get(&channel_id)
+            .map(|ch| {
+                (
+                    ch.is_private,
+                    ch.members.iter().copied().collect::<Vec<_>>(),
+                )
+            })
+            .ok_or("Channel not found")?;
+        if members.contains(&session_id) {
+            return Ok(KnockOutcome::Joined);
+        }
+        if self.check_server_access(&session, server_id).is_ok()
+            && (!is_private
+                || self
+                    .get_effective_permissions(server_id, Some(&channel_id), &user_id)
+                    .await
+                    .contains(Permissions::VIEW_CHANNELS))
+        {
+            return Ok(KnockOutcome::Open);
+        }
+
+        let now = Instant::now();
+        self.knocks
+            .retain(|_, at| now.duration_since(*at) < KNOCK_INTERVAL);
+        let key = (user_id, channel_id.clone());
+        if self.knocks.contains_key(&key) {
+            return Ok(KnockOutcome::TooSoon);
+        }
+        self.knocks.insert(key, now);
+
+        let event = ChatEvent::ChannelKnock {
+            server_id: server_id.to_string(),
+            channel: channel_name.clone(),
+            nickname: session.nickname.clone(),
+            message: message.map(String::from),
+        };
+        for sid in members {
+            let Some(member) = self.get_session(sid) else {
+                continue;
+            };
+            let Some(uid) = &member.user_id else {
+                continue;
+            };
+            if self
+                .get_effective_permissions(server_id, Some(&channel_id), uid)
+                .await
+                .contains(Permissions::KICK_MEMBERS)
+            {
+                let _ = member.send(event.clone());
+            }
+        }
+
+        info!(nickname = %session.nickname, %server_id, %channel_name, "knocked on channel");
+        Ok(KnockOutcome::Delivered)
+    }
+
+    /// Leave a channel.
+    pub fn part_channel(
+        &self,
+        session_id: SessionId,
+        server_id: &str,
+        channel_name: &str,
+        reason: Option<String>,
+    ) -> Result<(), String> {
+        let channel_name = normalize_channel_name(channel_name);
+        let channel_id = self.resolve_channel_id(server_id, &channel_name)?;
+
+        let session = self
+            .sessions
+            .get(&session_id)
+            .ok_or("Session not found")?
+            .clone();
+
+        // The user's other sessions leave along with this one
+        let mut parted = Vec::new();
+        if let Some(mut channel) = self.channels.get_mut(&channel_id)
+            && channel.members.contains(&session_id)
+        {
+            for sid in self.sibling_session_ids(&session) {
+                if channel.members.remove(&sid) {
+                    parted.push(sid);
+                }
+            }
+        }
+
+        if parted.is_empty() {
+            return Err(format!("Not in channel {channel_name}"));
+        }
+
+        let part_event = ChatEvent::Part {
+            nickname: session.nickname.clone(),
+            server_id: server_id.to_string(),
+            channel: channel_name.clone(),
+            reason,
+        };
+        for sid in &parted {
+            if let Some(s) = self.get_session(*sid) {
+                let _ = s.send(part_event.clone());
+            }
+        }
+        self.broadcast_to_channel(&channel_id, &part_event, None);
+
+        // Remove empty channels from memory (but not from DB)
+        self.channels
+            .remove_if(&channel_id, |_, ch| ch.members.is_empty());
+
+        info!(nickname = %session.nickname, %server_id, %channel_name, "parted channel");
+        Ok(())
+    }
+
+    /// Send a message to a channel or user (DM), with optional reply and attachments.
+    pub fn send_message(
+        &self,
+        session_id: SessionId,
+        server_id: &str,
+        target: &str,
+        content: &str,
+        reply_to_id: Option<&str>,
+        attachment_ids: Option<&[String]>,
+    ) -> Result<(), String> {
+        self.send_message_as(
+            session_id,
+            server_id,
+            target,
+            content,
+            reply_to_id,
+            attachment_ids,
+            MessageKind::Normal,
+        )
+    }
+
+    /// Send a message of a particular kind: a `/me` action or a notice
+    /// rather than ordinary text.
+    #[allow(clippy::too_many_arguments)]
+    pub fn send_message_as(
+        &self,
+        session_id: SessionId,
+        server_id: &str,
+        target: &str,
+        content: &str,
+        reply_to_id: Option<&str>,
+        attachment_ids: Option<&[String]>,
+        kind: MessageKind,
+    ) -> Result<(), String> {
+        validation::validate_message(content)?;
+        let content = &validation::sanitize_html(content);
+        if kind == MessageKind::System {
+            return Err("System messages can't be sent".into());
+        }
+
+        let session = self
+            .sessions
+            .get(&session_id)
+            .ok_or("Session not found")?
+            .clone();
+
+        if !self.message_limiter.check(&session.nickname) {
+            return Err("Rate limit exceeded. Please slow down.".into());
+        }
+        self.message_rate.record(Instant::now());
 
         // Enforce timeout: timed-out users cannot send messages
         if let Some(pool) = &self.db
@@ -947,81 +1588,33 @@ impl ChatEngine {
             }
         }
 
-        // Evaluate automod rules (keyword, mention_spam, link_filter)
-        if let Some(pool) = &self.db {
-            let pool = pool.clone();
+        // Enforce SEND_MESSAGES in moderated channels, which deny it to @everyone
+        if target.starts_with('#')
+            && self.db.is_some()
+            && let Ok(channel_id) = self.resolve_channel_id(server_id, target)
+            && self.channels.get(&channel_id).is_some_and(|ch| ch.moderated)
+        {
             let srv = server_id.to_string();
-            let content_clone = content.to_string();
-            let automod_err = tokio::task::block_in_place(|| {
+            let uid = session.user_id.clone().unwrap_or_default();
+            let can_send = tokio::task::block_in_place(|| {
                 tokio::runtime::Handle::current().block_on(async {
-                    let rules = crate::db::queries::automod::get_enabled_rules(&pool, &srv)
+                    self.get_effective_permissions(&srv, Some(&channel_id), &uid)
                         .await
-                        .unwrap_or_default();
-                    for rule in rules {
-                        let triggered = match rule.rule_type.as_str() {
-                            "keyword" => {
-                                // Config: {"words":["bad","spam"]}
-                                if let Ok(config) = serde_json::from_str::<serde_json::Value>(&rule.config) {
-                                    if let Some(words) = config.get("words").and_then(|w| w.as_array()) {
-                                        let lower = content_clone.to_lowercase();
-                                        words.iter().any(|w| {
-                                            w.as_str()
-                                                .is_some_and(|kw| lower.contains(&kw.to_lowercase()))
-                                        })
-                                    } else {
-                                        false
-                                    }
-                                } else {
-                                    false
-                                }
-                            }
-                            "mention_spam" => {
-                                // Config: {"max_mentions":5}
-                                if let Ok(config) = serde_json::from_str::<serde_json::Value>(&rule.config) {
-                                    let max = config
-                                        .get("max_mentions")
-                                        .and_then(|m| m.as_i64())
-                                        .unwrap_or(5) as usize;
-                                    let mention_count = content_clone.matches('@').count();
-                                    mention_count > max
-                                } else {
-                                    false
-                                }
-                            }
-                            "link_filter" => {
-                                // Config: {"block_all":true}
-                                if let Ok(config) = serde_json::from_str::<serde_json::Value>(&rule.config) {
-                                    let block_all = config
-                                        .get("block_all")
-                                        .and_then(|b| b.as_bool())
-                                        .unwrap_or(false);
-                                    if block_all {
-                                        content_clone.contains("http://")
-                                            || content_clone.contains("https://")
-                                    } else {
-                                        false
-                                    }
-                                } else {
-                                    false
-                                }
-                            }
-                            _ => false,
-                        };
-                        if triggered {
-                            return Some(format!(
-                                "Message blocked by automod rule: {}",
-                                rule.name
-                            ));
-                        }
-                    }
-                    None
+                        .contains(Permissions::SEND_MESSAGES)
                 })
             });
-            if let Some(err) = automod_err {
-                return Err(err);
+            if !can_send {
+                return Err("You do not have permission to send messages in this channel".into());
             }
         }
 
+        // Evaluate automod rules (keyword, mention_spam, link_filter)
+        if self.db.is_some() {
+            tokio::task::block_in_place(|| {
+                tokio::runtime::Handle::current().block_on(self.check_automod(server_id, content))
+            })?;
+        }
+
         // Build reply info if replying to a message
         let reply_to: Option<ReplyInfo> = if let Some(ref_id) = reply_to_id {
             if let Some(pool) = &self.db {
@@ -1098,6 +1691,7 @@ impl ChatEngine {
             avatar_url: session.avatar_url.clone(),
             reply_to: reply_to.clone(),
             attachments: attachments.clone(),
+            kind,
         };
 
         if target.starts_with('#') {
@@ -1115,11 +1709,31 @@ impl ChatEngine {
 
             drop(channel);
 
+            // Work out who the message mentions before it goes out, so their
+            // inbox entries are stored along with it
+            let mentions: Vec<(String, String, MentionKind)> = if self.db.is_some() {
+                let sender_id = session.user_id.clone();
+                tokio::task::block_in_place(|| {
+                    tokio::runtime::Handle::current().block_on(self.resolve_mentions(
+                        server_id,
+                        &channel_id,
+                        sender_id.as_deref(),
+                        content,
+                    ))
+                })
+                .into_iter()
+                .map(|(user_id, kind)| (Uuid::new_v4().to_string(), user_id, kind))
+                .collect()
+            } else {
+                vec![]
+            };
+
             if let Some(pool) = &self.db {
                 let pool = pool.clone();
                 let id = msg_id.to_string();
                 let srv = server_id.to_string();
                 let ch = channel_id.clone();
+                let stored_mentions = mentions.clone();
                 let sid = session_id.to_string();
                 let nick = session.nickname.clone();
                 let uid = session.user_id.clone().unwrap_or_else(|| sid.clone());
@@ -1131,15 +1745,38 @@ impl ChatEngine {
                         id: &id,
                         server_id: &srv,
                         channel_id: &ch,
-                        sender_id: &sid,
+                        sender_id: &uid,
                         sender_nick: &nick,
                         content: &msg,
                         reply_to_id: reply_id.as_deref(),
+                        kind: kind.as_str(),
                     };
                     if let Err(e) =
                         crate::db::queries::messages::insert_message(&pool, &params).await
                     {
                         error!(error = %e, "failed to persist message");
+                    } else if !stored_mentions.is_empty() {
+                        let new_mentions: Vec<_> = stored_mentions
+                            .iter()
+                            .map(|(mention_id, user_id, kind)| {
+                                crate::db::queries::mentions::NewMention {
+                                    id: mention_id,
+                                    user_id,
+                                    kind: kind.as_str(),
+                                }
+                            })
+                            .collect();
+                        if let Err(e) = crate::db::queries::mentions::insert_mentions(
+                            &pool,
+                            &id,
+                            &srv,
+                            &ch,
+                            &new_mentions,
+                        )
+                        .await
+                        {
+                            error!(error = %e, "failed to store mentions");
+                        }
                     }
                     // Link attachments to the message (use user_id, not session_id)
                     if let Some(att_ids) = att_ids
@@ -1154,7 +1791,36 @@ impl ChatEngine {
                 });
             }
 
-            self.broadcast_to_channel(&channel_id, &event, Some(session_id));
+            self.broadcast_to_channel(&channel_id, &event, session.echo_exclusion());
+
+            if !mentions.is_empty() {
+                let timestamp = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+                let by_user: HashMap<String, ChatEvent> = mentions
+                    .into_iter()
+                    .map(|(mention_id, user_id, kind)| {
+                        let mention = ChatEvent::Mention {
+                            mention: MentionInfo {
+                                id: mention_id,
+                                message_id: msg_id.to_string(),
+                                server_id: server_id.to_string(),
+                                channel: channel_name.clone(),
+                                from: session.nickname.clone(),
+                                content: content.to_string(),
+                                kind,
+                                timestamp: timestamp.clone(),
+                                read: false,
+                            },
+                        };
+                        (user_id, mention)
+                    })
+                    .collect();
+                // One pass over the sessions, however many were mentioned
+                for s in self.sessions.iter() {
+                    if let Some(mention) = s.user_id.as_ref().and_then(|uid| by_user.get(uid)) {
+                        let _ = s.send(mention.clone());
+                    }
+                }
+            }
 
             // Async link embed unfurling — extract URLs and resolve OG metadata
             let urls = super::embeds::extract_urls(content);
@@ -1219,38 +1885,138 @@ impl ChatEngine {
                     }
                 });
             }
+        } else if let Some(conversation_id) = target.strip_prefix('&') {
+            // Group DM
+            let sender_id = session.user_id.clone().ok_or("AUTH_REQUIRED")?;
+            let pool = self.db.as_ref().ok_or("No database configured")?;
+            // Offline participants only ever see the stored copy, so it's
+            // written before the send counts as done, as for one-to-one DMs
+            let participants = tokio::task::block_in_place(|| {
+                tokio::runtime::Handle::current().block_on(async {
+                    use crate::db::queries::direct_messages;
+                    let participants = self
+                        .group_dm_participant_ids(pool, conversation_id, &sender_id)
+                        .await?;
+                    direct_messages::insert_direct_message(
+                        pool,
+                        &direct_messages::InsertDirectMessageParams {
+                            id: &msg_id.to_string(),
+                            conversation_id,
+                            sender_id: &sender_id,
+                            sender_nick: &session.nickname,
+                            target_user_id: None,
+                            content,
+                            kind: kind.as_str(),
+                        },
+                    )
+                    .await
+                    .map_err(|e| format!("DB error: {e}"))?;
+                    Ok::<_, String>(participants)
+                })
+            })?;
+            self.send_to_users(&participants, &event, session.echo_exclusion());
         } else {
             // DM
-            let target_session_id = self
-                .nick_to_session
-                .get(target)
-                .ok_or(format!("No such user: {target}"))?;
+            let recipient = self.resolve_dm_recipient(target)?;
+            // Messages left for someone offline wait in a conversation, which
+            // only an account can have
+            if recipient.session_id.is_none() && session.user_id.is_none() {
+                return Err("AUTH_REQUIRED".into());
+            }
 
             if let Some(pool) = &self.db {
                 let pool = pool.clone();
                 let id = msg_id.to_string();
-                let sid = session_id.to_string();
                 let nick = session.nickname.clone();
-                let target_sid = target_session_id.value().to_string();
                 let msg = content.to_string();
-                tokio::spawn(async move {
-                    if let Err(e) = crate::db::queries::messages::insert_dm(
-                        &pool,
-                        &id,
-                        &sid,
-                        &nick,
-                        &target_sid,
-                        &msg,
-                    )
-                    .await
-                    {
-                        error!(error = %e, "failed to persist DM");
+                match (session.user_id.clone(), recipient.user_id.clone()) {
+                    // Between accounts, the message joins their conversation
+                    (Some(sender_id), Some(target_user_id)) => {
+                        let new_conversation_id = Uuid::new_v4().to_string();
+                        let persist = async move {
+                            use crate::db::queries::direct_messages;
+                            let conversation_id =
+                                direct_messages::get_or_create_direct_conversation(
+                                    &pool,
+                                    &new_conversation_id,
+                                    &sender_id,
+                                    &target_user_id,
+                                )
+                                .await?;
+                            direct_messages::insert_direct_message(
+                                &pool,
+                                &direct_messages::InsertDirectMessageParams {
+                                    id: &id,
+                                    conversation_id: &conversation_id,
+                                    sender_id: &sender_id,
+                                    sender_nick: &nick,
+                                    target_user_id: Some(&target_user_id),
+                                    content: &msg,
+                                    kind: kind.as_str(),
+                                },
+                            )
+                            .await
+                        };
+                        if recipient.session_id.is_none() {
+                            // The stored copy is all an offline recipient will
+                            // get, so it must be written, in order, before the
+                            // send counts as done
+                            tokio::task::block_in_place(|| {
+                                tokio::runtime::Handle::current().block_on(persist)
+                            })
+                            .map_err(|e| format!("DB error: {e}"))?;
+                        } else {
+                            tokio::spawn(async move {
+                                if let Err(e) = persist.await {
+                                    error!(error = %e, "failed to persist DM");
+                                }
+                            });
+                        }
                     }
-                });
+                    // A guest on either end is only addressable by session
+                    (sender_id, target_user_id) => {
+                        let sid = sender_id.unwrap_or_else(|| session.id.to_string());
+                        let target_sid = target_user_id
+                            .or_else(|| recipient.session_id.map(|s| s.to_string()))
+                            .unwrap_or_default();
+                        tokio::spawn(async move {
+                            if let Err(e) = crate::db::queries::messages::insert_dm(
+                                &pool,
+                                &id,
+                                &sid,
+                                &nick,
+                                &target_sid,
+                                &msg,
+                                kind.as_str(),
+                            )
+                            .await
+                            {
+                                error!(error = %e, "failed to persist DM");
+                            }
+                        });
+                    }
+                }
             }
 
-            if let Some(target_session) = self.sessions.get(target_session_id.value()) {
-                let _ = target_session.send(event);
+            // Every client of the recipient gets the DM, and so do the
+            // sender's other clients, so the conversation follows them around.
+            // An offline recipient finds it in their conversation list.
+            let mut recipients: HashSet<SessionId> =
+                match (&recipient.user_id, recipient.session_id) {
+                    (Some(uid), _) => self.user_session_ids(uid).into_iter().collect(),
+                    (None, Some(sid)) => HashSet::from([sid]),
+                    (None, None) => HashSet::new(),
+                };
+            let echo_exclusion = session.echo_exclusion();
+            recipients.extend(
+                self.sibling_session_ids(&session)
+                    .into_iter()
+                    .filter(|sid| Some(*sid) != echo_exclusion),
+            );
+            for sid in recipients {
+                if let Some(recipient) = self.sessions.get(&sid) {
+                    let _ = recipient.send(event.clone());
+                }
             }
         }
 
@@ -1341,6 +2107,15 @@ impl ChatEngine {
         let has_more = rows.len() as i64 > limit;
         let rows: Vec<_> = rows.into_iter().take(limit as usize).collect();
 
+        Ok((self.history_messages(pool, rows).await, has_more))
+    }
+
+    /// Attach reactions, reply previews and attachments to stored message rows.
+    async fn history_messages(
+        &self,
+        pool: &SqlitePool,
+        rows: Vec<crate::db::models::MessageRow>,
+    ) -> Vec<HistoryMessage> {
         // Collect message IDs for batch reaction lookup
         let msg_ids: Vec<String> = rows.iter().map(|r| r.id.clone()).collect();
 
@@ -1410,8 +2185,7 @@ impl ChatEngine {
             }
         }
 
-        let messages: Vec<HistoryMessage> = rows
-            .into_iter()
+        rows.into_iter()
             .map(|row| {
                 let reactions = reaction_map.get(&row.id).map(|emoji_map| {
                     emoji_map
@@ -1434,89 +2208,642 @@ impl ChatEngine {
                     id: row.id.parse().unwrap_or_default(),
                     from: row.sender_nick,
                     content: row.content,
-                    timestamp: row.created_at.parse().unwrap_or_else(|_| Utc::now()),
+                    timestamp: parse_db_timestamp(&row.created_at).unwrap_or_else(Utc::now),
                     edited_at,
                     reply_to,
                     reactions,
                     attachments,
                     embeds: None,
+                    kind: MessageKind::from_db(&row.kind),
                 }
             })
-            .collect();
-
-        Ok((messages, has_more))
-    }
-
-    /// List all channels in a server.
-    pub fn list_channels(&self, server_id: &str) -> Vec<ChannelInfo> {
-        self.channels
-            .iter()
-            .filter(|ch| ch.server_id == server_id)
-            .map(|entry| ChannelInfo {
-                id: entry.id.clone(),
-                server_id: entry.server_id.clone(),
-                name: entry.name.clone(),
-                topic: entry.topic.clone(),
-                member_count: entry.member_count(),
-                category_id: entry.category_id.clone(),
-                position: entry.position,
-                is_private: entry.is_private,
-                channel_type: entry.channel_type.clone(),
-                thread_parent_message_id: entry.thread_parent_message_id.clone(),
-                archived: entry.archived,
-            })
             .collect()
     }
 
-    /// Get members of a channel.
-    pub fn get_members(
+    /// Fetch direct message history with another user, or in a group DM
+    /// given as `&<id>`, newest first, like `fetch_history`. `before` is a
+    /// message timestamp.
+    pub async fn fetch_dm_history(
         &self,
-        server_id: &str,
-        channel_name: &str,
-    ) -> Result<Vec<MemberInfo>, String> {
-        let channel_name = normalize_channel_name(channel_name);
-        let channel_id = self.resolve_channel_id(server_id, &channel_name)?;
+        session_id: SessionId,
+        peer: &str,
+        before: Option<&str>,
+        limit: i64,
+    ) -> Result<(Vec<HistoryMessage>, bool), String> {
+        use crate::db::queries::messages::{HistoryScope, fetch_history_range};
 
-        let channel = self
-            .channels
-            .get(&channel_id)
-            .ok_or(format!("No such channel: {channel_name}"))?;
+        let Some(pool) = &self.db else {
+            return Ok((vec![], false));
+        };
+        let session = self
+            .sessions
+            .get(&session_id)
+            .ok_or("Session not found")?
+            .clone();
 
-        Ok(channel
-            .members
-            .iter()
-            .filter_map(|sid| {
-                self.sessions.get(sid).map(|s| MemberInfo {
-                    nickname: s.nickname.clone(),
-                    avatar_url: s.avatar_url.clone(),
-                    status: None,
-                    custom_status: None,
-                    status_emoji: None,
-                    user_id: s.user_id.clone(),
-                })
-            })
-            .collect())
-    }
+        let me = history_participant_id(&session);
+        let peer_id;
+        let scope = match peer.strip_prefix('&') {
+            Some(group_id) => {
+                self.group_dm_participant_ids(pool, group_id, &me).await?;
+                HistoryScope::Group(group_id)
+            }
+            None => {
+                peer_id = self.resolve_dm_participant(pool, peer).await?;
+                HistoryScope::Direct(&me, &peer_id)
+            }
+        };
+        // Every message at `before` sorts after rowid 0
+        let before = before.map(|time| (time.to_string(), 0));
 
-    // ── Message editing & deletion ─────────────────────────────────
+        let mut rows = fetch_history_range(pool, &scope, None, before.as_ref(), true, limit + 1)
+            .await
+            .map_err(|e| format!("Failed to fetch history: {e}"))?;
+        let has_more = rows.len() as i64 > limit;
+        if has_more {
+            rows.remove(0);
+        }
+        rows.reverse();
 
-    /// Edit a message's content. Only the sender or a moderator+ can edit.
-    pub async fn edit_message(
+        Ok((self.history_messages(pool, rows).await, has_more))
+    }
+
+    /// Fetch a window of history around anchors, oldest first. `target` is a
+    /// channel name, `&<id>` for a group DM or, for direct messages, the
+    /// other user's nickname.
+    /// Timestamp anchors are matched at the database's one-second resolution.
+    pub async fn fetch_history_window(
         &self,
         session_id: SessionId,
-        message_id: &str,
-        new_content: &str,
-    ) -> Result<(), String> {
-        validation::validate_message(new_content)?;
-        let new_content = &validation::sanitize_html(new_content);
+        server_id: &str,
+        target: &str,
+        query: &HistoryQuery,
+        limit: i64,
+    ) -> Result<Vec<HistoryMessage>, String> {
+        use crate::db::queries::messages::{HistoryScope, fetch_history_range};
 
+        let Some(pool) = &self.db else {
+            return Ok(vec![]);
+        };
         let session = self
             .sessions
             .get(&session_id)
             .ok_or("Session not found")?
             .clone();
 
-        let pool = self.db.as_ref().ok_or("No database configured")?;
+        let me = history_participant_id(&session);
+        let channel_id;
+        let peer_id;
+        let scope = if target.starts_with('#') {
+            let channel_name = normalize_channel_name(target);
+            channel_id = self.resolve_channel_id(server_id, &channel_name)?;
+            self.check_history_access(&session, server_id, &channel_id)
+                .await?;
+            HistoryScope::Channel(&channel_id)
+        } else if let Some(group_id) = target.strip_prefix('&') {
+            self.group_dm_participant_ids(pool, group_id, &me).await?;
+            HistoryScope::Group(group_id)
+        } else {
+            peer_id = self.resolve_dm_participant(pool, target).await?;
+            HistoryScope::Direct(&me, &peer_id)
+        };
+
+        let db_err = |e: sqlx::Error| format!("Failed to fetch history: {e}");
+        let rows = match query {
+            HistoryQuery::Latest(None) => {
+                fetch_history_range(pool, &scope, None, None, true, limit)
+                    .await
+                    .map_err(db_err)?
+            }
+            HistoryQuery::Latest(Some(anchor)) => {
+                let after = history_cursor(pool, anchor).await?;
+                fetch_history_range(pool, &scope, Some(&after), None, true, limit)
+                    .await
+                    .map_err(db_err)?
+            }
+            HistoryQuery::Before(anchor) => {
+                let before = history_cursor(pool, anchor).await?;
+                fetch_history_range(pool, &scope, None, Some(&before), true, limit)
+                    .await
+                    .map_err(db_err)?
+            }
+            HistoryQuery::After(anchor) => {
+                let after = history_cursor(pool, anchor).await?;
+                fetch_history_range(pool, &scope, Some(&after), None, false, limit)
+                    .await
+                    .map_err(db_err)?
+            }
+            HistoryQuery::Around(anchor) => {
+                let (time, rowid) = history_cursor(pool, anchor).await?;
+                let before_anchor = (time.clone(), rowid);
+                let mut rows =
+                    fetch_history_range(pool, &scope, None, Some(&before_anchor), true, limit / 2)
+                        .await
+                        .map_err(db_err)?;
+                // Step back one rowid so the anchor message itself is included
+                let from_anchor = (time, rowid - 1);
+                let remaining = limit - rows.len() as i64;
+                rows.extend(
+                    fetch_history_range(pool, &scope, Some(&from_anchor), None, false, remaining)
+                        .await
+                        .map_err(db_err)?,
+                );
+                rows
+            }
+            HistoryQuery::Between(start, end) => {
+                let start = history_cursor(pool, start).await?;
+                let end = history_cursor(pool, end).await?;
+                if start <= end {
+                    fetch_history_range(pool, &scope, Some(&start), Some(&end), false, limit)
+                        .await
+                        .map_err(db_err)?
+                } else {
+                    // Counting back from a later first anchor
+                    fetch_history_range(pool, &scope, Some(&end), Some(&start), true, limit)
+                        .await
+                        .map_err(db_err)?
+                }
+            }
+        };
+
+        Ok(self.history_messages(pool, rows).await)
+    }
+
+    /// List the conversations a session can replay that saw messages strictly
+    /// between `after` and `before`: its current channels, its DM partners and
+    /// its group DMs.
+    /// Sorted by latest message time, oldest first.
+    pub async fn history_targets(
+        &self,
+        session_id: SessionId,
+        after: chrono::DateTime<Utc>,
+        before: chrono::DateTime<Utc>,
+    ) -> Result<Vec<HistoryTarget>, String> {
+        let Some(pool) = &self.db else {
+            return Ok(vec![]);
+        };
+        let session = self
+            .sessions
+            .get(&session_id)
+            .ok_or("Session not found")?
+            .clone();
+        let after = after.format("%Y-%m-%d %H:%M:%S").to_string();
+        let before = before.format("%Y-%m-%d %H:%M:%S").to_string();
+
+        let joined: Vec<(String, String, String)> = self
+            .channels
+            .iter()
+            .filter(|ch| ch.members.contains(&session_id))
+            .map(|ch| (ch.id.clone(), ch.server_id.clone(), ch.name.clone()))
+            .collect();
+        let channel_ids: Vec<String> = joined.iter().map(|(id, _, _)| id.clone()).collect();
+
+        let mut targets = Vec::new();
+        let active = crate::db::queries::messages::fetch_channel_activity(
+            pool,
+            &channel_ids,
+            &after,
+            &before,
+        )
+        .await
+        .map_err(|e| format!("DB error: {e}"))?;
+        for (channel_id, latest) in active {
+            if let Some((_, server_id, name)) = joined.iter().find(|(id, _, _)| *id == channel_id)
+                && let Some(latest) = parse_db_timestamp(&latest)
+            {
+                targets.push(HistoryTarget {
+                    server_id: Some(server_id.clone()),
+                    target: name.clone(),
+                    latest,
+                });
+            }
+        }
+
+        let me = history_participant_id(&session);
+        let partners = crate::db::queries::messages::fetch_dm_activity(pool, &me, &after, &before)
+            .await
+            .map_err(|e| format!("DB error: {e}"))?;
+        for (peer, latest) in partners {
+            let Some(latest) = parse_db_timestamp(&latest) else {
+                continue;
+            };
+            let online = self
+                .sessions
+                .iter()
+                .find(|s| history_participant_id(s) == peer)
+                .map(|s| s.nickname.clone());
+            let nick = match online {
+                Some(nick) => Some(nick),
+                None => crate::db::queries::users::get_user(pool, &peer)
+                    .await
+                    .ok()
+                    .flatten()
+                    .map(|(_, username, _, _)| username),
+            };
+            if let Some(nick) = nick {
+                targets.push(HistoryTarget {
+                    server_id: None,
+                    target: nick,
+                    latest,
+                });
+            }
+        }
+
+        let groups =
+            crate::db::queries::direct_messages::fetch_group_activity(pool, &me, &after, &before)
+                .await
+                .map_err(|e| format!("DB error: {e}"))?;
+        for (conversation_id, latest) in groups {
+            if let Some(latest) = parse_db_timestamp(&latest) {
+                targets.push(HistoryTarget {
+                    server_id: None,
+                    target: format!("&{conversation_id}"),
+                    latest,
+                });
+            }
+        }
+
+        targets.sort_by_key(|t| t.latest);
+        Ok(targets)
+    }
+
+    /// Reading a channel's history requires being in it, or belonging to its
+    /// server with VIEW_CHANNELS if the channel is private.
+    async fn check_history_access(
+        &self,
+        session: &UserSession,
+        server_id: &str,
+        channel_id: &str,
+    ) -> Result<(), String> {
+        let (in_channel, is_private) = self
+            .channels
+            .get(channel_id)
+            .map(|ch| (ch.members.contains(&session.id), ch.is_private))
+            .ok_or("Channel not found")?;
+        if in_channel {
+            return Ok(());
+        }
+        let Some(uid) = &session.user_id else {
+            return Err("You are not in that channel".into());
+        };
+        if !self.user_is_server_member(server_id, uid) {
+            return Err("You are not a member of this server".into());
+        }
+        if is_private
+            && !self
+                .get_effective_permissions(server_id, Some(channel_id), uid)
+                .await
+                .contains(Permissions::VIEW_CHANNELS)
+        {
+            return Err("You do not have permission to view this channel".into());
+        }
+        Ok(())
+    }
+
+    /// Find who a direct message to `nick` is for: a connected user, or an
+    /// account that's offline.
+    fn resolve_dm_recipient(&self, nick: &str) -> Result<DmRecipient, String> {
+        if let Some(session) = self.get_session_by_nick(nick) {
+            return Ok(DmRecipient {
+                session_id: Some(session.id),
+                user_id: session.user_id.clone(),
+            });
+        }
+        let pool = self.db.as_ref().ok_or(format!("No such user: {nick}"))?;
+        let user = tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current()
+                .block_on(crate::db::queries::users::get_user_by_nickname(pool, nick))
+        })
+        .map_err(|e| format!("DB error: {e}"))?;
+        let (user_id, ..) = user.ok_or(format!("No such user: {nick}"))?;
+        Ok(DmRecipient {
+            session_id: None,
+            user_id: Some(user_id),
+        })
+    }
+
+    /// Resolve a nickname to the participant ID its direct messages are stored under.
+    async fn resolve_dm_participant(
+        &self,
+        pool: &SqlitePool,
+        nick: &str,
+    ) -> Result<String, String> {
+        if let Some(session) = self.get_session_by_nick(nick) {
+            return Ok(history_participant_id(&session));
+        }
+        crate::db::queries::users::get_user_by_nickname(pool, nick)
+            .await
+            .map_err(|e| format!("DB error: {e}"))?
+            .map(|(user_id, ..)| user_id)
+            .ok_or(format!("No such user: {nick}"))
+    }
+
+    /// List all channels in a server.
+    pub fn list_channels(&self, server_id: &str) -> Vec<ChannelInfo> {
+        self.channels
+            .iter()
+            .filter(|ch| ch.server_id == server_id)
+            .map(|entry| ChannelInfo {
+                id: entry.id.clone(),
+                server_id: entry.server_id.clone(),
+                name: entry.name.clone(),
+                topic: entry.topic.clone(),
+                member_count: entry.member_count(),
+                category_id: entry.category_id.clone(),
+                position: entry.position,
+                is_private: entry.is_private,
+                channel_type: entry.channel_type.clone(),
+                thread_parent_message_id: entry.thread_parent_message_id.clone(),
+                archived: entry.archived,
+                created_at: entry.created_at,
+                topic_set_at: entry.topic_set_at,
+            })
+            .collect()
+    }
+
+    /// Channels a session can see on every server: the default server's, and
+    /// those of each server its user belongs to, less any channel the user lacks
+    /// VIEW_CHANNELS in. Guests see the default server's public channels.
+    pub async fn list_visible_channels(&self, session_id: SessionId) -> Vec<ChannelInfo> {
+        let Some(session) = self.get_session(session_id) else {
+            return Vec::new();
+        };
+        let mut server_ids = vec![DEFAULT_SERVER_ID.to_string()];
+        if let Some(uid) = &session.user_id {
+            server_ids.extend(
+                self.servers
+                    .iter()
+                    .filter(|srv| srv.id != DEFAULT_SERVER_ID && srv.member_user_ids.contains(uid))
+                    .map(|srv| srv.id.clone()),
+            );
+        }
+
+        let mut visible = Vec::new();
+        for server_id in server_ids {
+            for channel in self.list_channels(&server_id) {
+                let can_view = match &session.user_id {
+                    Some(uid) => self
+                        .get_effective_permissions(&server_id, Some(&channel.id), uid)
+                        .await
+                        .contains(Permissions::VIEW_CHANNELS),
+                    None => !channel.is_private,
+                };
+                if can_view {
+                    visible.push(channel);
+                }
+            }
+        }
+        visible
+    }
+
+    /// Get members of a channel.
+    pub fn get_members(
+        &self,
+        server_id: &str,
+        channel_name: &str,
+    ) -> Result<Vec<MemberInfo>, String> {
+        let channel_name = normalize_channel_name(channel_name);
+        let channel_id = self.resolve_channel_id(server_id, &channel_name)?;
+
+        if !self.channels.contains_key(&channel_id) {
+            return Err(format!("No such channel: {channel_name}"));
+        }
+
+        Ok(self.channel_member_infos(server_id, &channel_id))
+    }
+
+    /// Live members of a channel, with the status prefixes their permissions earn them.
+    fn channel_member_infos(&self, server_id: &str, channel_id: &str) -> Vec<MemberInfo> {
+        let Some(channel) = self.channels.get(channel_id) else {
+            return Vec::new();
+        };
+        // A user connected from several clients is listed once
+        let mut listed = HashSet::new();
+        let sessions: Vec<Arc<UserSession>> = channel
+            .members
+            .iter()
+            .filter_map(|sid| self.get_session(*sid))
+            .filter(|s| listed.insert(s.nickname.clone()))
+            .collect();
+        drop(channel);
+
+        let ctx = if self.db.is_some() {
+            let user_ids = session_user_ids(&sessions);
+            tokio::task::block_in_place(|| {
+                tokio::runtime::Handle::current()
+                    .block_on(self.prefix_context(server_id, channel_id, &user_ids))
+            })
+        } else {
+            PrefixContext::default()
+        };
+
+        sessions
+            .iter()
+            .map(|s| MemberInfo {
+                nickname: s.nickname.clone(),
+                avatar_url: s.avatar_url.clone(),
+                status: None,
+                custom_status: None,
+                status_emoji: None,
+                user_id: s.user_id.clone(),
+                prefixes: self.prefixes_in(&ctx, server_id, s.user_id.as_deref()),
+            })
+            .collect()
+    }
+
+    /// Load the roles and overrides that decide status prefixes in a channel,
+    /// with the role assignments of the given users.
+    async fn prefix_context(
+        &self,
+        server_id: &str,
+        channel_id: &str,
+        user_ids: &[String],
+    ) -> PrefixContext {
+        let mut ctx = PrefixContext::default();
+        let Some(pool) = &self.db else {
+            return ctx;
+        };
+
+        let roles = crate::db::queries::roles::list_roles(pool, server_id)
+            .await
+            .unwrap_or_default();
+        for role in roles {
+            let perms = Permissions::from_bits_truncate(role.permissions as u64);
+            if role.is_default != 0 {
+                ctx.everyone_role_id = role.id.clone();
+                ctx.base = perms;
+            }
+            ctx.role_permissions.insert(role.id, perms);
+        }
+
+        let assignments =
+            crate::db::queries::roles::get_user_roles_for_users(pool, server_id, user_ids)
+                .await
+                .unwrap_or_default();
+        for row in assignments {
+            ctx.user_roles
+                .entry(row.user_id)
+                .or_default()
+                .push(row.role_id);
+        }
+
+        ctx.overrides = crate::db::queries::channels::get_channel_overrides(pool, channel_id)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|o| ChannelOverride {
+                target_type: if o.target_type == "role" {
+                    OverrideTargetType::Role
+                } else {
+                    OverrideTargetType::User
+                },
+                target_id: o.target_id,
+                allow: Permissions::from_bits_truncate(o.allow_bits as u64),
+                deny: Permissions::from_bits_truncate(o.deny_bits as u64),
+            })
+            .collect();
+
+        ctx
+    }
+
+    /// Status prefixes of a member (None for guests) in the channel `ctx` was loaded for.
+    fn prefixes_in(&self, ctx: &PrefixContext, server_id: &str, user_id: Option<&str>) -> String {
+        let everyone = permissions::compute_effective_permissions(
+            ctx.base,
+            &[],
+            &ctx.overrides,
+            &ctx.everyone_role_id,
+            "",
+            false,
+        );
+        let Some(user_id) = user_id else {
+            return member_prefixes(false, everyone, everyone);
+        };
+        let is_owner = self.is_server_owner(server_id, user_id);
+        member_prefixes(is_owner, self.permissions_in(ctx, server_id, user_id), everyone)
+    }
+
+    /// A member's permissions in the channel `ctx` was loaded for.
+    fn permissions_in(&self, ctx: &PrefixContext, server_id: &str, user_id: &str) -> Permissions {
+        let is_owner = self.is_server_owner(server_id, user_id);
+        let role_perms: Vec<(String, Permissions)> = ctx
+            .user_roles
+            .get(user_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| ctx.role_permissions.get(id).map(|p| (id.clone(), *p)))
+            .collect();
+        permissions::compute_effective_permissions(
+            ctx.base,
+            &role_perms,
+            &ctx.overrides,
+            &ctx.everyone_role_id,
+            user_id,
+            is_owner,
+        )
+    }
+
+    /// Status prefixes of live channel members in a server, optionally narrowed
+    /// to one user or one channel.
+    async fn prefix_snapshot(
+        &self,
+        server_id: &str,
+        user_id: Option<&str>,
+        channel_id: Option<&str>,
+    ) -> Vec<PrefixSnapshot> {
+        let channels: Vec<(String, String, Vec<SessionId>)> = self
+            .channels
+            .iter()
+            .filter(|ch| ch.server_id == server_id)
+            .filter(|ch| channel_id.is_none_or(|id| ch.id == id))
+            .map(|ch| {
+                (
+                    ch.id.clone(),
+                    ch.name.clone(),
+                    ch.members.iter().copied().collect(),
+                )
+            })
+            .collect();
+
+        let mut snapshot = Vec::new();
+        for (channel_id, channel, members) in channels {
+            let mut seen = HashSet::new();
+            let sessions: Vec<Arc<UserSession>> = members
+                .iter()
+                .filter_map(|sid| self.get_session(*sid))
+                .filter(|s| user_id.is_none_or(|uid| s.user_id.as_deref() == Some(uid)))
+                .filter(|s| seen.insert(s.nickname.clone()))
+                .collect();
+            if sessions.is_empty() {
+                continue;
+            }
+            let user_ids = session_user_ids(&sessions);
+            let ctx = self.prefix_context(server_id, &channel_id, &user_ids).await;
+            for session in sessions {
+                snapshot.push(PrefixSnapshot {
+                    channel_id: channel_id.clone(),
+                    channel: channel.clone(),
+                    nickname: session.nickname.clone(),
+                    prefixes: self.prefixes_in(&ctx, server_id, session.user_id.as_deref()),
+                });
+            }
+        }
+        snapshot
+    }
+
+    /// Tell channels about members whose status prefixes differ between two snapshots.
+    fn broadcast_prefix_changes(
+        &self,
+        server_id: &str,
+        before: &[PrefixSnapshot],
+        after: &[PrefixSnapshot],
+    ) {
+        for entry in after {
+            let old = before
+                .iter()
+                .find(|b| b.channel_id == entry.channel_id && b.nickname == entry.nickname)
+                .map(|b| b.prefixes.as_str())
+                .unwrap_or("");
+            if old == entry.prefixes {
+                continue;
+            }
+            let event = ChatEvent::MemberPrefixUpdate {
+                server_id: server_id.to_string(),
+                channel: entry.channel.clone(),
+                nickname: entry.nickname.clone(),
+                added: entry
+                    .prefixes
+                    .chars()
+                    .filter(|c| !old.contains(*c))
+                    .collect(),
+                removed: old
+                    .chars()
+                    .filter(|c| !entry.prefixes.contains(*c))
+                    .collect(),
+            };
+            self.broadcast_to_channel(&entry.channel_id, &event, None);
+        }
+    }
+
+    // ── Message editing & deletion ─────────────────────────────────
+
+    /// Edit a message's content. Only the sender or a moderator+ can edit.
+    pub async fn edit_message(
+        &self,
+        session_id: SessionId,
+        message_id: &str,
+        new_content: &str,
+    ) -> Result<(), String> {
+        validation::validate_message(new_content)?;
+        let new_content = &validation::sanitize_html(new_content);
+
+        let session = self
+            .sessions
+            .get(&session_id)
+            .ok_or("Session not found")?
+            .clone();
+
+        let pool = self.db.as_ref().ok_or("No database configured")?;
 
         let msg = crate::db::queries::messages::get_message_by_id(pool, message_id)
             .await
@@ -1529,6 +2856,11 @@ impl ChatEngine {
             return Err("You can only edit your own messages".into());
         }
 
+        // An edit is held to the same automod rules as a new message
+        if let Some(server_id) = msg.server_id.as_deref() {
+            self.check_automod(server_id, new_content).await?;
+        }
+
         crate::db::queries::messages::update_message_content(pool, message_id, new_content)
             .await
             .map_err(|e| format!("DB error: {e}"))?;
@@ -1547,6 +2879,7 @@ impl ChatEngine {
             id: message_id.parse().unwrap_or_default(),
             server_id: server_id.clone(),
             channel: channel_name,
+            from: session.nickname.clone(),
             content: new_content.to_string(),
             edited_at: Utc::now(),
         };
@@ -1557,6 +2890,52 @@ impl ChatEngine {
         Ok(())
     }
 
+    /// Every version of a message, oldest first, ending with what it says now.
+    /// Only its author and those who can manage messages in its channel can
+    /// see what it used to say.
+    pub async fn message_edit_history(
+        &self,
+        user_id: &str,
+        message_id: &str,
+    ) -> Result<Vec<MessageRevisionInfo>, String> {
+        let pool = self.db.as_ref().ok_or("No database configured")?;
+
+        let msg = crate::db::queries::messages::get_message_by_id(pool, message_id)
+            .await
+            .map_err(|e| format!("DB error: {e}"))?
+            .filter(|m| m.deleted_at.is_none())
+            .ok_or("Message not found")?;
+
+        if msg.sender_id != user_id {
+            let can_manage = match (msg.server_id.as_deref(), msg.channel_id.as_deref()) {
+                (Some(server_id), Some(channel_id)) => self
+                    .get_effective_permissions(server_id, Some(channel_id), user_id)
+                    .await
+                    .contains(Permissions::MANAGE_MESSAGES),
+                _ => false,
+            };
+            if !can_manage {
+                return Err("FORBIDDEN: insufficient permissions to view edit history".into());
+            }
+        }
+
+        let mut revisions: Vec<MessageRevisionInfo> =
+            crate::db::queries::messages::get_message_revisions(pool, message_id)
+                .await
+                .map_err(|e| format!("DB error: {e}"))?
+                .into_iter()
+                .map(|r| MessageRevisionInfo {
+                    content: r.content,
+                    timestamp: r.written_at,
+                })
+                .collect();
+        revisions.push(MessageRevisionInfo {
+            content: msg.content,
+            timestamp: msg.edited_at.unwrap_or(msg.created_at),
+        });
+        Ok(revisions)
+    }
+
     /// Delete a message (soft delete). Sender can delete own, moderator+ can delete any.
     pub async fn delete_message(
         &self,
@@ -1574,6 +2953,7 @@ impl ChatEngine {
         let msg = crate::db::queries::messages::get_message_by_id(pool, message_id)
             .await
             .map_err(|e| format!("DB error: {e}"))?
+            .filter(|m| m.deleted_at.is_none())
             .ok_or("Message not found")?;
 
         let sender_id = session.user_id.as_deref().ok_or("Authentication required to delete messages")?;
@@ -1595,7 +2975,56 @@ impl ChatEngine {
             }
         }
 
-        crate::db::queries::messages::soft_delete_message(pool, message_id)
+        crate::db::queries::messages::soft_delete_message(pool, message_id)
+            .await
+            .map_err(|e| format!("DB error: {e}"))?;
+
+        let server_id = msg.server_id.ok_or("Message has no server")?;
+        let channel_id = msg.channel_id.ok_or("Message has no channel")?;
+
+        let channel_name = self
+            .channels
+            .get(&channel_id)
+            .map(|ch| ch.name.clone())
+            .unwrap_or_default();
+
+        let event = ChatEvent::MessageDelete {
+            id: message_id.parse().unwrap_or_default(),
+            server_id,
+            channel: channel_name,
+            deleted_by: session.nickname.clone(),
+        };
+
+        self.broadcast_to_channel(&channel_id, &event, None);
+
+        Ok(())
+    }
+
+    // ── Reactions ────────────────────────────────────────────────────
+
+    /// Add a reaction to a message.
+    pub async fn add_reaction(
+        &self,
+        session_id: SessionId,
+        message_id: &str,
+        emoji: &str,
+    ) -> Result<(), String> {
+        let session = self
+            .sessions
+            .get(&session_id)
+            .ok_or("Session not found")?
+            .clone();
+
+        let pool = self.db.as_ref().ok_or("No database configured")?;
+
+        let msg = crate::db::queries::messages::get_message_by_id(pool, message_id)
+            .await
+            .map_err(|e| format!("DB error: {e}"))?
+            .ok_or("Message not found")?;
+
+        let user_id = session.user_id.as_deref().unwrap_or(&session.nickname);
+
+        crate::db::queries::messages::add_reaction(pool, message_id, user_id, emoji)
             .await
             .map_err(|e| format!("DB error: {e}"))?;
 
@@ -1608,10 +3037,13 @@ impl ChatEngine {
             .map(|ch| ch.name.clone())
             .unwrap_or_default();
 
-        let event = ChatEvent::MessageDelete {
-            id: message_id.parse().unwrap_or_default(),
+        let event = ChatEvent::ReactionAdd {
+            message_id: message_id.parse().unwrap_or_default(),
             server_id,
             channel: channel_name,
+            user_id: user_id.to_string(),
+            nickname: session.nickname.clone(),
+            emoji: emoji.to_string(),
         };
 
         self.broadcast_to_channel(&channel_id, &event, None);
@@ -1619,10 +3051,8 @@ impl ChatEngine {
         Ok(())
     }
 
-    // ── Reactions ────────────────────────────────────────────────────
-
-    /// Add a reaction to a message.
-    pub async fn add_reaction(
+    /// Remove a reaction from a message.
+    pub async fn remove_reaction(
         &self,
         session_id: SessionId,
         message_id: &str,
@@ -1643,7 +3073,7 @@ impl ChatEngine {
 
         let user_id = session.user_id.as_deref().unwrap_or(&session.nickname);
 
-        crate::db::queries::messages::add_reaction(pool, message_id, user_id, emoji)
+        crate::db::queries::messages::remove_reaction(pool, message_id, user_id, emoji)
             .await
             .map_err(|e| format!("DB error: {e}"))?;
 
@@ -1656,7 +3086,7 @@ impl ChatEngine {
             .map(|ch| ch.name.clone())
             .unwrap_or_default();
 
-        let event = ChatEvent::ReactionAdd {
+        let event = ChatEvent::ReactionRemove {
             message_id: message_id.parse().unwrap_or_default(),
             server_id,
             channel: channel_name,
@@ -1670,114 +3100,686 @@ impl ChatEngine {
         Ok(())
     }
 
-    /// Remove a reaction from a message.
-    pub async fn remove_reaction(
+    // ── Typing indicators ────────────────────────────────────────────
+
+    /// Broadcast a typing indicator to a channel.
+    pub fn send_typing(
+        &self,
+        session_id: SessionId,
+        server_id: &str,
+        channel_name: &str,
+    ) -> Result<(), String> {
+        self.broadcast_typing(session_id, server_id, channel_name, |nickname, channel| {
+            ChatEvent::TypingStart {
+                server_id: server_id.to_string(),
+                channel,
+                nickname,
+            }
+        })
+    }
+
+    /// Broadcast that a user stopped typing in a channel. `paused` means they
+    /// still have unsent input.
+    pub fn stop_typing(
+        &self,
+        session_id: SessionId,
+        server_id: &str,
+        channel_name: &str,
+        paused: bool,
+    ) -> Result<(), String> {
+        self.broadcast_typing(session_id, server_id, channel_name, |nickname, channel| {
+            ChatEvent::TypingStop {
+                server_id: server_id.to_string(),
+                channel,
+                nickname,
+                paused,
+            }
+        })
+    }
+
+    /// Send a typing event, built from the typist's nickname and the
+    /// normalized channel name, to everyone else in the channel.
+    fn broadcast_typing(
+        &self,
+        session_id: SessionId,
+        server_id: &str,
+        channel_name: &str,
+        event: impl FnOnce(String, String) -> ChatEvent,
+    ) -> Result<(), String> {
+        let channel_name = normalize_channel_name(channel_name);
+
+        let session = self
+            .sessions
+            .get(&session_id)
+            .ok_or("Session not found")?
+            .clone();
+
+        let channel_id = self.resolve_channel_id(server_id, &channel_name)?;
+
+        let event = event(session.nickname.clone(), channel_name);
+
+        self.broadcast_to_channel(&channel_id, &event, session.echo_exclusion());
+
+        Ok(())
+    }
+
+    // ── Read state ────────────────────────────────────────────────────
+
+    /// Mark a channel as read for a user, up to a specific message ID.
+    pub async fn mark_read(
         &self,
         session_id: SessionId,
+        server_id: &str,
+        channel_name: &str,
         message_id: &str,
-        emoji: &str,
     ) -> Result<(), String> {
         let session = self
             .sessions
             .get(&session_id)
             .ok_or("Session not found")?
             .clone();
-
+
+        let user_id = session.user_id.as_deref().ok_or("AUTH_REQUIRED")?;
+        let pool = self.db.as_ref().ok_or("No database configured")?;
+
+        let channel_name = normalize_channel_name(channel_name);
+        let channel_id = self.resolve_channel_id(server_id, &channel_name)?;
+
+        crate::db::queries::messages::mark_channel_read(pool, user_id, &channel_id, message_id)
+            .await
+            .map_err(|e| format!("DB error: {e}"))?;
+
+        Ok(())
+    }
+
+    /// Mark a direct message conversation as read up to a message. `peer` is
+    /// the other user's nickname, or `&<id>` for a group DM.
+    pub async fn mark_dm_read(
+        &self,
+        session_id: SessionId,
+        peer: &str,
+        message_id: &str,
+    ) -> Result<(), String> {
+        use crate::db::queries::direct_messages;
+
+        let session = self
+            .sessions
+            .get(&session_id)
+            .ok_or("Session not found")?
+            .clone();
+
+        let user_id = session.user_id.as_deref().ok_or("AUTH_REQUIRED")?;
+        let pool = self.db.as_ref().ok_or("No database configured")?;
+
+        let conversation_id = match peer.strip_prefix('&') {
+            Some(group_id) => {
+                self.group_dm_participant_ids(pool, group_id, user_id)
+                    .await?;
+                group_id.to_string()
+            }
+            None => {
+                let peer_id = self.resolve_dm_participant(pool, peer).await?;
+                direct_messages::find_direct_conversation(pool, user_id, &peer_id)
+                    .await
+                    .map_err(|e| format!("DB error: {e}"))?
+                    .ok_or(format!("No conversation with {peer}"))?
+            }
+        };
+
+        let marked =
+            direct_messages::mark_conversation_read(pool, &conversation_id, user_id, message_id)
+                .await
+                .map_err(|e| format!("DB error: {e}"))?;
+        if !marked {
+            return Err("Message not found".into());
+        }
+        Ok(())
+    }
+
+    /// A user's direct message conversations, most recently active first,
+    /// with how many messages in each they haven't read.
+    pub async fn list_dm_conversations(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<DmConversationInfo>, String> {
+        use crate::db::queries::direct_messages;
+
+        let Some(pool) = &self.db else {
+            return Ok(vec![]);
+        };
+        let rows = direct_messages::list_conversations(pool, user_id)
+            .await
+            .map_err(|e| format!("DB error: {e}"))?;
+        let ids: Vec<String> = rows.iter().map(|r| r.id.clone()).collect();
+        let participants = direct_messages::get_participants(pool, &ids)
+            .await
+            .map_err(|e| format!("DB error: {e}"))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| DmConversationInfo {
+                participants: participants
+                    .iter()
+                    .filter(|p| p.conversation_id == row.id && p.user_id != user_id)
+                    .map(|p| DmParticipant {
+                        user_id: p.user_id.clone(),
+                        nickname: self
+                            .nick_of_user(&p.user_id)
+                            .unwrap_or_else(|| p.username.clone()),
+                        avatar_url: p.avatar_url.clone(),
+                    })
+                    .collect(),
+                last_message_at: row.last_message_at.as_deref().and_then(parse_db_timestamp),
+                unread_count: row.unread_count,
+                is_group: row.is_group,
+                name: row.name,
+                owner_id: row.owner_id,
+                id: row.id,
+            })
+            .collect())
+    }
+
+    /// Start a group DM with the given users, owned by the session's user.
+    /// Returns the new conversation's ID.
+    pub async fn create_group_dm(
+        &self,
+        session_id: SessionId,
+        nicknames: &[String],
+        name: Option<&str>,
+    ) -> Result<String, String> {
+        use crate::db::queries::direct_messages;
+
+        let session = self
+            .sessions
+            .get(&session_id)
+            .ok_or("Session not found")?
+            .clone();
+        let owner_id = session.user_id.clone().ok_or("AUTH_REQUIRED")?;
+        let pool = self.db.as_ref().ok_or("No database configured")?;
+        let name = group_dm_name(name)?;
+        // Checked before looking anyone up, so a long list costs nothing;
+        // repeats and the caller's own nick don't count towards the limit
+        let own_key = nick_key(&session.nickname);
+        let mut seen = HashSet::new();
+        let nicknames: Vec<&String> = nicknames
+            .iter()
+            .filter(|nick| {
+                let key = nick_key(nick);
+                key != own_key && seen.insert(key)
+            })
+            .collect();
+        if nicknames.len() >= MAX_GROUP_DM_PARTICIPANTS {
+            return Err(format!(
+                "A group DM can have at most {MAX_GROUP_DM_PARTICIPANTS} participants"
+            ));
+        }
+
+        let mut member_ids: Vec<String> = Vec::new();
+        for nick in nicknames {
+            let user_id = self
+                .find_user_id_by_nick(nick)
+                .await
+                .ok_or(format!("No such user: {nick}"))?;
+            if user_id != owner_id && !member_ids.contains(&user_id) {
+                member_ids.push(user_id);
+            }
+        }
+        if member_ids.is_empty() {
+            return Err("A group DM needs at least one other participant".into());
+        }
+        if member_ids.len() + 1 > MAX_GROUP_DM_PARTICIPANTS {
+            return Err(format!(
+                "A group DM can have at most {MAX_GROUP_DM_PARTICIPANTS} participants"
+            ));
+        }
+
+        let conversation_id = Uuid::new_v4().to_string();
+        direct_messages::create_group_conversation(
+            pool,
+            &conversation_id,
+            &owner_id,
+            name.as_deref(),
+            &member_ids,
+        )
+        .await
+        .map_err(|e| format!("DB error: {e}"))?;
+
+        let change = GroupDmChange::Created {
+            by: session.nickname.clone(),
+        };
+        self.announce_group_dm_change(pool, &session, &conversation_id, change, None)
+            .await?;
+        info!(nickname = %session.nickname, %conversation_id, "created group DM");
+        Ok(conversation_id)
+    }
+
+    /// Add someone to a group DM. Anyone in it may add people, up to
+    /// `MAX_GROUP_DM_PARTICIPANTS`.
+    pub async fn add_group_dm_participant(
+        &self,
+        session_id: SessionId,
+        conversation_id: &str,
+        nickname: &str,
+    ) -> Result<(), String> {
+        use crate::db::queries::direct_messages;
+
+        let session = self
+            .sessions
+            .get(&session_id)
+            .ok_or("Session not found")?
+            .clone();
+        let user_id = session.user_id.clone().ok_or("AUTH_REQUIRED")?;
+        let pool = self.db.as_ref().ok_or("No database configured")?;
+        let participants = self
+            .group_dm_participant_ids(pool, conversation_id, &user_id)
+            .await?;
+
+        let new_id = self
+            .find_user_id_by_nick(nickname)
+            .await
+            .ok_or(format!("No such user: {nickname}"))?;
+        if participants.contains(&new_id) {
+            return Err(format!("{nickname} is already in this group"));
+        }
+        if participants.len() >= MAX_GROUP_DM_PARTICIPANTS {
+            return Err(format!(
+                "A group DM can have at most {MAX_GROUP_DM_PARTICIPANTS} participants"
+            ));
+        }
+
+        let added = direct_messages::add_participant(
+            pool,
+            conversation_id,
+            &new_id,
+            MAX_GROUP_DM_PARTICIPANTS,
+        )
+        .await
+        .map_err(|e| format!("DB error: {e}"))?;
+        if !added {
+            // Someone else got in first
+            let participants = self
+                .group_dm_participant_ids(pool, conversation_id, &user_id)
+                .await?;
+            return Err(if participants.contains(&new_id) {
+                format!("{nickname} is already in this group")
+            } else {
+                format!("A group DM can have at most {MAX_GROUP_DM_PARTICIPANTS} participants")
+            });
+        }
+        let change = GroupDmChange::Added {
+            by: session.nickname.clone(),
+            nickname: self.find_nick_by_user_id(&new_id).await,
+        };
+        self.announce_group_dm_change(pool, &session, conversation_id, change, None)
+            .await
+    }
+
+    /// Remove someone from a group DM. Only its owner may, and not themselves:
+    /// owners leave with `leave_group_dm`.
+    pub async fn remove_group_dm_participant(
+        &self,
+        session_id: SessionId,
+        conversation_id: &str,
+        nickname: &str,
+    ) -> Result<(), String> {
+        use crate::db::queries::direct_messages;
+
+        let session = self
+            .sessions
+            .get(&session_id)
+            .ok_or("Session not found")?
+            .clone();
+        let user_id = session.user_id.clone().ok_or("AUTH_REQUIRED")?;
+        let pool = self.db.as_ref().ok_or("No database configured")?;
+        let participants = self
+            .group_dm_participant_ids(pool, conversation_id, &user_id)
+            .await?;
+        self.require_group_dm_owner(pool, conversation_id, &user_id)
+            .await?;
+
+        let target_id = self
+            .find_user_id_by_nick(nickname)
+            .await
+            .ok_or(format!("No such user: {nickname}"))?;
+        if target_id == user_id {
+            return Err("Use leave to leave a group you own".into());
+        }
+        if !participants.contains(&target_id) {
+            return Err(format!("{nickname} is not in this group"));
+        }
+
+        direct_messages::remove_participant(pool, conversation_id, &target_id)
+            .await
+            .map_err(|e| format!("DB error: {e}"))?;
+        let change = GroupDmChange::Removed {
+            by: session.nickname.clone(),
+            nickname: self.find_nick_by_user_id(&target_id).await,
+        };
+        self.announce_group_dm_change(pool, &session, conversation_id, change, Some(&target_id))
+            .await
+    }
+
+    /// Set or clear a group DM's name. Anyone in it may.
+    pub async fn rename_group_dm(
+        &self,
+        session_id: SessionId,
+        conversation_id: &str,
+        name: Option<&str>,
+    ) -> Result<(), String> {
+        use crate::db::queries::direct_messages;
+
+        let session = self
+            .sessions
+            .get(&session_id)
+            .ok_or("Session not found")?
+            .clone();
+        let user_id = session.user_id.clone().ok_or("AUTH_REQUIRED")?;
+        let pool = self.db.as_ref().ok_or("No database configured")?;
+        self.group_dm_participant_ids(pool, conversation_id, &user_id)
+            .await?;
+        let name = group_dm_name(name)?;
+
+        direct_messages::rename_conversation(pool, conversation_id, name.as_deref())
+            .await
+            .map_err(|e| format!("DB error: {e}"))?;
+        let change = GroupDmChange::Renamed {
+            by: session.nickname.clone(),
+            name,
+        };
+        self.announce_group_dm_change(pool, &session, conversation_id, change, None)
+            .await
+    }
+
+    /// Leave a group DM. An owner who leaves hands the group to whoever has
+    /// been in it longest; the last one out deletes it.
+    pub async fn leave_group_dm(
+        &self,
+        session_id: SessionId,
+        conversation_id: &str,
+    ) -> Result<(), String> {
+        use crate::db::queries::direct_messages;
+
+        let session = self
+            .sessions
+            .get(&session_id)
+            .ok_or("Session not found")?
+            .clone();
+        let user_id = session.user_id.clone().ok_or("AUTH_REQUIRED")?;
         let pool = self.db.as_ref().ok_or("No database configured")?;
-
-        let msg = crate::db::queries::messages::get_message_by_id(pool, message_id)
+        let participants = self
+            .group_dm_participant_ids(pool, conversation_id, &user_id)
+            .await?;
+        let was_owner = self
+            .require_group_dm_owner(pool, conversation_id, &user_id)
             .await
-            .map_err(|e| format!("DB error: {e}"))?
-            .ok_or("Message not found")?;
-
-        let user_id = session.user_id.as_deref().unwrap_or(&session.nickname);
+            .is_ok();
 
-        crate::db::queries::messages::remove_reaction(pool, message_id, user_id, emoji)
+        direct_messages::remove_participant(pool, conversation_id, &user_id)
             .await
             .map_err(|e| format!("DB error: {e}"))?;
+        let remaining: Vec<&String> = participants.iter().filter(|id| **id != user_id).collect();
+        let Some(next_owner) = remaining.first() else {
+            direct_messages::delete_conversation(pool, conversation_id)
+                .await
+                .map_err(|e| format!("DB error: {e}"))?;
+            let info = GroupDmInfo {
+                id: conversation_id.to_string(),
+                target: format!("&{conversation_id}"),
+                name: None,
+                owner_id: user_id.clone(),
+                participants: vec![],
+            };
+            let event = ChatEvent::GroupDmUpdate {
+                conversation: info,
+                change: GroupDmChange::Left {
+                    nickname: session.nickname.clone(),
+                },
+            };
+            self.send_to_users(std::slice::from_ref(&user_id), &event, None);
+            return Ok(());
+        };
 
-        let server_id = msg.server_id.ok_or("Message has no server")?;
-        let channel_id = msg.channel_id.ok_or("Message has no channel")?;
-
-        let channel_name = self
-            .channels
-            .get(&channel_id)
-            .map(|ch| ch.name.clone())
-            .unwrap_or_default();
-
-        let event = ChatEvent::ReactionRemove {
-            message_id: message_id.parse().unwrap_or_default(),
-            server_id,
-            channel: channel_name,
-            user_id: user_id.to_string(),
+        let change = GroupDmChange::Left {
             nickname: session.nickname.clone(),
-            emoji: emoji.to_string(),
         };
-
-        self.broadcast_to_channel(&channel_id, &event, None);
-
+        self.announce_group_dm_change(pool, &session, conversation_id, change, Some(&user_id))
+            .await?;
+        if was_owner {
+            direct_messages::set_owner(pool, conversation_id, next_owner)
+                .await
+                .map_err(|e| format!("DB error: {e}"))?;
+            let change = GroupDmChange::OwnerChanged {
+                by: session.nickname.clone(),
+                nickname: self.find_nick_by_user_id(next_owner).await,
+            };
+            self.announce_group_dm_change(pool, &session, conversation_id, change, None)
+                .await?;
+        }
         Ok(())
     }
 
-    // ── Typing indicators ────────────────────────────────────────────
-
-    /// Broadcast a typing indicator to a channel.
-    pub fn send_typing(
+    /// Hand a group DM to another participant. Only its owner may.
+    pub async fn transfer_group_dm_ownership(
         &self,
         session_id: SessionId,
-        server_id: &str,
-        channel_name: &str,
+        conversation_id: &str,
+        nickname: &str,
     ) -> Result<(), String> {
-        let channel_name = normalize_channel_name(channel_name);
+        use crate::db::queries::direct_messages;
 
         let session = self
             .sessions
             .get(&session_id)
             .ok_or("Session not found")?
             .clone();
+        let user_id = session.user_id.clone().ok_or("AUTH_REQUIRED")?;
+        let pool = self.db.as_ref().ok_or("No database configured")?;
+        let participants = self
+            .group_dm_participant_ids(pool, conversation_id, &user_id)
+            .await?;
+        self.require_group_dm_owner(pool, conversation_id, &user_id)
+            .await?;
 
-        let channel_id = self.resolve_channel_id(server_id, &channel_name)?;
+        let new_owner = self
+            .find_user_id_by_nick(nickname)
+            .await
+            .ok_or(format!("No such user: {nickname}"))?;
+        if !participants.contains(&new_owner) {
+            return Err(format!("{nickname} is not in this group"));
+        }
+        if new_owner == user_id {
+            return Ok(());
+        }
 
-        let event = ChatEvent::TypingStart {
-            server_id: server_id.to_string(),
-            channel: channel_name,
-            nickname: session.nickname.clone(),
+        direct_messages::set_owner(pool, conversation_id, &new_owner)
+            .await
+            .map_err(|e| format!("DB error: {e}"))?;
+        let change = GroupDmChange::OwnerChanged {
+            by: session.nickname.clone(),
+            nickname: self.find_nick_by_user_id(&new_owner).await,
         };
+        self.announce_group_dm_change(pool, &session, conversation_id, change, None)
+            .await
+    }
 
-        self.broadcast_to_channel(&channel_id, &event, Some(session_id));
+    /// A group DM the session's user is in.
+    pub async fn group_dm(
+        &self,
+        session_id: SessionId,
+        conversation_id: &str,
+    ) -> Result<GroupDmInfo, String> {
+        let user_id = self.get_user_id(session_id)?;
+        let pool = self.db.as_ref().ok_or("No database configured")?;
+        self.group_dm_participant_ids(pool, conversation_id, &user_id)
+            .await?;
+        self.group_dm_info(pool, conversation_id).await
+    }
 
-        Ok(())
+    /// Every group DM a user is in, most recently active first.
+    pub async fn list_group_dms(&self, user_id: &str) -> Result<Vec<GroupDmInfo>, String> {
+        let Some(pool) = &self.db else {
+            return Ok(vec![]);
+        };
+        let rows = crate::db::queries::direct_messages::list_conversations(pool, user_id)
+            .await
+            .map_err(|e| format!("DB error: {e}"))?;
+        let mut groups = Vec::new();
+        for row in rows.into_iter().filter(|r| r.is_group) {
+            groups.push(self.group_dm_info(pool, &row.id).await?);
+        }
+        Ok(groups)
     }
 
-    // ── Read state ────────────────────────────────────────────────────
+    /// The user IDs in a group DM, provided `user_id` is one of them.
+    async fn group_dm_participant_ids(
+        &self,
+        pool: &SqlitePool,
+        conversation_id: &str,
+        user_id: &str,
+    ) -> Result<Vec<String>, String> {
+        use crate::db::queries::direct_messages;
 
-    /// Mark a channel as read for a user, up to a specific message ID.
-    pub async fn mark_read(
+        let not_found = || format!("No such group DM: &{conversation_id}");
+        direct_messages::get_group_conversation(pool, conversation_id)
+            .await
+            .map_err(|e| format!("DB error: {e}"))?
+            .ok_or_else(not_found)?;
+        let participants = direct_messages::participant_ids(pool, conversation_id)
+            .await
+            .map_err(|e| format!("DB error: {e}"))?;
+        if !participants.iter().any(|id| id == user_id) {
+            return Err(not_found());
+        }
+        Ok(participants)
+    }
+
+    async fn require_group_dm_owner(
         &self,
-        session_id: SessionId,
-        server_id: &str,
-        channel_name: &str,
-        message_id: &str,
+        pool: &SqlitePool,
+        conversation_id: &str,
+        user_id: &str,
     ) -> Result<(), String> {
-        let session = self
-            .sessions
-            .get(&session_id)
-            .ok_or("Session not found")?
-            .clone();
-
-        let user_id = session.user_id.as_deref().ok_or("AUTH_REQUIRED")?;
-        let pool = self.db.as_ref().ok_or("No database configured")?;
+        let group =
+            crate::db::queries::direct_messages::get_group_conversation(pool, conversation_id)
+                .await
+                .map_err(|e| format!("DB error: {e}"))?
+                .ok_or(format!("No such group DM: &{conversation_id}"))?;
+        if group.owner_id.as_deref() != Some(user_id) {
+            return Err("FORBIDDEN: only the group owner can do that".into());
+        }
+        Ok(())
+    }
 
-        let channel_name = normalize_channel_name(channel_name);
-        let channel_id = self.resolve_channel_id(server_id, &channel_name)?;
+    async fn group_dm_info(
+        &self,
+        pool: &SqlitePool,
+        conversation_id: &str,
+    ) -> Result<GroupDmInfo, String> {
+        use crate::db::queries::direct_messages;
 
-        crate::db::queries::messages::mark_channel_read(pool, user_id, &channel_id, message_id)
+        let group = direct_messages::get_group_conversation(pool, conversation_id)
+            .await
+            .map_err(|e| format!("DB error: {e}"))?
+            .ok_or(format!("No such group DM: &{conversation_id}"))?;
+        let participants = direct_messages::get_participants(pool, std::slice::from_ref(&group.id))
             .await
             .map_err(|e| format!("DB error: {e}"))?;
+        Ok(GroupDmInfo {
+            target: format!("&{}", group.id),
+            name: group.name,
+            owner_id: group.owner_id.unwrap_or_default(),
+            participants: participants
+                .into_iter()
+                .map(|p| DmParticipant {
+                    nickname: self
+                        .nick_of_user(&p.user_id)
+                        .unwrap_or_else(|| p.username.clone()),
+                    user_id: p.user_id,
+                    avatar_url: p.avatar_url,
+                })
+                .collect(),
+            id: group.id,
+        })
+    }
+
+    /// Tell a group DM's participants about a change, and anyone who just
+    /// left or was removed, then record it as a system message in the
+    /// conversation.
+    async fn announce_group_dm_change(
+        &self,
+        pool: &SqlitePool,
+        actor: &UserSession,
+        conversation_id: &str,
+        change: GroupDmChange,
+        departed: Option<&str>,
+    ) -> Result<(), String> {
+        use crate::db::queries::direct_messages;
+
+        let info = self.group_dm_info(pool, conversation_id).await?;
+        let participants: Vec<String> = info
+            .participants
+            .iter()
+            .map(|p| p.user_id.clone())
+            .collect();
+        let content = change.describe();
+        let target = info.target.clone();
+
+        let mut notified = participants.clone();
+        notified.extend(departed.map(String::from));
+        self.send_to_users(
+            &notified,
+            &ChatEvent::GroupDmUpdate {
+                conversation: info,
+                change,
+            },
+            None,
+        );
 
+        let msg_id = Uuid::new_v4();
+        direct_messages::insert_direct_message(
+            pool,
+            &direct_messages::InsertDirectMessageParams {
+                id: &msg_id.to_string(),
+                conversation_id,
+                sender_id: actor.user_id.as_deref().unwrap_or_default(),
+                sender_nick: &actor.nickname,
+                target_user_id: None,
+                content: &content,
+                kind: MessageKind::System.as_str(),
+            },
+        )
+        .await
+        .map_err(|e| format!("DB error: {e}"))?;
+        let event = ChatEvent::Message {
+            id: msg_id,
+            server_id: None,
+            from: actor.nickname.clone(),
+            target,
+            content,
+            timestamp: Utc::now(),
+            avatar_url: actor.avatar_url.clone(),
+            reply_to: None,
+            attachments: None,
+            kind: MessageKind::System,
+        };
+        self.send_to_users(&participants, &event, None);
         Ok(())
     }
 
+    /// Send an event to every live session of the given users, except `exclude`.
+    fn send_to_users(&self, user_ids: &[String], event: &ChatEvent, exclude: Option<SessionId>) {
+        for session in self.sessions.iter() {
+            if Some(session.id) != exclude
+                && session
+                    .user_id
+                    .as_ref()
+                    .is_some_and(|uid| user_ids.contains(uid))
+            {
+                let _ = session.send(event.clone());
+            }
+        }
+    }
+
     /// Get unread counts for all channels in a server for a user.
     pub async fn get_unread_counts(
         &self,
@@ -1810,6 +3812,86 @@ impl ChatEngine {
             .collect())
     }
 
+    /// Save an IRC client's read markers as it disconnects: the last message
+    /// it was sent in each conversation, and a marker at the current time for
+    /// conversations it has none for yet, so it can be replayed what it misses.
+    pub async fn save_read_markers(
+        &self,
+        session_id: SessionId,
+        client: &str,
+        seen: &HashMap<MarkerTarget, String>,
+    ) -> Result<(), String> {
+        use crate::db::queries::messages::{mark_client_read, touch_client_read};
+
+        let session = self.get_session(session_id).ok_or("Session not found")?;
+        let (Some(user_id), Some(pool)) = (session.user_id.as_deref(), &self.db) else {
+            return Ok(());
+        };
+
+        let mut targets: HashSet<MarkerTarget> = self
+            .channels
+            .iter()
+            .filter(|ch| ch.members.contains(&session_id))
+            .map(|ch| MarkerTarget::Channel(ch.server_id.clone(), ch.name.clone()))
+            .collect();
+        targets.insert(MarkerTarget::Direct);
+        targets.extend(seen.keys().cloned());
+
+        for target in targets {
+            let Some(key) = self.read_marker_key(&target) else {
+                continue;
+            };
+            match seen.get(&target) {
+                Some(message_id) => mark_client_read(pool, user_id, client, &key, message_id).await,
+                None => touch_client_read(pool, user_id, client, &key).await,
+            }
+            .map_err(|e| format!("DB error: {e}"))?;
+        }
+        Ok(())
+    }
+
+    /// Where an IRC client left off in a conversation: its own read marker,
+    /// or for channels the account's. Returns the anchor to replay from and
+    /// the time the marker was set.
+    pub async fn read_marker(
+        &self,
+        session_id: SessionId,
+        client: &str,
+        target: &MarkerTarget,
+    ) -> Option<(HistoryAnchor, chrono::DateTime<Utc>)> {
+        use crate::db::queries::messages::get_client_read;
+
+        let session = self.get_session(session_id)?;
+        let user_id = session.user_id.as_deref()?;
+        let pool = self.db.as_ref()?;
+        let key = self.read_marker_key(target)?;
+
+        let mut marker = get_client_read(pool, user_id, client, &key).await;
+        if let (Ok(None), MarkerTarget::Channel(..)) = (&marker, target) {
+            marker = get_client_read(pool, user_id, "", &key).await;
+        }
+        let (message_id, read_at) = marker
+            .inspect_err(|e| warn!(error = %e, "failed to read read marker"))
+            .ok()??;
+
+        let read_at = parse_db_timestamp(&read_at)?;
+        let anchor = match message_id {
+            Some(id) => HistoryAnchor::MessageId(id),
+            None => HistoryAnchor::Timestamp(read_at),
+        };
+        Some((anchor, read_at))
+    }
+
+    /// The `read_states` conversation key of a marker target.
+    fn read_marker_key(&self, target: &MarkerTarget) -> Option<String> {
+        match target {
+            MarkerTarget::Channel(server_id, channel) => {
+                self.resolve_channel_id(server_id, channel).ok()
+            }
+            MarkerTarget::Direct => Some(DIRECT_READ_MARKER.to_string()),
+        }
+    }
+
     // ── Roles ────────────────────────────────────────────────────────
 
     /// Get effective permissions for a user in a channel.
@@ -2021,9 +4103,12 @@ impl ChatEngine {
         role_id: &str,
     ) -> Result<Vec<String>, String> {
         let pool = self.db.as_ref().ok_or("No database configured")?;
+        let before = self.prefix_snapshot(server_id, Some(user_id), None).await;
         crate::db::queries::roles::assign_role(pool, server_id, user_id, role_id)
             .await
             .map_err(|e| format!("Failed to assign role: {e}"))?;
+        let after = self.prefix_snapshot(server_id, Some(user_id), None).await;
+        self.broadcast_prefix_changes(server_id, &before, &after);
         let roles = crate::db::queries::roles::get_user_roles(pool, server_id, user_id)
             .await
             .map_err(|e| format!("DB error: {e}"))?;
@@ -2038,9 +4123,12 @@ impl ChatEngine {
         role_id: &str,
     ) -> Result<Vec<String>, String> {
         let pool = self.db.as_ref().ok_or("No database configured")?;
+        let before = self.prefix_snapshot(server_id, Some(user_id), None).await;
         crate::db::queries::roles::remove_role(pool, server_id, user_id, role_id)
             .await
             .map_err(|e| format!("Failed to remove role: {e}"))?;
+        let after = self.prefix_snapshot(server_id, Some(user_id), None).await;
+        self.broadcast_prefix_changes(server_id, &before, &after);
         let roles = crate::db::queries::roles::get_user_roles(pool, server_id, user_id)
             .await
             .map_err(|e| format!("DB error: {e}"))?;
@@ -2195,6 +4283,153 @@ impl ChatEngine {
         })
     }
 
+    // ── IRC operators ───────────────────────────────────────────────
+
+    /// Make a session an IRC operator. Only system admins may.
+    pub async fn oper(&self, session_id: SessionId) -> Result<(), String> {
+        let session = self.get_session(session_id).ok_or("Session not found")?;
+        let user_id = session.user_id.as_deref().ok_or("AUTH_REQUIRED")?;
+        let pool = self.db.as_ref().ok_or("No database configured")?;
+
+        let is_admin = crate::db::queries::servers::is_system_admin(pool, user_id)
+            .await
+            .map_err(|e| format!("DB error: {e}"))?;
+        if !is_admin {
+            return Err("FORBIDDEN: not a system admin".into());
+        }
+
+        session.set_oper();
+        info!(nickname = %session.nickname, "became IRC operator");
+        Ok(())
+    }
+
+    /// Whether a session is an IRC operator.
+    pub fn is_oper(&self, session_id: SessionId) -> bool {
+        self.get_session(session_id).is_some_and(|s| s.is_oper())
+    }
+
+    fn require_oper(&self, session_id: SessionId) -> Result<Arc<UserSession>, String> {
+        let session = self.get_session(session_id).ok_or("Session not found")?;
+        if !session.is_oper() {
+            return Err("FORBIDDEN: not an IRC operator".into());
+        }
+        Ok(session)
+    }
+
+    /// Disconnect every session behind a nick, always-on ones included.
+    pub fn kill(
+        &self,
+        session_id: SessionId,
+        target_nick: &str,
+        reason: &str,
+    ) -> Result<(), String> {
+        let oper = self.require_oper(session_id)?;
+        let target = self
+            .get_session_by_nick(target_nick)
+            .ok_or_else(|| format!("No such nick: {target_nick}"))?;
+
+        let quit_reason = format!("Killed ({} ({reason}))", oper.nickname);
+        for sid in self.sibling_session_ids(&target) {
+            let Some(victim) = self.get_session(sid) else {
+                continue;
+            };
+            let _ = victim.send(ChatEvent::Killed {
+                killed_by: oper.nickname.clone(),
+                reason: reason.to_string(),
+            });
+            // Detached sessions go for good rather than staying always-on
+            victim.detach();
+            self.end_session(sid, Some(quit_reason.clone()));
+        }
+
+        info!(oper = %oper.nickname, target = %target_nick, %reason, "killed user");
+        Ok(())
+    }
+
+    /// Send a message to every IRC operator.
+    pub fn wallops(&self, session_id: SessionId, message: &str) -> Result<(), String> {
+        let oper = self.require_oper(session_id)?;
+        let event = ChatEvent::Wallops {
+            from: oper.nickname.clone(),
+            message: message.to_string(),
+        };
+        for session in self.sessions.iter().filter(|s| s.is_oper()) {
+            let _ = session.send(event.clone());
+        }
+        Ok(())
+    }
+
+    /// Send a notice to every connected session, web and IRC alike.
+    pub fn global_notice(&self, session_id: SessionId, message: &str) -> Result<(), String> {
+        let oper = self.require_oper(session_id)?;
+        let event = ChatEvent::ServerNotice {
+            message: format!("[Global notice from {}] {message}", oper.nickname),
+        };
+        for session in self.sessions.iter() {
+            let _ = session.send(event.clone());
+        }
+        info!(oper = %oper.nickname, "sent global notice");
+        Ok(())
+    }
+
+    /// Put a user in a channel whatever its access rules.
+    pub fn sajoin(
+        &self,
+        session_id: SessionId,
+        target_nick: &str,
+        server_id: &str,
+        channel_name: &str,
+    ) -> Result<(), String> {
+        self.require_oper(session_id)?;
+        let target = self
+            .get_session_by_nick(target_nick)
+            .ok_or_else(|| format!("No such nick: {target_nick}"))?;
+        let channel_name = normalize_channel_name(channel_name);
+        if self.resolve_channel_id(server_id, &channel_name).is_err() {
+            validation::validate_channel_name(&channel_name)?;
+        }
+
+        self.enter_channel(&target, server_id, &channel_name);
+        Ok(())
+    }
+
+    /// Take a user out of a channel.
+    pub fn sapart(
+        &self,
+        session_id: SessionId,
+        target_nick: &str,
+        server_id: &str,
+        channel_name: &str,
+        reason: Option<String>,
+    ) -> Result<(), String> {
+        self.require_oper(session_id)?;
+        let target = self
+            .get_session_by_nick(target_nick)
+            .ok_or_else(|| format!("No such nick: {target_nick}"))?;
+        self.part_channel(target.id, server_id, channel_name, reason)
+    }
+
+    /// Session, channel and message counters.
+    pub fn stats(&self) -> ServerStats {
+        let mut stats = ServerStats {
+            irc_sessions: 0,
+            web_sessions: 0,
+            detached_sessions: 0,
+            channels: self.channels.len(),
+            servers: self.servers.len(),
+            messages_last_minute: self.message_rate.last_minute(Instant::now()),
+            uptime_secs: (Utc::now() - self.started_at).num_seconds(),
+        };
+        for session in self.sessions.iter() {
+            match (session.is_detached(), session.protocol) {
+                (true, _) => stats.detached_sessions += 1,
+                (false, Protocol::Irc) => stats.irc_sessions += 1,
+                (false, Protocol::WebSocket) => stats.web_sessions += 1,
+            }
+        }
+        stats
+    }
+
     // ── Utility ─────────────────────────────────────────────────────
 
     /// Get a reference to the database pool (if configured).
@@ -2202,9 +4437,24 @@ impl ChatEngine {
         self.db.as_ref()
     }
 
+    /// Current user, session and channel counts.
+    pub fn lusers(&self) -> LuserCounts {
+        let users: HashSet<String> = self
+            .sessions
+            .iter()
+            .map(|s| s.user_id.clone().unwrap_or_else(|| s.nickname.clone()))
+            .collect();
+        LuserCounts {
+            users: users.len(),
+            clients: self.sessions.len(),
+            channels: self.channels.len(),
+            servers: self.servers.len(),
+        }
+    }
+
     /// Check if a nickname is available.
     pub fn is_nick_available(&self, nickname: &str) -> bool {
-        !self.nick_to_session.contains_key(nickname)
+        !self.nick_to_session.contains_key(&nick_key(nickname))
     }
 
     /// Get a session by ID.
@@ -2212,6 +4462,47 @@ impl ChatEngine {
         self.sessions.get(&session_id).map(|s| s.clone())
     }
 
+    /// Get the session currently holding a nickname.
+    pub fn get_session_by_nick(&self, nickname: &str) -> Option<Arc<UserSession>> {
+        let session_id = *self.nick_to_session.get(&nick_key(nickname))?;
+        self.get_session(session_id)
+    }
+
+    /// Resolve a nickname to a registered user ID, checking live sessions before the database.
+    pub async fn find_user_id_by_nick(&self, nickname: &str) -> Option<String> {
+        if let Some(user_id) = self
+            .get_session_by_nick(nickname)
+            .and_then(|s| s.user_id.clone())
+        {
+            return Some(user_id);
+        }
+        let pool = self.db.as_ref()?;
+        crate::db::queries::users::get_user_by_nickname(pool, nickname)
+            .await
+            .ok()
+            .flatten()
+            .map(|(user_id, ..)| user_id)
+    }
+
+    /// Get the nickname a user is known by: their live session's, else their username.
+    pub async fn find_nick_by_user_id(&self, user_id: &str) -> String {
+        let (nickname, _) = self.find_user_display_info(user_id);
+        if self
+            .sessions
+            .iter()
+            .any(|s| s.user_id.as_deref() == Some(user_id))
+        {
+            return nickname;
+        }
+        if let Some(pool) = &self.db
+            && let Ok(Some((_, username, ..))) =
+                crate::db::queries::users::get_user(pool, user_id).await
+        {
+            return username;
+        }
+        nickname
+    }
+
     /// Resolve a channel name within a server to its channel ID.
     pub fn resolve_channel_id(
         &self,
@@ -2267,7 +4558,13 @@ impl ChatEngine {
         }
 
         // Persist to DB
+        let mut was_invisible = false;
         if let Some(pool) = &self.db {
+            was_invisible = crate::db::queries::presence::get_presence(pool, &user_id)
+                .await
+                .ok()
+                .flatten()
+                .is_some_and(|p| p.status == "invisible");
             crate::db::queries::presence::upsert_presence(
                 pool,
                 &user_id,
@@ -2300,11 +4597,13 @@ impl ChatEngine {
                     server_id: server.id.clone(),
                     presence: presence.clone(),
                 };
-                // Send to all sessions in this server's channels
+                // Send to all sessions in this server's channels, once each
+                let mut notified = HashSet::new();
                 for channel_id in server.channel_ids.iter() {
                     if let Some(channel) = self.channels.get(channel_id) {
                         for &member_sid in &channel.members {
                             if member_sid != session_id
+                                && notified.insert(member_sid)
                                 && let Some(s) = self.sessions.get(&member_sid)
                             {
                                 let _ = s.send(event.clone());
@@ -2315,6 +4614,12 @@ impl ChatEngine {
             }
         }
 
+        // Going invisible looks like going offline to anyone monitoring the nick
+        let invisible = status == "invisible";
+        if invisible != was_invisible {
+            self.notify_monitors(&session.nickname, !invisible);
+        }
+
         Ok(())
     }
 
@@ -2384,6 +4689,33 @@ impl ChatEngine {
         Ok(presences)
     }
 
+    /// Presence of whoever currently holds a nickname, as other users see it.
+    /// None for guests, unknown nicks, or users who never set a presence.
+    pub fn presence_of_nick(&self, nickname: &str) -> Option<super::events::PresenceInfo> {
+        let session = self.get_session_by_nick(nickname)?;
+        let user_id = session.user_id.clone()?;
+        let pool = self.db.as_ref()?;
+
+        let row = tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current()
+                .block_on(crate::db::queries::presence::get_presence(pool, &user_id))
+        })
+        .ok()??;
+
+        Some(super::events::PresenceInfo {
+            user_id,
+            nickname: session.nickname.clone(),
+            avatar_url: session.avatar_url.clone(),
+            status: if row.status == "invisible" {
+                "offline".into()
+            } else {
+                row.status
+            },
+            custom_status: row.custom_status,
+            status_emoji: row.status_emoji,
+        })
+    }
+
     /// Find a user's display info (nickname, avatar) from active sessions or return defaults.
     fn find_user_display_info(&self, user_id: &str) -> (String, Option<String>) {
         for session in self.sessions.iter() {
@@ -2394,6 +4726,89 @@ impl ChatEngine {
         (format!("user-{}", &user_id[..8.min(user_id.len())]), None)
     }
 
+    // ── Monitoring ───────────────────────────────────────────
+
+    /// Start monitoring nicknames. Returns those that didn't fit under
+    /// `MONITOR_LIMIT`, which aren't monitored.
+    pub fn monitor_add(&self, session_id: SessionId, nicknames: &[String]) -> Vec<String> {
+        let mut watched = self.monitors.entry(session_id).or_default();
+        let mut rejected = Vec::new();
+        for nickname in nicknames {
+            let key = nick_key(nickname);
+            if watched.len() >= MONITOR_LIMIT && !watched.contains(&key) {
+                rejected.push(nickname.clone());
+            } else {
+                watched.insert(key);
+            }
+        }
+        rejected
+    }
+
+    /// Stop monitoring nicknames.
+    pub fn monitor_remove(&self, session_id: SessionId, nicknames: &[String]) {
+        if let Some(mut watched) = self.monitors.get_mut(&session_id) {
+            for nickname in nicknames {
+                watched.remove(&nick_key(nickname));
+            }
+        }
+    }
+
+    /// Stop monitoring every nickname.
+    pub fn monitor_clear(&self, session_id: SessionId) {
+        self.monitors.remove(&session_id);
+    }
+
+    /// The nicknames a session monitors, case-folded and sorted.
+    pub fn monitor_list(&self, session_id: SessionId) -> Vec<String> {
+        let mut nicknames: Vec<String> = self
+            .monitors
+            .get(&session_id)
+            .map(|watched| watched.iter().cloned().collect())
+            .unwrap_or_default();
+        nicknames.sort();
+        nicknames
+    }
+
+    /// Whether a nickname is online as monitoring sees it: someone holds it
+    /// and they aren't invisible.
+    pub fn is_nick_online(&self, nickname: &str) -> bool {
+        self.get_session_by_nick(nickname).is_some()
+            && self
+                .presence_of_nick(nickname)
+                .is_none_or(|p| p.status != "offline")
+    }
+
+    /// Tell the sessions monitoring a nickname that it came online or went offline.
+    fn notify_monitors(&self, nickname: &str, online: bool) {
+        let key = nick_key(nickname);
+        let watchers: Vec<SessionId> = self
+            .monitors
+            .iter()
+            .filter(|watched| watched.contains(&key))
+            .map(|watched| *watched.key())
+            .collect();
+        for sid in watchers {
+            if let Some(session) = self.get_session(sid) {
+                let _ = session.send(ChatEvent::MonitorStatus {
+                    nickname: nickname.to_string(),
+                    online,
+                });
+            }
+        }
+    }
+
+    /// The name a user's account is known by: their Bluesky DID if they
+    /// signed in with one, else their username.
+    pub fn account_name(&self, user_id: &str) -> Option<String> {
+        let pool = self.db.as_ref()?;
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current()
+                .block_on(crate::db::queries::users::get_account_name(pool, user_id))
+        })
+        .ok()
+        .flatten()
+    }
+
     // ── Server Nicknames ─────────────────────────────────────
 
     /// Set a user's server-specific display name.
@@ -2560,6 +4975,191 @@ impl ChatEngine {
             .collect())
     }
 
+    // ── Mentions ─────────────────────────────────────────────────
+
+    /// Who a channel message mentions, and how, once the sender's right to
+    /// mention `@everyone` and roles, each recipient's access to the channel
+    /// and their notification settings are taken into account. Someone
+    /// mentioned more than one way is counted the most direct way.
+    async fn resolve_mentions(
+        &self,
+        server_id: &str,
+        channel_id: &str,
+        sender_id: Option<&str>,
+        content: &str,
+    ) -> Vec<(String, MentionKind)> {
+        use crate::db::queries::{mentions, notifications, roles};
+
+        let parsed = super::mentions::parse_mentions(content);
+        let Some(pool) = &self.db else {
+            return vec![];
+        };
+        if parsed.is_empty() {
+            return vec![];
+        }
+        let can_mention_everyone = match sender_id {
+            Some(uid) => self
+                .get_effective_permissions(server_id, Some(channel_id), uid)
+                .await
+                .contains(Permissions::MENTION_EVERYONE),
+            None => false,
+        };
+
+        let members: HashSet<String> = self
+            .servers
+            .get(server_id)
+            .map(|s| s.member_user_ids.clone())
+            .unwrap_or_default();
+        let mut mentioned: HashMap<String, MentionKind> = HashMap::new();
+        let mut mention = |user_id: &str, kind: MentionKind| {
+            let entry = mentioned.entry(user_id.to_string()).or_insert(kind);
+            *entry = (*entry).max(kind);
+        };
+
+        if !parsed.names.is_empty() {
+            let mut names = mentions::member_names(pool, server_id)
+                .await
+                .unwrap_or_default();
+            names.extend(self.sessions.iter().filter_map(|s| {
+                let uid = s.user_id.as_ref().filter(|uid| members.contains(*uid))?;
+                Some((uid.clone(), s.nickname.clone()))
+            }));
+            for (user_id, name) in &names {
+                if parsed.names.contains(&name.to_lowercase()) {
+                    mention(user_id, MentionKind::User);
+                }
+            }
+
+            if can_mention_everyone {
+                let mentioned_roles: Vec<String> = roles::list_roles(pool, server_id)
+                    .await
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|r| r.is_default == 0 && parsed.names.contains(&r.name.to_lowercase()))
+                    .map(|r| r.id)
+                    .collect();
+                if !mentioned_roles.is_empty() {
+                    for assignment in roles::get_all_user_roles(pool, server_id)
+                        .await
+                        .unwrap_or_default()
+                    {
+                        if mentioned_roles.contains(&assignment.role_id) {
+                            mention(&assignment.user_id, MentionKind::Role);
+                        }
+                    }
+                }
+            }
+        }
+        if parsed.everyone && can_mention_everyone {
+            for user_id in &members {
+                mention(user_id, MentionKind::Everyone);
+            }
+        }
+        if let Some(sender_id) = sender_id {
+            mentioned.remove(sender_id);
+        }
+        mentioned.retain(|user_id, _| members.contains(user_id));
+        if mentioned.is_empty() {
+            return vec![];
+        }
+
+        // Everyone's access and settings are loaded at once, however many are mentioned
+        let user_ids: Vec<String> = mentioned.keys().cloned().collect();
+        let ctx = self.prefix_context(server_id, channel_id, &user_ids).await;
+        let mut settings: HashMap<String, Vec<_>> = HashMap::new();
+        for row in notifications::get_member_notification_settings(pool, server_id)
+            .await
+            .unwrap_or_default()
+        {
+            settings.entry(row.user_id.clone()).or_default().push(row);
+        }
+
+        let now = Utc::now();
+        let mut resolved: Vec<(String, MentionKind)> = mentioned
+            .into_iter()
+            .filter(|(user_id, kind)| {
+                self.permissions_in(&ctx, server_id, user_id)
+                    .contains(Permissions::VIEW_CHANNELS)
+                    && super::mentions::mention_allowed(
+                        settings.get(user_id).map_or(&[], Vec::as_slice),
+                        channel_id,
+                        *kind,
+                        now,
+                    )
+            })
+            .collect();
+        resolved.sort();
+        resolved
+    }
+
+    /// A page of a user's mentions inbox, newest first, and whether there
+    /// are older ones. `before` is the ID of the last mention already seen.
+    pub async fn list_mentions(
+        &self,
+        user_id: &str,
+        before: Option<&str>,
+        limit: usize,
+        unread_only: bool,
+    ) -> Result<(Vec<MentionInfo>, bool), String> {
+        let Some(pool) = &self.db else {
+            return Ok((vec![], false));
+        };
+        let mut rows = crate::db::queries::mentions::list_mentions(
+            pool,
+            user_id,
+            before,
+            unread_only,
+            limit as i64 + 1,
+        )
+        .await
+        .map_err(|e| format!("DB error: {e}"))?;
+        let has_more = rows.len() > limit;
+        rows.truncate(limit);
+
+        Ok((
+            rows.into_iter()
+                .map(|r| MentionInfo {
+                    id: r.id,
+                    message_id: r.message_id,
+                    server_id: r.server_id,
+                    channel: r.channel_name,
+                    from: r.sender_nick,
+                    content: r.content,
+                    kind: MentionKind::from_db(&r.kind),
+                    timestamp: r.created_at,
+                    read: r.read_at.is_some(),
+                })
+                .collect(),
+            has_more,
+        ))
+    }
+
+    /// Mark one of a user's mentions as read.
+    pub async fn mark_mention_read(&self, user_id: &str, mention_id: &str) -> Result<(), String> {
+        let pool = self.db.as_ref().ok_or("No database configured")?;
+        let found = crate::db::queries::mentions::mark_mention_read(pool, user_id, mention_id)
+            .await
+            .map_err(|e| format!("DB error: {e}"))?;
+        if found {
+            Ok(())
+        } else {
+            Err(format!("No such mention: {mention_id}"))
+        }
+    }
+
+    /// Mark all of a user's mentions as read, or only those in one server.
+    /// Returns how many were unread.
+    pub async fn mark_all_mentions_read(
+        &self,
+        user_id: &str,
+        server_id: Option<&str>,
+    ) -> Result<u64, String> {
+        let pool = self.db.as_ref().ok_or("No database configured")?;
+        crate::db::queries::mentions::mark_all_mentions_read(pool, user_id, server_id)
+            .await
+            .map_err(|e| format!("DB error: {e}"))
+    }
+
     // ── Pinning ─────────────────────────────────────────────────
 
     /// Pin a message in a channel. Requires MANAGE_MESSAGES permission or ownership of the message.
@@ -2738,8 +5338,11 @@ impl ChatEngine {
         let parent_channel_id = self.resolve_channel_id(server_id, &parent_channel_name)?;
 
         // Validate thread name
-        if name.is_empty() || name.len() > 100 {
-            return Err("Thread name must be between 1 and 100 characters".into());
+        if name.is_empty() || name.len() > validation::MAX_THREAD_NAME_LENGTH {
+            return Err(format!(
+                "Thread name must be between 1 and {} characters",
+                validation::MAX_THREAD_NAME_LENGTH
+            ));
         }
 
         let channel_type = if is_private {
@@ -2781,6 +5384,7 @@ impl ChatEngine {
         );
         ch.channel_type = channel_type.to_string();
         ch.thread_parent_message_id = Some(message_id.to_string());
+        ch.thread_parent_channel_id = Some(parent_channel_id.clone());
         ch.auto_archive_minutes = 1440;
         ch.is_private = is_private;
 
@@ -2902,6 +5506,41 @@ impl ChatEngine {
         Ok(())
     }
 
+    /// The name of the channel a thread was started in, or None if
+    /// `channel_name` isn't a thread.
+    pub fn thread_parent_name(&self, server_id: &str, channel_name: &str) -> Option<String> {
+        let thread_id = self
+            .channel_name_index
+            .get(&(server_id.to_string(), channel_name.to_string()))?
+            .clone();
+        let parent_id = self
+            .channels
+            .get(&thread_id)?
+            .thread_parent_channel_id
+            .clone()?;
+        self.channels.get(&parent_id).map(|ch| ch.name.clone())
+    }
+
+    /// IDs and names of the threads started in a channel, oldest first. Times
+    /// are compared to the second, as stored, so the order survives a restart.
+    pub fn threads_of(&self, server_id: &str, parent_channel_name: &str) -> Vec<(String, String)> {
+        let parent_channel_name = normalize_channel_name(parent_channel_name);
+        let Ok(parent_id) = self.resolve_channel_id(server_id, &parent_channel_name) else {
+            return Vec::new();
+        };
+        let mut threads: Vec<(i64, String, String)> = self
+            .channels
+            .iter()
+            .filter(|ch| ch.thread_parent_channel_id.as_deref() == Some(parent_id.as_str()))
+            .map(|ch| (ch.created_at.timestamp(), ch.id.clone(), ch.name.clone()))
+            .collect();
+        threads.sort();
+        threads
+            .into_iter()
+            .map(|(_, id, name)| (id, name))
+            .collect()
+    }
+
     // ── Bookmarks ───────────────────────────────────────────────
 
     /// Add a bookmark on a message for the authenticated user.
@@ -3028,6 +5667,57 @@ impl ChatEngine {
         }
     }
 
+    /// Remove a user's live sessions from every channel of a server they were
+    /// kicked or banned from, telling each channel (and the user) who did it.
+    fn evict_from_server_channels(
+        &self,
+        actor_session_id: SessionId,
+        server_id: &str,
+        target_user_id: &str,
+        reason: Option<&str>,
+    ) {
+        let kicked_by = self
+            .get_session(actor_session_id)
+            .map(|s| s.nickname.clone())
+            .unwrap_or_default();
+        let targets = self.user_session_ids(target_user_id);
+        let Some(nickname) = self.nick_of_user(target_user_id) else {
+            return;
+        };
+        let channels: Vec<(String, String)> = self
+            .channels
+            .iter()
+            .filter(|ch| ch.server_id == server_id)
+            .map(|ch| (ch.id.clone(), ch.name.clone()))
+            .collect();
+
+        for (channel_id, channel_name) in channels {
+            let present = self
+                .channels
+                .get(&channel_id)
+                .is_some_and(|ch| targets.iter().any(|sid| ch.members.contains(sid)));
+            if !present {
+                continue;
+            }
+            // One kick for the user, heard by every one of their sessions
+            let event = ChatEvent::ChannelKick {
+                server_id: server_id.to_string(),
+                channel: channel_name.clone(),
+                nickname: nickname.clone(),
+                kicked_by: kicked_by.clone(),
+                reason: reason.map(String::from),
+            };
+            self.broadcast_to_channel(&channel_id, &event, None);
+            if let Some(mut ch) = self.channels.get_mut(&channel_id) {
+                for sid in &targets {
+                    ch.members.remove(sid);
+                }
+            }
+            self.channels
+                .remove_if(&channel_id, |_, ch| ch.members.is_empty());
+        }
+    }
+
     /// Kick a member from a server.
     pub async fn kick_member(
         &self,
@@ -3057,6 +5747,7 @@ impl ChatEngine {
         if let Some(mut server) = self.servers.get_mut(server_id) {
             server.member_user_ids.remove(target_user_id);
         }
+        self.evict_from_server_channels(session_id, server_id, target_user_id, reason);
 
         // Log to audit log
         let audit_id = Uuid::new_v4().to_string();
@@ -3142,6 +5833,7 @@ impl ChatEngine {
         if let Some(mut server) = self.servers.get_mut(server_id) {
             server.member_user_ids.remove(target_user_id);
         }
+        self.evict_from_server_channels(session_id, server_id, target_user_id, reason);
 
         // Audit log
         let audit_id = Uuid::new_v4().to_string();
@@ -3222,8 +5914,12 @@ impl ChatEngine {
         Ok(())
     }
 
-    /// Get the list of bans for a server.
-    pub async fn list_bans(&self, session_id: SessionId, server_id: &str) -> Result<(), String> {
+    /// Get the bans for a server.
+    pub async fn get_bans(
+        &self,
+        session_id: SessionId,
+        server_id: &str,
+    ) -> Result<Vec<BanInfo>, String> {
         self.require_permission(session_id, server_id, None, Permissions::BAN_MEMBERS)
             .await?;
 
@@ -3235,7 +5931,7 @@ impl ChatEngine {
             .await
             .map_err(|e| format!("Failed to list bans: {e}"))?;
 
-        let bans: Vec<BanInfo> = rows
+        Ok(rows
             .into_iter()
             .map(|r| BanInfo {
                 id: r.id,
@@ -3244,7 +5940,12 @@ impl ChatEngine {
                 reason: r.reason,
                 created_at: r.created_at,
             })
-            .collect();
+            .collect())
+    }
+
+    /// Send the list of bans for a server to the requesting session.
+    pub async fn list_bans(&self, session_id: SessionId, server_id: &str) -> Result<(), String> {
+        let bans = self.get_bans(session_id, server_id).await?;
 
         if let Some(session) = self.get_session(session_id) {
             let _ = session.send(ChatEvent::BanList {
@@ -3311,6 +6012,33 @@ impl ChatEngine {
         Ok(())
     }
 
+    /// Get the members of a server whose timeout has not yet expired, as (user_id, timeout_until).
+    pub async fn get_timeouts(
+        &self,
+        session_id: SessionId,
+        server_id: &str,
+    ) -> Result<Vec<(String, String)>, String> {
+        self.require_permission(session_id, server_id, None, Permissions::KICK_MEMBERS)
+            .await?;
+
+        let Some(pool) = &self.db else {
+            return Err("No database configured".into());
+        };
+
+        let rows = crate::db::queries::moderation::list_member_timeouts(pool, server_id)
+            .await
+            .map_err(|e| format!("Failed to list timeouts: {e}"))?;
+
+        let now = Utc::now();
+        Ok(rows
+            .into_iter()
+            .filter(|(_, until)| {
+                chrono::NaiveDateTime::parse_from_str(until, "%Y-%m-%d %H:%M:%S")
+                    .is_ok_and(|dt| dt.and_utc() > now)
+            })
+            .collect())
+    }
+
     /// Set slow mode on a channel.
     pub async fn set_slowmode(
         &self,
@@ -3374,17 +6102,164 @@ impl ChatEngine {
             .await
             .map_err(|e| format!("Failed to set NSFW: {e}"))?;
 
-        // Broadcast
-        let event = ChatEvent::NsfwUpdate {
+        // Broadcast
+        let event = ChatEvent::NsfwUpdate {
+            server_id: server_id.to_string(),
+            channel: channel_name.to_string(),
+            is_nsfw,
+        };
+        self.broadcast_to_server(server_id, &event);
+
+        Ok(())
+    }
+
+    /// Make a channel private (members-only) or public.
+    pub async fn set_channel_private(
+        &self,
+        session_id: SessionId,
+        server_id: &str,
+        channel_name: &str,
+        is_private: bool,
+    ) -> Result<(), String> {
+        self.require_permission(session_id, server_id, None, Permissions::MANAGE_CHANNELS)
+            .await?;
+
+        let Some(pool) = &self.db else {
+            return Err("No database configured".into());
+        };
+
+        let channel_id = self.resolve_channel_id(server_id, channel_name)?;
+
+        crate::db::queries::channels::set_channel_private(pool, &channel_id, is_private)
+            .await
+            .map_err(|e| format!("Failed to set channel privacy: {e}"))?;
+
+        if let Some(mut ch) = self.channels.get_mut(&channel_id) {
+            ch.is_private = is_private;
+        }
+
+        // Broadcast
+        let event = ChatEvent::PrivacyUpdate {
+            server_id: server_id.to_string(),
+            channel: channel_name.to_string(),
+            is_private,
+        };
+        self.broadcast_to_server(server_id, &event);
+
+        Ok(())
+    }
+
+    /// Moderate a channel, or lift moderation. A moderated channel denies
+    /// SEND_MESSAGES to @everyone through a channel override and allows it for
+    /// every other role that grants it, so only members holding a role may speak.
+    pub async fn set_channel_moderated(
+        &self,
+        session_id: SessionId,
+        server_id: &str,
+        channel_name: &str,
+        moderated: bool,
+    ) -> Result<(), String> {
+        self.require_permission(session_id, server_id, None, Permissions::MANAGE_CHANNELS)
+            .await?;
+
+        let Some(pool) = &self.db else {
+            return Err("No database configured".into());
+        };
+
+        let channel_id = self.resolve_channel_id(server_id, channel_name)?;
+
+        let roles = crate::db::queries::roles::list_roles(pool, server_id)
+            .await
+            .map_err(|e| format!("DB error: {e}"))?;
+        let overrides = crate::db::queries::channels::get_channel_overrides(pool, &channel_id)
+            .await
+            .map_err(|e| format!("DB error: {e}"))?;
+        let before = self
+            .prefix_snapshot(server_id, None, Some(&channel_id))
+            .await;
+
+        for role in &roles {
+            let (allow, deny) = if role.is_default != 0 {
+                (Permissions::empty(), Permissions::SEND_MESSAGES)
+            } else if Permissions::from_bits_truncate(role.permissions as u64)
+                .contains(Permissions::SEND_MESSAGES)
+            {
+                (Permissions::SEND_MESSAGES, Permissions::empty())
+            } else {
+                continue;
+            };
+            let existing = overrides
+                .iter()
+                .find(|o| o.target_type == "role" && o.target_id == role.id);
+            update_role_override(
+                pool,
+                &channel_id,
+                &role.id,
+                existing,
+                allow,
+                deny,
+                moderated,
+            )
+            .await
+            .map_err(|e| format!("Failed to update channel override: {e}"))?;
+        }
+
+        if let Some(mut ch) = self.channels.get_mut(&channel_id) {
+            ch.moderated = moderated;
+        }
+
+        // Broadcast the mode, then who gained or lost a voice by it
+        let event = ChatEvent::ModeratedUpdate {
             server_id: server_id.to_string(),
             channel: channel_name.to_string(),
-            is_nsfw,
+            moderated,
         };
         self.broadcast_to_server(server_id, &event);
+        let after = self
+            .prefix_snapshot(server_id, None, Some(&channel_id))
+            .await;
+        self.broadcast_prefix_changes(server_id, &before, &after);
 
         Ok(())
     }
 
+    /// Get the moderation state of a channel.
+    pub async fn channel_moderation(
+        &self,
+        server_id: &str,
+        channel_name: &str,
+    ) -> Result<ChannelModeration, String> {
+        let channel_name = normalize_channel_name(channel_name);
+        let channel_id = self.resolve_channel_id(server_id, &channel_name)?;
+
+        let mut state = self
+            .channels
+            .get(&channel_id)
+            .map(|ch| ChannelModeration {
+                is_private: ch.is_private,
+                moderated: ch.moderated,
+                created_at: ch.created_at,
+            })
+            .unwrap_or(ChannelModeration {
+                is_private: false,
+                moderated: false,
+                created_at: Utc::now(),
+            });
+
+        let Some(pool) = &self.db else {
+            return Ok(state);
+        };
+
+        if let Ok(Some(row)) = crate::db::queries::channels::get_channel(pool, &channel_id).await {
+            state.is_private = row.is_private != 0;
+            if let Some(created_at) = parse_db_timestamp(&row.created_at) {
+                state.created_at = created_at;
+            }
+        }
+
+        Ok(state)
+    }
+
     /// Bulk delete messages in a channel (up to 100).
     pub async fn bulk_delete_messages(
         &self,
@@ -3475,6 +6350,71 @@ impl ChatEngine {
 
     // ── AutoMod ──
 
+    /// Check content against a server's enabled automod rules (keyword,
+    /// mention_spam, link_filter), naming the first rule it breaks.
+    async fn check_automod(&self, server_id: &str, content: &str) -> Result<(), String> {
+        let Some(pool) = &self.db else {
+            return Ok(());
+        };
+        let rules = crate::db::queries::automod::get_enabled_rules(pool, server_id)
+            .await
+            .unwrap_or_default();
+        for rule in rules {
+            let triggered = match rule.rule_type.as_str() {
+                "keyword" => {
+                    // Config: {"words":["bad","spam"]}
+                    if let Ok(config) = serde_json::from_str::<serde_json::Value>(&rule.config) {
+                        if let Some(words) = config.get("words").and_then(|w| w.as_array()) {
+                            let lower = content.to_lowercase();
+                            words.iter().any(|w| {
+                                w.as_str()
+                                    .is_some_and(|kw| lower.contains(&kw.to_lowercase()))
+                            })
+                        } else {
+                            false
+                        }
+                    } else {
+                        false
+                    }
+                }
+                "mention_spam" => {
+                    // Config: {"max_mentions":5}
+                    if let Ok(config) = serde_json::from_str::<serde_json::Value>(&rule.config) {
+                        let max = config
+                            .get("max_mentions")
+                            .and_then(|m| m.as_i64())
+                            .unwrap_or(5) as usize;
+                        let mention_count = content.matches('@').count();
+                        mention_count > max
+                    } else {
+                        false
+                    }
+                }
+                "link_filter" => {
+                    // Config: {"block_all":true}
+                    if let Ok(config) = serde_json::from_str::<serde_json::Value>(&rule.config) {
+                        let block_all = config
+                            .get("block_all")
+                            .and_then(|b| b.as_bool())
+                            .unwrap_or(false);
+                        if block_all {
+                            content.contains("http://") || content.contains("https://")
+                        } else {
+                            false
+                        }
+                    } else {
+                        false
+                    }
+                }
+                _ => false,
+            };
+            if triggered {
+                return Err(format!("Message blocked by automod rule: {}", rule.name));
+            }
+        }
+        Ok(())
+    }
+
     /// Create an automod rule.
     pub async fn create_automod_rule(
         &self,
@@ -3691,6 +6631,29 @@ impl ChatEngine {
             .require_permission(session_id, server_id, None, Permissions::CREATE_INVITES)
             .await?;
 
+        let invite = self
+            .store_invite(server_id, &user_id, max_uses, expires_at, channel_id)
+            .await?;
+
+        if let Some(session) = self.get_session(session_id) {
+            let _ = session.send(ChatEvent::InviteCreate {
+                server_id: server_id.to_string(),
+                invite,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Generate and save a new invite code for a server.
+    async fn store_invite(
+        &self,
+        server_id: &str,
+        user_id: &str,
+        max_uses: Option<i32>,
+        expires_at: Option<&str>,
+        channel_id: Option<&str>,
+    ) -> Result<InviteInfo, String> {
         let Some(pool) = &self.db else {
             return Err("No database configured".into());
         };
@@ -3705,31 +6668,22 @@ impl ChatEngine {
             .collect();
 
         crate::db::queries::invites::create_invite(
-            pool, &invite_id, server_id, &code, &user_id, max_uses, expires_at, channel_id,
+            pool, &invite_id, server_id, &code, user_id, max_uses, expires_at, channel_id,
         )
         .await
         .map_err(|e| format!("Failed to create invite: {e}"))?;
 
-        let invite = InviteInfo {
+        Ok(InviteInfo {
             id: invite_id,
             code,
             server_id: server_id.to_string(),
-            created_by: user_id,
+            created_by: user_id.to_string(),
             max_uses,
             use_count: 0,
             expires_at: expires_at.map(String::from),
             channel_id: channel_id.map(String::from),
             created_at: Utc::now().to_rfc3339(),
-        };
-
-        if let Some(session) = self.get_session(session_id) {
-            let _ = session.send(ChatEvent::InviteCreate {
-                server_id: server_id.to_string(),
-                invite,
-            });
-        }
-
-        Ok(())
+        })
     }
 
     /// List invites for a server. Requires MANAGE_SERVER permission.
@@ -4281,6 +7235,82 @@ impl ChatEngine {
         Ok(())
     }
 
+    /// Set or clear a server's message of the day. Requires MANAGE_SERVER permission.
+    pub async fn set_server_motd(
+        &self,
+        session_id: SessionId,
+        server_id: &str,
+        motd: Option<&str>,
+    ) -> Result<(), String> {
+        self.require_permission(session_id, server_id, None, Permissions::MANAGE_SERVER)
+            .await?;
+
+        let motd = motd.map(str::trim).filter(|m| !m.is_empty());
+        if motd.is_some_and(|m| m.len() > validation::MAX_MOTD_LENGTH) {
+            return Err(format!(
+                "MOTD too long (max {} characters)",
+                validation::MAX_MOTD_LENGTH
+            ));
+        }
+
+        let Some(pool) = &self.db else {
+            return Err("No database configured".into());
+        };
+
+        crate::db::queries::servers::set_server_motd(pool, server_id, motd)
+            .await
+            .map_err(|e| format!("Failed to update MOTD: {e}"))?;
+
+        if let Some(session) = self.get_session(session_id) {
+            let _ = session.send(ChatEvent::ServerMotd {
+                server_id: server_id.to_string(),
+                motd: motd.map(String::from),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Get a server's own message of the day. Requires VIEW_CHANNELS permission.
+    pub async fn get_server_motd(
+        &self,
+        session_id: SessionId,
+        server_id: &str,
+    ) -> Result<(), String> {
+        self.require_permission(session_id, server_id, None, Permissions::VIEW_CHANNELS)
+            .await?;
+
+        let Some(pool) = &self.db else {
+            return Err("No database configured".into());
+        };
+
+        let server = crate::db::queries::servers::get_server(pool, server_id)
+            .await
+            .map_err(|e| format!("DB error: {e}"))?
+            .ok_or("Server not found")?;
+
+        if let Some(session) = self.get_session(session_id) {
+            let _ = session.send(ChatEvent::ServerMotd {
+                server_id: server.id,
+                motd: server.motd,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// The MOTD to show for a server: its own if set, otherwise the instance MOTD.
+    /// With no server, always the instance MOTD.
+    pub async fn effective_motd(&self, server_id: Option<&str>) -> Option<String> {
+        if let (Some(server_id), Some(pool)) = (server_id, &self.db)
+            && let Ok(Some(server)) = crate::db::queries::servers::get_server(pool, server_id).await
+            && let Some(motd) = server.motd.filter(|m| !m.trim().is_empty())
+        {
+            return Some(motd);
+        }
+        self.motd.clone()
+    }
+
     // ── Announcements ──
 
     /// Set a channel as an announcement channel. Requires MANAGE_CHANNELS permission.
@@ -5377,6 +8407,7 @@ impl ChatEngine {
             avatar_url: avatar,
             reply_to: None,
             attachments: None,
+            kind: MessageKind::Normal,
         };
 
         // Persist the message
@@ -5397,6 +8428,7 @@ impl ChatEngine {
                     sender_nick: &nick,
                     content: &msg,
                     reply_to_id: None,
+                    kind: "normal",
                 };
                 if let Err(e) = crate::db::queries::messages::insert_message(&pool, &params).await {
                     error!(error = %e, "failed to persist webhook message");
@@ -5516,6 +8548,199 @@ fn webhook_row_to_info(row: crate::db::models::WebhookRow) -> WebhookInfo {
     }
 }
 
+/// A validated group DM name, with blank meaning no name.
+fn group_dm_name(name: Option<&str>) -> Result<Option<String>, String> {
+    let Some(name) = name.map(str::trim).filter(|n| !n.is_empty()) else {
+        return Ok(None);
+    };
+    validation::validate_group_dm_name(name)?;
+    Ok(Some(validation::sanitize_html(name)))
+}
+
+/// Who a direct message is addressed to. A guest has only a session; an
+/// offline user only an account.
+struct DmRecipient {
+    session_id: Option<SessionId>,
+    user_id: Option<String>,
+}
+
+/// The ID a session's direct messages are stored under: its user ID, or the
+/// session ID for guests.
+fn history_participant_id(session: &UserSession) -> String {
+    session
+        .user_id
+        .clone()
+        .unwrap_or_else(|| session.id.to_string())
+}
+
+/// The accounts behind some sessions, for loading their roles in one query.
+fn session_user_ids(sessions: &[Arc<UserSession>]) -> Vec<String> {
+    sessions.iter().filter_map(|s| s.user_id.clone()).collect()
+}
+
+/// Parse a stored timestamp: SQLite's `datetime('now')` form or RFC 3339.
+fn parse_db_timestamp(value: &str) -> Option<chrono::DateTime<Utc>> {
+    chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .map(|dt| dt.and_utc())
+        .or_else(|_| value.parse())
+        .ok()
+}
+
+/// Convert a history anchor to a message-order cursor. A timestamp sorts
+/// before every message stored in the same second.
+async fn history_cursor(
+    pool: &SqlitePool,
+    anchor: &HistoryAnchor,
+) -> Result<crate::db::queries::messages::HistoryCursor, String> {
+    match anchor {
+        HistoryAnchor::MessageId(id) => crate::db::queries::messages::get_message_cursor(pool, id)
+            .await
+            .map_err(|e| format!("DB error: {e}"))?
+            .ok_or(format!("No such message: {id}")),
+        HistoryAnchor::Timestamp(time) => Ok((time.format("%Y-%m-%d %H:%M:%S").to_string(), 0)),
+    }
+}
+
+/// Add (`set`) or remove permission bits on a role's channel override. Bits
+/// added to one side are cleared from the other, and an override that ends up
+/// neither allowing nor denying anything is deleted.
+async fn update_role_override(
+    pool: &SqlitePool,
+    channel_id: &str,
+    role_id: &str,
+    existing: Option<&crate::db::models::ChannelPermissionOverrideRow>,
+    allow: Permissions,
+    deny: Permissions,
+    set: bool,
+) -> Result<(), sqlx::Error> {
+    let (mut allow_bits, mut deny_bits) = existing
+        .map(|o| {
+            (
+                Permissions::from_bits_truncate(o.allow_bits as u64),
+                Permissions::from_bits_truncate(o.deny_bits as u64),
+            )
+        })
+        .unwrap_or((Permissions::empty(), Permissions::empty()));
+    if set {
+        allow_bits = (allow_bits | allow) & !deny;
+        deny_bits = (deny_bits | deny) & !allow;
+    } else {
+        allow_bits &= !allow;
+        deny_bits &= !deny;
+    }
+
+    if allow_bits.is_empty() && deny_bits.is_empty() {
+        if existing.is_some() {
+            crate::db::queries::channels::delete_channel_override(
+                pool, channel_id, "role", role_id,
+            )
+            .await?;
+        }
+        return Ok(());
+    }
+
+    let id = existing
+        .map(|o| o.id.clone())
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    crate::db::queries::channels::set_channel_override(
+        pool,
+        &id,
+        channel_id,
+        "role",
+        role_id,
+        allow_bits.bits() as i64,
+        deny_bits.bits() as i64,
+    )
+    .await
+}
+
+/// Counts of messages sent per second over the last minute, kept in a ring of
+/// one slot per second. Each slot packs the second it counts (since `start`)
+/// into its high 32 bits and the count into its low 32, so a slot left over
+/// from an earlier minute can be told apart and restarted without a lock.
+struct MessageRate {
+    start: Instant,
+    slots: [AtomicU64; 60],
+}
+
+impl MessageRate {
+    fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            slots: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+
+    fn second(&self, now: Instant) -> u64 {
+        now.saturating_duration_since(self.start).as_secs() & u64::from(u32::MAX)
+    }
+
+    /// Note a message sent at `now`.
+    fn record(&self, now: Instant) {
+        let second = self.second(now);
+        let slot = &self.slots[(second % 60) as usize];
+        let _ = slot.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |packed| {
+            Some(if packed >> 32 == second {
+                packed.saturating_add(1)
+            } else {
+                (second << 32) | 1
+            })
+        });
+    }
+
+    /// Messages sent in the minute up to `now`.
+    fn last_minute(&self, now: Instant) -> usize {
+        let second = self.second(now);
+        self.slots
+            .iter()
+            .map(|slot| slot.load(Ordering::Relaxed))
+            .filter(|packed| second.wrapping_sub(packed >> 32) < 60)
+            .map(|packed| (packed & u64::from(u32::MAX)) as usize)
+            .sum()
+    }
+}
+
+/// Let a user see a channel through their own override, keeping whatever else
+/// it already allows or denies.
+async fn allow_user_view(
+    pool: &SqlitePool,
+    channel_id: &str,
+    user_id: &str,
+) -> Result<(), sqlx::Error> {
+    let overrides = crate::db::queries::channels::get_channel_overrides(pool, channel_id).await?;
+    let existing = overrides
+        .iter()
+        .find(|o| o.target_type == "user" && o.target_id == user_id);
+    let (allow_bits, deny_bits) = existing
+        .map(|o| {
+            (
+                Permissions::from_bits_truncate(o.allow_bits as u64),
+                Permissions::from_bits_truncate(o.deny_bits as u64),
+            )
+        })
+        .unwrap_or((Permissions::empty(), Permissions::empty()));
+
+    let id = existing
+        .map(|o| o.id.clone())
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    crate::db::queries::channels::set_channel_override(
+        pool,
+        &id,
+        channel_id,
+        "user",
+        user_id,
+        (allow_bits | Permissions::VIEW_CHANNELS).bits() as i64,
+        (deny_bits - Permissions::VIEW_CHANNELS).bits() as i64,
+    )
+    .await
+}
+
+/// How nicknames are compared: case-insensitively, with the `ascii`
+/// casemapping IRC clients are told about.
+fn nick_key(nickname: &str) -> String {
+    nickname.to_ascii_lowercase()
+}
+
 /// Ensure channel names are lowercase and start with #.
 fn normalize_channel_name(name: &str) -> String {
     let name = name.to_lowercase();
@@ -5530,6 +8755,24 @@ fn normalize_channel_name(name: &str) -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_message_rate_counts_the_last_minute() {
+        let rate = MessageRate::new();
+        let t0 = rate.start;
+        assert_eq!(rate.last_minute(t0), 0);
+
+        rate.record(t0);
+        rate.record(t0 + Duration::from_millis(500));
+        rate.record(t0 + Duration::from_secs(30));
+        assert_eq!(rate.last_minute(t0 + Duration::from_secs(30)), 3);
+        assert_eq!(rate.last_minute(t0 + Duration::from_secs(60)), 1);
+
+        // A slot reused a minute later starts counting again
+        rate.record(t0 + Duration::from_secs(90));
+        assert_eq!(rate.last_minute(t0 + Duration::from_secs(90)), 1);
+        assert_eq!(rate.last_minute(t0 + Duration::from_secs(200)), 0);
+    }
+
     #[test]
     fn test_normalize_channel_name() {
         assert_eq!(normalize_channel_name("#General"), "#general");
@@ -5578,6 +8821,160 @@ mod tests {
         assert!(engine.get_session(sid2).is_some());
     }
 
+    #[tokio::test]
+    async fn test_account_sessions_share_nick_and_channels() {
+        let engine = setup_engine();
+
+        let (web, mut web_rx) = engine
+            .connect(Some("u1".into()), "alice".into(), Protocol::WebSocket, None)
+            .unwrap();
+        let (bob, mut bob_rx) = engine
+            .connect(None, "bob".into(), Protocol::WebSocket, None)
+            .unwrap();
+        engine
+            .join_channel(web, DEFAULT_SERVER_ID, "#general")
+            .unwrap();
+        engine
+            .join_channel(bob, DEFAULT_SERVER_ID, "#general")
+            .unwrap();
+        while web_rx.try_recv().is_ok() {}
+        while bob_rx.try_recv().is_ok() {}
+
+        // A second client asking for another nick gets the account's nick and
+        // is placed in its channels without the channel seeing a join
+        let (irc, mut irc_rx) = engine
+            .connect(Some("u1".into()), "alice_irc".into(), Protocol::Irc, None)
+            .unwrap();
+        assert!(engine.get_session(web).is_some());
+        assert_eq!(engine.get_session(irc).unwrap().nickname, "alice");
+        assert!(engine.is_nick_available("alice_irc"));
+        assert!(matches!(irc_rx.try_recv(), Ok(ChatEvent::Join { .. })));
+        match irc_rx.try_recv() {
+            Ok(ChatEvent::Names { members, .. }) => {
+                assert_eq!(members.iter().filter(|m| m.nickname == "alice").count(), 1);
+            }
+            other => panic!("Expected Names, got {other:?}"),
+        }
+        assert!(bob_rx.try_recv().is_err());
+
+        // Both of alice's clients get bob's messages
+        engine
+            .send_message(bob, DEFAULT_SERVER_ID, "#general", "hi", None, None)
+            .unwrap();
+        assert!(matches!(web_rx.try_recv(), Ok(ChatEvent::Message { .. })));
+        assert!(matches!(irc_rx.try_recv(), Ok(ChatEvent::Message { .. })));
+
+        // Joining from one client joins the other; the channel sees one join
+        engine
+            .join_channel(irc, DEFAULT_SERVER_ID, "#rust")
+            .unwrap();
+        engine
+            .join_channel(bob, DEFAULT_SERVER_ID, "#rust")
+            .unwrap();
+        while web_rx.try_recv().is_ok() {}
+        while irc_rx.try_recv().is_ok() {}
+        while bob_rx.try_recv().is_ok() {}
+
+        // Parting from one client parts both
+        engine
+            .part_channel(web, DEFAULT_SERVER_ID, "#rust", None)
+            .unwrap();
+        assert!(matches!(irc_rx.try_recv(), Ok(ChatEvent::Part { .. })));
+        assert!(matches!(bob_rx.try_recv(), Ok(ChatEvent::Part { .. })));
+        assert!(bob_rx.try_recv().is_err());
+        assert!(
+            engine
+                .part_channel(irc, DEFAULT_SERVER_ID, "#rust", None)
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_account_quits_with_last_session() {
+        let engine = setup_engine();
+
+        let (web, _web_rx) = engine
+            .connect(Some("u1".into()), "alice".into(), Protocol::WebSocket, None)
+            .unwrap();
+        let (irc, _irc_rx) = engine
+            .connect(Some("u1".into()), "alice".into(), Protocol::Irc, None)
+            .unwrap();
+        let (bob, mut bob_rx) = engine
+            .connect(None, "bob".into(), Protocol::WebSocket, None)
+            .unwrap();
+        engine
+            .join_channel(web, DEFAULT_SERVER_ID, "#general")
+            .unwrap();
+        engine
+            .join_channel(bob, DEFAULT_SERVER_ID, "#general")
+            .unwrap();
+        while bob_rx.try_recv().is_ok() {}
+
+        engine.disconnect(web);
+        assert!(bob_rx.try_recv().is_err());
+        assert_eq!(engine.get_session_by_nick("alice").unwrap().id, irc);
+
+        engine.disconnect(irc);
+        assert!(matches!(bob_rx.try_recv(), Ok(ChatEvent::Quit { .. })));
+        assert!(engine.is_nick_available("alice"));
+    }
+
+    #[tokio::test]
+    async fn test_echo_message_and_dm_fan_out() {
+        let engine = setup_engine();
+
+        let (web, mut web_rx) = engine
+            .connect(Some("u1".into()), "alice".into(), Protocol::WebSocket, None)
+            .unwrap();
+        let (irc, mut irc_rx) = engine
+            .connect(Some("u1".into()), "alice".into(), Protocol::Irc, None)
+            .unwrap();
+        let (bob, mut bob_rx) = engine
+            .connect(None, "bob".into(), Protocol::WebSocket, None)
+            .unwrap();
+        engine
+            .join_channel(web, DEFAULT_SERVER_ID, "#general")
+            .unwrap();
+        engine
+            .join_channel(bob, DEFAULT_SERVER_ID, "#general")
+            .unwrap();
+        while web_rx.try_recv().is_ok() {}
+        while irc_rx.try_recv().is_ok() {}
+        while bob_rx.try_recv().is_ok() {}
+
+        // Without echo-message only the other client sees what was sent
+        engine.set_echo_message(web, true);
+        engine
+            .send_message(irc, DEFAULT_SERVER_ID, "#general", "from irc", None, None)
+            .unwrap();
+        assert!(matches!(web_rx.try_recv(), Ok(ChatEvent::Message { .. })));
+        assert!(irc_rx.try_recv().is_err());
+
+        engine.set_echo_message(irc, true);
+        engine
+            .send_message(irc, DEFAULT_SERVER_ID, "#general", "echoed", None, None)
+            .unwrap();
+        assert!(matches!(web_rx.try_recv(), Ok(ChatEvent::Message { .. })));
+        assert!(matches!(irc_rx.try_recv(), Ok(ChatEvent::Message { .. })));
+        while bob_rx.try_recv().is_ok() {}
+
+        // A DM reaches every client of both parties except a non-echoing sender
+        engine
+            .send_message(bob, DEFAULT_SERVER_ID, "alice", "psst", None, None)
+            .unwrap();
+        assert!(matches!(web_rx.try_recv(), Ok(ChatEvent::Message { .. })));
+        assert!(matches!(irc_rx.try_recv(), Ok(ChatEvent::Message { .. })));
+        assert!(bob_rx.try_recv().is_err());
+
+        engine.set_echo_message(irc, false);
+        engine
+            .send_message(irc, DEFAULT_SERVER_ID, "bob", "hi bob", None, None)
+            .unwrap();
+        assert!(matches!(bob_rx.try_recv(), Ok(ChatEvent::Message { .. })));
+        assert!(matches!(web_rx.try_recv(), Ok(ChatEvent::Message { .. })));
+        assert!(irc_rx.try_recv().is_err());
+    }
+
     #[tokio::test]
     async fn test_join_and_message() {
         let engine = setup_engine();
@@ -5808,6 +9205,14 @@ mod tests {
             .unwrap();
         assert!(!engine.is_nick_available("alice"));
         assert!(engine.is_nick_available("bob"));
+
+        // Nicks differing only in case are the same nick
+        assert!(!engine.is_nick_available("ALICE"));
+        assert!(engine.get_session_by_nick("Alice").is_some());
+        let (_sid, _rx) = engine
+            .connect(None, "Bob".into(), Protocol::Irc, None)
+            .unwrap();
+        assert!(!engine.is_nick_available("bob"));
     }
 
     #[tokio::test]
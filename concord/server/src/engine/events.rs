@@ -28,6 +28,8 @@ pub enum ChatEvent {
         reply_to: Option<ReplyInfo>,
         #[serde(skip_serializing_if = "Option::is_none")]
         attachments: Option<Vec<AttachmentInfo>>,
+        #[serde(default, skip_serializing_if = "MessageKind::is_normal")]
+        kind: MessageKind,
     },
 
     /// A message was edited.
@@ -35,6 +37,9 @@ pub enum ChatEvent {
         id: MessageId,
         server_id: String,
         channel: String,
+        /// Nickname of the message's author (who is also the editor).
+        #[serde(default)]
+        from: String,
         content: String,
         edited_at: DateTime<Utc>,
     },
@@ -44,6 +49,15 @@ pub enum ChatEvent {
         id: MessageId,
         server_id: String,
         channel: String,
+        /// Nickname of whoever deleted it: the author or a moderator.
+        #[serde(default)]
+        deleted_by: String,
+    },
+
+    /// Every version of a message, oldest first; the last is what it says now.
+    MessageEditHistory {
+        message_id: String,
+        revisions: Vec<MessageRevisionInfo>,
     },
 
     /// A reaction was added to a message.
@@ -73,6 +87,16 @@ pub enum ChatEvent {
         nickname: String,
     },
 
+    /// A user stopped typing: they cleared their input, or (`paused`) left
+    /// it unsent for a while.
+    TypingStop {
+        server_id: String,
+        channel: String,
+        nickname: String,
+        #[serde(default)]
+        paused: bool,
+    },
+
     /// User joined a channel.
     Join {
         nickname: String,
@@ -174,6 +198,15 @@ pub enum ChatEvent {
         role_ids: Vec<String>,
     },
 
+    /// A channel member's status prefixes changed (e.g. a role was assigned).
+    MemberPrefixUpdate {
+        server_id: String,
+        channel: String,
+        nickname: String,
+        added: String,
+        removed: String,
+    },
+
     /// List of categories in a server.
     CategoryList {
         server_id: String,
@@ -346,6 +379,71 @@ pub enum ChatEvent {
         is_nsfw: bool,
     },
 
+    /// Channel private (members-only) flag was updated.
+    PrivacyUpdate {
+        server_id: String,
+        channel: String,
+        is_private: bool,
+    },
+
+    /// Channel was made moderated (only members holding a role may speak) or opened up again.
+    ModeratedUpdate {
+        server_id: String,
+        channel: String,
+        moderated: bool,
+    },
+
+    /// A member's sessions were removed from a channel because they were kicked or banned.
+    ChannelKick {
+        server_id: String,
+        channel: String,
+        nickname: String,
+        kicked_by: String,
+        reason: Option<String>,
+    },
+
+    /// A user was invited into a channel. `code` is a single-use server invite
+    /// when they weren't a member of the channel's server yet.
+    ChannelInvite {
+        server_id: String,
+        channel: String,
+        invited_by: String,
+        code: Option<String>,
+    },
+
+    /// A user asked to be let into a channel. Sent to its moderators.
+    ChannelKnock {
+        server_id: String,
+        channel: String,
+        nickname: String,
+        message: Option<String>,
+    },
+
+    /// An operator message, sent to every IRC operator.
+    Wallops { from: String, message: String },
+
+    /// This session was disconnected by an operator.
+    Killed { killed_by: String, reason: String },
+
+    /// A nickname this session monitors came online or went offline.
+    MonitorStatus { nickname: String, online: bool },
+
+    /// A group DM was created or changed. Sent to everyone in it, and to
+    /// whoever just left or was removed.
+    GroupDmUpdate {
+        conversation: GroupDmInfo,
+        change: GroupDmChange,
+    },
+
+    /// You were mentioned in a channel message.
+    Mention { mention: MentionInfo },
+
+    /// A page of your mentions inbox, newest first.
+    MentionList {
+        mentions: Vec<MentionInfo>,
+        has_more: bool,
+    },
+
     /// Bulk messages were deleted.
     BulkMessageDelete {
         server_id: String,
@@ -420,6 +518,12 @@ pub enum ChatEvent {
     /// Server community settings.
     ServerCommunity { community: ServerCommunityInfo },
 
+    /// A server's own message of the day (None when it uses the instance MOTD).
+    ServerMotd {
+        server_id: String,
+        motd: Option<String>,
+    },
+
     /// Discoverable servers list.
     DiscoverServers { servers: Vec<ServerCommunityInfo> },
 
@@ -517,6 +621,45 @@ pub enum ChatEvent {
     Error { code: String, message: String },
 }
 
+/// How a message is presented: ordinary text, a `/me` action, a notice
+/// (which IRC clients must never answer automatically), or a system message
+/// recording a change to a group DM.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageKind {
+    #[default]
+    Normal,
+    Action,
+    Notice,
+    System,
+}
+
+impl MessageKind {
+    pub fn is_normal(&self) -> bool {
+        *self == MessageKind::Normal
+    }
+
+    /// The value stored in `messages.kind`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MessageKind::Normal => "normal",
+            MessageKind::Action => "action",
+            MessageKind::Notice => "notice",
+            MessageKind::System => "system",
+        }
+    }
+
+    /// Parse a stored `messages.kind`, treating anything unknown as normal.
+    pub fn from_db(kind: &str) -> Self {
+        match kind {
+            "action" => MessageKind::Action,
+            "notice" => MessageKind::Notice,
+            "system" => MessageKind::System,
+            _ => MessageKind::Normal,
+        }
+    }
+}
+
 /// Info about a replied-to message.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReplyInfo {
@@ -559,6 +702,9 @@ pub struct ChannelInfo {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub thread_parent_message_id: Option<String>,
     pub archived: bool,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topic_set_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -574,6 +720,9 @@ pub struct MemberInfo {
     pub status_emoji: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub user_id: Option<String>,
+    /// Status prefixes (`~&@%+`) the member holds in the channel, highest first.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub prefixes: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -592,6 +741,8 @@ pub struct HistoryMessage {
     pub attachments: Option<Vec<AttachmentInfo>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub embeds: Option<Vec<EmbedInfo>>,
+    #[serde(default, skip_serializing_if = "MessageKind::is_normal")]
+    pub kind: MessageKind,
 }
 
 /// Metadata for a file attachment.
@@ -624,6 +775,79 @@ pub struct UnreadCount {
     pub count: i64,
 }
 
+/// A direct message conversation in a user's conversation list.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DmConversationInfo {
+    pub id: String,
+    /// Group conversations are messaged at `&<id>`; one-to-one ones at the
+    /// other participant's nickname.
+    pub is_group: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner_id: Option<String>,
+    /// Everyone in the conversation other than the user listing it.
+    pub participants: Vec<DmParticipant>,
+    pub last_message_at: Option<DateTime<Utc>>,
+    pub unread_count: i64,
+}
+
+/// Someone in a direct message conversation. `nickname` is what to address
+/// messages to: their live nickname if connected, otherwise their username.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DmParticipant {
+    pub user_id: String,
+    pub nickname: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar_url: Option<String>,
+}
+
+/// A group direct message conversation, with everyone in it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupDmInfo {
+    pub id: String,
+    /// The message target for the conversation: `&` followed by its ID.
+    pub target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub owner_id: String,
+    pub participants: Vec<DmParticipant>,
+}
+
+/// What happened to a group DM. `by` and `nickname` are nicknames.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum GroupDmChange {
+    Created { by: String },
+    Added { by: String, nickname: String },
+    Removed { by: String, nickname: String },
+    Left { nickname: String },
+    Renamed { by: String, name: Option<String> },
+    OwnerChanged { by: String, nickname: String },
+}
+
+impl GroupDmChange {
+    /// The text of the system message recording the change.
+    pub fn describe(&self) -> String {
+        match self {
+            GroupDmChange::Created { by } => format!("{by} created the group"),
+            GroupDmChange::Added { by, nickname } => format!("{by} added {nickname}"),
+            GroupDmChange::Removed { by, nickname } => format!("{by} removed {nickname}"),
+            GroupDmChange::Left { nickname } => format!("{nickname} left the group"),
+            GroupDmChange::Renamed {
+                by,
+                name: Some(name),
+            } => {
+                format!("{by} renamed the group to {name}")
+            }
+            GroupDmChange::Renamed { by, name: None } => format!("{by} removed the group name"),
+            GroupDmChange::OwnerChanged { nickname, .. } => {
+                format!("{nickname} is now the group owner")
+            }
+        }
+    }
+}
+
 /// Role metadata sent to clients.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RoleInfo {
@@ -716,6 +940,13 @@ pub struct SearchResultMessage {
     pub edited_at: Option<DateTime<Utc>>,
 }
 
+/// One version of a message, and when it was written.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageRevisionInfo {
+    pub content: String,
+    pub timestamp: String,
+}
+
 /// Info about a pinned message.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PinnedMessageInfo {
@@ -768,6 +999,50 @@ pub struct BookmarkInfo {
     pub created_at: String,
 }
 
+/// How a message mentioned someone: by nickname, by one of their roles, or
+/// with `@everyone`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MentionKind {
+    Everyone,
+    Role,
+    User,
+}
+
+impl MentionKind {
+    /// The value stored in `mentions.kind`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MentionKind::Everyone => "everyone",
+            MentionKind::Role => "role",
+            MentionKind::User => "user",
+        }
+    }
+
+    /// Parse a stored `mentions.kind`.
+    pub fn from_db(kind: &str) -> Self {
+        match kind {
+            "everyone" => MentionKind::Everyone,
+            "role" => MentionKind::Role,
+            _ => MentionKind::User,
+        }
+    }
+}
+
+/// An entry in a user's mentions inbox.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MentionInfo {
+    pub id: String,
+    pub message_id: String,
+    pub server_id: String,
+    pub channel: String,
+    pub from: String,
+    pub content: String,
+    pub kind: MentionKind,
+    pub timestamp: String,
+    pub read: bool,
+}
+
 /// Audit log entry sent to clients.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuditLogEntry {
@@ -1127,6 +1402,7 @@ mod tests {
                 file_size: 1234,
                 url: "https://example.com/file.txt".into(),
             }]),
+            kind: MessageKind::Action,
         };
         let restored = roundtrip(&event);
         match restored {
@@ -1137,11 +1413,13 @@ mod tests {
                 server_id,
                 reply_to,
                 attachments,
+                kind,
                 ..
             } => {
                 assert_eq!(from, "alice");
                 assert_eq!(target, "#general");
                 assert_eq!(content, "Hello, world!");
+                assert_eq!(kind, MessageKind::Action);
                 assert_eq!(server_id, Some("srv1".into()));
                 assert!(reply_to.is_some());
                 assert_eq!(reply_to.unwrap().from, "bob");
@@ -1163,6 +1441,7 @@ mod tests {
             avatar_url: None,
             reply_to: None,
             attachments: None,
+            kind: MessageKind::Normal,
         };
         let json = serde_json::to_string(&event).unwrap();
         // Optional None fields should be skipped
@@ -1170,6 +1449,7 @@ mod tests {
         assert!(!json.contains("avatar_url"));
         assert!(!json.contains("reply_to"));
         assert!(!json.contains("attachments"));
+        assert!(!json.contains("kind"));
         let restored = roundtrip(&event);
         match restored {
             ChatEvent::Message { from, target, .. } => {
@@ -1186,6 +1466,7 @@ mod tests {
             id: Uuid::new_v4(),
             server_id: "srv1".into(),
             channel: "#general".into(),
+            from: "alice".into(),
             content: "edited content".into(),
             edited_at: Utc::now(),
         };
@@ -1207,6 +1488,7 @@ mod tests {
             id: Uuid::new_v4(),
             server_id: "srv1".into(),
             channel: "#general".into(),
+            deleted_by: "mod".into(),
         };
         let restored = roundtrip(&event);
         match restored {
@@ -1754,6 +2036,7 @@ mod tests {
             avatar_url: None,
             reply_to: None,
             attachments: None,
+            kind: MessageKind::Normal,
         };
         let json = serde_json::to_string(&event).unwrap();
         assert!(json.contains(r#""type":"message""#));
@@ -1767,6 +2050,7 @@ mod tests {
                     id: Uuid::new_v4(),
                     server_id: "s".into(),
                     channel: "c".into(),
+                    from: "alice".into(),
                     content: "x".into(),
                     edited_at: Utc::now(),
                 },
@@ -1777,6 +2061,7 @@ mod tests {
                     id: Uuid::new_v4(),
                     server_id: "s".into(),
                     channel: "c".into(),
+                    deleted_by: "alice".into(),
                 },
                 "message_delete",
             ),
@@ -1819,6 +2104,16 @@ mod tests {
                 },
                 "member_kick",
             ),
+            (
+                ChatEvent::ChannelKick {
+                    server_id: "s".into(),
+                    channel: "c".into(),
+                    nickname: "n".into(),
+                    kicked_by: "a".into(),
+                    reason: None,
+                },
+                "channel_kick",
+            ),
             (
                 ChatEvent::BulkMessageDelete {
                     server_id: "s".into(),
@@ -1827,6 +2122,74 @@ mod tests {
                 },
                 "bulk_message_delete",
             ),
+            (
+                ChatEvent::ChannelInvite {
+                    server_id: "s".into(),
+                    channel: "c".into(),
+                    invited_by: "a".into(),
+                    code: None,
+                },
+                "channel_invite",
+            ),
+            (
+                ChatEvent::ChannelKnock {
+                    server_id: "s".into(),
+                    channel: "c".into(),
+                    nickname: "n".into(),
+                    message: None,
+                },
+                "channel_knock",
+            ),
+            (
+                ChatEvent::Wallops {
+                    from: "a".into(),
+                    message: "m".into(),
+                },
+                "wallops",
+            ),
+            (
+                ChatEvent::Killed {
+                    killed_by: "a".into(),
+                    reason: "r".into(),
+                },
+                "killed",
+            ),
+            (
+                ChatEvent::MonitorStatus {
+                    nickname: "a".into(),
+                    online: true,
+                },
+                "monitor_status",
+            ),
+            (
+                ChatEvent::GroupDmUpdate {
+                    conversation: GroupDmInfo {
+                        id: "g".into(),
+                        target: "&g".into(),
+                        name: None,
+                        owner_id: "u".into(),
+                        participants: vec![],
+                    },
+                    change: GroupDmChange::Left {
+                        nickname: "n".into(),
+                    },
+                },
+                "group_dm_update",
+            ),
+            (
+                ChatEvent::MessageEditHistory {
+                    message_id: "m".into(),
+                    revisions: vec![],
+                },
+                "message_edit_history",
+            ),
+            (
+                ChatEvent::MentionList {
+                    mentions: vec![],
+                    has_more: false,
+                },
+                "mention_list",
+            ),
         ];
 
         for (event, expected_type) in events {
@@ -1888,6 +2251,8 @@ mod tests {
                 channel_type: "text".into(),
                 thread_parent_message_id: None,
                 archived: false,
+                created_at: Utc::now(),
+                topic_set_at: None,
             }
         );
         let _ = format!(
@@ -1899,6 +2264,7 @@ mod tests {
                 custom_status: None,
                 status_emoji: None,
                 user_id: None,
+                prefixes: String::new(),
             }
         );
         let _ = format!(
@@ -0,0 +1,219 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+use super::events::MentionKind;
+use crate::db::models::NotificationSettingRow;
+
+/// The `@` mentions written in a message: names (lowercased, each once) and
+/// whether it mentions `@everyone`. A name may turn out to be a member, a role
+/// or nobody; that's for the caller to resolve.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ParsedMentions {
+    pub names: Vec<String>,
+    pub everyone: bool,
+}
+
+impl ParsedMentions {
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty() && !self.everyone
+    }
+}
+
+/// Characters allowed in a mentioned name: those of an IRC nickname.
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || "_-[]\\`^{}|".contains(c)
+}
+
+/// Find the `@name` mentions in a message. An `@` only starts a mention at
+/// the beginning of a word, so email addresses aren't mentions.
+pub fn parse_mentions(content: &str) -> ParsedMentions {
+    let mut parsed = ParsedMentions::default();
+    let mut prev: Option<char> = None;
+    let mut chars = content.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let at_word_start = prev.is_none_or(|p| !is_name_char(p) && p != '@');
+        prev = Some(c);
+        if c != '@' || !at_word_start {
+            continue;
+        }
+        let start = i + 1;
+        let mut end = start;
+        while let Some(&(j, n)) = chars.peek() {
+            if !is_name_char(n) {
+                break;
+            }
+            end = j + n.len_utf8();
+            prev = Some(n);
+            chars.next();
+        }
+        let name = content[start..end].to_lowercase();
+        if name.is_empty() {
+            continue;
+        }
+        if name == "everyone" {
+            parsed.everyone = true;
+        } else if !parsed.names.contains(&name) {
+            parsed.names.push(name);
+        }
+    }
+    parsed
+}
+
+/// Whether a user's notification settings let a mention in a channel reach
+/// their inbox. `settings` are the user's rows for the channel's server, as
+/// returned by `get_notification_settings`.
+///
+/// Settings for the channel override those for the server, which override
+/// the user's global ones, except that a level of `default` defers to the
+/// broader setting and a mute anywhere above the channel applies to it.
+pub fn mention_allowed(
+    settings: &[NotificationSettingRow],
+    channel_id: &str,
+    kind: MentionKind,
+    now: DateTime<Utc>,
+) -> bool {
+    let mut applicable: Vec<&NotificationSettingRow> = settings
+        .iter()
+        .filter(|s| s.channel_id.as_deref().is_none_or(|c| c == channel_id))
+        .collect();
+    applicable.sort_by_key(|s| (s.channel_id.is_some(), s.server_id.is_some()));
+
+    if applicable.iter().any(|s| is_muted(s, now)) {
+        return false;
+    }
+    let level = applicable
+        .iter()
+        .rev()
+        .map(|s| s.level.as_str())
+        .find(|level| *level != "default");
+    if level == Some("none") {
+        return false;
+    }
+    match (kind, applicable.last()) {
+        (MentionKind::Everyone, Some(s)) => s.suppress_everyone == 0,
+        (MentionKind::Role, Some(s)) => s.suppress_roles == 0,
+        _ => true,
+    }
+}
+
+/// A mute with no end, or one that hasn't ended yet. An end time that can't
+/// be read is treated as no end.
+fn is_muted(setting: &NotificationSettingRow, now: DateTime<Utc>) -> bool {
+    if setting.muted == 0 {
+        return false;
+    }
+    let Some(until) = setting.mute_until.as_deref() else {
+        return true;
+    };
+    let until = DateTime::parse_from_rfc3339(until)
+        .map(|t| t.with_timezone(&Utc))
+        .or_else(|_| {
+            NaiveDateTime::parse_from_str(until, "%Y-%m-%d %H:%M:%S").map(|t| t.and_utc())
+        });
+    match until {
+        Ok(until) => until > now,
+        Err(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(content: &str) -> Vec<String> {
+        parse_mentions(content).names
+    }
+
+    #[test]
+    fn test_parse_mentions() {
+        assert_eq!(names("hey @Bob and @carol_"), ["bob", "carol_"]);
+        assert_eq!(names("@bob: look, @BOB!"), ["bob"]);
+        assert_eq!(names("(@[away]dave)"), ["[away]dave"]);
+        assert!(names("mail bob@example.com or @ or @@").is_empty());
+        assert!(parse_mentions("no mentions here").is_empty());
+
+        let parsed = parse_mentions("@everyone meeting with @mods");
+        assert!(parsed.everyone);
+        assert_eq!(parsed.names, ["mods"]);
+        assert!(!parse_mentions("@everyones").everyone);
+    }
+
+    fn setting(
+        server_id: Option<&str>,
+        channel_id: Option<&str>,
+        level: &str,
+    ) -> NotificationSettingRow {
+        NotificationSettingRow {
+            id: "ns".into(),
+            user_id: "u".into(),
+            server_id: server_id.map(Into::into),
+            channel_id: channel_id.map(Into::into),
+            level: level.into(),
+            suppress_everyone: 0,
+            suppress_roles: 0,
+            muted: 0,
+            mute_until: None,
+            created_at: String::new(),
+            updated_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_mention_allowed_by_level() {
+        let now = Utc::now();
+        assert!(mention_allowed(&[], "c1", MentionKind::Everyone, now));
+
+        let server_none = setting(Some("s1"), None, "none");
+        assert!(!mention_allowed(
+            std::slice::from_ref(&server_none),
+            "c1",
+            MentionKind::User,
+            now
+        ));
+        // The channel can turn mentions back on, but `default` defers to the server
+        let channel = setting(Some("s1"), Some("c1"), "mentions");
+        let rows = [server_none.clone(), channel];
+        assert!(mention_allowed(&rows, "c1", MentionKind::User, now));
+        assert!(!mention_allowed(&rows, "c2", MentionKind::User, now));
+        let rows = [server_none, setting(Some("s1"), Some("c1"), "default")];
+        assert!(!mention_allowed(&rows, "c1", MentionKind::User, now));
+    }
+
+    #[test]
+    fn test_mention_allowed_suppressions() {
+        let now = Utc::now();
+        let mut server = setting(Some("s1"), None, "all");
+        server.suppress_everyone = 1;
+        server.suppress_roles = 1;
+        let rows = [server];
+        assert!(!mention_allowed(&rows, "c1", MentionKind::Everyone, now));
+        assert!(!mention_allowed(&rows, "c1", MentionKind::Role, now));
+        assert!(mention_allowed(&rows, "c1", MentionKind::User, now));
+    }
+
+    #[test]
+    fn test_mention_allowed_mutes() {
+        let now = Utc::now();
+        let mut muted = setting(None, None, "all");
+        muted.muted = 1;
+        assert!(!mention_allowed(
+            std::slice::from_ref(&muted),
+            "c1",
+            MentionKind::User,
+            now
+        ));
+
+        muted.mute_until = Some((now + chrono::Duration::hours(1)).to_rfc3339());
+        assert!(!mention_allowed(
+            std::slice::from_ref(&muted),
+            "c1",
+            MentionKind::User,
+            now
+        ));
+        muted.mute_until = Some(
+            (now - chrono::Duration::hours(1))
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string(),
+        );
+        assert!(mention_allowed(&[muted], "c1", MentionKind::User, now));
+    }
+}
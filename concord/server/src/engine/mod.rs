@@ -2,6 +2,7 @@ pub mod channel;
 pub mod chat_engine;
 pub mod embeds;
 pub mod events;
+pub mod mentions;
 pub mod permissions;
 pub mod rate_limiter;
 pub mod server;
@@ -147,6 +147,40 @@ pub fn compute_effective_permissions(
     perms
 }
 
+// ── Member status prefixes ──────────────────────────────────
+
+/// Status prefixes shown next to channel members, highest rank first:
+/// owner, administrator, server manager, moderator, voiced.
+pub const MEMBER_PREFIXES: &str = "~&@%+";
+
+/// Work out the status prefixes a channel member holds, highest rank first.
+///
+/// `perms` are the member's effective permissions in the channel and
+/// `everyone_perms` those of @everyone there. Being able to speak only earns
+/// `+` where @everyone cannot (a moderated channel); otherwise everyone would
+/// carry it.
+pub fn member_prefixes(is_owner: bool, perms: Permissions, everyone_perms: Permissions) -> String {
+    let mut prefixes = String::new();
+    if is_owner {
+        prefixes.push('~');
+    }
+    if perms.contains(Permissions::ADMINISTRATOR) {
+        prefixes.push('&');
+    }
+    if perms.contains(Permissions::MANAGE_SERVER) {
+        prefixes.push('@');
+    }
+    if perms.contains(Permissions::KICK_MEMBERS) {
+        prefixes.push('%');
+    }
+    if perms.contains(Permissions::SEND_MESSAGES)
+        && !everyone_perms.contains(Permissions::SEND_MESSAGES)
+    {
+        prefixes.push('+');
+    }
+    prefixes
+}
+
 // ── Legacy role compat ──────────────────────────────────────
 
 /// Server-level roles ordered by privilege level (legacy, kept for backward compat).
@@ -216,6 +250,26 @@ impl ServerRole {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_member_prefixes() {
+        let muted = DEFAULT_EVERYONE - Permissions::SEND_MESSAGES;
+        assert_eq!(
+            member_prefixes(true, Permissions::all(), DEFAULT_EVERYONE),
+            "~&@%"
+        );
+        assert_eq!(
+            member_prefixes(false, DEFAULT_ADMIN, DEFAULT_EVERYONE),
+            "@%"
+        );
+        assert_eq!(member_prefixes(false, DEFAULT_MODERATOR, muted), "%+");
+        assert_eq!(
+            member_prefixes(false, DEFAULT_EVERYONE, DEFAULT_EVERYONE),
+            ""
+        );
+        assert_eq!(member_prefixes(false, DEFAULT_EVERYONE, muted), "+");
+        assert_eq!(member_prefixes(false, muted, muted), "");
+    }
+
     #[test]
     fn test_role_ordering() {
         assert!(ServerRole::Owner > ServerRole::Admin);
@@ -1,4 +1,6 @@
 use std::collections::HashSet;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use chrono::{DateTime, Utc};
 use tokio::sync::mpsc;
@@ -31,6 +33,16 @@ pub struct UserSession {
     pub connected_at: DateTime<Utc>,
     /// Avatar URL (from Bluesky profile or other source).
     pub avatar_url: Option<String>,
+    /// The account name IRC clients are shown for this user, looked up once
+    /// when the session is set up.
+    account: OnceLock<String>,
+    /// Whether this session wants its own messages echoed back (IRC `echo-message`).
+    echo_message: AtomicBool,
+    /// Set once the client has gone and an always-on account is kept online
+    /// in its place.
+    detached: AtomicBool,
+    /// Whether a system admin has used OPER on this session.
+    oper: AtomicBool,
 }
 
 impl UserSession {
@@ -51,12 +63,57 @@ impl UserSession {
             channels: HashSet::new(),
             connected_at: Utc::now(),
             avatar_url,
+            account: OnceLock::new(),
+            echo_message: AtomicBool::new(false),
+            detached: AtomicBool::new(false),
+            oper: AtomicBool::new(false),
         }
     }
 
+    pub fn account(&self) -> Option<&str> {
+        self.account.get().map(String::as_str)
+    }
+
+    pub fn set_account(&self, account: String) {
+        let _ = self.account.set(account);
+    }
+
+    pub fn echo_message(&self) -> bool {
+        self.echo_message.load(Ordering::Relaxed)
+    }
+
+    pub fn set_echo_message(&self, enabled: bool) {
+        self.echo_message.store(enabled, Ordering::Relaxed);
+    }
+
+    /// The session to leave out when broadcasting this session's own
+    /// messages: itself, unless it asked for echoes.
+    pub fn echo_exclusion(&self) -> Option<SessionId> {
+        (!self.echo_message()).then_some(self.id)
+    }
+
+    pub fn is_detached(&self) -> bool {
+        self.detached.load(Ordering::Relaxed)
+    }
+
+    /// Keep the session without a client: it stays in its channels and keeps
+    /// its nick, and events sent to it are dropped.
+    pub fn detach(&self) {
+        self.detached.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_oper(&self) -> bool {
+        self.oper.load(Ordering::Relaxed)
+    }
+
+    pub fn set_oper(&self) {
+        self.oper.store(true, Ordering::Relaxed);
+    }
+
     /// Send an event to this session. Returns false if the channel is closed
     /// or the outbound queue is full (slow client protection — drops event rather than blocking).
+    /// Detached sessions accept and drop everything.
     pub fn send(&self, event: ChatEvent) -> bool {
-        self.outbound.try_send(event).is_ok()
+        self.is_detached() || self.outbound.try_send(event).is_ok()
     }
 }
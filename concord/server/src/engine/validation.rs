@@ -4,15 +4,24 @@ pub const MAX_MESSAGE_LENGTH: usize = 2000;
 /// Maximum channel name length.
 pub const MAX_CHANNEL_NAME_LENGTH: usize = 50;
 
+/// Maximum thread name length.
+pub const MAX_THREAD_NAME_LENGTH: usize = 100;
+
 /// Maximum topic length.
 pub const MAX_TOPIC_LENGTH: usize = 500;
 
+/// Maximum group DM name length.
+pub const MAX_GROUP_DM_NAME_LENGTH: usize = 100;
+
 /// Maximum server name length.
 pub const MAX_SERVER_NAME_LENGTH: usize = 100;
 
 /// Maximum nickname length.
 pub const MAX_NICKNAME_LENGTH: usize = 32;
 
+/// Maximum message of the day length.
+pub const MAX_MOTD_LENGTH: usize = 4000;
+
 /// Validate a server name. Must be 1-100 chars, non-empty after trimming.
 pub fn validate_server_name(name: &str) -> Result<(), String> {
     let trimmed = name.trim();
@@ -99,6 +108,17 @@ pub fn validate_topic(topic: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// Validate a group DM name. Empty clears the name.
+pub fn validate_group_dm_name(name: &str) -> Result<(), String> {
+    if name.len() > MAX_GROUP_DM_NAME_LENGTH {
+        return Err(format!(
+            "Group name too long (max {} characters)",
+            MAX_GROUP_DM_NAME_LENGTH
+        ));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
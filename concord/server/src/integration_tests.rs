@@ -13,7 +13,7 @@ mod tests {
     };
     use crate::db::pool::{create_pool, run_migrations};
     use crate::db::queries;
-    use crate::engine::chat_engine::ChatEngine;
+    use crate::engine::chat_engine::{ChatEngine, DEFAULT_SERVER_ID};
     use crate::engine::events::ChatEvent;
     use crate::engine::permissions::{
         ChannelOverride, DEFAULT_EVERYONE, DEFAULT_MODERATOR, OverrideTargetType, Permissions,
@@ -78,6 +78,84 @@ mod tests {
         while rx.try_recv().is_ok() {}
     }
 
+    /// A server set up by `setup_server_with_members`. Users, sessions and
+    /// receivers are in the order the nicknames were given.
+    struct TestServer<const N: usize> {
+        engine: ChatEngine,
+        pool: SqlitePool,
+        server_id: String,
+        users: [String; N],
+        sessions: [uuid::Uuid; N],
+        rxs: [tokio::sync::mpsc::Receiver<ChatEvent>; N],
+    }
+
+    /// Create a server named `name`, owned by the first of `nicknames`, and
+    /// have each of them join it, connect and join #general. Events from the
+    /// setup are drained.
+    async fn setup_server_with_members<const N: usize>(
+        name: &str,
+        nicknames: [&str; N],
+    ) -> TestServer<N> {
+        let (engine, pool) = setup_engine().await;
+        let mut users = Vec::new();
+        for nickname in nicknames {
+            users.push(create_test_user(&pool, nickname).await);
+        }
+        let server_id = engine
+            .create_server(name.into(), users[0].clone(), None)
+            .await
+            .unwrap();
+        for user_id in &users[1..] {
+            engine.join_server(user_id, &server_id).await.unwrap();
+        }
+
+        let mut sessions = Vec::new();
+        let mut rxs = Vec::new();
+        for (user_id, nickname) in users.iter().zip(nicknames) {
+            let (sid, rx) = connect_user(&engine, Some(user_id), nickname);
+            engine.join_channel(sid, &server_id, "#general").unwrap();
+            sessions.push(sid);
+            rxs.push(rx);
+        }
+        for rx in &mut rxs {
+            drain_events(rx);
+        }
+
+        TestServer {
+            engine,
+            pool,
+            server_id,
+            users: users.try_into().unwrap(),
+            sessions: sessions.try_into().unwrap(),
+            rxs: rxs.try_into().unwrap(),
+        }
+    }
+
+    /// Add a member to a server without connecting them. Returns the user_id.
+    async fn add_offline_member(
+        engine: &ChatEngine,
+        pool: &SqlitePool,
+        server_id: &str,
+        nickname: &str,
+    ) -> String {
+        let user_id = create_test_user(pool, nickname).await;
+        engine.join_server(&user_id, server_id).await.unwrap();
+        user_id
+    }
+
+    /// Give a member the server's built-in Moderator role.
+    async fn make_moderator(pool: &SqlitePool, server_id: &str, user_id: &str) {
+        let moderator = queries::roles::list_roles(pool, server_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|r| r.name == "Moderator")
+            .unwrap();
+        queries::roles::assign_role(pool, server_id, user_id, &moderator.id)
+            .await
+            .unwrap();
+    }
+
     // ═══════════════════════════════════════════════════════════════
     //  1. Migration Verification Tests
     // ═══════════════════════════════════════════════════════════════
@@ -94,7 +172,7 @@ mod tests {
                 .fetch_one(&pool)
                 .await
                 .unwrap();
-        assert_eq!(max_version, 12, "All 12 migrations should be recorded");
+        assert_eq!(max_version, 20, "All 20 migrations should be recorded");
     }
 
     #[tokio::test]
@@ -109,7 +187,7 @@ mod tests {
             .fetch_one(&pool)
             .await
             .unwrap();
-        assert_eq!(count, 12, "No duplicate migration entries after re-run");
+        assert_eq!(count, 20, "No duplicate migration entries after re-run");
     }
 
     #[tokio::test]
@@ -1303,6 +1381,7 @@ mod tests {
                     sender_nick: "alice",
                     content: &format!("Message {i}"),
                     reply_to_id: None,
+                    kind: "normal",
                 },
             )
             .await
@@ -1419,6 +1498,7 @@ mod tests {
                 sender_nick: "alice",
                 content: "This should start a thread",
                 reply_to_id: None,
+                kind: "normal",
             },
         )
         .await
@@ -1464,6 +1544,7 @@ mod tests {
                 sender_nick: "alice",
                 content: "Thread reply",
                 reply_to_id: None,
+                kind: "normal",
             },
         )
         .await
@@ -1567,6 +1648,7 @@ mod tests {
                 sender_nick: "alice",
                 content: "How do I fix this bug?",
                 reply_to_id: None,
+                kind: "normal",
             },
         )
         .await
@@ -1587,7 +1669,7 @@ mod tests {
         .unwrap();
 
         // Tag the thread
-        queries::forum_tags::set_thread_tags(&pool, &thread_id, &[tag1_id.clone()])
+        queries::forum_tags::set_thread_tags(&pool, &thread_id, std::slice::from_ref(&tag1_id))
             .await
             .unwrap();
 
@@ -1642,6 +1724,7 @@ mod tests {
                     sender_nick: "alice",
                     content: &format!("Parent {i}"),
                     reply_to_id: None,
+                    kind: "normal",
                 },
             )
             .await
@@ -1720,7 +1803,7 @@ mod tests {
         }
     }
 
-    #[tokio::test]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_join_event_contains_correct_fields() {
         let (engine, pool) = setup_engine().await;
 
@@ -1840,6 +1923,7 @@ mod tests {
                 sender_nick: "alice",
                 content: "Test",
                 reply_to_id: None,
+                kind: "normal",
             },
         )
         .await
@@ -1951,6 +2035,7 @@ mod tests {
                 sender_nick: "alice",
                 content: "React to this!",
                 reply_to_id: None,
+                kind: "normal",
             },
         )
         .await
@@ -1979,7 +2064,7 @@ mod tests {
         assert!(!dup, "Duplicate reaction should be ignored");
 
         // Get reactions
-        let reactions = queries::messages::get_reactions_for_messages(&pool, &[msg_id.clone()])
+        let reactions = queries::messages::get_reactions_for_messages(&pool, std::slice::from_ref(&msg_id))
             .await
             .unwrap();
         assert_eq!(reactions.len(), 3);
@@ -1991,7 +2076,7 @@ mod tests {
         assert!(removed);
 
         let reactions_after =
-            queries::messages::get_reactions_for_messages(&pool, &[msg_id.clone()])
+            queries::messages::get_reactions_for_messages(&pool, std::slice::from_ref(&msg_id))
                 .await
                 .unwrap();
         assert_eq!(reactions_after.len(), 2);
@@ -2025,6 +2110,7 @@ mod tests {
                 sender_nick: "alice",
                 content: "Important message!",
                 reply_to_id: None,
+                kind: "normal",
             },
         )
         .await
@@ -2467,6 +2553,7 @@ mod tests {
                     sender_nick: "alice",
                     content: &format!("Msg {i}"),
                     reply_to_id: None,
+                    kind: "normal",
                 },
             )
             .await
@@ -2604,6 +2691,7 @@ mod tests {
                 sender_nick: "alice",
                 content: "Original message",
                 reply_to_id: None,
+                kind: "normal",
             },
         )
         .await
@@ -2621,6 +2709,7 @@ mod tests {
                 sender_nick: "alice",
                 content: "This is a reply",
                 reply_to_id: Some(&msg1_id),
+                kind: "normal",
             },
         )
         .await
@@ -2805,4 +2894,2033 @@ mod tests {
         // Alice should not receive her own message
         assert!(rx1.try_recv().is_err());
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_history_window_anchors() {
+        use crate::engine::chat_engine::{HistoryAnchor, HistoryQuery};
+
+        let (engine, pool) = setup_engine().await;
+        let user_id = create_test_user(&pool, "alice").await;
+        let server_id = engine
+            .create_server("History".into(), user_id.clone(), None)
+            .await
+            .unwrap();
+        let (sid, mut rx) = connect_user(&engine, Some(&user_id), "alice");
+        engine.join_channel(sid, &server_id, "#general").unwrap();
+        drain_events(&mut rx);
+
+        let channel_id = engine.resolve_channel_id(&server_id, "#general").unwrap();
+        let mut ids = Vec::new();
+        for i in 0..6 {
+            let id = Uuid::new_v4().to_string();
+            queries::messages::insert_message(
+                &pool,
+                &queries::messages::InsertMessageParams {
+                    id: &id,
+                    server_id: &server_id,
+                    channel_id: &channel_id,
+                    sender_id: &user_id,
+                    sender_nick: "alice",
+                    content: &format!("line {i}"),
+                    reply_to_id: None,
+                    kind: "normal",
+                },
+            )
+            .await
+            .unwrap();
+            ids.push(id);
+        }
+        let contents = |msgs: Vec<crate::engine::events::HistoryMessage>| {
+            msgs.into_iter().map(|m| m.content).collect::<Vec<_>>()
+        };
+        let anchor = |i: usize| HistoryAnchor::MessageId(ids[i].clone());
+
+        let latest = engine
+            .fetch_history_window(sid, &server_id, "#general", &HistoryQuery::Latest(None), 2)
+            .await
+            .unwrap();
+        assert_eq!(contents(latest), ["line 4", "line 5"]);
+
+        let before = engine
+            .fetch_history_window(
+                sid,
+                &server_id,
+                "#general",
+                &HistoryQuery::Before(anchor(3)),
+                10,
+            )
+            .await
+            .unwrap();
+        assert_eq!(contents(before), ["line 0", "line 1", "line 2"]);
+
+        let after = engine
+            .fetch_history_window(
+                sid,
+                &server_id,
+                "#general",
+                &HistoryQuery::After(anchor(3)),
+                1,
+            )
+            .await
+            .unwrap();
+        assert_eq!(contents(after), ["line 4"]);
+
+        let around = engine
+            .fetch_history_window(
+                sid,
+                &server_id,
+                "#general",
+                &HistoryQuery::Around(anchor(3)),
+                3,
+            )
+            .await
+            .unwrap();
+        assert_eq!(contents(around), ["line 2", "line 3", "line 4"]);
+
+        // A later first anchor counts back from it
+        let between = engine
+            .fetch_history_window(
+                sid,
+                &server_id,
+                "#general",
+                &HistoryQuery::Between(anchor(5), anchor(0)),
+                2,
+            )
+            .await
+            .unwrap();
+        assert_eq!(contents(between), ["line 3", "line 4"]);
+
+        // Timestamps in the future bound nothing
+        let future = HistoryAnchor::Timestamp(chrono::Utc::now() + chrono::Duration::hours(1));
+        let all = engine
+            .fetch_history_window(
+                sid,
+                &server_id,
+                "#general",
+                &HistoryQuery::Before(future),
+                50,
+            )
+            .await
+            .unwrap();
+        assert_eq!(all.len(), 6);
+
+        let unknown = engine
+            .fetch_history_window(
+                sid,
+                &server_id,
+                "#general",
+                &HistoryQuery::Before(HistoryAnchor::MessageId("nope".into())),
+                10,
+            )
+            .await;
+        assert!(unknown.is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_dm_history_and_targets() {
+        use crate::engine::chat_engine::HistoryQuery;
+
+        let (engine, pool) = setup_engine().await;
+        let alice = create_test_user(&pool, "alice").await;
+        let bob = create_test_user(&pool, "bob").await;
+        let (sid_a, _rx_a) = connect_user(&engine, Some(&alice), "alice");
+        let (sid_b, _rx_b) = connect_user(&engine, Some(&bob), "bob");
+
+        engine
+            .send_message(sid_a, DEFAULT_SERVER_ID, "bob", "hi bob", None, None)
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        engine
+            .send_message(sid_b, DEFAULT_SERVER_ID, "alice", "hi alice", None, None)
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        // DMs are stored under user IDs, so history survives reconnects
+        engine.disconnect(sid_b);
+        let history = engine
+            .fetch_history_window(
+                sid_a,
+                DEFAULT_SERVER_ID,
+                "bob",
+                &HistoryQuery::Latest(None),
+                10,
+            )
+            .await
+            .unwrap();
+        let lines: Vec<(String, String)> =
+            history.into_iter().map(|m| (m.from, m.content)).collect();
+        assert_eq!(
+            lines,
+            [
+                ("alice".to_string(), "hi bob".to_string()),
+                ("bob".to_string(), "hi alice".to_string())
+            ]
+        );
+
+        let targets = engine
+            .history_targets(
+                sid_a,
+                chrono::Utc::now() - chrono::Duration::hours(1),
+                chrono::Utc::now() + chrono::Duration::hours(1),
+            )
+            .await
+            .unwrap();
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].target, "bob");
+        assert!(targets[0].server_id.is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_dm_to_offline_user_waits_in_conversation() {
+        let (engine, pool) = setup_engine().await;
+        let alice = create_test_user(&pool, "alice").await;
+        let bob = create_test_user(&pool, "bob").await;
+        let (sid_a, _rx_a) = connect_user(&engine, Some(&alice), "alice");
+
+        // Bob isn't connected, but has an account to leave messages for.
+        // They are stored, in order, by the time each send returns.
+        engine
+            .send_message(
+                sid_a,
+                DEFAULT_SERVER_ID,
+                "bob",
+                "are you there?",
+                None,
+                None,
+            )
+            .unwrap();
+        engine
+            .send_message(sid_a, DEFAULT_SERVER_ID, "bob", "call me", None, None)
+            .unwrap();
+        assert!(
+            engine
+                .send_message(sid_a, DEFAULT_SERVER_ID, "nobody", "hello?", None, None)
+                .is_err()
+        );
+        // Guests can only message people who are online
+        let (sid_g, _rx_g) = connect_user(&engine, None, "guest");
+        assert_eq!(
+            engine.send_message(sid_g, DEFAULT_SERVER_ID, "bob", "hi", None, None),
+            Err("AUTH_REQUIRED".to_string())
+        );
+
+        let conversations = engine.list_dm_conversations(&bob).await.unwrap();
+        assert_eq!(conversations.len(), 1);
+        assert_eq!(conversations[0].unread_count, 2);
+        assert_eq!(conversations[0].participants.len(), 1);
+        assert_eq!(conversations[0].participants[0].user_id, alice);
+        assert_eq!(conversations[0].participants[0].nickname, "alice");
+        assert_eq!(
+            engine.list_dm_conversations(&alice).await.unwrap()[0].unread_count,
+            0
+        );
+
+        // Bob comes online and reads the conversation back, newest first
+        let (sid_b, _rx_b) = connect_user(&engine, Some(&bob), "bob");
+        let (history, has_more) = engine
+            .fetch_dm_history(sid_b, "alice", None, 1)
+            .await
+            .unwrap();
+        assert!(has_more);
+        assert_eq!(history[0].content, "call me");
+        let (history, _) = engine
+            .fetch_dm_history(sid_b, "alice", None, 10)
+            .await
+            .unwrap();
+        let contents: Vec<&str> = history.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, ["call me", "are you there?"]);
+
+        let first = history[1].id.to_string();
+        engine.mark_dm_read(sid_b, "alice", &first).await.unwrap();
+        assert_eq!(
+            engine.list_dm_conversations(&bob).await.unwrap()[0].unread_count,
+            1
+        );
+        let latest = history[0].id.to_string();
+        engine.mark_dm_read(sid_b, "alice", &latest).await.unwrap();
+        assert_eq!(
+            engine.list_dm_conversations(&bob).await.unwrap()[0].unread_count,
+            0
+        );
+        // Reading an older message, or one from elsewhere, doesn't unread anything
+        engine.mark_dm_read(sid_b, "alice", &first).await.unwrap();
+        assert_eq!(
+            engine
+                .mark_dm_read(sid_b, "alice", &Uuid::new_v4().to_string())
+                .await,
+            Err("Message not found".to_string())
+        );
+        assert_eq!(
+            engine.list_dm_conversations(&bob).await.unwrap()[0].unread_count,
+            0
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_dm_to_offline_user_reports_storage_failure() {
+        let (engine, pool) = setup_engine().await;
+        let alice = create_test_user(&pool, "alice").await;
+        create_test_user(&pool, "bob").await;
+        let (sid_a, _rx_a) = connect_user(&engine, Some(&alice), "alice");
+        sqlx::query(
+            "CREATE TRIGGER refuse_messages BEFORE INSERT ON messages \
+             BEGIN SELECT RAISE(ABORT, 'disk full'); END",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        // With bob offline, a DM that can't be stored isn't delivered at all
+        let err = engine
+            .send_message(sid_a, DEFAULT_SERVER_ID, "bob", "lost?", None, None)
+            .unwrap_err();
+        assert!(err.starts_with("DB error: "), "{err}");
+        assert!(err.contains("disk full"), "{err}");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_mentions_inbox() {
+        use crate::engine::chat_engine::UpdateNotificationSettingsParams;
+        use crate::engine::events::MentionKind;
+
+        let TestServer {
+            engine,
+            pool,
+            server_id,
+            users: [alice, bob, carol],
+            sessions: [sid_a, sid_b, sid_c],
+            rxs: [mut rx_a, mut rx_b, mut rx_c],
+        } = setup_server_with_members("Mentions", ["alice", "bob", "carol"]).await;
+        let dave = add_offline_member(&engine, &pool, &server_id, "dave").await;
+        make_moderator(&pool, &server_id, &dave).await;
+        engine
+            .update_notification_settings(
+                sid_c,
+                &UpdateNotificationSettingsParams {
+                    server_id: &server_id,
+                    channel_id: None,
+                    level: "all",
+                    suppress_everyone: true,
+                    suppress_roles: false,
+                    muted: false,
+                    mute_until: None,
+                },
+            )
+            .await
+            .unwrap();
+        drain_events(&mut rx_a);
+        drain_events(&mut rx_b);
+        drain_events(&mut rx_c);
+
+        // Without MENTION_EVERYONE only the nickname counts
+        engine
+            .send_message(
+                sid_b,
+                &server_id,
+                "#general",
+                "@everyone, @Carol, @dave and @moderator: hi",
+                None,
+                None,
+            )
+            .unwrap();
+        let mention = std::iter::from_fn(|| rx_c.try_recv().ok())
+            .find_map(|e| match e {
+                ChatEvent::Mention { mention } => Some(mention),
+                _ => None,
+            })
+            .expect("carol should be told she was mentioned");
+        assert_eq!(mention.kind, MentionKind::User);
+        assert_eq!(mention.from, "bob");
+        assert_eq!(mention.channel, "#general");
+        assert!(
+            !std::iter::from_fn(|| rx_a.try_recv().ok())
+                .any(|e| matches!(e, ChatEvent::Mention { .. }))
+        );
+
+        // The owner can; dave is offline and not in the channel but still
+        // gets it, and carol has @everyone suppressed
+        engine
+            .send_message(
+                sid_a,
+                &server_id,
+                "#general",
+                "@everyone @Moderator ping @bob",
+                None,
+                None,
+            )
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let (bobs, _) = engine.list_mentions(&bob, None, 10, false).await.unwrap();
+        assert_eq!(bobs.len(), 1);
+        assert_eq!(bobs[0].kind, MentionKind::User);
+        assert_eq!(bobs[0].content, "@everyone @Moderator ping @bob");
+        let (daves, _) = engine.list_mentions(&dave, None, 10, false).await.unwrap();
+        assert_eq!(daves.len(), 2);
+        assert_eq!(daves[0].kind, MentionKind::Role);
+        let (carols, has_more) = engine.list_mentions(&carol, None, 10, false).await.unwrap();
+        assert_eq!(carols.len(), 1);
+        assert!(!has_more);
+        assert!(engine.list_mentions(&alice, None, 10, false).await.unwrap().0.is_empty());
+
+        // Paging and marking read
+        let (page, has_more) = engine.list_mentions(&dave, None, 1, false).await.unwrap();
+        assert!(has_more);
+        let (rest, has_more) = engine
+            .list_mentions(&dave, Some(&page[0].id), 1, false)
+            .await
+            .unwrap();
+        assert!(!has_more);
+        assert_eq!(rest[0].kind, MentionKind::User);
+        engine.mark_mention_read(&dave, &page[0].id).await.unwrap();
+        assert!(engine.mark_mention_read(&bob, &page[0].id).await.is_err());
+        let (unread, _) = engine.list_mentions(&dave, None, 10, true).await.unwrap();
+        assert_eq!(unread.len(), 1);
+        assert!(!unread[0].read);
+        assert_eq!(
+            engine
+                .mark_all_mentions_read(&dave, Some(&server_id))
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(engine.list_mentions(&dave, None, 10, true).await.unwrap().0.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_message_edit_history_and_automod_on_edit() {
+        let TestServer {
+            engine,
+            pool,
+            server_id,
+            users: [_, bob],
+            sessions: [_, sid_b],
+            rxs: [mut rx_a, _rx_b],
+        } = setup_server_with_members("Edits", ["alice", "bob"]).await;
+        let carol = add_offline_member(&engine, &pool, &server_id, "carol").await;
+        let dave = add_offline_member(&engine, &pool, &server_id, "dave").await;
+        make_moderator(&pool, &server_id, &carol).await;
+        queries::automod::create_rule(
+            &pool,
+            &CreateAutomodRuleParams {
+                id: &Uuid::new_v4().to_string(),
+                server_id: &server_id,
+                name: "No spam",
+                rule_type: "keyword",
+                config: r#"{"words":["spam"]}"#,
+                action_type: "delete",
+                timeout_duration_seconds: None,
+            },
+        )
+        .await
+        .unwrap();
+
+        engine
+            .send_message(sid_b, &server_id, "#general", "hello", None, None)
+            .unwrap();
+        let message_id = match rx_a.try_recv().unwrap() {
+            ChatEvent::Message { id, .. } => id.to_string(),
+            other => panic!("Expected Message, got {other:?}"),
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        engine
+            .edit_message(sid_b, &message_id, "hello there")
+            .await
+            .unwrap();
+        engine
+            .edit_message(sid_b, &message_id, "hello again")
+            .await
+            .unwrap();
+
+        // Editing can't slip past automod, and a blocked edit changes nothing
+        let err = engine
+            .edit_message(sid_b, &message_id, "buy spam")
+            .await
+            .unwrap_err();
+        assert!(err.contains("automod"), "{err}");
+
+        let history = engine.message_edit_history(&bob, &message_id).await.unwrap();
+        let contents: Vec<&str> = history.iter().map(|r| r.content.as_str()).collect();
+        assert_eq!(contents, ["hello", "hello there", "hello again"]);
+
+        // Moderators can see it too; other members can't
+        assert_eq!(
+            engine
+                .message_edit_history(&carol, &message_id)
+                .await
+                .unwrap()
+                .len(),
+            3
+        );
+        let err = engine
+            .message_edit_history(&dave, &message_id)
+            .await
+            .unwrap_err();
+        assert!(err.starts_with("FORBIDDEN"), "{err}");
+        assert!(
+            engine
+                .message_edit_history(&bob, "no-such-message")
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_group_dm_messages_and_membership() {
+        use crate::engine::events::{GroupDmChange, MessageKind};
+
+        let (engine, pool) = setup_engine().await;
+        let alice = create_test_user(&pool, "alice").await;
+        let bob = create_test_user(&pool, "bob").await;
+        let carol = create_test_user(&pool, "carol").await;
+        let dave = create_test_user(&pool, "dave").await;
+        let (sid_a, mut rx_a) = connect_user(&engine, Some(&alice), "alice");
+        let (sid_b, mut rx_b) = connect_user(&engine, Some(&bob), "bob");
+
+        // Carol is offline but still joins the group
+        let names = vec!["bob".to_string(), "carol".to_string()];
+        let id = engine
+            .create_group_dm(sid_a, &names, Some("weekend"))
+            .await
+            .unwrap();
+        let target = format!("&{id}");
+        match rx_b.try_recv().unwrap() {
+            ChatEvent::GroupDmUpdate {
+                conversation,
+                change,
+            } => {
+                assert_eq!(conversation.target, target);
+                assert_eq!(conversation.owner_id, alice);
+                assert_eq!(conversation.participants.len(), 3);
+                assert_eq!(change, GroupDmChange::Created { by: "alice".into() });
+            }
+            other => panic!("Expected GroupDmUpdate, got {other:?}"),
+        }
+        match rx_b.try_recv().unwrap() {
+            ChatEvent::Message { content, kind, .. } => {
+                assert_eq!(kind, MessageKind::System);
+                assert_eq!(content, "alice created the group");
+            }
+            other => panic!("Expected a system message, got {other:?}"),
+        }
+        drain_events(&mut rx_a);
+
+        engine
+            .send_message(sid_b, DEFAULT_SERVER_ID, &target, "saturday?", None, None)
+            .unwrap();
+        assert!(matches!(
+            rx_a.try_recv().unwrap(),
+            ChatEvent::Message { target: t, content, .. } if t == target && content == "saturday?"
+        ));
+        let carols = engine.list_dm_conversations(&carol).await.unwrap();
+        assert_eq!(carols.len(), 1);
+        assert!(carols[0].is_group);
+        assert_eq!(carols[0].name.as_deref(), Some("weekend"));
+        assert_eq!(carols[0].participants.len(), 2);
+        // The creation notice and bob's message
+        assert_eq!(carols[0].unread_count, 2);
+
+        // Only participants can post, and only accounts can be participants
+        let (sid_d, mut rx_d) = connect_user(&engine, Some(&dave), "dave");
+        assert!(
+            engine
+                .send_message(sid_d, DEFAULT_SERVER_ID, &target, "let me in", None, None)
+                .is_err()
+        );
+        let (sid_g, _rx_g) = connect_user(&engine, None, "guest");
+        assert_eq!(
+            engine.send_message(sid_g, DEFAULT_SERVER_ID, &target, "hi", None, None),
+            Err("AUTH_REQUIRED".to_string())
+        );
+
+        // Anyone in the group can add people; only the owner removes them
+        engine
+            .add_group_dm_participant(sid_b, &id, "dave")
+            .await
+            .unwrap();
+        assert!(matches!(
+            rx_d.try_recv().unwrap(),
+            ChatEvent::GroupDmUpdate {
+                change: GroupDmChange::Added { .. },
+                ..
+            }
+        ));
+        let err = engine
+            .remove_group_dm_participant(sid_b, &id, "dave")
+            .await
+            .unwrap_err();
+        assert!(err.starts_with("FORBIDDEN"), "{err}");
+        engine
+            .remove_group_dm_participant(sid_a, &id, "dave")
+            .await
+            .unwrap();
+        drain_events(&mut rx_d);
+        assert!(engine.group_dm(sid_d, &id).await.is_err());
+
+        engine
+            .rename_group_dm(sid_b, &id, Some("sunday"))
+            .await
+            .unwrap();
+        engine
+            .transfer_group_dm_ownership(sid_a, &id, "bob")
+            .await
+            .unwrap();
+        let group = engine.group_dm(sid_a, &id).await.unwrap();
+        assert_eq!(group.name.as_deref(), Some("sunday"));
+        assert_eq!(group.owner_id, bob);
+
+        engine.leave_group_dm(sid_a, &id).await.unwrap();
+        assert!(
+            engine
+                .send_message(sid_a, DEFAULT_SERVER_ID, &target, "still here?", None, None)
+                .is_err()
+        );
+
+        // The membership changes are part of the conversation's history
+        let (history, _) = engine
+            .fetch_dm_history(sid_b, &target, None, 50)
+            .await
+            .unwrap();
+        let notes: Vec<&str> = history
+            .iter()
+            .filter(|m| m.kind == MessageKind::System)
+            .map(|m| m.content.as_str())
+            .rev()
+            .collect();
+        assert_eq!(
+            notes,
+            [
+                "alice created the group",
+                "bob added dave",
+                "alice removed dave",
+                "bob renamed the group to sunday",
+                "bob is now the group owner",
+                "alice left the group",
+            ]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_group_dm_owner_leaving_and_participant_limit() {
+        use crate::engine::chat_engine::MAX_GROUP_DM_PARTICIPANTS;
+
+        let (engine, pool) = setup_engine().await;
+        let alice = create_test_user(&pool, "alice").await;
+        let bob = create_test_user(&pool, "bob").await;
+        create_test_user(&pool, "carol").await;
+        let (sid_a, _rx_a) = connect_user(&engine, Some(&alice), "alice");
+        let (sid_b, _rx_b) = connect_user(&engine, Some(&bob), "bob");
+
+        let mut names = Vec::new();
+        for n in 0..MAX_GROUP_DM_PARTICIPANTS {
+            let name = format!("member{n}");
+            create_test_user(&pool, &name).await;
+            names.push(name);
+        }
+        assert!(engine.create_group_dm(sid_a, &names, None).await.is_err());
+        // Too many names is refused before any are looked up
+        let ghosts: Vec<String> = (0..MAX_GROUP_DM_PARTICIPANTS)
+            .map(|n| format!("ghost{n}"))
+            .collect();
+        assert_eq!(
+            engine.create_group_dm(sid_a, &ghosts, None).await,
+            Err(format!(
+                "A group DM can have at most {MAX_GROUP_DM_PARTICIPANTS} participants"
+            ))
+        );
+        names.truncate(MAX_GROUP_DM_PARTICIPANTS - 2);
+        names.push("bob".into());
+        // A repeated nick and the owner's own don't count towards the limit
+        let mut padded = names.clone();
+        padded.extend(["BOB".to_string(), "alice".to_string()]);
+        let full = engine.create_group_dm(sid_a, &padded, None).await.unwrap();
+        let group = engine.group_dm(sid_a, &full).await.unwrap();
+        assert_eq!(group.participants.len(), MAX_GROUP_DM_PARTICIPANTS);
+        let id = engine.create_group_dm(sid_a, &names, None).await.unwrap();
+        assert!(
+            engine
+                .add_group_dm_participant(sid_a, &id, "carol")
+                .await
+                .is_err()
+        );
+        assert!(engine.create_group_dm(sid_a, &[], None).await.is_err());
+
+        // The owner leaving hands the group to whoever has been in it longest
+        engine.leave_group_dm(sid_a, &id).await.unwrap();
+        let group = engine.group_dm(sid_b, &id).await.unwrap();
+        assert_eq!(group.participants.len(), MAX_GROUP_DM_PARTICIPANTS - 1);
+        assert_ne!(group.owner_id, alice);
+        assert_eq!(
+            Some(group.owner_id.clone()),
+            engine.find_user_id_by_nick("member0").await
+        );
+
+        // The last one out deletes the group
+        let solo = engine
+            .create_group_dm(sid_b, &["alice".to_string()], None)
+            .await
+            .unwrap();
+        engine.leave_group_dm(sid_a, &solo).await.unwrap();
+        engine.leave_group_dm(sid_b, &solo).await.unwrap();
+        assert!(
+            queries::direct_messages::get_group_conversation(&pool, &solo)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_irc_group_dm_commands() {
+        use crate::irc::connection::CapState;
+        use crate::irc::group_dm::handle_command;
+        use crate::irc::parser::IrcMessage;
+
+        let (engine, pool) = setup_engine().await;
+        let alice = create_test_user(&pool, "alice").await;
+        let bob = create_test_user(&pool, "bob").await;
+        create_test_user(&pool, "carol").await;
+        let (sid_a, _rx_a) = connect_user(&engine, Some(&alice), "alice");
+        let (sid_b, _rx_b) = connect_user(&engine, Some(&bob), "bob");
+        let id = engine
+            .create_group_dm(sid_a, &["bob".to_string()], None)
+            .await
+            .unwrap();
+        let target = format!("&{id}");
+        let caps = CapState::default();
+        let run = |sid, nick: &'static str, line: String| {
+            let engine = &engine;
+            let caps = &caps;
+            async move {
+                let msg = IrcMessage::parse(&line).unwrap();
+                handle_command(engine, sid, nick, caps, &msg).await
+            }
+        };
+
+        let lines = run(sid_b, "bob", format!("JOIN {target}")).await;
+        assert!(lines[0].ends_with(&format!("JOIN {target}")));
+        assert!(lines[1].ends_with(":@alice bob"));
+        let lines = run(sid_b, "bob", "JOIN &nonexistent".into()).await;
+        assert!(lines[0].contains(" 473 bob &nonexistent "));
+
+        let lines = run(sid_b, "bob", format!("INVITE carol {target}")).await;
+        assert!(lines[0].contains(&format!(" 341 bob carol {target}")));
+        let lines = run(sid_b, "bob", format!("KICK {target} carol")).await;
+        assert!(lines[0].contains(" 482 bob "), "{lines:?}");
+        let lines = run(sid_a, "alice", format!("KICK {target}")).await;
+        assert!(lines[0].contains(" 461 alice KICK "), "{lines:?}");
+        assert!(
+            run(sid_a, "alice", format!("KICK {target} carol"))
+                .await
+                .is_empty()
+        );
+
+        assert!(
+            run(sid_b, "bob", format!("TOPIC {target} :plans"))
+                .await
+                .is_empty()
+        );
+        let lines = run(sid_a, "alice", format!("TOPIC {target}")).await;
+        assert!(lines[0].ends_with(&format!("332 alice {target} plans")));
+
+        assert!(
+            run(sid_a, "alice", format!("MODE {target} +o bob"))
+                .await
+                .is_empty()
+        );
+        assert_eq!(engine.group_dm(sid_a, &id).await.unwrap().owner_id, bob);
+
+        assert!(
+            run(sid_a, "alice", format!("PART {target}"))
+                .await
+                .is_empty()
+        );
+        let lines = run(sid_a, "alice", format!("NAMES {target}")).await;
+        assert!(lines[0].contains(" 366 alice "));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_irc_channel_modes_map_onto_moderation() {
+        use crate::irc::commands::handle_command;
+        use crate::irc::connection::CapState;
+        use crate::irc::moderation::handle_mode;
+        use crate::irc::parser::IrcMessage;
+
+        let TestServer {
+            engine,
+            pool,
+            server_id,
+            users: [_, bob],
+            sessions: [sid_a, sid_b],
+            rxs: [mut rx_a, mut rx_b],
+        } = setup_server_with_members("mods", ["alice", "bob"]).await;
+
+        let mode = |line: &str| IrcMessage::parse(line).unwrap();
+
+        // Query reports the real (empty) state plus the creation time
+        let replies = handle_mode(&engine, sid_a, "alice", &mode("MODE #mods/general")).await;
+        assert!(replies[0].contains(" 324 alice #mods/general +"));
+        assert!(replies[1].contains(" 329 alice #mods/general "));
+
+        // Members without MANAGE_CHANNELS cannot moderate the channel
+        let replies = handle_mode(&engine, sid_b, "bob", &mode("MODE #mods/general +m")).await;
+        assert!(replies[0].contains(" 482 bob #mods/general "));
+
+        // +m silences @everyone; +v hands bob a speaking role again
+        let replies = handle_mode(&engine, sid_a, "alice", &mode("MODE #mods/general +ms")).await;
+        assert!(replies.is_empty(), "{replies:?}");
+        let replies = handle_mode(&engine, sid_a, "alice", &mode("MODE #mods/general")).await;
+        assert!(replies[0].ends_with(" 324 alice #mods/general +ms"));
+        let reloaded = ChatEngine::new(Some(pool.clone()));
+        reloaded.load_servers_from_db().await.unwrap();
+        reloaded.load_channels_from_db().await.unwrap();
+        let state = reloaded
+            .channel_moderation(&server_id, "#general")
+            .await
+            .unwrap();
+        assert!(state.moderated, "+m should survive a restart");
+        assert!(
+            engine
+                .send_message(sid_b, &server_id, "#general", "hello?", None, None)
+                .is_err()
+        );
+        // Over IRC the channel can't be sent to, rather than missing
+        let replies = handle_command(
+            &engine,
+            sid_b,
+            "bob",
+            &CapState::default(),
+            &mode("PRIVMSG #mods/general :hello?"),
+        );
+        assert_eq!(replies.len(), 1);
+        assert!(replies[0].contains(" 404 bob #mods/general "), "{replies:?}");
+        drain_events(&mut rx_a);
+        let replies =
+            handle_mode(&engine, sid_a, "alice", &mode("MODE #mods/general +v bob")).await;
+        assert!(replies.is_empty(), "{replies:?}");
+        let voiced = std::iter::from_fn(|| rx_a.try_recv().ok()).any(|event| {
+            matches!(event, ChatEvent::MemberPrefixUpdate { nickname, added, .. }
+                if nickname == "bob" && added == "+")
+        });
+        assert!(voiced, "+v should announce bob's new prefix");
+        engine
+            .send_message(sid_b, &server_id, "#general", "thanks", None, None)
+            .unwrap();
+
+        // Unknown modes are rejected
+        let replies = handle_mode(&engine, sid_a, "alice", &mode("MODE #mods/general +z")).await;
+        assert!(replies[0].contains(" 472 alice z "));
+
+        // +b bans bob, removing him from the channel, and shows up in the ban list
+        drain_events(&mut rx_b);
+        let replies = handle_mode(
+            &engine,
+            sid_a,
+            "alice",
+            &mode("MODE #mods/general +b bob!*@*"),
+        )
+        .await;
+        assert_eq!(
+            replies,
+            vec![":alice!alice@concord MODE #mods/general +b bob!*@*".to_string()]
+        );
+        let mut kicked = false;
+        while let Ok(event) = rx_b.try_recv() {
+            if let ChatEvent::ChannelKick {
+                nickname,
+                kicked_by,
+                ..
+            } = event
+            {
+                assert_eq!((nickname.as_str(), kicked_by.as_str()), ("bob", "alice"));
+                kicked = true;
+            }
+        }
+        assert!(kicked, "bob should be kicked from #general");
+        assert!(!engine.user_is_server_member(&server_id, &bob));
+
+        let replies = handle_mode(&engine, sid_a, "alice", &mode("MODE #mods/general +b")).await;
+        assert_eq!(replies.len(), 2);
+        assert!(replies[0].contains(" 367 alice #mods/general bob!*@* alice "));
+        assert!(replies[1].contains(" 368 alice #mods/general "));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_member_prefixes_follow_roles() {
+        let TestServer {
+            engine,
+            server_id,
+            users: [_, bob],
+            rxs: [mut rx_a, _rx_b],
+            ..
+        } = setup_server_with_members("ranks", ["alice", "bob"]).await;
+
+        let prefixes = |engine: &ChatEngine| -> Vec<(String, String)> {
+            engine
+                .get_members(&server_id, "#general")
+                .unwrap()
+                .into_iter()
+                .map(|m| (m.nickname, m.prefixes))
+                .collect()
+        };
+        let mut members = prefixes(&engine);
+        members.sort();
+        assert_eq!(
+            members,
+            vec![
+                ("alice".to_string(), "~&@%".to_string()),
+                ("bob".to_string(), String::new()),
+            ]
+        );
+
+        // Granting the Admin role makes bob an op; taking it away reverts him
+        let roles = engine.list_roles(&server_id).await.unwrap();
+        let admin = roles.iter().find(|r| r.name == "Admin").unwrap();
+        engine
+            .assign_role(&server_id, &bob, &admin.id)
+            .await
+            .unwrap();
+        let update = std::iter::from_fn(|| rx_a.try_recv().ok()).find_map(|event| match event {
+            ChatEvent::MemberPrefixUpdate {
+                nickname,
+                added,
+                removed,
+                ..
+            } => Some((nickname, added, removed)),
+            _ => None,
+        });
+        assert_eq!(
+            update,
+            Some(("bob".to_string(), "@%".to_string(), String::new()))
+        );
+
+        engine
+            .remove_role(&server_id, &bob, &admin.id)
+            .await
+            .unwrap();
+        let update = std::iter::from_fn(|| rx_a.try_recv().ok()).find_map(|event| match event {
+            ChatEvent::MemberPrefixUpdate { removed, .. } => Some(removed),
+            _ => None,
+        });
+        assert_eq!(update.as_deref(), Some("@%"));
+    }
+
+    #[tokio::test]
+    async fn test_server_motd_overrides_instance_motd() {
+        let pool = setup_db().await;
+        let engine = ChatEngine::new(Some(pool.clone())).with_motd(Some("Instance MOTD".into()));
+        let alice = create_test_user(&pool, "alice").await;
+        let bob = create_test_user(&pool, "bob").await;
+        let server_id = engine
+            .create_server("motd".into(), alice.clone(), None)
+            .await
+            .unwrap();
+        engine.join_server(&bob, &server_id).await.unwrap();
+        let (sid_a, mut rx_a) = connect_user(&engine, Some(&alice), "alice");
+        let (sid_b, _rx_b) = connect_user(&engine, Some(&bob), "bob");
+
+        assert_eq!(
+            engine.effective_motd(Some(&server_id)).await.as_deref(),
+            Some("Instance MOTD")
+        );
+
+        // Only members with MANAGE_SERVER may change it
+        let err = engine
+            .set_server_motd(sid_b, &server_id, Some("Hijacked"))
+            .await
+            .unwrap_err();
+        assert!(err.contains("FORBIDDEN"));
+
+        drain_events(&mut rx_a);
+        engine
+            .set_server_motd(sid_a, &server_id, Some("Server MOTD"))
+            .await
+            .unwrap();
+        let event = rx_a.try_recv().unwrap();
+        assert!(
+            matches!(event, ChatEvent::ServerMotd { motd: Some(ref m), .. } if m == "Server MOTD")
+        );
+        assert_eq!(
+            engine.effective_motd(Some(&server_id)).await.as_deref(),
+            Some("Server MOTD")
+        );
+        assert_eq!(
+            engine.effective_motd(None).await.as_deref(),
+            Some("Instance MOTD")
+        );
+
+        // Clearing it falls back to the instance MOTD
+        engine
+            .set_server_motd(sid_a, &server_id, Some("  "))
+            .await
+            .unwrap();
+        assert_eq!(
+            engine.effective_motd(Some(&server_id)).await.as_deref(),
+            Some("Instance MOTD")
+        );
+
+        let counts = engine.lusers();
+        assert_eq!((counts.users, counts.clients), (2, 2));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_irc_away_bridges_presence() {
+        use crate::irc::commands::{handle_away, handle_command};
+        use crate::irc::connection::CapState;
+        use crate::irc::parser::IrcMessage;
+
+        let TestServer {
+            engine,
+            server_id,
+            sessions: [sid_a, sid_b],
+            rxs: [_rx_a, mut rx_b],
+            ..
+        } = setup_server_with_members("away", ["alice", "bob"]).await;
+        engine
+            .create_channel_in_server(&server_id, "#random", None, false)
+            .await
+            .unwrap();
+        engine.join_channel(sid_a, &server_id, "#random").unwrap();
+        engine.join_channel(sid_b, &server_id, "#random").unwrap();
+        drain_events(&mut rx_b);
+
+        let msg = |line: &str| IrcMessage::parse(line).unwrap();
+        let replies = handle_away(&engine, sid_a, "alice", &msg("AWAY :Gone fishing")).await;
+        assert!(replies[0].contains(" 306 alice "));
+
+        // One update per shared server, however many channels are shared
+        let updates: Vec<_> = std::iter::from_fn(|| rx_b.try_recv().ok())
+            .filter_map(|event| match event {
+                ChatEvent::PresenceUpdate { presence, .. } => Some(presence),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].status, "idle");
+        assert_eq!(updates[0].custom_status.as_deref(), Some("Gone fishing"));
+
+        let caps = CapState::default();
+        let replies = handle_command(&engine, sid_b, "bob", &caps, &msg("WHOIS alice"));
+        assert!(replies.contains(&":concord 301 bob alice :Gone fishing".to_string()));
+        let replies = handle_command(&engine, sid_b, "bob", &caps, &msg("PRIVMSG alice :hi"));
+        assert_eq!(
+            replies,
+            vec![":concord 301 bob alice :Gone fishing".to_string()]
+        );
+
+        let replies = handle_away(&engine, sid_a, "alice", &msg("AWAY")).await;
+        assert!(replies[0].contains(" 305 alice "));
+        let replies = handle_command(&engine, sid_b, "bob", &caps, &msg("PRIVMSG alice :back?"));
+        assert!(replies.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_irc_action_notice_and_formatting() {
+        use crate::engine::chat_engine::HistoryQuery;
+        use crate::engine::events::MessageKind;
+        use crate::irc::commands::handle_command;
+        use crate::irc::connection::CapState;
+        use crate::irc::parser::IrcMessage;
+
+        let TestServer {
+            engine,
+            server_id,
+            sessions: [sid_a, sid_b],
+            rxs: [_rx_a, mut rx_b],
+            ..
+        } = setup_server_with_members("fmt", ["alice", "bob"]).await;
+
+        let caps = CapState::default();
+        let send = |line: &str| {
+            handle_command(
+                &engine,
+                sid_a,
+                "alice",
+                &caps,
+                &IrcMessage::parse(line).unwrap(),
+            )
+        };
+        assert!(send("PRIVMSG #fmt/general :\x01ACTION waves \x02hard\x02\x01").is_empty());
+        assert!(send("NOTICE #fmt/general :build \x1Dgreen\x1D").is_empty());
+        assert!(send("PRIVMSG #fmt/general :\x01VERSION\x01").is_empty());
+        // NOTICE never draws an error, even for an unknown target
+        assert!(send("NOTICE #fmt/nowhere :hello?").is_empty());
+
+        let received: Vec<_> = std::iter::from_fn(|| rx_b.try_recv().ok())
+            .filter_map(|event| match event {
+                ChatEvent::Message { content, kind, .. } => Some((content, kind)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            received,
+            vec![
+                ("waves **hard**".to_string(), MessageKind::Action),
+                ("build *green*".to_string(), MessageKind::Notice),
+            ]
+        );
+
+        // The DB inserts happen in a tokio::spawn
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let history = engine
+            .fetch_history_window(
+                sid_b,
+                &server_id,
+                "#general",
+                &HistoryQuery::Latest(None),
+                10,
+            )
+            .await
+            .unwrap();
+        let kinds: Vec<_> = history.iter().map(|m| m.kind).collect();
+        assert_eq!(kinds, vec![MessageKind::Action, MessageKind::Notice]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_irc_redact_react_reply_and_edit() {
+        use crate::irc::commands::{handle_command, handle_tagmsg};
+        use crate::irc::connection::CapState;
+        use crate::irc::edits::{handle_edit, handle_redact};
+        use crate::irc::parser::IrcMessage;
+
+        let TestServer {
+            engine,
+            sessions: [sid_a, sid_b],
+            rxs: [_rx_a, mut rx_b],
+            ..
+        } = setup_server_with_members("edits", ["alice", "bob"]).await;
+
+        let msg = |line: &str| IrcMessage::parse(line).unwrap();
+        let caps = CapState::default();
+        let next_message_id = |rx: &mut tokio::sync::mpsc::Receiver<ChatEvent>| {
+            std::iter::from_fn(|| rx.try_recv().ok())
+                .find_map(|event| match event {
+                    ChatEvent::Message { id, reply_to, .. } => Some((id, reply_to)),
+                    _ => None,
+                })
+                .unwrap()
+        };
+
+        handle_command(
+            &engine,
+            sid_a,
+            "alice",
+            &caps,
+            &msg("PRIVMSG #edits/general :lunch?"),
+        );
+        let (first, _) = next_message_id(&mut rx_b);
+        // The reply's preview is read back from the stored first message
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        handle_command(
+            &engine,
+            sid_a,
+            "alice",
+            &caps,
+            &msg(&format!(
+                "@+draft/reply={first} PRIVMSG #edits/general :at noon"
+            )),
+        );
+        let (second, reply_to) = next_message_id(&mut rx_b);
+        assert_eq!(reply_to.unwrap().id, first.to_string());
+        // The DB inserts happen in a tokio::spawn
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        // Reaction and unreaction via TAGMSG
+        let react = format!("@+draft/react=\u{1f44d};+draft/reply={first} TAGMSG #edits/general");
+        assert!(handle_tagmsg(&engine, sid_b, &msg(&react)).await.is_empty());
+        let unreact = react.replace("+draft/react", "+draft/unreact");
+        assert!(
+            handle_tagmsg(&engine, sid_b, &msg(&unreact))
+                .await
+                .is_empty()
+        );
+
+        // Edit via +draft/edit, by the author only
+        let edit = format!("@+draft/edit={second} PRIVMSG #edits/general :at \x02one\x02");
+        assert!(
+            handle_edit(&engine, sid_a, "alice", &msg(&edit))
+                .await
+                .is_empty()
+        );
+        let denied = handle_edit(&engine, sid_b, "bob", &msg(&edit)).await;
+        assert!(denied[0].contains("only edit your own"));
+
+        // Redaction: members can't delete others' messages, authors can
+        let redact = format!("REDACT #edits/general {first}");
+        let denied = handle_redact(&engine, sid_b, "bob", &msg(&redact)).await;
+        assert!(denied[0].starts_with(":concord FAIL REDACT REDACT_FORBIDDEN #edits/general "));
+        assert!(
+            handle_redact(&engine, sid_a, "alice", &msg(&redact))
+                .await
+                .is_empty()
+        );
+        let unknown = handle_redact(&engine, sid_a, "alice", &msg(&redact)).await;
+        assert!(unknown[0].contains(" UNKNOWN_MSGID "));
+
+        let events: Vec<_> = std::iter::from_fn(|| rx_b.try_recv().ok()).collect();
+        assert!(events.iter().any(|e| matches!(
+            e,
+            ChatEvent::ReactionAdd { message_id, emoji, .. } if *message_id == first && emoji == "\u{1f44d}"
+        )));
+        assert!(
+            events
+                .iter()
+                .any(|e| matches!(e, ChatEvent::ReactionRemove { .. }))
+        );
+        assert!(events.iter().any(|e| matches!(
+            e,
+            ChatEvent::MessageEdit { id, from, content, .. }
+                if *id == second && from == "alice" && content == "at **one**"
+        )));
+        assert!(events.iter().any(|e| matches!(
+            e,
+            ChatEvent::MessageDelete { id, deleted_by, .. } if *id == first && deleted_by == "alice"
+        )));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_irc_typing_tagmsg() {
+        use crate::irc::commands::handle_tagmsg;
+        use crate::irc::parser::IrcMessage;
+
+        let TestServer {
+            engine,
+            sessions: [sid_a, _],
+            rxs: [mut rx_a, mut rx_b],
+            ..
+        } = setup_server_with_members("typing", ["alice", "bob"]).await;
+
+        for state in ["active", "paused", "done", "bogus"] {
+            let line = format!("@+typing={state} TAGMSG #typing/general");
+            let msg = IrcMessage::parse(&line).unwrap();
+            assert!(handle_tagmsg(&engine, sid_a, &msg).await.is_empty());
+        }
+
+        let events: Vec<_> = std::iter::from_fn(|| rx_b.try_recv().ok()).collect();
+        assert_eq!(events.len(), 3);
+        assert!(
+            matches!(&events[0], ChatEvent::TypingStart { nickname, .. } if nickname == "alice")
+        );
+        assert!(matches!(
+            events[1],
+            ChatEvent::TypingStop { paused: true, .. }
+        ));
+        assert!(matches!(
+            events[2],
+            ChatEvent::TypingStop { paused: false, .. }
+        ));
+        // The typist doesn't hear their own indicator
+        assert!(rx_a.try_recv().is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_always_on_keeps_session_and_replays_backlog() {
+        use crate::irc::connection::CapState;
+        use crate::irc::history::{ReadMarkers, direct_replay, join_replay};
+
+        let TestServer {
+            engine,
+            server_id,
+            users: [alice, _],
+            sessions: [sid_a, sid_b],
+            rxs: [_rx_a, mut rx_b],
+            ..
+        } = setup_server_with_members("bnc", ["alice", "bob"]).await;
+        engine.set_always_on(&alice, true).await.unwrap();
+        assert!(engine.always_on(&alice).await.unwrap());
+
+        // The client goes away but alice stays: no QUIT, and her nick is kept
+        ReadMarkers::default().save(&engine, sid_a, "laptop").await;
+        engine.disconnect(sid_a);
+        assert!(engine.get_session(sid_a).unwrap().is_detached());
+        assert!(!engine.is_nick_available("alice"));
+        assert!(rx_b.try_recv().is_err());
+
+        // Messages to her are still accepted and stored
+        engine
+            .send_message(
+                sid_b,
+                &server_id,
+                "#general",
+                "while you were out",
+                None,
+                None,
+            )
+            .unwrap();
+        engine
+            .send_message(sid_b, &server_id, "alice", "psst", None, None)
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        drain_events(&mut rx_b);
+
+        // A new client takes over the session and its channels without a JOIN
+        let (sid_a2, mut rx_a2) = connect_user(&engine, Some(&alice), "alice");
+        assert!(engine.get_session(sid_a).is_none());
+        assert!(matches!(rx_a2.try_recv(), Ok(ChatEvent::Join { .. })));
+        assert!(rx_b.try_recv().is_err());
+
+        // and is replayed what it missed since its markers
+        let caps = CapState::default();
+        let mut markers = ReadMarkers::default();
+        let channel = join_replay(
+            &engine,
+            sid_a2,
+            &caps,
+            "laptop",
+            &mut markers,
+            &server_id,
+            "#general",
+        )
+        .await;
+        assert_eq!(channel.len(), 1);
+        assert!(channel[0].starts_with(":bob!bob@concord PRIVMSG #bnc/general :["));
+        assert!(channel[0].ends_with("] while you were out"));
+        let direct = direct_replay(&engine, sid_a2, "alice", &caps, "laptop", &mut markers).await;
+        assert_eq!(direct.len(), 1);
+        assert!(direct[0].starts_with(":bob!bob@concord PRIVMSG alice :["));
+        assert!(direct[0].ends_with("] psst"));
+
+        // A client that never connected before has no markers, so no DM replay
+        let fresh = direct_replay(
+            &engine,
+            sid_a2,
+            "alice",
+            &caps,
+            "phone",
+            &mut ReadMarkers::default(),
+        )
+        .await;
+        assert!(fresh.is_empty());
+
+        // Turning always-on off ends a detached session for real
+        engine.disconnect(sid_a2);
+        assert!(rx_b.try_recv().is_err());
+        engine.set_always_on(&alice, false).await.unwrap();
+        assert!(engine.is_nick_available("alice"));
+        let events: Vec<_> = std::iter::from_fn(|| rx_b.try_recv().ok()).collect();
+        assert!(
+            events
+                .iter()
+                .any(|e| matches!(e, ChatEvent::Quit { nickname, .. } if nickname == "alice"))
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_irc_invite_knock_and_invite_only_joins() {
+        use crate::irc::access::{handle_invite, handle_join, handle_knock};
+        use crate::irc::parser::IrcMessage;
+
+        let (engine, pool) = setup_engine().await;
+        let alice = create_test_user(&pool, "alice").await;
+        let bob = create_test_user(&pool, "bob").await;
+        let dave = create_test_user(&pool, "dave").await;
+        let server_id = engine
+            .create_server("guild".into(), alice.clone(), None)
+            .await
+            .unwrap();
+        let (sid_a, mut rx_a) = connect_user(&engine, Some(&alice), "alice");
+        let (sid_b, mut rx_b) = connect_user(&engine, Some(&bob), "bob");
+        let msg = |line: &str| IrcMessage::parse(line).unwrap();
+        engine.join_channel(sid_a, &server_id, "#general").unwrap();
+
+        // A server bob hasn't joined needs an invite code; an unknown one doesn't exist
+        let err = engine
+            .join_channel(sid_b, &server_id, "#general")
+            .unwrap_err();
+        assert!(err.starts_with("NOT_MEMBER"), "{err}");
+        let replies = handle_join(&engine, sid_b, "bob", &msg("JOIN #guild/general")).await;
+        assert_eq!(replies.len(), 2);
+        assert!(replies[0].contains(" 475 bob #guild/general "));
+        assert!(replies[1].contains("/join #guild/general <code>"));
+        let replies = handle_join(&engine, sid_b, "bob", &msg("JOIN #nowhere/general")).await;
+        assert!(replies[0].contains(" 403 bob #nowhere/general "));
+        let replies = handle_join(
+            &engine,
+            sid_b,
+            "bob",
+            &msg("JOIN #guild/general not-a-code"),
+        )
+        .await;
+        assert!(replies[0].contains(" 475 bob #guild/general "));
+
+        // INVITE sends bob a single-use code, which JOIN takes as the key
+        drain_events(&mut rx_b);
+        let replies =
+            handle_invite(&engine, sid_a, "alice", &msg("INVITE bob #guild/general")).await;
+        assert_eq!(replies[0], ":concord 341 alice bob #guild/general");
+        let code = std::iter::from_fn(|| rx_b.try_recv().ok())
+            .find_map(|event| match event {
+                ChatEvent::ChannelInvite { code, .. } => code,
+                _ => None,
+            })
+            .expect("bob should be sent an invite code");
+        let replies = handle_join(
+            &engine,
+            sid_b,
+            "bob",
+            &msg(&format!("JOIN #guild/general {code}")),
+        )
+        .await;
+        assert!(replies.is_empty(), "{replies:?}");
+        assert!(engine.user_is_server_member(&server_id, &bob));
+        assert!(engine.in_channel(sid_b, &server_id, "#general"));
+
+        // A private channel hidden from @everyone is invite-only
+        engine.join_channel(sid_a, &server_id, "#secret").unwrap();
+        // The new channel is stored in a tokio::spawn
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        engine
+            .set_channel_private(sid_a, &server_id, "#secret", true)
+            .await
+            .unwrap();
+        let everyone = queries::roles::get_default_role(&pool, &server_id)
+            .await
+            .unwrap()
+            .unwrap();
+        let secret_id = engine.resolve_channel_id(&server_id, "#secret").unwrap();
+        queries::channels::set_channel_override(
+            &pool,
+            "ovr-secret",
+            &secret_id,
+            "role",
+            &everyone.id,
+            0,
+            Permissions::VIEW_CHANNELS.bits() as i64,
+        )
+        .await
+        .unwrap();
+        let err = engine
+            .join_channel(sid_b, &server_id, "#secret")
+            .unwrap_err();
+        assert!(err.starts_with("FORBIDDEN"), "{err}");
+        let replies = handle_join(&engine, sid_b, "bob", &msg("JOIN #guild/secret")).await;
+        assert!(replies[0].contains(" 473 bob #guild/secret "));
+        assert!(replies[1].contains("/knock #guild/secret"));
+
+        // Guests can't enter private channels at all
+        let (sid_g, _rx_g) = connect_user(&engine, None, "guest");
+        let err = engine
+            .join_channel(sid_g, &server_id, "#secret")
+            .unwrap_err();
+        assert_eq!(err, "AUTH_REQUIRED");
+        let replies = handle_join(&engine, sid_g, "guest", &msg("JOIN #guild/secret")).await;
+        assert!(replies[0].contains(" 473 guest #guild/secret "));
+        assert!(replies[1].contains("logged in"));
+
+        // KNOCK reaches the channel's moderators, once a minute
+        drain_events(&mut rx_a);
+        let replies = handle_knock(
+            &engine,
+            sid_b,
+            "bob",
+            &msg("KNOCK #guild/secret :let me in"),
+        )
+        .await;
+        assert_eq!(
+            replies,
+            vec![":concord 711 bob #guild/secret :Your KNOCK has been delivered".to_string()]
+        );
+        let knocked = std::iter::from_fn(|| rx_a.try_recv().ok()).any(|event| {
+            matches!(event, ChatEvent::ChannelKnock { nickname, message, .. }
+                if nickname == "bob" && message.as_deref() == Some("let me in"))
+        });
+        assert!(knocked, "alice should hear bob's knock");
+        let replies = handle_knock(&engine, sid_b, "bob", &msg("KNOCK #guild/secret")).await;
+        assert!(replies[0].contains(" 712 bob #guild/secret "));
+        let replies = handle_knock(&engine, sid_b, "bob", &msg("KNOCK #guild/general")).await;
+        assert!(replies[0].contains(" 714 bob #guild/general "));
+
+        // Any member may invite others into a public channel
+        let replies =
+            handle_invite(&engine, sid_b, "bob", &msg("INVITE dave #guild/general")).await;
+        assert_eq!(replies[0], ":concord 341 bob dave #guild/general");
+        assert!(replies[1].contains("single-use invite code"));
+        assert!(!engine.user_is_server_member(&server_id, &dave));
+
+        // Inviting bob into the private channel lets him join it
+        let replies =
+            handle_invite(&engine, sid_a, "alice", &msg("INVITE bob #guild/secret")).await;
+        assert_eq!(
+            replies,
+            vec![":concord 341 alice bob #guild/secret".to_string()]
+        );
+        let replies = handle_join(&engine, sid_b, "bob", &msg("JOIN #guild/secret")).await;
+        assert!(replies.is_empty(), "{replies:?}");
+        let replies =
+            handle_invite(&engine, sid_b, "bob", &msg("INVITE alice #guild/secret")).await;
+        assert!(replies[0].contains(" 443 bob alice #guild/secret "));
+        // ...but opening a private channel to someone takes Kick Members
+        let replies = handle_invite(&engine, sid_b, "bob", &msg("INVITE dave #guild/secret")).await;
+        assert!(replies[0].contains(" 482 bob #guild/secret "));
+        let replies = handle_knock(&engine, sid_b, "bob", &msg("KNOCK #guild/secret")).await;
+        assert!(replies[0].contains(" 714 bob #guild/secret "));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_irc_operator_commands() {
+        use crate::irc::commands::handle_command;
+        use crate::irc::connection::CapState;
+        use crate::irc::oper::{handle_delserver, handle_oper};
+        use crate::irc::parser::IrcMessage;
+
+        let (engine, pool) = setup_engine().await;
+        let root = create_test_user(&pool, "root").await;
+        let bob = create_test_user(&pool, "bob").await;
+        queries::servers::set_system_admin(&pool, &root, true)
+            .await
+            .unwrap();
+        let token = crate::auth::token::generate_irc_token();
+        let hash = crate::auth::token::hash_irc_token(&token).unwrap();
+        queries::users::create_irc_token(&pool, "tok-root", &root, &hash, None)
+            .await
+            .unwrap();
+        let server_id = engine
+            .create_server("guild".into(), root.clone(), None)
+            .await
+            .unwrap();
+        let (sid_r, mut rx_r) = connect_user(&engine, Some(&root), "root");
+        let (sid_b, mut rx_b) = connect_user(&engine, Some(&bob), "bob");
+        engine
+            .join_channel(sid_r, DEFAULT_SERVER_ID, "#lobby")
+            .unwrap();
+        engine
+            .join_channel(sid_b, DEFAULT_SERVER_ID, "#lobby")
+            .unwrap();
+        let msg = |line: &str| IrcMessage::parse(line).unwrap();
+        let caps = CapState::default();
+        let command = |sid, nick: &str, line: &str| {
+            handle_command(&engine, sid, nick, &caps, &IrcMessage::parse(line).unwrap())
+        };
+
+        // Operator commands need OPER, which needs a system admin's own token
+        let replies = command(sid_b, "bob", "KILL root :bye");
+        assert!(replies[0].contains(" 481 bob "));
+        let replies = handle_oper(&engine, sid_b, "bob", &msg(&format!("OPER root {token}"))).await;
+        assert!(replies[0].contains(" 464 bob "));
+        let replies = handle_oper(&engine, sid_r, "root", &msg("OPER root wrong")).await;
+        assert!(replies[0].contains(" 464 root "));
+        let replies =
+            handle_oper(&engine, sid_r, "root", &msg(&format!("OPER root {token}"))).await;
+        assert_eq!(
+            replies,
+            vec![
+                ":concord 381 root :You are now an IRC operator".to_string(),
+                ":root!root@concord MODE root +o".to_string(),
+            ]
+        );
+        assert!(engine.is_oper(sid_r));
+        let replies = command(sid_b, "bob", "WHOIS root");
+        assert!(replies.iter().any(|r| r.contains(" 313 bob root ")));
+
+        // WALLOPS reaches operators; GLOBOPS reaches everyone
+        drain_events(&mut rx_r);
+        drain_events(&mut rx_b);
+        assert!(command(sid_r, "root", "WALLOPS :ops only").is_empty());
+        assert!(command(sid_r, "root", "GLOBOPS :maintenance at noon").is_empty());
+        assert!(
+            matches!(rx_r.try_recv(), Ok(ChatEvent::Wallops { message, .. }) if message == "ops only")
+        );
+        assert!(
+            matches!(rx_b.try_recv(), Ok(ChatEvent::ServerNotice { message })
+            if message.contains("maintenance at noon"))
+        );
+        assert!(rx_b.try_recv().is_err(), "bob isn't an operator");
+
+        // STATS counts sessions and recent messages
+        engine.join_channel(sid_r, &server_id, "#general").unwrap();
+        engine
+            .send_message(sid_r, &server_id, "#general", "hello", None, None)
+            .unwrap();
+        // Let the spawned DB insert finish before DELSERVER's cascade below
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let replies = command(sid_r, "root", "STATS z");
+        assert_eq!(replies.len(), 4);
+        assert!(replies[0].ends_with(" 249 root z :Sessions: 0 IRC, 2 web, 0 detached"));
+        assert!(replies[2].ends_with(" 249 root z :Messages: 1 in the last minute"));
+        assert!(replies[3].contains(" 219 root z "));
+        assert!(command(sid_r, "root", "STATS u")[0].contains(" 242 root :Server Up 0 days "));
+
+        // SAJOIN and SAPART move bob past the server's membership rules
+        assert!(command(sid_r, "root", "SAJOIN bob #guild/general").is_empty());
+        assert!(engine.in_channel(sid_b, &server_id, "#general"));
+        assert!(command(sid_r, "root", "SAPART bob #guild/general").is_empty());
+        assert!(!engine.in_channel(sid_b, &server_id, "#general"));
+
+        // DELSERVER deletes a server by name
+        let replies = handle_delserver(&engine, sid_r, "root", &msg("DELSERVER guild")).await;
+        assert!(replies[0].contains("deleted"));
+        assert!(engine.find_server_by_name("guild").is_none());
+        let replies = handle_delserver(&engine, sid_r, "root", &msg("DELSERVER guild")).await;
+        assert!(replies[0].contains(" 402 root guild "));
+
+        // KILL disconnects bob, and his channels see why
+        drain_events(&mut rx_r);
+        drain_events(&mut rx_b);
+        assert!(command(sid_r, "root", "KILL bob :spamming").is_empty());
+        assert!(
+            matches!(rx_b.try_recv(), Ok(ChatEvent::Killed { killed_by, reason })
+            if killed_by == "root" && reason == "spamming")
+        );
+        assert!(engine.get_session(sid_b).is_none());
+        assert!(
+            matches!(rx_r.try_recv(), Ok(ChatEvent::Quit { nickname, reason })
+            if nickname == "bob" && reason.as_deref() == Some("Killed (root (spamming))"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_irc_cert_add_binds_presented_certificate() {
+        use crate::irc::certs::handle_cert;
+        use crate::irc::parser::IrcMessage;
+
+        let (engine, pool) = setup_engine().await;
+        let alice = create_test_user(&pool, "alice").await;
+        let bob = create_test_user(&pool, "bob").await;
+        let (sid_a, _rx_a) = connect_user(&engine, Some(&alice), "alice");
+        let (sid_b, _rx_b) = connect_user(&engine, Some(&bob), "bob");
+        let (sid_g, _rx_g) = connect_user(&engine, None, "guest");
+        let msg = |line: &str| IrcMessage::parse(line).unwrap();
+        let fp = "ab".repeat(32);
+
+        // Only a logged-in client that presented a certificate can bind one
+        let replies = handle_cert(&engine, sid_a, "alice", None, &msg("CERT ADD")).await;
+        assert!(replies[0].contains("client certificate"));
+        let replies = handle_cert(&engine, sid_g, "guest", Some(&fp), &msg("CERT ADD")).await;
+        assert!(replies[0].contains("logged in"));
+        let holder = queries::users::get_user_by_cert_fingerprint(&pool, &fp).await;
+        assert!(holder.unwrap().is_none());
+
+        let replies =
+            handle_cert(&engine, sid_a, "alice", Some(&fp), &msg("CERT ADD :my laptop")).await;
+        assert!(replies[0].contains(&format!("Certificate {fp} added")));
+        let certs = queries::users::list_irc_certs(&pool, &alice).await.unwrap();
+        assert_eq!(certs.len(), 1);
+        assert_eq!(certs[0].1, fp);
+        assert_eq!(certs[0].2.as_deref(), Some("my laptop"));
+
+        let replies = handle_cert(&engine, sid_a, "alice", Some(&fp), &msg("CERT ADD")).await;
+        assert!(replies[0].contains("already on your account"));
+
+        // Another account is refused without being told who holds it
+        let replies = handle_cert(&engine, sid_b, "bob", Some(&fp), &msg("CERT ADD")).await;
+        assert!(replies[0].ends_with(":Could not add the certificate"));
+        assert!(queries::users::list_irc_certs(&pool, &bob).await.unwrap().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_irc_monitor_whox_ison_userhost() {
+        use crate::irc::commands::handle_command;
+        use crate::irc::connection::CapState;
+        use crate::irc::parser::IrcMessage;
+
+        fn monitor_events(rx: &mut tokio::sync::mpsc::Receiver<ChatEvent>) -> Vec<(String, bool)> {
+            let mut events = Vec::new();
+            while let Ok(event) = rx.try_recv() {
+                if let ChatEvent::MonitorStatus { nickname, online } = event {
+                    events.push((nickname, online));
+                }
+            }
+            events
+        }
+
+        let (engine, pool) = setup_engine().await;
+        let bob = create_test_user(&pool, "bob").await;
+        let alice = Uuid::new_v4().to_string();
+        queries::users::create_with_oauth(
+            &pool,
+            &queries::users::CreateOAuthUser {
+                user_id: &alice,
+                username: "alice",
+                email: None,
+                avatar_url: None,
+                oauth_id: "oauth-alice",
+                provider: "atproto",
+                provider_id: "did:plc:alice",
+            },
+        )
+        .await
+        .unwrap();
+        let (sid_b, mut rx_b) = connect_user(&engine, Some(&bob), "bob");
+        let caps = CapState::default();
+        let command = |line: &str| {
+            handle_command(
+                &engine,
+                sid_b,
+                "bob",
+                &caps,
+                &IrcMessage::parse(line).unwrap(),
+            )
+        };
+
+        // MONITOR follows alice's first client connecting, not her second
+        assert_eq!(command("MONITOR + alice"), vec![":concord 731 bob alice"]);
+        let (sid_a, _rx_a) = connect_user(&engine, Some(&alice), "alice");
+        let (sid_a2, _rx_a2) = connect_user(&engine, Some(&alice), "alice");
+        assert_eq!(monitor_events(&mut rx_b), vec![("alice".to_string(), true)]);
+        // Let connect's spawned presence write land before changing presence
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        // Going invisible looks like going offline, to MONITOR and ISON alike
+        engine
+            .set_presence(sid_a, "invisible", None, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            monitor_events(&mut rx_b),
+            vec![("alice".to_string(), false)]
+        );
+        assert_eq!(command("ISON alice bob"), vec![":concord 303 bob bob"]);
+        engine
+            .set_presence(sid_a, "idle", Some("lunch"), None)
+            .await
+            .unwrap();
+        assert_eq!(monitor_events(&mut rx_b), vec![("alice".to_string(), true)]);
+        assert_eq!(
+            command("ISON alice bob"),
+            vec![":concord 303 bob :alice bob"]
+        );
+        assert_eq!(
+            command("MONITOR S"),
+            vec![":concord 730 bob alice!alice@concord"]
+        );
+
+        // USERHOST marks alice away
+        assert_eq!(
+            command("USERHOST alice bob nobody"),
+            vec![":concord 302 bob :alice=-alice@concord bob=+bob@concord"]
+        );
+
+        // WHOX carries the account (a DID for Bluesky users) and away flag
+        engine
+            .join_channel(sid_b, DEFAULT_SERVER_ID, "#lobby")
+            .unwrap();
+        engine
+            .join_channel(sid_a, DEFAULT_SERVER_ID, "#lobby")
+            .unwrap();
+        let replies = command("WHO #lobby %tcnfa,42");
+        assert_eq!(replies.len(), 3);
+        assert!(replies.contains(&":concord 354 bob 42 #lobby alice G did:plc:alice".to_string()));
+        assert!(replies.contains(&":concord 354 bob 42 #lobby bob H bob".to_string()));
+        assert_eq!(replies[2], ":concord 315 bob #lobby :End of /WHO list");
+        assert_eq!(
+            command("WHO alice"),
+            vec![
+                ":concord 352 bob * alice concord concord alice G :0 alice".to_string(),
+                ":concord 315 bob alice :End of /WHO list".to_string(),
+            ]
+        );
+        let replies = command("WHOIS alice");
+        assert!(
+            replies.contains(&":concord 330 bob alice did:plc:alice :is logged in as".to_string())
+        );
+
+        // MONITOR reports alice offline once her last client goes
+        drain_events(&mut rx_b);
+        engine.disconnect(sid_a2);
+        assert!(monitor_events(&mut rx_b).is_empty());
+        engine.disconnect(sid_a);
+        assert_eq!(
+            monitor_events(&mut rx_b),
+            vec![("alice".to_string(), false)]
+        );
+        assert_eq!(command("MONITOR S"), vec![":concord 731 bob alice"]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_irc_thread_names() {
+        use crate::irc::access::handle_join;
+        use crate::irc::commands::{handle_command, parse_irc_channel, to_irc_channel};
+        use crate::irc::connection::CapState;
+        use crate::irc::list::handle_list;
+        use crate::irc::parser::IrcMessage;
+
+        let TestServer {
+            engine,
+            pool,
+            server_id,
+            users: [alice],
+            sessions: [sid_a],
+            rxs: [mut rx_a],
+        } = setup_server_with_members("guild", ["alice"]).await;
+        let msg = |line: &str| IrcMessage::parse(line).unwrap();
+
+        let general_id = engine.resolve_channel_id(&server_id, "#general").unwrap();
+        let parent_id = Uuid::new_v4().to_string();
+        queries::messages::insert_message(
+            &pool,
+            &queries::messages::InsertMessageParams {
+                id: &parent_id,
+                server_id: &server_id,
+                channel_id: &general_id,
+                sender_id: &alice,
+                sender_nick: "alice",
+                content: "Login is broken",
+                reply_to_id: None,
+                kind: "normal",
+            },
+        )
+        .await
+        .unwrap();
+        drain_events(&mut rx_a);
+        engine
+            .create_thread(
+                sid_a,
+                &server_id,
+                "#general",
+                "Why does login fail?",
+                &parent_id,
+                false,
+            )
+            .await
+            .unwrap();
+        assert!(
+            std::iter::from_fn(|| rx_a.try_recv().ok())
+                .any(|event| matches!(event, ChatEvent::ThreadCreate { .. }))
+        );
+
+        // Threads are named after their parent channel, both ways
+        let thread = "#why does login fail?";
+        assert_eq!(
+            to_irc_channel(&engine, &server_id, thread),
+            "#guild/general/why-does-login-fail"
+        );
+        assert_eq!(
+            parse_irc_channel(&engine, "#guild/general/Why-Does-Login-Fail"),
+            (server_id.clone(), thread.to_string())
+        );
+        assert_eq!(
+            parse_irc_channel(&engine, "#guild/general"),
+            (server_id.clone(), "#general".to_string())
+        );
+
+        // Joining by thread name works even though the thread name has spaces,
+        // and a thread that doesn't exist isn't created
+        let replies = handle_join(
+            &engine,
+            sid_a,
+            "alice",
+            &msg("JOIN #guild/general/why-does-login-fail"),
+        )
+        .await;
+        assert!(replies.is_empty(), "{replies:?}");
+        assert!(engine.in_channel(sid_a, &server_id, thread));
+        let replies = handle_join(&engine, sid_a, "alice", &msg("JOIN #guild/general/nope")).await;
+        assert!(replies[0].contains(" 403 alice #guild/general/nope "));
+
+        // Replies go into the thread, reaching alice's other client
+        let (_sid_a2, mut rx_a2) = connect_user(&engine, Some(&alice), "alice");
+        let caps = CapState::default();
+        handle_command(
+            &engine,
+            sid_a,
+            "alice",
+            &caps,
+            &msg("PRIVMSG #guild/general/why-does-login-fail :me too"),
+        );
+        let target = std::iter::from_fn(|| rx_a2.try_recv().ok())
+            .find_map(|event| match event {
+                ChatEvent::Message {
+                    target, content, ..
+                } if content == "me too" => Some(target),
+                _ => None,
+            })
+            .expect("the reply should be delivered to the thread");
+        assert_eq!(target, thread);
+
+        // LIST shows threads under their IRC names, with their archived state
+        let list = async || {
+            handle_list(&engine, sid_a, "alice", &msg("LIST #guild/*"))
+                .await
+                .into_iter()
+                .find(|line| line.contains("/general/"))
+                .unwrap()
+        };
+        assert_eq!(
+            list().await,
+            ":concord 322 alice #guild/general/why-does-login-fail 2 [thread]"
+        );
+        let thread_id = engine.resolve_channel_id(&server_id, thread).unwrap();
+        engine
+            .archive_thread(sid_a, &server_id, &thread_id)
+            .await
+            .unwrap();
+        assert_eq!(
+            list().await,
+            ":concord 322 alice #guild/general/why-does-login-fail 2 :[archived thread]"
+        );
+
+        // A restart finds each thread's parent channel again
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let reloaded = ChatEngine::new(Some(pool.clone()));
+        reloaded.load_servers_from_db().await.unwrap();
+        reloaded.load_channels_from_db().await.unwrap();
+        assert_eq!(
+            to_irc_channel(&reloaded, &server_id, thread),
+            "#guild/general/why-does-login-fail"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_irc_thread_slugs_are_unique_per_channel() {
+        use crate::irc::commands::{parse_irc_channel, to_irc_channel};
+
+        let TestServer {
+            engine,
+            pool,
+            server_id,
+            users: [alice],
+            sessions: [sid_a],
+            rxs: [_rx_a],
+        } = setup_server_with_members("guild", ["alice"]).await;
+        let general_id = engine.resolve_channel_id(&server_id, "#general").unwrap();
+
+        // Names that differ only in case and punctuation slug the same
+        let names = ["Bug fix", "bug-fix", "BUG FIX!"];
+        for name in names {
+            let parent_id = Uuid::new_v4().to_string();
+            queries::messages::insert_message(
+                &pool,
+                &queries::messages::InsertMessageParams {
+                    id: &parent_id,
+                    server_id: &server_id,
+                    channel_id: &general_id,
+                    sender_id: &alice,
+                    sender_nick: "alice",
+                    content: name,
+                    reply_to_id: None,
+                    kind: "normal",
+                },
+            )
+            .await
+            .unwrap();
+            engine
+                .create_thread(sid_a, &server_id, "#general", name, &parent_id, false)
+                .await
+                .unwrap();
+        }
+
+        // One keeps the plain slug and the rest add the start of their ID;
+        // each IRC name leads back to its own thread
+        let threads = ["#bug fix", "#bug-fix", "#bug fix!"];
+        let irc_names: Vec<String> = threads
+            .iter()
+            .map(|thread| to_irc_channel(&engine, &server_id, thread))
+            .collect();
+        let plain = "#guild/general/bug-fix";
+        assert_eq!(irc_names.iter().filter(|n| *n == plain).count(), 1);
+        for (thread, irc_name) in threads.iter().zip(&irc_names) {
+            let id = engine.resolve_channel_id(&server_id, thread).unwrap();
+            assert!(*irc_name == plain || *irc_name == format!("{plain}-{}", &id[..8]));
+            assert_eq!(
+                parse_irc_channel(&engine, irc_name),
+                (server_id.clone(), thread.to_string())
+            );
+        }
+
+        // Every thread keeps its IRC name across a restart
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let reloaded = ChatEngine::new(Some(pool.clone()));
+        reloaded.load_servers_from_db().await.unwrap();
+        reloaded.load_channels_from_db().await.unwrap();
+        for (thread, irc_name) in threads.iter().zip(&irc_names) {
+            assert_eq!(&to_irc_channel(&reloaded, &server_id, thread), irc_name);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_irc_list_across_servers_with_elist() {
+        use crate::irc::list::handle_list;
+        use crate::irc::parser::IrcMessage;
+
+        let (engine, pool) = setup_engine().await;
+        let alice = create_test_user(&pool, "alice").await;
+        let bob = create_test_user(&pool, "bob").await;
+        let guild = engine
+            .create_server("guild".into(), alice.clone(), None)
+            .await
+            .unwrap();
+        engine
+            .create_server("other".into(), alice.clone(), None)
+            .await
+            .unwrap();
+        engine.join_server(&bob, &guild).await.unwrap();
+        let (sid_a, _rx_a) = connect_user(&engine, Some(&alice), "alice");
+        let (sid_b, _rx_b) = connect_user(&engine, Some(&bob), "bob");
+        let list = async |sid, nick: &str, line: &str| {
+            handle_list(&engine, sid, nick, &IrcMessage::parse(line).unwrap())
+                .await
+                .into_iter()
+                .filter(|reply| reply.contains(" 322 "))
+                .filter_map(|reply| reply.split(' ').nth(3).map(String::from))
+                .collect::<Vec<String>>()
+        };
+
+        // #secret is hidden from @everyone, so bob can't see it
+        engine.join_channel(sid_a, &guild, "#secret").unwrap();
+        engine
+            .set_channel_private(sid_a, &guild, "#secret", true)
+            .await
+            .unwrap();
+        let everyone = queries::roles::get_default_role(&pool, &guild)
+            .await
+            .unwrap()
+            .unwrap();
+        let secret_id = engine.resolve_channel_id(&guild, "#secret").unwrap();
+        queries::channels::set_channel_override(
+            &pool,
+            "ovr-secret",
+            &secret_id,
+            "role",
+            &everyone.id,
+            0,
+            Permissions::VIEW_CHANNELS.bits() as i64,
+        )
+        .await
+        .unwrap();
+        engine.join_channel(sid_b, &guild, "#dev").unwrap();
+        engine.join_channel(sid_a, &guild, "#dev").unwrap();
+
+        // LIST covers every server the user is on, each channel prefixed
+        assert_eq!(
+            list(sid_a, "alice", "LIST").await,
+            vec![
+                "#guild/dev",
+                "#guild/general",
+                "#guild/secret",
+                "#other/general"
+            ]
+        );
+        assert_eq!(
+            list(sid_b, "bob", "LIST").await,
+            vec!["#guild/dev", "#guild/general"]
+        );
+
+        // ELIST masks, negation and user counts
+        assert_eq!(
+            list(sid_a, "alice", "LIST #other/*").await,
+            vec!["#other/general"]
+        );
+        assert_eq!(
+            list(sid_a, "alice", "LIST !#guild/*").await,
+            vec!["#other/general"]
+        );
+        assert_eq!(
+            list(sid_a, "alice", "LIST #*/gen*,>0").await,
+            Vec::<String>::new()
+        );
+        assert_eq!(list(sid_a, "alice", "LIST >1").await, vec!["#guild/dev"]);
+        assert_eq!(
+            list(sid_a, "alice", "LIST C<60,<2,#guild/*").await,
+            vec!["#guild/general", "#guild/secret"]
+        );
+        assert_eq!(
+            list(sid_a, "alice", "LIST C>60").await,
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            list(sid_a, "alice", "LIST T<60").await,
+            Vec::<String>::new()
+        );
+    }
 }
@@ -0,0 +1,222 @@
+use tracing::warn;
+
+use crate::engine::chat_engine::{ChatEngine, DEFAULT_SERVER_ID, KnockOutcome};
+use crate::engine::events::SessionId;
+
+use super::commands::{parse_irc_channel, to_irc_channel};
+use super::formatter;
+use super::parser::IrcMessage;
+
+/// Handle `JOIN <channels> [keys]` and return the reply lines.
+///
+/// Channels on a server the user hasn't joined take a server invite code as
+/// their key. A refused join says why and how to get in.
+pub async fn handle_join(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    msg: &IrcMessage,
+) -> Vec<String> {
+    let Some(channels_param) = msg.params.first() else {
+        return vec![formatter::err_needmoreparams(nick, "JOIN")];
+    };
+
+    let mut keys = msg.params.get(1).into_iter().flat_map(|k| k.split(','));
+    let mut replies = Vec::new();
+
+    for channel in channels_param.split(',') {
+        let channel = channel.trim();
+        let key = keys.next().filter(|k| !k.is_empty());
+        if channel.is_empty() {
+            continue;
+        }
+
+        let Some((server_id, channel_name)) = parse_channel(engine, channel) else {
+            replies.push(formatter::err_nosuchchannel(nick, channel));
+            continue;
+        };
+
+        if let Some(limit) = engine.irc_channel_limit()
+            && engine.channel_count(session_id) >= limit
+            && !engine.in_channel(session_id, &server_id, &channel_name)
+        {
+            replies.push(formatter::err_toomanychannels(nick, channel));
+            continue;
+        }
+
+        if let Some(code) = key
+            && needs_invite(engine, session_id, &server_id)
+            && let Err(e) = engine.use_invite(session_id, code).await
+        {
+            warn!(error = %e, %channel, "JOIN invite code refused");
+            replies.push(formatter::err_badchannelkey(nick, channel));
+            replies.push(formatter::server_notice(nick, &format!("{channel}: {e}")));
+            continue;
+        }
+
+        if let Err(e) = engine.join_channel(session_id, &server_id, &channel_name) {
+            warn!(error = %e, %channel, "JOIN failed");
+            replies.extend(join_error(engine, nick, channel, &server_id, &e));
+        }
+    }
+
+    replies
+}
+
+/// Handle `INVITE <nick> <channel>`. Someone outside the channel's server is
+/// sent an invite code for it; a private channel is opened to them.
+pub async fn handle_invite(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    msg: &IrcMessage,
+) -> Vec<String> {
+    if msg.params.len() < 2 {
+        return vec![formatter::err_needmoreparams(nick, "INVITE")];
+    }
+    let target = &msg.params[0];
+    let channel = &msg.params[1];
+
+    let Some((server_id, channel_name)) = parse_channel(engine, channel) else {
+        return vec![formatter::err_nosuchchannel(nick, channel)];
+    };
+    let irc_channel = to_irc_channel(engine, &server_id, &channel_name);
+    if !engine.in_channel(session_id, &server_id, &channel_name) {
+        return vec![formatter::err_notonchannel(nick, &irc_channel)];
+    }
+    let Some(target_id) = engine.find_user_id_by_nick(target).await else {
+        return vec![formatter::err_nosuchnick(nick, target)];
+    };
+    if engine
+        .get_session_by_nick(target)
+        .is_some_and(|s| engine.in_channel(s.id, &server_id, &channel_name))
+    {
+        return vec![formatter::err_useronchannel(nick, target, &irc_channel)];
+    }
+
+    match engine
+        .invite_to_channel(session_id, &server_id, &channel_name, &target_id)
+        .await
+    {
+        Ok(code) => {
+            let mut replies = vec![formatter::rpl_inviting(nick, target, &irc_channel)];
+            if code.is_some() {
+                replies.push(formatter::server_notice(
+                    nick,
+                    &format!("{target} isn't on this server yet, so they were sent a single-use invite code"),
+                ));
+            }
+            replies
+        }
+        Err(e) => {
+            warn!(error = %e, %target, %channel, "INVITE failed");
+            if e == "AUTH_REQUIRED" || e.starts_with("FORBIDDEN") {
+                vec![formatter::err_chanoprivsneeded(nick, &irc_channel)]
+            } else {
+                vec![formatter::server_notice(nick, &e)]
+            }
+        }
+    }
+}
+
+/// Handle `KNOCK <channel> [message]`, asking a channel's moderators to let
+/// the user in.
+pub async fn handle_knock(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    msg: &IrcMessage,
+) -> Vec<String> {
+    let Some(channel) = msg.params.first() else {
+        return vec![formatter::err_needmoreparams(nick, "KNOCK")];
+    };
+    let Some((server_id, channel_name)) = parse_channel(engine, channel) else {
+        return vec![formatter::err_nosuchchannel(nick, channel)];
+    };
+    let irc_channel = to_irc_channel(engine, &server_id, &channel_name);
+    let message = msg.params.get(1).map(String::as_str);
+
+    match engine
+        .knock(session_id, &server_id, &channel_name, message)
+        .await
+    {
+        Ok(KnockOutcome::Delivered) => vec![formatter::rpl_knockdlvr(nick, &irc_channel)],
+        Ok(KnockOutcome::TooSoon) => vec![formatter::err_toomanyknock(nick, &irc_channel)],
+        Ok(KnockOutcome::Open) => vec![formatter::err_chanopen(nick, &irc_channel)],
+        Ok(KnockOutcome::Joined) => vec![formatter::err_knockonchan(nick, &irc_channel)],
+        Err(e) if e == "AUTH_REQUIRED" => vec![formatter::server_notice(
+            nick,
+            "You need to be logged in to knock",
+        )],
+        Err(e) => {
+            warn!(error = %e, %channel, "KNOCK failed");
+            vec![formatter::err_nosuchchannel(nick, channel)]
+        }
+    }
+}
+
+/// Parse a channel named in JOIN, INVITE or KNOCK. Unlike `parse_irc_channel`,
+/// a name with a `/` that matches no server, channel or thread is refused
+/// rather than taken for a new default-server channel.
+pub(super) fn parse_channel(engine: &ChatEngine, irc_name: &str) -> Option<(String, String)> {
+    let (server_id, channel_name) = parse_irc_channel(engine, irc_name);
+    if channel_name.contains('/')
+        && engine
+            .resolve_channel_id(&server_id, &channel_name)
+            .is_err()
+    {
+        return None;
+    }
+    Some((server_id, channel_name))
+}
+
+/// Whether a registered user has yet to join the server a channel is on.
+fn needs_invite(engine: &ChatEngine, session_id: SessionId, server_id: &str) -> bool {
+    server_id != DEFAULT_SERVER_ID
+        && engine
+            .get_session(session_id)
+            .and_then(|s| s.user_id.clone())
+            .is_some_and(|uid| !engine.user_is_server_member(server_id, &uid))
+}
+
+/// Replies for a refused JOIN: 475 when the channel's server needs an invite
+/// code, 473 for a private channel, each followed by how to get in.
+fn join_error(
+    engine: &ChatEngine,
+    nick: &str,
+    channel: &str,
+    server_id: &str,
+    error: &str,
+) -> Vec<String> {
+    match error {
+        e if e.starts_with("NOT_MEMBER") => {
+            let server = engine.get_server_name(server_id).unwrap_or_default();
+            vec![
+                formatter::err_badchannelkey(nick, channel),
+                formatter::server_notice(
+                    nick,
+                    &format!(
+                        "{channel} is on the server \"{server}\", which you haven't joined. \
+                         Join with an invite code as the key (/join {channel} <code>), \
+                         ask a member to /invite you, or /knock {channel}"
+                    ),
+                ),
+            ]
+        }
+        e if e.starts_with("FORBIDDEN") => vec![
+            formatter::err_inviteonlychan(nick, channel),
+            formatter::server_notice(
+                nick,
+                &format!(
+                    "{channel} is private. Ask a channel operator to /invite you, \
+                     or /knock {channel} to ask for access"
+                ),
+            ),
+        ],
+        "AUTH_REQUIRED" => vec![
+            formatter::err_inviteonlychan(nick, channel),
+            formatter::server_notice(nick, &format!("You need to be logged in to join {channel}")),
+        ],
+        _ => vec![formatter::err_nosuchchannel(nick, channel)],
+    }
+}
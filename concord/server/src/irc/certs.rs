@@ -0,0 +1,73 @@
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::db::queries::users;
+use crate::engine::chat_engine::ChatEngine;
+use crate::engine::events::SessionId;
+
+use super::formatter;
+use super::parser::IrcMessage;
+
+/// Handle `CERT ADD [label]`, binding the TLS client certificate this
+/// connection presented to the logged-in account for SASL EXTERNAL. Only a
+/// certificate the client has just proven it holds can be bound.
+pub async fn handle_cert(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    cert_fp: Option<&str>,
+    msg: &IrcMessage,
+) -> Vec<String> {
+    let Some(subcommand) = msg.params.first() else {
+        return vec![formatter::err_needmoreparams(nick, "CERT")];
+    };
+    if !subcommand.eq_ignore_ascii_case("ADD") {
+        return vec![formatter::server_notice(nick, "Usage: CERT ADD [label]")];
+    }
+    let (Some(db), Some(session)) = (engine.db(), engine.get_session(session_id)) else {
+        return vec![formatter::server_notice(nick, "Certificates can't be added here")];
+    };
+    let Some(user_id) = session.user_id.as_deref() else {
+        return vec![formatter::server_notice(
+            nick,
+            "You need to be logged in to add a certificate",
+        )];
+    };
+    let Some(fingerprint) = cert_fp else {
+        return vec![formatter::server_notice(
+            nick,
+            "Connect over TLS with a client certificate to add it",
+        )];
+    };
+
+    match users::list_irc_certs(db, user_id).await {
+        Ok(certs) if certs.iter().any(|(_, fp, ..)| fp == fingerprint) => {
+            return vec![formatter::server_notice(
+                nick,
+                "This certificate is already on your account",
+            )];
+        }
+        Ok(_) => {}
+        Err(e) => {
+            warn!(error = %e, "Failed to list IRC certificates");
+            return vec![formatter::server_notice(nick, "Could not add the certificate")];
+        }
+    }
+
+    let label = msg.params.get(1).map(String::as_str);
+    let cert_id = Uuid::new_v4().to_string();
+    match users::create_irc_cert(db, &cert_id, user_id, fingerprint, label).await {
+        Ok(()) => vec![formatter::server_notice(
+            nick,
+            &format!("Certificate {fingerprint} added; you can now log in with SASL EXTERNAL"),
+        )],
+        // Don't tell the client whether another account holds it
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            vec![formatter::server_notice(nick, "Could not add the certificate")]
+        }
+        Err(e) => {
+            warn!(error = %e, "Failed to store IRC certificate");
+            vec![formatter::server_notice(nick, "Could not add the certificate")]
+        }
+    }
+}
@@ -1,49 +1,366 @@
+use std::collections::HashSet;
+
 use tracing::warn;
 
-use crate::engine::chat_engine::{ChatEngine, DEFAULT_SERVER_ID};
-use crate::engine::events::SessionId;
+use crate::engine::chat_engine::{ChatEngine, DEFAULT_SERVER_ID, MONITOR_LIMIT};
+use crate::engine::events::{MemberInfo, MessageKind, PresenceInfo, SessionId};
+use crate::engine::permissions::MEMBER_PREFIXES;
+use crate::engine::validation::{
+    MAX_CHANNEL_NAME_LENGTH, MAX_NICKNAME_LENGTH, MAX_SERVER_NAME_LENGTH, MAX_THREAD_NAME_LENGTH,
+    MAX_TOPIC_LENGTH,
+};
 
+use super::connection::CapState;
+use super::edits;
 use super::formatter;
+use super::formatting::{self, IrcText};
+use super::history::CHATHISTORY_MAX_LIMIT;
+use super::list::ELIST;
+use super::monitor;
+use super::oper;
 use super::parser::IrcMessage;
 
+/// How much of a thread's ID tells its slug apart from an older thread's.
+const THREAD_ID_SUFFIX_LEN: usize = 8;
+
 /// Parse an IRC channel name into (server_id, engine_channel_name).
 ///
 /// Format:
-///   `#general`            -> (DEFAULT_SERVER_ID, "#general")   — default server
-///   `#my-guild/general`   -> (server_id,         "#general")   — named server
+///   `#general`                   -> (DEFAULT_SERVER_ID, "#general")   — default server
+///   `#my-guild/general`          -> (server_id,         "#general")   — named server
+///   `#my-guild/general/my-topic` -> (server_id,         thread name)  — thread
+///   `#general/my-topic`          -> (DEFAULT_SERVER_ID, thread name)  — default-server thread
 ///
 /// If the server name doesn't match any known server, falls back to treating
-/// the whole thing as a default-server channel name.
+/// the whole thing as a default-server channel or thread name. A thread part
+/// that names no thread is kept in the channel name, which is never a real
+/// channel since those can't be created with a `/` (see `access::parse_channel`).
 pub fn parse_irc_channel(engine: &ChatEngine, irc_name: &str) -> (String, String) {
     let bare = irc_name.strip_prefix('#').unwrap_or(irc_name);
 
-    if let Some(slash_pos) = bare.find('/') {
-        let server_name = &bare[..slash_pos];
-        let channel_name = &bare[slash_pos + 1..];
-        if let Some(server_id) = engine.find_server_by_name(server_name) {
-            return (server_id, format!("#{channel_name}"));
-        }
+    if let Some((server_name, path)) = bare.split_once('/')
+        && let Some(server_id) = engine.find_server_by_name(server_name)
+    {
+        let channel_name = resolve_channel_path(engine, &server_id, path);
+        return (server_id, channel_name);
     }
 
     // Default: treat as default server channel
-    (DEFAULT_SERVER_ID.to_string(), format!("#{bare}"))
+    let channel_name = resolve_channel_path(engine, DEFAULT_SERVER_ID, bare);
+    (DEFAULT_SERVER_ID.to_string(), channel_name)
+}
+
+/// The engine channel name for `channel` or `channel/thread-slug` on a server.
+fn resolve_channel_path(engine: &ChatEngine, server_id: &str, path: &str) -> String {
+    if let Some((parent, slug)) = path.split_once('/')
+        && let Some((thread, _)) = thread_slugs(engine, server_id, &format!("#{parent}"))
+            .into_iter()
+            .find(|(_, thread_slug)| thread_slug.eq_ignore_ascii_case(slug))
+    {
+        return thread;
+    }
+    format!("#{path}")
 }
 
 /// Convert an engine (server_id, channel_name) back to an IRC channel name.
 ///
 /// Default server channels keep their plain name (`#general`).
 /// Non-default server channels become `#server-name/channel-name`.
+/// Threads follow their parent channel's name with `/thread-slug`.
 pub fn to_irc_channel(engine: &ChatEngine, server_id: &str, channel_name: &str) -> String {
+    let bare_channel = match engine.thread_parent_name(server_id, channel_name) {
+        Some(parent) => format!(
+            "{}/{}",
+            parent.strip_prefix('#').unwrap_or(&parent),
+            thread_path_slug(engine, server_id, &parent, channel_name)
+        ),
+        None => channel_name
+            .strip_prefix('#')
+            .unwrap_or(channel_name)
+            .to_string(),
+    };
+
     if server_id == DEFAULT_SERVER_ID {
-        return channel_name.to_string();
+        return format!("#{bare_channel}");
     }
 
     if let Some(server_name) = engine.get_server_name(server_id) {
-        let bare_channel = channel_name.strip_prefix('#').unwrap_or(channel_name);
         format!("#{server_name}/{bare_channel}")
     } else {
-        channel_name.to_string()
+        format!("#{bare_channel}")
+    }
+}
+
+/// A thread name as it appears in IRC channel names: lowercase, with each run
+/// of anything but letters, digits, `-` and `_` turned into one `-`.
+pub fn thread_slug(thread_name: &str) -> String {
+    let mut slug = String::with_capacity(thread_name.len());
+    for c in thread_name.trim_start_matches('#').chars() {
+        if c.is_alphanumeric() || c == '-' || c == '_' {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        "thread".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+/// The slug of each thread started in a channel, as (thread name, slug),
+/// oldest first. Names can differ yet slug the same ("Bug fix", "bug-fix"), so
+/// after the oldest such thread the others add the start of their ID.
+fn thread_slugs(
+    engine: &ChatEngine,
+    server_id: &str,
+    parent_channel_name: &str,
+) -> Vec<(String, String)> {
+    let mut taken = HashSet::new();
+    engine
+        .threads_of(server_id, parent_channel_name)
+        .into_iter()
+        .map(|(id, name)| {
+            let base = thread_slug(&name);
+            let mut slug = base.clone();
+            if taken.contains(&slug) {
+                let short = id.get(..THREAD_ID_SUFFIX_LEN).unwrap_or(&id);
+                slug = format!("{base}-{short}");
+            }
+            if taken.contains(&slug) {
+                slug = format!("{base}-{id}");
+            }
+            taken.insert(slug.clone());
+            (name, slug)
+        })
+        .collect()
+}
+
+/// The slug a thread goes by after its parent channel's name in IRC channel
+/// names, unique among the parent's threads.
+pub fn thread_path_slug(
+    engine: &ChatEngine,
+    server_id: &str,
+    parent_channel_name: &str,
+    thread_name: &str,
+) -> String {
+    thread_slugs(engine, server_id, parent_channel_name)
+        .into_iter()
+        .find(|(name, _)| name == thread_name)
+        .map(|(_, slug)| slug)
+        .unwrap_or_else(|| thread_slug(thread_name))
+}
+
+/// Channel mode letters behind each status prefix in `MEMBER_PREFIXES`, in the
+/// same order. Owners are `Y` rather than the usual `q`, which Concord already
+/// uses for the quiet (timeout) list.
+pub const PREFIX_MODES: &str = "Yaohv";
+
+/// Most tokens a single RPL_ISUPPORT line may carry.
+const ISUPPORT_TOKENS_PER_LINE: usize = 13;
+
+/// Most nicks one USERHOST answers for, per RFC 2812.
+const USERHOST_MAX_NICKS: usize = 5;
+
+/// WHOX field letters, in the order a 354 reply carries them.
+const WHOX_FIELDS: &str = "tcuihsnfdlaor";
+
+/// The ISUPPORT `PREFIX=` token advertising status modes and their prefixes.
+pub fn isupport_prefix() -> String {
+    format!("PREFIX=({PREFIX_MODES}){MEMBER_PREFIXES}")
+}
+
+/// Everything advertised in RPL_ISUPPORT, derived from the engine's validation
+/// limits and the `[irc]` config.
+pub fn isupport_tokens(engine: &ChatEngine) -> Vec<String> {
+    // `#server/channel/thread` names: '#', the server name, '/', the channel name
+    // without its '#', then '/' and the thread's slug
+    let channel_len =
+        MAX_SERVER_NAME_LENGTH + MAX_CHANNEL_NAME_LENGTH + 1 + MAX_THREAD_NAME_LENGTH + 1;
+    let mut tokens: Vec<String> = vec![
+        // Nicks are matched case-insensitively, folding A-Z only
+        "CASEMAPPING=ascii".into(),
+    ];
+    if let Some(limit) = engine.irc_channel_limit() {
+        tokens.push(format!("CHANLIMIT=#:{limit}"));
     }
+    tokens.extend([
+        // b/q lists, then m, p and s flags (see irc::moderation)
+        "CHANMODES=bq,,,mps".into(),
+        format!("CHANNELLEN={channel_len}"),
+        "CHANTYPES=#&".into(),
+        format!("CHATHISTORY={CHATHISTORY_MAX_LIMIT}"),
+        format!("ELIST={ELIST}"),
+        "KNOCK".into(),
+        format!("MONITOR={MONITOR_LIMIT}"),
+        "MSGREFTYPES=msgid,timestamp".into(),
+        format!("NETWORK={}", formatter::network_name()),
+        format!("NICKLEN={MAX_NICKNAME_LENGTH}"),
+        isupport_prefix(),
+        format!("TOPICLEN={MAX_TOPIC_LENGTH}"),
+        "WHOX".into(),
+    ]);
+    tokens
+}
+
+/// RPL_ISUPPORT lines for the welcome burst.
+pub fn isupport_lines(engine: &ChatEngine, nick: &str) -> Vec<String> {
+    let tokens = isupport_tokens(engine);
+    tokens
+        .chunks(ISUPPORT_TOKENS_PER_LINE)
+        .map(|chunk| {
+            let chunk: Vec<&str> = chunk.iter().map(String::as_str).collect();
+            formatter::rpl_isupport(nick, &chunk)
+        })
+        .collect()
+}
+
+/// LUSERS replies, from the engine's live session and channel maps.
+pub fn lusers_lines(engine: &ChatEngine, nick: &str) -> Vec<String> {
+    let counts = engine.lusers();
+    vec![
+        formatter::rpl_luserclient(nick, counts.users),
+        formatter::rpl_luserchannels(nick, counts.channels),
+        formatter::rpl_luserme(nick, counts.clients),
+    ]
+}
+
+/// MOTD replies. `MOTD <server-name>` shows that server's own MOTD when it has
+/// one; otherwise, and with no target, the instance MOTD.
+pub async fn motd_lines(engine: &ChatEngine, nick: &str, target: Option<&str>) -> Vec<String> {
+    let server_id = target
+        .map(|t| t.trim_start_matches('#'))
+        .and_then(|t| engine.find_server_by_name(t.split('/').next().unwrap_or(t)));
+    let Some(motd) = engine.effective_motd(server_id.as_deref()).await else {
+        return vec![formatter::err_nomotd(nick)];
+    };
+
+    let mut lines = vec![formatter::rpl_motdstart(nick)];
+    lines.extend(motd.lines().map(|line| formatter::rpl_motd(nick, line)));
+    lines.push(formatter::rpl_endofmotd(nick));
+    lines
+}
+
+/// Mode letter for a status prefix character.
+fn prefix_mode(prefix: char) -> Option<char> {
+    let index = MEMBER_PREFIXES.chars().position(|p| p == prefix)?;
+    PREFIX_MODES.chars().nth(index)
+}
+
+/// Mode string (e.g. `+Yo-v`) for prefixes gained and lost; empty if none.
+pub fn prefix_mode_change(added: &str, removed: &str) -> String {
+    let mut modes = String::new();
+    for (sign, prefixes) in [('+', added), ('-', removed)] {
+        let letters: String = prefixes.chars().filter_map(prefix_mode).collect();
+        if !letters.is_empty() {
+            modes.push(sign);
+            modes.push_str(&letters);
+        }
+    }
+    modes
+}
+
+/// A member as shown in NAMES or WHO: only the highest prefix unless the
+/// client enabled `multi-prefix`.
+fn shown_prefixes<'a>(prefixes: &'a str, caps: &CapState) -> &'a str {
+    if caps.has("multi-prefix") {
+        prefixes
+    } else {
+        prefixes
+            .get(..prefixes.chars().next().map_or(0, char::len_utf8))
+            .unwrap_or("")
+    }
+}
+
+/// A NAMES entry: `@nick`, or `@nick!nick@concord` with `userhost-in-names`.
+pub fn names_entry(member: &MemberInfo, caps: &CapState) -> String {
+    let prefixes = shown_prefixes(&member.prefixes, caps);
+    if caps.has("userhost-in-names") {
+        let nick = &member.nickname;
+        format!("{prefixes}{nick}!{nick}@{}", formatter::server_name())
+    } else {
+        format!("{prefixes}{}", member.nickname)
+    }
+}
+
+/// The away message IRC shows for a presence: idle and do-not-disturb users are
+/// away, with their custom status as the reason.
+pub fn away_message(presence: &PresenceInfo) -> Option<String> {
+    let fallback = match presence.status.as_str() {
+        "idle" => "Away",
+        "dnd" => "Do not disturb",
+        _ => return None,
+    };
+    Some(
+        presence
+            .custom_status
+            .clone()
+            .filter(|s| !s.trim().is_empty())
+            .unwrap_or_else(|| fallback.to_string()),
+    )
+}
+
+/// `AWAY :message` marks the user idle with the message as their custom status;
+/// a bare `AWAY` puts them back online.
+pub async fn handle_away(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    msg: &IrcMessage,
+) -> Vec<String> {
+    let message = msg.params.first().filter(|m| !m.trim().is_empty());
+    let result = match message {
+        Some(message) => {
+            engine
+                .set_presence(session_id, "idle", Some(message), None)
+                .await
+        }
+        None => engine.set_presence(session_id, "online", None, None).await,
+    };
+    match result {
+        Ok(()) if message.is_some() => vec![formatter::rpl_nowaway(nick)],
+        Ok(()) => vec![formatter::rpl_unaway(nick)],
+        Err(e) => {
+            warn!(error = %e, "AWAY failed");
+            vec![formatter::server_notice(nick, &e)]
+        }
+    }
+}
+
+/// Handle TAGMSG, which carries only client-only tags: `+typing` becomes a
+/// typing indicator and `+draft/react`/`+draft/unreact` a reaction. Other
+/// tags aren't relayed, and like NOTICE a TAGMSG is never answered.
+pub async fn handle_tagmsg(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    msg: &IrcMessage,
+) -> Vec<String> {
+    if let (Some(state), Some(target)) = (msg.tag("+typing"), msg.params.first())
+        && target.starts_with('#')
+    {
+        let (server_id, channel_name) = parse_irc_channel(engine, target);
+        let result = match state {
+            "active" => engine.send_typing(session_id, &server_id, &channel_name),
+            "paused" => engine.stop_typing(session_id, &server_id, &channel_name, true),
+            "done" => engine.stop_typing(session_id, &server_id, &channel_name, false),
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            warn!(error = %e, %target, "TAGMSG typing failed");
+        }
+    }
+
+    edits::apply_reaction_tags(engine, session_id, msg).await;
+    vec![]
+}
+
+/// RPL_AWAY for a target that is currently away.
+fn away_reply(engine: &ChatEngine, nick: &str, target: &str) -> Option<String> {
+    let presence = engine.presence_of_nick(target)?;
+    let message = away_message(&presence)?;
+    Some(formatter::rpl_away(nick, target, &message))
 }
 
 /// Process a single IRC command from a registered (authenticated) client.
@@ -52,56 +369,36 @@ pub fn handle_command(
     engine: &ChatEngine,
     session_id: SessionId,
     nick: &str,
+    caps: &CapState,
     msg: &IrcMessage,
 ) -> Vec<String> {
     match msg.command.as_str() {
-        "JOIN" => handle_join(engine, session_id, nick, msg),
         "PART" => handle_part(engine, session_id, nick, msg),
         "PRIVMSG" => handle_privmsg(engine, session_id, nick, msg),
+        "NOTICE" => handle_notice(engine, session_id, msg),
         "TOPIC" => handle_topic(engine, session_id, nick, msg),
-        "NAMES" => handle_names(engine, nick, msg),
-        "LIST" => handle_list(engine, nick, msg),
-        "WHO" => handle_who(engine, nick, msg),
+        "NAMES" => handle_names(engine, nick, caps, msg),
+        "WHO" => handle_who(engine, nick, caps, msg),
         "WHOIS" => handle_whois(engine, nick, msg),
-        "QUIT" => vec![], // Handled at connection level
+        "USERHOST" => handle_userhost(engine, nick, msg),
+        "ISON" => handle_ison(engine, nick, msg),
+        "MONITOR" => monitor::handle_monitor(engine, session_id, nick, msg),
+        "LUSERS" => lusers_lines(engine, nick),
+        "KILL" => oper::handle_kill(engine, session_id, nick, msg),
+        "WALLOPS" | "GLOBOPS" => oper::handle_wallops(engine, session_id, nick, msg),
+        "STATS" => oper::handle_stats(engine, session_id, nick, msg),
+        "SAJOIN" | "SAPART" => oper::handle_sajoin_sapart(engine, session_id, nick, msg),
+        "QUIT" | "CAP" | "MODE" | "KICK" | "MOTD" | "AWAY" | "REDACT" | "TAGMSG" => {
+            vec![] // Handled at connection level
+        }
         "PING" => {
             let token = msg.params.first().map(|s| s.as_str()).unwrap_or("concord");
             vec![formatter::pong(token)]
         }
         "PONG" => vec![], // Just acknowledge, no response needed
-        "NICK" | "USER" | "PASS" => {
+        "NICK" | "USER" | "PASS" | "AUTHENTICATE" => {
             vec![formatter::err_alreadyregistered(nick)]
         }
-        // CAP, MODE — common client sends these, just ignore or give minimal response
-        "CAP" => {
-            if msg.params.first().map(|s| s.as_str()) == Some("LS") {
-                vec![format!(":{} CAP * LS :", formatter::server_name())]
-            } else {
-                vec![]
-            }
-        }
-        "MODE" => {
-            if let Some(target) = msg.params.first() {
-                if target.starts_with('#') {
-                    // Translate channel name for display
-                    let (server_id, channel_name) = parse_irc_channel(engine, target);
-                    let irc_channel = to_irc_channel(engine, &server_id, &channel_name);
-                    vec![format!(
-                        ":{} 324 {} {} +",
-                        formatter::server_name(),
-                        nick,
-                        irc_channel
-                    )]
-                } else {
-                    vec![format!(":{} 221 {} +", formatter::server_name(), nick)]
-                }
-            } else {
-                vec![formatter::err_needmoreparams(nick, "MODE")]
-            }
-        }
-        "USERHOST" | "ISON" => {
-            vec![]
-        }
         _ => {
             warn!(command = %msg.command, "unknown IRC command");
             vec![formatter::err_unknowncommand(nick, &msg.command)]
@@ -109,38 +406,6 @@ pub fn handle_command(
     }
 }
 
-fn handle_join(
-    engine: &ChatEngine,
-    session_id: SessionId,
-    nick: &str,
-    msg: &IrcMessage,
-) -> Vec<String> {
-    let Some(channels_param) = msg.params.first() else {
-        return vec![formatter::err_needmoreparams(nick, "JOIN")];
-    };
-
-    let mut replies = Vec::new();
-
-    for channel in channels_param.split(',') {
-        let channel = channel.trim();
-        if channel.is_empty() {
-            continue;
-        }
-
-        let (server_id, channel_name) = parse_irc_channel(engine, channel);
-
-        match engine.join_channel(session_id, &server_id, &channel_name) {
-            Ok(()) => {}
-            Err(e) => {
-                warn!(error = %e, %channel, "JOIN failed");
-                replies.push(formatter::err_nosuchchannel(nick, channel));
-            }
-        }
-    }
-
-    replies
-}
-
 fn handle_part(
     engine: &ChatEngine,
     session_id: SessionId,
@@ -182,30 +447,81 @@ fn handle_privmsg(
     }
 
     let target = &msg.params[0];
-    let content = &msg.params[1];
+    let (content, kind) = match formatting::classify(&msg.params[1]) {
+        IrcText::Plain(text) => (formatting::irc_to_markdown(text), MessageKind::Normal),
+        IrcText::Action(text) => (formatting::irc_to_markdown(text), MessageKind::Action),
+        // VERSION, PING and friends are addressed to clients, not to Concord
+        IrcText::Ctcp => return vec![],
+    };
+
+    let reply_to = msg.tag("+draft/reply");
+    if let Err(e) = relay_message(engine, session_id, target, &content, reply_to, kind) {
+        warn!(error = %e, %target, "PRIVMSG failed");
+        // A channel that refuses the message (+m, a timeout) still exists
+        return if target.starts_with('#') {
+            vec![formatter::err_cannotsendtochan(nick, target)]
+        } else {
+            vec![formatter::err_nosuchnick(nick, target)]
+        };
+    }
 
     if target.starts_with('#') {
-        // Channel message — parse server/channel from IRC name
-        let (server_id, channel_name) = parse_irc_channel(engine, target);
-        if let Err(e) =
-            engine.send_message(session_id, &server_id, &channel_name, content, None, None)
-        {
-            warn!(error = %e, %target, "PRIVMSG failed");
-            return vec![formatter::err_nosuchnick(nick, target)];
-        }
+        vec![]
     } else {
-        // DM — use default server
-        if let Err(e) =
-            engine.send_message(session_id, DEFAULT_SERVER_ID, target, content, None, None)
-        {
-            warn!(error = %e, %target, "PRIVMSG failed");
-            return vec![formatter::err_nosuchnick(nick, target)];
-        }
+        away_reply(engine, nick, target).into_iter().collect()
     }
+}
 
+/// NOTICE is relayed like PRIVMSG but, per RFC 2812, never answered — not
+/// even with an error or an away reply. CTCP replies are dropped.
+fn handle_notice(engine: &ChatEngine, session_id: SessionId, msg: &IrcMessage) -> Vec<String> {
+    let [target, text, ..] = msg.params.as_slice() else {
+        return vec![];
+    };
+    let IrcText::Plain(text) = formatting::classify(text) else {
+        return vec![];
+    };
+    let content = formatting::irc_to_markdown(text);
+    let reply_to = msg.tag("+draft/reply");
+    if let Err(e) = relay_message(
+        engine,
+        session_id,
+        target,
+        &content,
+        reply_to,
+        MessageKind::Notice,
+    ) {
+        warn!(error = %e, %target, "NOTICE failed");
+    }
     vec![]
 }
 
+/// Send a message to an IRC target: a channel, or a nick (DM on the default
+/// server). `reply_to` comes from the `+draft/reply` client tag.
+fn relay_message(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    target: &str,
+    content: &str,
+    reply_to: Option<&str>,
+    kind: MessageKind,
+) -> Result<(), String> {
+    let (server_id, engine_target) = if target.starts_with('#') {
+        parse_irc_channel(engine, target)
+    } else {
+        (DEFAULT_SERVER_ID.to_string(), target.to_string())
+    };
+    engine.send_message_as(
+        session_id,
+        &server_id,
+        &engine_target,
+        content,
+        reply_to,
+        None,
+        kind,
+    )
+}
+
 fn handle_topic(
     engine: &ChatEngine,
     session_id: SessionId,
@@ -244,7 +560,7 @@ fn handle_topic(
     }
 }
 
-fn handle_names(engine: &ChatEngine, nick: &str, msg: &IrcMessage) -> Vec<String> {
+fn handle_names(engine: &ChatEngine, nick: &str, caps: &CapState, msg: &IrcMessage) -> Vec<String> {
     let Some(channel_param) = msg.params.first() else {
         return vec![formatter::err_needmoreparams(nick, "NAMES")];
     };
@@ -254,7 +570,7 @@ fn handle_names(engine: &ChatEngine, nick: &str, msg: &IrcMessage) -> Vec<String
 
     match engine.get_members(&server_id, &channel_name) {
         Ok(member_infos) => {
-            let nicks: Vec<String> = member_infos.iter().map(|m| m.nickname.clone()).collect();
+            let nicks: Vec<String> = member_infos.iter().map(|m| names_entry(m, caps)).collect();
             vec![
                 formatter::rpl_namreply(nick, &irc_channel, &nicks),
                 formatter::rpl_endofnames(nick, &irc_channel),
@@ -264,88 +580,156 @@ fn handle_names(engine: &ChatEngine, nick: &str, msg: &IrcMessage) -> Vec<String
     }
 }
 
-fn handle_list(engine: &ChatEngine, nick: &str, msg: &IrcMessage) -> Vec<String> {
-    // LIST with no args: show default server channels
-    // LIST #server-name/* : show channels for a specific server
-    let server_id = if let Some(pattern) = msg.params.first() {
-        let bare = pattern.strip_prefix('#').unwrap_or(pattern);
-        if let Some(server_name) = bare.strip_suffix("/*") {
-            if let Some(sid) = engine.find_server_by_name(server_name) {
-                sid
-            } else {
-                // Unknown server — return empty list
-                return vec![formatter::rpl_listend(nick)];
-            }
-        } else {
-            DEFAULT_SERVER_ID.to_string()
-        }
-    } else {
-        DEFAULT_SERVER_ID.to_string()
-    };
-
-    let channels = engine.list_channels(&server_id);
-    let mut replies = Vec::with_capacity(channels.len() + 1);
-
-    for ch in &channels {
-        let irc_name = to_irc_channel(engine, &server_id, &ch.name);
-        replies.push(formatter::rpl_list(
-            nick,
-            &irc_name,
-            ch.member_count,
-            &ch.topic,
-        ));
-    }
-    replies.push(formatter::rpl_listend(nick));
-
-    replies
+/// One user in a WHO reply.
+struct WhoEntry {
+    /// The channel they were found in, or `*` for a nick lookup.
+    channel: String,
+    nickname: String,
+    user_id: Option<String>,
+    prefixes: String,
 }
 
-fn handle_who(engine: &ChatEngine, nick: &str, msg: &IrcMessage) -> Vec<String> {
+/// Handle `WHO <channel|nick> [%fields[,token]]`. With a `%` field list
+/// (WHOX) each user gets a 354 carrying just those fields.
+fn handle_who(engine: &ChatEngine, nick: &str, caps: &CapState, msg: &IrcMessage) -> Vec<String> {
     let Some(target) = msg.params.first() else {
         return vec![formatter::err_needmoreparams(nick, "WHO")];
     };
+    let whox = msg.params.get(1).and_then(|p| p.strip_prefix('%'));
 
-    let mut replies = Vec::new();
-
-    if target.starts_with('#') {
+    let (mask, entries) = if target.starts_with('#') {
         let (server_id, channel_name) = parse_irc_channel(engine, target);
         let irc_channel = to_irc_channel(engine, &server_id, &channel_name);
+        let entries: Vec<WhoEntry> = engine
+            .get_members(&server_id, &channel_name)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|member| WhoEntry {
+                channel: irc_channel.clone(),
+                prefixes: shown_prefixes(&member.prefixes, caps).to_string(),
+                nickname: member.nickname,
+                user_id: member.user_id,
+            })
+            .collect();
+        (irc_channel, entries)
+    } else {
+        let entries = engine
+            .get_session_by_nick(target)
+            .map(|session| WhoEntry {
+                channel: "*".into(),
+                nickname: session.nickname.clone(),
+                user_id: session.user_id.clone(),
+                prefixes: String::new(),
+            })
+            .into_iter()
+            .collect();
+        (target.clone(), entries)
+    };
 
-        if let Ok(members) = engine.get_members(&server_id, &channel_name) {
-            for member in &members {
-                replies.push(format!(
-                    ":{} {} {} {} {} {} {} {} H :0 {}",
-                    formatter::server_name(),
-                    super::numerics::RPL_WHOREPLY,
-                    nick,
-                    irc_channel,
-                    member.nickname,
-                    formatter::server_name(),
-                    formatter::server_name(),
-                    member.nickname,
-                    member.nickname,
-                ));
+    let mut replies: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            let flags = who_flags(engine, entry);
+            match whox {
+                Some(query) => {
+                    formatter::rpl_whospcrpl(nick, &whox_fields(engine, entry, &flags, query))
+                }
+                None => formatter::rpl_whoreply(nick, &entry.channel, &entry.nickname, &flags),
             }
-        }
+        })
+        .collect();
+    replies.push(formatter::rpl_endofwho(nick, &mask));
+    replies
+}
 
-        replies.push(format!(
-            ":{} {} {} {} :End of /WHO list",
-            formatter::server_name(),
-            super::numerics::RPL_ENDOFWHO,
-            nick,
-            irc_channel,
-        ));
-    } else {
-        replies.push(format!(
-            ":{} {} {} {} :End of /WHO list",
-            formatter::server_name(),
-            super::numerics::RPL_ENDOFWHO,
-            nick,
-            target,
-        ));
+/// WHO flags: `H` (here) or `G` (gone, i.e. away), `*` for an IRC operator,
+/// then the user's status prefixes.
+fn who_flags(engine: &ChatEngine, entry: &WhoEntry) -> String {
+    let away = engine
+        .presence_of_nick(&entry.nickname)
+        .and_then(|p| away_message(&p))
+        .is_some();
+    let oper = engine
+        .get_session_by_nick(&entry.nickname)
+        .is_some_and(|s| s.is_oper());
+    format!(
+        "{}{}{}",
+        if away { 'G' } else { 'H' },
+        if oper { "*" } else { "" },
+        entry.prefixes
+    )
+}
+
+/// The fields a WHOX query (`tcuhnfar`, optionally `,token`) asked for, in
+/// WHOX order. Concord has no idle times, IP addresses or op levels to show.
+fn whox_fields(engine: &ChatEngine, entry: &WhoEntry, flags: &str, query: &str) -> Vec<String> {
+    let (letters, token) = query.split_once(',').unwrap_or((query, "0"));
+    WHOX_FIELDS
+        .chars()
+        .filter(|&c| letters.contains(c))
+        .map(|c| match c {
+            't' => token.to_string(),
+            'c' => entry.channel.clone(),
+            'u' | 'n' | 'r' => entry.nickname.clone(),
+            'i' => "255.255.255.255".into(),
+            'h' | 's' => formatter::server_name().into(),
+            'f' => flags.to_string(),
+            'a' => entry
+                .user_id
+                .as_deref()
+                .and_then(|uid| engine.account_name(uid))
+                .unwrap_or_else(|| "0".into()),
+            'o' => "n/a".into(),
+            _ => "0".into(), // d (hops) and l (idle seconds)
+        })
+        .collect()
+}
+
+/// `USERHOST nick...`: `nick=+nick@concord` for each connected nick, with
+/// `*` after an operator's nick and `-` instead of `+` for someone away.
+fn handle_userhost(engine: &ChatEngine, nick: &str, msg: &IrcMessage) -> Vec<String> {
+    if msg.params.is_empty() {
+        return vec![formatter::err_needmoreparams(nick, "USERHOST")];
     }
+    let replies: Vec<String> = msg
+        .params
+        .iter()
+        .flat_map(|p| p.split_whitespace())
+        .take(USERHOST_MAX_NICKS)
+        .filter_map(|target| engine.get_session_by_nick(target))
+        .map(|session| {
+            let entry = WhoEntry {
+                channel: "*".into(),
+                nickname: session.nickname.clone(),
+                user_id: session.user_id.clone(),
+                prefixes: String::new(),
+            };
+            let flags = who_flags(engine, &entry);
+            format!(
+                "{0}{1}={2}{0}@{3}",
+                session.nickname,
+                if session.is_oper() { "*" } else { "" },
+                if flags.starts_with('G') { '-' } else { '+' },
+                formatter::server_name()
+            )
+        })
+        .collect();
+    vec![formatter::rpl_userhost(nick, &replies)]
+}
 
-    replies
+/// `ISON nick...`: which of the nicks are online, as MONITOR would report them.
+fn handle_ison(engine: &ChatEngine, nick: &str, msg: &IrcMessage) -> Vec<String> {
+    if msg.params.is_empty() {
+        return vec![formatter::err_needmoreparams(nick, "ISON")];
+    }
+    let online: Vec<String> = msg
+        .params
+        .iter()
+        .flat_map(|p| p.split_whitespace())
+        .filter(|target| engine.is_nick_online(target))
+        .map(String::from)
+        .collect();
+    vec![formatter::rpl_ison(nick, &online)]
 }
 
 fn handle_whois(engine: &ChatEngine, nick: &str, msg: &IrcMessage) -> Vec<String> {
@@ -354,12 +738,145 @@ fn handle_whois(engine: &ChatEngine, nick: &str, msg: &IrcMessage) -> Vec<String
     };
 
     if !engine.is_nick_available(target) {
-        vec![
+        let mut replies = vec![
             formatter::rpl_whoisuser(nick, target),
             formatter::rpl_whoisserver(nick, target),
-            formatter::rpl_endofwhois(nick, target),
-        ]
+        ];
+        let session = engine.get_session_by_nick(target);
+        if session.as_ref().is_some_and(|s| s.is_oper()) {
+            replies.push(formatter::rpl_whoisoperator(nick, target));
+        }
+        if let Some(account) = session
+            .and_then(|s| s.user_id.clone())
+            .and_then(|uid| engine.account_name(&uid))
+        {
+            replies.push(formatter::rpl_whoisaccount(nick, target, &account));
+        }
+        replies.extend(away_reply(engine, nick, target));
+        replies.push(formatter::rpl_endofwhois(nick, target));
+        replies
     } else {
         vec![formatter::err_nosuchnick(nick, target)]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn member(nickname: &str, prefixes: &str) -> MemberInfo {
+        MemberInfo {
+            nickname: nickname.into(),
+            avatar_url: None,
+            status: None,
+            custom_status: None,
+            status_emoji: None,
+            user_id: None,
+            prefixes: prefixes.into(),
+        }
+    }
+
+    #[test]
+    fn test_away_message() {
+        let mut presence = PresenceInfo {
+            user_id: "u1".into(),
+            nickname: "alice".into(),
+            avatar_url: None,
+            status: "online".into(),
+            custom_status: Some("Coding".into()),
+            status_emoji: None,
+        };
+        assert_eq!(away_message(&presence), None);
+
+        presence.status = "idle".into();
+        assert_eq!(away_message(&presence).as_deref(), Some("Coding"));
+
+        presence.status = "dnd".into();
+        presence.custom_status = None;
+        assert_eq!(away_message(&presence).as_deref(), Some("Do not disturb"));
+
+        presence.status = "offline".into();
+        assert_eq!(away_message(&presence), None);
+    }
+
+    #[test]
+    fn test_thread_slug() {
+        assert_eq!(thread_slug("#release-notes"), "release-notes");
+        assert_eq!(thread_slug("#Why is CI/CD slow?"), "why-is-ci-cd-slow");
+        assert_eq!(thread_slug("#  spaced   out  "), "spaced-out");
+        assert_eq!(thread_slug("#café_talk"), "café_talk");
+        assert_eq!(thread_slug("#???"), "thread");
+    }
+
+    #[test]
+    fn test_isupport_prefix() {
+        assert_eq!(isupport_prefix(), "PREFIX=(Yaohv)~&@%+");
+    }
+
+    #[test]
+    fn test_isupport_lines() {
+        let engine = ChatEngine::new(None).with_irc_channel_limit(50);
+        let lines = isupport_lines(&engine, "alice");
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with(":concord 005 alice CASEMAPPING=ascii CHANLIMIT=#:50 "));
+        assert!(lines[0].contains(" CHANNELLEN=252 "));
+        assert!(lines[0].contains(" CHANTYPES=#& "));
+        assert!(lines[0].contains(" CHATHISTORY=100 "));
+        assert!(lines[0].contains(" ELIST=CMNTU "));
+        assert!(lines[0].contains(" KNOCK "));
+        assert!(lines[0].contains(" MONITOR=100 "));
+        assert!(lines[0].contains(" NETWORK=Concord "));
+        assert!(lines[0].contains(" NICKLEN=32 "));
+        assert!(lines[1].contains(" TOPICLEN=500 "));
+        assert!(lines[1].contains(" WHOX "));
+        for line in &lines {
+            assert!(line.ends_with(" :are supported by this server"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_motd_lines() {
+        let engine = ChatEngine::new(None);
+        assert_eq!(
+            motd_lines(&engine, "alice", None).await,
+            vec![":concord 422 alice :MOTD File is missing".to_string()]
+        );
+
+        let engine = ChatEngine::new(None).with_motd(Some("Welcome!\nBe nice".into()));
+        let lines = motd_lines(&engine, "alice", Some("unknown-server")).await;
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].contains(" 375 alice "));
+        assert_eq!(lines[1], ":concord 372 alice :- Welcome!");
+        assert_eq!(lines[2], ":concord 372 alice :- Be nice");
+        assert!(lines[3].contains(" 376 alice "));
+    }
+
+    #[test]
+    fn test_prefix_mode_change() {
+        assert_eq!(prefix_mode_change("~&", "+"), "+Ya-v");
+        assert_eq!(prefix_mode_change("", "@%"), "-oh");
+        assert_eq!(prefix_mode_change("", ""), "");
+    }
+
+    #[test]
+    fn test_names_entry() {
+        let mut caps = CapState::default();
+        let alice = member("alice", "@%");
+        assert_eq!(names_entry(&alice, &caps), "@alice");
+        assert_eq!(names_entry(&member("bob", ""), &caps), "bob");
+
+        caps.handle(
+            "alice",
+            &IrcMessage::parse("CAP REQ multi-prefix").unwrap(),
+            true,
+        );
+        assert_eq!(names_entry(&alice, &caps), "@%alice");
+
+        caps.handle(
+            "alice",
+            &IrcMessage::parse("CAP REQ userhost-in-names").unwrap(),
+            true,
+        );
+        assert_eq!(names_entry(&alice, &caps), "@%alice!alice@concord");
+    }
+}
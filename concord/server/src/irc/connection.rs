@@ -1,33 +1,52 @@
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chrono::{DateTime, Utc};
+use futures_util::{Sink, SinkExt, Stream, StreamExt};
 use sqlx::SqlitePool;
 use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
 use tokio::sync::mpsc;
 use tracing::{info, warn};
-
-/// Maximum bytes per IRC line (RFC 2812 says 512; we allow 4096 for safety).
-const MAX_LINE_LENGTH: usize = 4096;
-/// Idle timeout — disconnect clients that send nothing for 5 minutes.
-const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+use uuid::Uuid;
 
 use crate::auth::token::verify_irc_token;
+use crate::config::IrcSection;
 use crate::db::queries::users;
 use crate::engine::chat_engine::{ChatEngine, DEFAULT_SERVER_ID};
-use crate::engine::events::{ChatEvent, SessionId};
+use crate::engine::events::{ChatEvent, MessageKind, SessionId};
 use crate::engine::user_session::Protocol;
+use crate::engine::validation;
 
-use super::commands::{self, to_irc_channel};
+use super::access;
+use super::certs;
+use super::commands::{self, names_entry, to_irc_channel};
+use super::edits;
 use super::formatter;
+use super::formatting;
+use super::group_dm;
+use super::history;
+use super::list;
+use super::moderation;
+use super::oper;
 use super::parser::IrcMessage;
 
-/// Read a line from the IRC connection, capped at MAX_LINE_LENGTH bytes.
+/// Minimum gap between `+typing=active` notifications for the same typist and
+/// channel, as the IRCv3 typing spec asks of senders.
+const TYPING_THROTTLE: Duration = Duration::from_secs(3);
+/// How long a closing connection waits for its last lines to be written.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Read a line from the IRC connection, capped at `max_len` bytes.
 /// Returns Ok(0) on EOF, Ok(n) on success, Err on I/O error or line too long.
 async fn read_bounded_line<R: AsyncRead + Unpin>(
     reader: &mut BufReader<R>,
     buf: &mut String,
+    max_len: usize,
 ) -> std::io::Result<usize> {
-    // Fill the internal buffer and check for a newline within MAX_LINE_LENGTH
+    // Fill the internal buffer and check for a newline within max_len
     loop {
         let available = reader.buffer();
         if let Some(pos) = available.iter().position(|&b| b == b'\n') {
@@ -39,7 +58,7 @@ async fn read_bounded_line<R: AsyncRead + Unpin>(
             reader.consume(len);
             return Ok(len);
         }
-        if available.len() >= MAX_LINE_LENGTH {
+        if available.len() >= max_len {
             // Too long without a newline — discard and signal error
             let discard_len = available.len();
             reader.consume(discard_len);
@@ -64,31 +83,269 @@ enum RegState {
         pass: Option<String>,
         nick: Option<String>,
         user_received: bool,
+        /// (user_id, account name) once SASL has succeeded.
+        account: Option<(String, String)>,
     },
     /// Fully registered with the chat engine.
     Registered { session_id: SessionId, nick: String },
 }
 
+/// IRCv3 capabilities offered in `CAP LS`, with the value advertised to
+/// version 302 clients (e.g. `sasl=PLAIN,EXTERNAL`).
+const SUPPORTED_CAPS: &[(&str, Option<&str>)] = &[
+    ("away-notify", None),
+    ("batch", None),
+    ("cap-notify", None),
+    ("draft/chathistory", None),
+    ("draft/message-redaction", None),
+    ("echo-message", None),
+    ("extended-join", None),
+    ("message-tags", None),
+    ("multi-prefix", None),
+    ("sasl", Some(SASL_MECHANISMS)),
+    ("server-time", None),
+    ("userhost-in-names", None),
+];
+
+/// Read-marker client name for connections that don't give one in USER.
+const DEFAULT_CLIENT: &str = "irc";
+
+/// Longest client name accepted in USER.
+const MAX_CLIENT_NAME_LEN: usize = 32;
+
+/// SASL mechanisms offered via the `sasl` capability and RPL_SASLMECHS.
+const SASL_MECHANISMS: &str = "PLAIN,EXTERNAL";
+/// AUTHENTICATE payloads arrive in 400-byte chunks; a full chunk means more follow.
+const SASL_CHUNK_SIZE: usize = 400;
+/// Upper bound on a reassembled SASL payload.
+const SASL_MAX_PAYLOAD: usize = 4096;
+/// Failed SASL logins a connection gets before it is closed. Each one costs
+/// a password hash check.
+const MAX_SASL_FAILURES: u32 = 3;
+
+/// Soft limit on the capability list carried by a single CAP reply line.
+/// Longer lists are split, with `*` marking every line but the last.
+const CAP_LINE_BUDGET: usize = 400;
+
+/// Per-connection IRCv3 capability negotiation state.
+///
+/// Lives alongside `RegState` for the lifetime of the connection. While the
+/// client is negotiating (it sent `CAP LS` or `CAP REQ` before registering),
+/// registration is held open until `CAP END`.
+#[derive(Debug, Default)]
+pub struct CapState {
+    /// CAP version the client announced with `CAP LS <version>` (0 if none).
+    version: u32,
+    /// True between the first pre-registration CAP command and `CAP END`.
+    negotiating: bool,
+    /// Capabilities the client has enabled.
+    enabled: HashSet<String>,
+}
+
+impl CapState {
+    /// Whether the client has enabled the given capability.
+    pub fn has(&self, cap: &str) -> bool {
+        self.enabled.contains(cap)
+    }
+
+    /// Whether registration must wait for `CAP END`.
+    pub fn negotiating(&self) -> bool {
+        self.negotiating
+    }
+
+    /// Handle a `CAP` command and return the reply lines.
+    /// `nick` is the client's nickname, or `*` before registration.
+    pub fn handle(&mut self, nick: &str, msg: &IrcMessage, registered: bool) -> Vec<String> {
+        let Some(subcommand) = msg.params.first() else {
+            return vec![formatter::err_needmoreparams(nick, "CAP")];
+        };
+        let subcommand = subcommand.to_uppercase();
+
+        match subcommand.as_str() {
+            "LS" => {
+                if !registered {
+                    self.negotiating = true;
+                }
+                let version = msg
+                    .params
+                    .get(1)
+                    .and_then(|v| v.parse::<u32>().ok())
+                    .unwrap_or(0);
+                self.version = self.version.max(version);
+                // cap-notify is implied for 302 clients and cannot be disabled
+                if self.version >= 302 {
+                    self.enabled.insert("cap-notify".to_string());
+                }
+
+                let tokens: Vec<String> = SUPPORTED_CAPS
+                    .iter()
+                    .map(|(name, value)| match value {
+                        Some(v) if self.version >= 302 => format!("{name}={v}"),
+                        _ => name.to_string(),
+                    })
+                    .collect();
+                self.cap_list_lines(nick, "LS", &tokens)
+            }
+            "LIST" => {
+                let mut tokens: Vec<String> = self.enabled.iter().cloned().collect();
+                tokens.sort();
+                self.cap_list_lines(nick, "LIST", &tokens)
+            }
+            "REQ" => {
+                if !registered {
+                    self.negotiating = true;
+                }
+                let requested = msg.params.get(1).map(|s| s.as_str()).unwrap_or("");
+                let caps: Vec<&str> = requested.split_whitespace().collect();
+
+                // Requests are all-or-nothing: one unknown cap rejects the lot
+                let acceptable = !caps.is_empty()
+                    && caps.iter().all(|cap| {
+                        let (disable, name) = match cap.strip_prefix('-') {
+                            Some(name) => (true, name),
+                            None => (false, *cap),
+                        };
+                        let known = SUPPORTED_CAPS.iter().any(|(n, _)| *n == name);
+                        let sticky = disable && name == "cap-notify" && self.version >= 302;
+                        known && !sticky
+                    });
+
+                if !acceptable {
+                    return vec![formatter::cap(nick, "NAK", requested, false)];
+                }
+
+                for cap in &caps {
+                    match cap.strip_prefix('-') {
+                        Some(name) => {
+                            self.enabled.remove(name);
+                        }
+                        None => {
+                            self.enabled.insert(cap.to_string());
+                        }
+                    }
+                }
+                vec![formatter::cap(nick, "ACK", &caps.join(" "), false)]
+            }
+            "END" => {
+                self.negotiating = false;
+                vec![]
+            }
+            _ => vec![formatter::err_invalidcapcmd(nick, &subcommand)],
+        }
+    }
+
+    /// Build one or more `CAP <subcommand>` lines for a capability list,
+    /// splitting long lists for 302 clients.
+    fn cap_list_lines(&self, nick: &str, subcommand: &str, tokens: &[String]) -> Vec<String> {
+        if self.version < 302 {
+            return vec![formatter::cap(nick, subcommand, &tokens.join(" "), false)];
+        }
+
+        let mut chunks: Vec<String> = Vec::new();
+        let mut current = String::new();
+        for token in tokens {
+            if !current.is_empty() && current.len() + 1 + token.len() > CAP_LINE_BUDGET {
+                chunks.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(token);
+        }
+        chunks.push(current);
+
+        let last = chunks.len() - 1;
+        chunks
+            .iter()
+            .enumerate()
+            .map(|(i, chunk)| formatter::cap(nick, subcommand, chunk, i < last))
+            .collect()
+    }
+}
+
+/// SASL mechanisms supported by `AUTHENTICATE`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SaslMechanism {
+    /// Account name plus IRC token.
+    Plain,
+    /// TLS client certificate fingerprint bound to a user.
+    External,
+}
+
+/// Progress of a SASL exchange started with `AUTHENTICATE <mechanism>`.
+#[derive(Debug, Default)]
+enum SaslState {
+    #[default]
+    Idle,
+    /// Waiting for the client's (possibly chunked) response payload.
+    Awaiting {
+        mechanism: SaslMechanism,
+        buffer: String,
+    },
+}
+
 /// Handle a single IRC client connection from accept to close.
 /// Accepts any stream implementing AsyncRead + AsyncWrite (plain TCP or TLS).
-pub async fn handle_irc_connection<S>(stream: S, peer: String, engine: Arc<ChatEngine>, db: SqlitePool)
-where
+/// `cert_fp` is the SHA-256 fingerprint of the TLS client certificate, if one was presented.
+pub async fn handle_irc_connection<S>(
+    stream: S,
+    peer: String,
+    engine: Arc<ChatEngine>,
+    db: SqlitePool,
+    config: Arc<IrcSection>,
+    cert_fp: Option<String>,
+) where
     S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (reader, writer) = tokio::io::split(stream);
+
+    let max_len = config.max_line_length;
+    let lines =
+        futures_util::stream::unfold(BufReader::new(reader), move |mut reader| async move {
+            let mut line = String::new();
+            match read_bounded_line(&mut reader, &mut line, max_len).await {
+                Ok(0) => None,
+                Ok(_) => Some((Ok(line), reader)),
+                Err(e) => Some((Err(e), reader)),
+            }
+        });
+    let sink = futures_util::sink::unfold(writer, |mut writer, line: String| async move {
+        writer.write_all(format!("{}\r\n", line).as_bytes()).await?;
+        Ok::<_, std::io::Error>(writer)
+    });
+
+    handle_connection(lines, sink, peer, engine, db, config, cert_fp).await;
+}
+
+/// Run the IRC state machine over any line-oriented transport: `lines` yields
+/// one inbound line at a time (without its terminator) and ends at EOF, and
+/// `sink` takes outbound lines, adding whatever framing the transport needs.
+/// Clients must register within the configured timeout, and once registered
+/// are sent a PING whenever they go quiet and dropped if they don't answer.
+pub async fn handle_connection<L, W>(
+    lines: L,
+    sink: W,
+    peer: String,
+    engine: Arc<ChatEngine>,
+    db: SqlitePool,
+    config: Arc<IrcSection>,
+    cert_fp: Option<String>,
+) where
+    L: Stream<Item = std::io::Result<String>> + Send,
+    W: Sink<String> + Send + 'static,
 {
     info!(%peer, "IRC client connected");
 
-    let (reader, writer) = tokio::io::split(stream);
-    let mut reader = BufReader::new(reader);
-    let mut writer = writer;
+    let mut lines = std::pin::pin!(lines);
 
     // Channel for outbound lines (from event loop and command handlers)
     let (out_tx, mut out_rx) = mpsc::unbounded_channel::<String>();
 
     // Spawn writer task
     let write_handle = tokio::spawn(async move {
+        let mut sink = std::pin::pin!(sink);
         while let Some(line) = out_rx.recv().await {
-            let data = format!("{}\r\n", line);
-            if writer.write_all(data.as_bytes()).await.is_err() {
+            if sink.send(line).await.is_err() {
                 break;
             }
         }
@@ -98,23 +355,32 @@ where
         pass: None,
         nick: None,
         user_received: false,
+        account: None,
     };
 
-    let mut line_buf = String::new();
+    let mut caps = CapState::default();
+    let mut sasl = SaslState::default();
+    let mut sasl_failures = 0;
+    let mut away = AwayTracker::default();
+    let mut typing = TypingThrottle::default();
+    // Which client this is, for its read markers (`USER <name>@<client>`)
+    let mut client = DEFAULT_CLIENT.to_string();
+    let mut markers = history::ReadMarkers::default();
+
     let mut event_rx: Option<mpsc::Receiver<ChatEvent>> = None;
+    let registration_deadline = tokio::time::Instant::now() + config.registration_timeout();
+    let mut keepalive = Keepalive::new(config.ping_interval(), config.ping_timeout());
 
     loop {
         // When registered, also select on engine events
         if let Some(ref mut rx) = event_rx {
             tokio::select! {
-                result = tokio::time::timeout(IDLE_TIMEOUT, read_bounded_line(&mut reader, &mut line_buf)) => {
-                    match result {
-                        Ok(Ok(0)) | Ok(Err(_)) | Err(_) => break, // EOF, error, or timeout
-                        Ok(Ok(_)) => {}
-                    }
-
-                    let line = line_buf.trim_end().to_string();
-                    line_buf.clear();
+                result = lines.next() => {
+                    let line = match result {
+                        Some(Ok(line)) => line.trim_end().to_string(),
+                        _ => break, // EOF or error
+                    };
+                    keepalive.heard(tokio::time::Instant::now());
 
                     if line.is_empty() {
                         continue;
@@ -136,31 +402,111 @@ where
                             break;
                         }
 
-                        let replies = commands::handle_command(&engine, *session_id, nick, &msg);
+                        let replies = match msg.command.as_str() {
+                            "CAP" => {
+                                let replies = caps.handle(nick, &msg, true);
+                                engine.set_echo_message(*session_id, caps.has("echo-message"));
+                                replies
+                            }
+                            "CHATHISTORY" => history::handle_chathistory(&engine, *session_id, nick, &caps, &msg).await,
+                            "JOIN" | "PART" | "TOPIC" | "NAMES" | "MODE" | "KICK"
+                                if msg.params.first().is_some_and(|t| group_dm::is_group_target(t)) =>
+                            {
+                                group_dm::handle_command(&engine, *session_id, nick, &caps, &msg).await
+                            }
+                            "INVITE" if msg.params.get(1).is_some_and(|t| group_dm::is_group_target(t)) => {
+                                group_dm::handle_command(&engine, *session_id, nick, &caps, &msg).await
+                            }
+                            "JOIN" => access::handle_join(&engine, *session_id, nick, &msg).await,
+                            "INVITE" => access::handle_invite(&engine, *session_id, nick, &msg).await,
+                            "KNOCK" => access::handle_knock(&engine, *session_id, nick, &msg).await,
+                            "LIST" => list::handle_list(&engine, *session_id, nick, &msg).await,
+                            "CERT" => certs::handle_cert(&engine, *session_id, nick, cert_fp.as_deref(), &msg).await,
+                            "OPER" => oper::handle_oper(&engine, *session_id, nick, &msg).await,
+                            "DELSERVER" => oper::handle_delserver(&engine, *session_id, nick, &msg).await,
+                            "MODE" => moderation::handle_mode(&engine, *session_id, nick, &msg).await,
+                            "KICK" => moderation::handle_kick(&engine, *session_id, nick, &msg).await,
+                            "AWAY" => commands::handle_away(&engine, *session_id, nick, &msg).await,
+                            "REDACT" => edits::handle_redact(&engine, *session_id, nick, &msg).await,
+                            "TAGMSG" => commands::handle_tagmsg(&engine, *session_id, &msg).await,
+                            "PRIVMSG" if msg.tag("+draft/edit").is_some() => {
+                                edits::handle_edit(&engine, *session_id, nick, &msg).await
+                            }
+                            "MOTD" => commands::motd_lines(&engine, nick, msg.params.first().map(String::as_str)).await,
+                            _ => commands::handle_command(&engine, *session_id, nick, &caps, &msg),
+                        };
                         for reply in replies {
                             send_line(&out_tx, &reply);
                         }
                     }
                 }
+                _ = tokio::time::sleep_until(keepalive.deadline()) => {
+                    if keepalive.expire(tokio::time::Instant::now()) {
+                        send_line(&out_tx, &format!("PING :{}", formatter::server_name()));
+                    } else {
+                        let nick = match state {
+                            RegState::Registered { ref nick, .. } => nick.as_str(),
+                            _ => "*",
+                        };
+                        send_line(&out_tx, &format!(
+                            "ERROR :Closing Link: {} (Ping timeout: {} seconds)",
+                            nick,
+                            config.ping_timeout_secs
+                        ));
+                        break;
+                    }
+                }
                 event = rx.recv() => {
                     let Some(event) = event else { break };
-                    if let RegState::Registered { ref nick, .. } = state {
-                        let lines = event_to_irc_lines(&engine, nick, &event);
+                    if let RegState::Registered { ref session_id, ref nick } = state {
+                        if !away.allow(&event) {
+                            continue;
+                        }
+                        if !typing.allow(&event, Instant::now()) {
+                            continue;
+                        }
+                        let lines = event_to_irc_lines(&engine, nick, &caps, &event);
                         for line in lines {
                             send_line(&out_tx, &line);
                         }
+                        if matches!(event, ChatEvent::Killed { .. }) {
+                            break;
+                        }
+                        markers.record(&event);
+
+                        // NAMES closes the join burst; clients that can't ask for
+                        // history get the most recent lines replayed after it.
+                        if let ChatEvent::Names { server_id, channel, .. } = &event
+                            && !caps.has("draft/chathistory")
+                        {
+                            let replay = history::join_replay(
+                                &engine,
+                                *session_id,
+                                &caps,
+                                &client,
+                                &mut markers,
+                                server_id,
+                                channel,
+                            )
+                            .await;
+                            for line in replay {
+                                send_line(&out_tx, &line);
+                            }
+                        }
                     }
                 }
             }
         } else {
-            // Not registered yet — just read lines (with timeout)
-            match tokio::time::timeout(IDLE_TIMEOUT, read_bounded_line(&mut reader, &mut line_buf)).await {
-                Ok(Ok(0)) | Ok(Err(_)) | Err(_) => break, // EOF, error, or timeout
-                Ok(Ok(_)) => {}
-            }
-
-            let line = line_buf.trim_end().to_string();
-            line_buf.clear();
+            // Not registered yet — just read lines, until the registration deadline
+            let line = match tokio::time::timeout_at(registration_deadline, lines.next()).await {
+                Ok(Some(Ok(line))) => line.trim_end().to_string(),
+                Ok(_) => break, // EOF or error
+                Err(_) => {
+                    send_line(&out_tx, "ERROR :Closing Link: * (Registration timed out)");
+                    break;
+                }
+            };
+            keepalive.heard(tokio::time::Instant::now());
 
             if line.is_empty() {
                 continue;
@@ -171,36 +517,73 @@ where
                 Err(_) => continue,
             };
 
-            // Handle CAP during registration
-            if msg.command == "CAP" {
-                if msg.params.first().map(|s| s.as_str()) == Some("LS") {
-                    send_line(
-                        &out_tx,
-                        &format!(":{} CAP * LS :", formatter::server_name()),
-                    );
-                }
-                // CAP END just falls through
-                continue;
-            }
-
             // Process registration commands
             match msg.command.as_str() {
+                "CAP" => {
+                    for reply in caps.handle("*", &msg, false) {
+                        send_line(&out_tx, &reply);
+                    }
+                }
                 "PASS" => {
                     if let RegState::Unregistered { ref mut pass, .. } = state {
                         *pass = msg.params.first().cloned();
                     }
                 }
-                "NICK" => {
-                    let Some(wanted_nick) = msg.params.first() else {
-                        send_line(&out_tx, &formatter::err_nonicknamegiven("*"));
+                "AUTHENTICATE" => {
+                    let RegState::Unregistered {
+                        ref nick,
+                        ref mut account,
+                        ..
+                    } = state
+                    else {
                         continue;
                     };
+                    let target = nick.clone().unwrap_or_else(|| "*".to_string());
 
-                    if !engine.is_nick_available(wanted_nick) {
-                        send_line(&out_tx, &formatter::err_nicknameinuse("*", wanted_nick));
+                    let Some(arg) = msg.params.first() else {
+                        send_line(&out_tx, &formatter::err_needmoreparams(&target, "AUTHENTICATE"));
+                        continue;
+                    };
+                    if !caps.has("sasl") {
+                        send_line(&out_tx, &formatter::err_saslfail(&target));
                         continue;
                     }
+                    if account.is_some() {
+                        send_line(&out_tx, &formatter::err_saslalready(&target));
+                        continue;
+                    }
+
+                    let (replies, authenticated) = handle_authenticate(
+                        &db,
+                        cert_fp.as_deref(),
+                        &mut sasl,
+                        &mut sasl_failures,
+                        &target,
+                        arg,
+                    )
+                    .await;
+                    for reply in replies {
+                        send_line(&out_tx, &reply);
+                    }
+                    if sasl_failures >= MAX_SASL_FAILURES {
+                        send_line(&out_tx, &format!(
+                            "ERROR :Closing Link: {} (Too many failed SASL attempts)",
+                            target
+                        ));
+                        break;
+                    }
+                    if authenticated.is_some() {
+                        *account = authenticated;
+                    }
+                }
+                "NICK" => {
+                    let Some(wanted_nick) = msg.params.first() else {
+                        send_line(&out_tx, &formatter::err_nonicknamegiven("*"));
+                        continue;
+                    };
 
+                    // Availability is checked at registration time, once we
+                    // know whether the client authenticated.
                     if let RegState::Unregistered { ref mut nick, .. } = state {
                         *nick = Some(wanted_nick.clone());
                     }
@@ -212,6 +595,9 @@ where
                     } = state
                     {
                         *user_received = true;
+                        if let Some(name) = msg.params.first().and_then(|u| client_name(u)) {
+                            client = name.to_string();
+                        }
                     }
                 }
                 "QUIT" => break,
@@ -226,13 +612,27 @@ where
                 ref pass,
                 ref nick,
                 user_received,
+                ref account,
             } = state
-                && let (Some(nick_val), true) = (nick.as_ref(), user_received)
+                && let (Some(nick_val), true) = (nick.clone(), user_received)
+                && !caps.negotiating()
             {
-                // If a PASS was provided, validate it as an IRC token
-                let user_id = if let Some(pass_token) = pass {
-                    match validate_irc_pass(&db, pass_token, nick_val).await {
-                        Ok(Some(uid)) => Some(uid),
+                let pass = pass.clone();
+                let account = account.clone();
+
+                // SASL wins; otherwise a PASS is validated as an IRC token for the nick
+                let identity = if let Some(account) = account {
+                    Some(account)
+                } else if let Some(pass_token) = pass {
+                    let validated = match validate_irc_pass(&db, &pass_token, &nick_val).await {
+                        Ok(Some(uid)) => login_account(&db, &uid)
+                            .await
+                            .map(|account| Some((uid, account))),
+                        Ok(None) => Ok(None),
+                        Err(e) => Err(e),
+                    };
+                    match validated {
+                        Ok(Some(identity)) => Some(identity),
                         Ok(None) => {
                             send_line(
                                 &out_tx,
@@ -261,17 +661,78 @@ where
                     None
                 };
 
+                // Authenticated users are never turned away over a nick collision;
+                // guests must pick a free nick.
+                let session_nick = match &identity {
+                    Some((uid, account)) => resolve_login_nick(&engine, &nick_val, uid, account),
+                    None => {
+                        if !engine.is_nick_available(&nick_val) {
+                            send_line(&out_tx, &formatter::err_nicknameinuse("*", &nick_val));
+                            if let RegState::Unregistered { ref mut nick, .. } = state {
+                                *nick = None;
+                            }
+                            continue;
+                        }
+                        nick_val.clone()
+                    }
+                };
+                let (user_id, account) = identity.unzip();
+
                 // Try to register with the engine
-                match engine.connect(user_id, nick_val.clone(), Protocol::Irc, None) {
+                match engine.connect(user_id, session_nick.clone(), Protocol::Irc, None) {
                     Ok((sid, rx)) => {
-                        let nick_owned = nick_val.clone();
+                        // A logged-in user's other sessions may have given us their nick
+                        let nick_owned = engine
+                            .get_session(sid)
+                            .map(|s| s.nickname.clone())
+                            .unwrap_or(session_nick);
+                        engine.set_echo_message(sid, caps.has("echo-message"));
+                        if let Some(account) = account {
+                            engine.set_account(sid, account);
+                        }
 
                         // Send welcome burst
                         send_line(&out_tx, &formatter::rpl_welcome(&nick_owned));
                         send_line(&out_tx, &formatter::rpl_yourhost(&nick_owned));
                         send_line(&out_tx, &formatter::rpl_created(&nick_owned));
                         send_line(&out_tx, &formatter::rpl_myinfo(&nick_owned));
-                        send_line(&out_tx, &formatter::err_nomotd(&nick_owned));
+                        for line in commands::isupport_lines(&engine, &nick_owned) {
+                            send_line(&out_tx, &line);
+                        }
+                        for line in commands::lusers_lines(&engine, &nick_owned) {
+                            send_line(&out_tx, &line);
+                        }
+                        for line in commands::motd_lines(&engine, &nick_owned, None).await {
+                            send_line(&out_tx, &line);
+                        }
+                        // Group DMs open like channels the user is already in
+                        if let Some(uid) = engine.get_session(sid).and_then(|s| s.user_id.clone()) {
+                            match engine.list_group_dms(&uid).await {
+                                Ok(groups) => {
+                                    for info in &groups {
+                                        for line in group_dm::join_lines(&nick_owned, &caps, info) {
+                                            send_line(&out_tx, &line);
+                                        }
+                                    }
+                                }
+                                Err(e) => warn!(error = %e, "failed to list group DMs"),
+                            }
+                        }
+                        // Channels replay after their NAMES; direct messages go now
+                        if !caps.has("draft/chathistory") {
+                            let replay = history::direct_replay(
+                                &engine,
+                                sid,
+                                &nick_owned,
+                                &caps,
+                                &client,
+                                &mut markers,
+                            )
+                            .await;
+                            for line in replay {
+                                send_line(&out_tx, &line);
+                            }
+                        }
 
                         state = RegState::Registered {
                             session_id: sid,
@@ -281,7 +742,7 @@ where
                     }
                     Err(e) => {
                         warn!(error = %e, "IRC registration failed");
-                        send_line(&out_tx, &formatter::err_nicknameinuse("*", nick_val));
+                        send_line(&out_tx, &formatter::err_nicknameinuse("*", &session_nick));
                     }
                 }
             }
@@ -290,24 +751,33 @@ where
 
     // Disconnect from engine if registered
     if let RegState::Registered { session_id, nick } = state {
+        markers.save(&engine, session_id, &client).await;
         engine.disconnect(session_id);
         info!(%peer, %nick, "IRC client disconnected");
     } else {
         info!(%peer, "IRC client disconnected (unregistered)");
     }
 
-    write_handle.abort();
+    // Let queued lines such as a closing ERROR go out, unless the client has stopped reading
+    drop(out_tx);
+    let mut write_handle = write_handle;
+    if tokio::time::timeout(FLUSH_TIMEOUT, &mut write_handle)
+        .await
+        .is_err()
+    {
+        write_handle.abort();
+    }
 }
 
-/// Validate an IRC PASS token against stored hashes.
+/// Validate an IRC token (from PASS or SASL PLAIN) against the account's stored hashes.
 /// Returns Ok(Some(user_id)) if the token matches, Ok(None) if not.
-async fn validate_irc_pass(
+pub(super) async fn validate_irc_pass(
     db: &SqlitePool,
     token: &str,
-    nickname: &str,
+    account: &str,
 ) -> Result<Option<String>, String> {
-    // Scoped lookup: only fetch tokens for this nickname (O(1) per user instead of O(n) global)
-    let hashes = users::get_irc_token_hashes_by_nick(db, nickname)
+    // Scoped lookup: only fetch tokens for this account (O(1) per user instead of O(n) global)
+    let hashes = users::get_irc_token_hashes_by_nick(db, account)
         .await
         .map_err(|e| format!("DB error: {}", e))?;
 
@@ -327,24 +797,221 @@ async fn validate_irc_pass(
     Ok(None)
 }
 
+/// Feed one `AUTHENTICATE` argument into the SASL exchange.
+/// Returns the reply lines and, on success, the authenticated (user_id, account).
+/// A completed exchange that fails adds one to `failures`.
+async fn handle_authenticate(
+    db: &SqlitePool,
+    cert_fp: Option<&str>,
+    sasl: &mut SaslState,
+    failures: &mut u32,
+    nick: &str,
+    arg: &str,
+) -> (Vec<String>, Option<(String, String)>) {
+    let SaslState::Awaiting { mechanism, buffer } = sasl else {
+        // Start of an exchange: the argument names the mechanism
+        let mechanism = match arg.to_uppercase().as_str() {
+            "*" => return (vec![formatter::err_saslaborted(nick)], None),
+            "PLAIN" => SaslMechanism::Plain,
+            "EXTERNAL" if cert_fp.is_some() => SaslMechanism::External,
+            "EXTERNAL" => return (vec![formatter::err_saslfail(nick)], None),
+            _ => {
+                return (
+                    vec![
+                        formatter::rpl_saslmechs(nick, SASL_MECHANISMS),
+                        formatter::err_saslfail(nick),
+                    ],
+                    None,
+                );
+            }
+        };
+        *sasl = SaslState::Awaiting {
+            mechanism,
+            buffer: String::new(),
+        };
+        return (vec![formatter::authenticate("+")], None);
+    };
+
+    if arg == "*" {
+        *sasl = SaslState::Idle;
+        return (vec![formatter::err_saslaborted(nick)], None);
+    }
+    if arg != "+" {
+        buffer.push_str(arg);
+    }
+    if buffer.len() > SASL_MAX_PAYLOAD {
+        *sasl = SaslState::Idle;
+        return (vec![formatter::err_sasltoolong(nick)], None);
+    }
+    if arg.len() == SASL_CHUNK_SIZE {
+        // A full chunk means the payload continues on the next line
+        return (vec![], None);
+    }
+
+    let mechanism = *mechanism;
+    let payload = std::mem::take(buffer);
+    *sasl = SaslState::Idle;
+
+    let result = match BASE64.decode(payload.as_bytes()) {
+        Ok(bytes) => match mechanism {
+            SaslMechanism::Plain => sasl_plain(db, &bytes).await,
+            SaslMechanism::External => sasl_external(db, cert_fp, &bytes).await,
+        },
+        Err(_) => Ok(None),
+    };
+
+    match result {
+        Ok(Some((user_id, account))) => (
+            vec![
+                formatter::rpl_loggedin(nick, &account),
+                formatter::rpl_saslsuccess(nick),
+            ],
+            Some((user_id, account)),
+        ),
+        Ok(None) => {
+            *failures += 1;
+            (vec![formatter::err_saslfail(nick)], None)
+        }
+        Err(e) => {
+            warn!(error = %e, "SASL authentication error");
+            *failures += 1;
+            (vec![formatter::err_saslfail(nick)], None)
+        }
+    }
+}
+
+/// SASL PLAIN: `authzid NUL authcid NUL token`, where authcid is the account
+/// name and token is one of the account's IRC tokens.
+async fn sasl_plain(db: &SqlitePool, payload: &[u8]) -> Result<Option<(String, String)>, String> {
+    let Ok(text) = std::str::from_utf8(payload) else {
+        return Ok(None);
+    };
+    let mut parts = text.split('\0');
+    let (Some(authzid), Some(authcid), Some(token), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Ok(None);
+    };
+    if !authzid.is_empty() && authzid != authcid {
+        return Ok(None);
+    }
+
+    let Some(user_id) = validate_irc_pass(db, token, authcid).await? else {
+        return Ok(None);
+    };
+    let account = login_account(db, &user_id).await?;
+    Ok(Some((user_id, account)))
+}
+
+/// SASL EXTERNAL: the TLS client certificate identifies the user. An optional
+/// authzid must match the bound account.
+async fn sasl_external(
+    db: &SqlitePool,
+    cert_fp: Option<&str>,
+    authzid: &[u8],
+) -> Result<Option<(String, String)>, String> {
+    let Some(fingerprint) = cert_fp else {
+        return Ok(None);
+    };
+    let Some((user_id, username)) = users::get_user_by_cert_fingerprint(db, fingerprint)
+        .await
+        .map_err(|e| format!("DB error: {}", e))?
+    else {
+        return Ok(None);
+    };
+    if !authzid.is_empty() && authzid != username.as_bytes() {
+        return Ok(None);
+    }
+    let account = login_account(db, &user_id).await?;
+    Ok(Some((user_id, account)))
+}
+
+/// The account name a login goes by, the same one WHOIS, WHOX and
+/// extended-join show: the atproto DID for Bluesky users, else the username.
+async fn login_account(db: &SqlitePool, user_id: &str) -> Result<String, String> {
+    users::get_account_name(db, user_id)
+        .await
+        .map_err(|e| format!("DB error: {}", e))?
+        .ok_or_else(|| format!("No such user: {user_id}"))
+}
+
+/// The client name in a USER username of the form `<name>@<client>`, which
+/// keeps that client's read markers apart from the account's other clients.
+fn client_name(username: &str) -> Option<&str> {
+    let (_, client) = username.split_once('@')?;
+    let valid = !client.is_empty()
+        && client.len() <= MAX_CLIENT_NAME_LEN
+        && client
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    valid.then_some(client)
+}
+
+/// Pick the session nickname for an authenticated login. An account that is
+/// already online elsewhere keeps its current nick. Otherwise the requested
+/// nick is kept unless a different user holds it; then the account name, or
+/// a numbered variant of it, is used instead. An account name that can't be
+/// a nick, such as a DID, numbers the requested nick instead.
+fn resolve_login_nick(engine: &ChatEngine, requested: &str, user_id: &str, account: &str) -> String {
+    if let Some(nick) = engine.nick_of_user(user_id) {
+        return nick;
+    }
+    let account = if validation::validate_nickname(account).is_ok() {
+        account
+    } else {
+        requested
+    };
+    let usable = |nick: &str| match engine.get_session_by_nick(nick) {
+        Some(session) => session.user_id.as_deref() == Some(user_id),
+        None => true,
+    };
+
+    if usable(requested) {
+        return requested.to_string();
+    }
+    if usable(account) {
+        return account.to_string();
+    }
+    (1..)
+        .map(|n| format!("{account}{n}"))
+        .find(|nick| usable(nick))
+        .unwrap_or_else(|| account.to_string())
+}
+
 /// Convert a ChatEvent to IRC protocol lines for a specific recipient.
-/// Uses the engine to translate (server_id, channel_name) to IRC format.
-fn event_to_irc_lines(engine: &ChatEngine, my_nick: &str, event: &ChatEvent) -> Vec<String> {
+/// Uses the engine to translate (server_id, channel_name) to IRC format, and
+/// the recipient's negotiated capabilities to pick the output form.
+fn event_to_irc_lines(
+    engine: &ChatEngine,
+    my_nick: &str,
+    caps: &CapState,
+    event: &ChatEvent,
+) -> Vec<String> {
     match event {
         ChatEvent::Message {
+            id,
             server_id,
             from,
             target,
             content,
+            timestamp,
+            reply_to,
+            kind,
             ..
         } => {
+            // Group DM changes arrive as JOIN, PART, KICK, TOPIC and MODE instead
+            if *kind == MessageKind::System {
+                return vec![];
+            }
             let irc_target = if target.starts_with('#') {
                 let sid = server_id.as_deref().unwrap_or(DEFAULT_SERVER_ID);
                 to_irc_channel(engine, sid, target)
             } else {
                 target.clone()
             };
-            vec![formatter::privmsg(from, &irc_target, content)]
+            let reply_to = reply_to.as_ref().map(|r| r.id.as_str());
+            let tags = message_tags(caps, *timestamp, &id.to_string(), reply_to);
+            message_lines(tags, from, &irc_target, content, *kind)
         }
         ChatEvent::Join {
             nickname,
@@ -353,7 +1020,16 @@ fn event_to_irc_lines(engine: &ChatEngine, my_nick: &str, event: &ChatEvent) ->
             ..
         } => {
             let irc_channel = to_irc_channel(engine, server_id, channel);
-            vec![formatter::join(nickname, &irc_channel)]
+            let line = if caps.has("extended-join") {
+                let account = engine
+                    .get_session_by_nick(nickname)
+                    .and_then(|session| session.account().map(String::from))
+                    .unwrap_or_else(|| "*".into());
+                formatter::extended_join(nickname, &irc_channel, &account, nickname)
+            } else {
+                formatter::join(nickname, &irc_channel)
+            };
+            vec![formatter::with_tags(&live_event_tags(caps), &line)]
         }
         ChatEvent::Part {
             nickname,
@@ -362,7 +1038,10 @@ fn event_to_irc_lines(engine: &ChatEngine, my_nick: &str, event: &ChatEvent) ->
             reason,
         } => {
             let irc_channel = to_irc_channel(engine, server_id, channel);
-            vec![formatter::part(nickname, &irc_channel, reason.as_deref())]
+            vec![formatter::with_tags(
+                &live_event_tags(caps),
+                &formatter::part(nickname, &irc_channel, reason.as_deref()),
+            )]
         }
         ChatEvent::Quit { nickname, reason } => {
             vec![formatter::quit(nickname, reason.as_deref())]
@@ -374,7 +1053,10 @@ fn event_to_irc_lines(engine: &ChatEngine, my_nick: &str, event: &ChatEvent) ->
             topic,
         } => {
             let irc_channel = to_irc_channel(engine, server_id, channel);
-            vec![formatter::topic_change(set_by, &irc_channel, topic)]
+            vec![formatter::with_tags(
+                &live_event_tags(caps),
+                &formatter::topic_change(set_by, &irc_channel, topic),
+            )]
         }
         ChatEvent::NickChange { old_nick, new_nick } => {
             vec![formatter::nick_change(old_nick, new_nick)]
@@ -385,7 +1067,7 @@ fn event_to_irc_lines(engine: &ChatEngine, my_nick: &str, event: &ChatEvent) ->
             members,
         } => {
             let irc_channel = to_irc_channel(engine, server_id, channel);
-            let nicks: Vec<String> = members.iter().map(|m| m.nickname.clone()).collect();
+            let nicks: Vec<String> = members.iter().map(|m| names_entry(m, caps)).collect();
             vec![
                 formatter::rpl_namreply(my_nick, &irc_channel, &nicks),
                 formatter::rpl_endofnames(my_nick, &irc_channel),
@@ -420,32 +1102,38 @@ fn event_to_irc_lines(engine: &ChatEngine, my_nick: &str, event: &ChatEvent) ->
                 message
             )]
         }
-        // Message edit: send a NOTICE indicating the edit
         ChatEvent::MessageEdit {
-            server_id, channel, ..
+            id,
+            server_id,
+            channel,
+            from,
+            content,
+            edited_at,
         } => {
             let irc_channel = to_irc_channel(engine, server_id, channel);
-            vec![format!(
-                ":{} NOTICE {} :* A message was edited in {}",
-                formatter::server_name(),
-                my_nick,
-                irc_channel
-            )]
+            edits::edit_lines(caps, from, &irc_channel, &id.to_string(), content, *edited_at)
         }
-        // Message delete: send a NOTICE indicating the deletion
         ChatEvent::MessageDelete {
-            server_id, channel, ..
+            id,
+            server_id,
+            channel,
+            deleted_by,
         } => {
             let irc_channel = to_irc_channel(engine, server_id, channel);
-            vec![format!(
-                ":{} NOTICE {} :* A message was deleted in {}",
-                formatter::server_name(),
-                my_nick,
-                irc_channel
-            )]
+            if caps.has("draft/message-redaction") {
+                let line = formatter::redact(deleted_by, &irc_channel, &id.to_string());
+                vec![formatter::with_tags(&live_event_tags(caps), &line)]
+            } else {
+                vec![format!(
+                    ":{} NOTICE {} :* A message was deleted in {}",
+                    formatter::server_name(),
+                    my_nick,
+                    irc_channel
+                )]
+            }
         }
-        // Reactions: send a NOTICE with the reaction info
         ChatEvent::ReactionAdd {
+            message_id,
             server_id,
             channel,
             nickname,
@@ -453,18 +1141,66 @@ fn event_to_irc_lines(engine: &ChatEngine, my_nick: &str, event: &ChatEvent) ->
             ..
         } => {
             let irc_channel = to_irc_channel(engine, server_id, channel);
-            vec![format!(
-                ":{} NOTICE {} :* {} reacted with {} in {}",
-                formatter::server_name(),
-                my_nick,
+            if caps.has("message-tags") {
+                vec![edits::reaction_tagmsg(
+                    caps,
+                    nickname,
+                    &irc_channel,
+                    "+draft/react",
+                    emoji,
+                    &message_id.to_string(),
+                )]
+            } else {
+                vec![format!(
+                    ":{} NOTICE {} :* {} reacted with {} in {}",
+                    formatter::server_name(),
+                    my_nick,
+                    nickname,
+                    emoji,
+                    irc_channel
+                )]
+            }
+        }
+        // Without client tags there's nothing useful to say about a removed reaction
+        ChatEvent::ReactionRemove {
+            message_id,
+            server_id,
+            channel,
+            nickname,
+            emoji,
+            ..
+        } if caps.has("message-tags") => {
+            let irc_channel = to_irc_channel(engine, server_id, channel);
+            vec![edits::reaction_tagmsg(
+                caps,
                 nickname,
+                &irc_channel,
+                "+draft/unreact",
                 emoji,
-                irc_channel
+                &message_id.to_string(),
             )]
         }
         ChatEvent::ReactionRemove { .. } => vec![],
-        // Typing indicators are not sent to IRC
-        ChatEvent::TypingStart { .. } => vec![],
+        // Typing indicators need client-only tags; `connection` throttles `active`
+        ChatEvent::TypingStart {
+            server_id,
+            channel,
+            nickname,
+        } if caps.has("message-tags") => {
+            let irc_channel = to_irc_channel(engine, server_id, channel);
+            vec![typing_tagmsg(caps, nickname, &irc_channel, "active")]
+        }
+        ChatEvent::TypingStop {
+            server_id,
+            channel,
+            nickname,
+            paused,
+        } if caps.has("message-tags") => {
+            let irc_channel = to_irc_channel(engine, server_id, channel);
+            let state = if *paused { "paused" } else { "done" };
+            vec![typing_tagmsg(caps, nickname, &irc_channel, state)]
+        }
+        ChatEvent::TypingStart { .. } | ChatEvent::TypingStop { .. } => vec![],
         // Embeds are WebSocket-only (rich previews don't map to IRC)
         ChatEvent::MessageEmbed { .. } => vec![],
         // Phase 5: Pinning — send NOTICEs for pin/unpin actions
@@ -500,11 +1236,14 @@ fn event_to_irc_lines(engine: &ChatEngine, my_nick: &str, event: &ChatEvent) ->
             thread,
         } => {
             let irc_channel = to_irc_channel(engine, server_id, parent_channel);
+            let name = thread.name.strip_prefix('#').unwrap_or(&thread.name);
             vec![format!(
-                ":{} NOTICE {} :\u{1f9f5} New thread: {}",
+                ":{} NOTICE {} :\u{1f9f5} New thread: {} (/join {}/{})",
                 formatter::server_name(),
                 irc_channel,
-                thread.name
+                name,
+                irc_channel,
+                commands::thread_path_slug(engine, server_id, parent_channel, &thread.name)
             )]
         }
         ChatEvent::ThreadUpdate {
@@ -542,6 +1281,100 @@ fn event_to_irc_lines(engine: &ChatEngine, my_nick: &str, event: &ChatEvent) ->
                 reason_text
             )]
         }
+        ChatEvent::ChannelKick {
+            server_id,
+            channel,
+            nickname,
+            kicked_by,
+            reason,
+        } => {
+            let irc_channel = to_irc_channel(engine, server_id, channel);
+            vec![formatter::kick(kicked_by, &irc_channel, nickname, reason.as_deref())]
+        }
+        ChatEvent::ChannelInvite {
+            server_id,
+            channel,
+            invited_by,
+            code,
+        } => {
+            let irc_channel = to_irc_channel(engine, server_id, channel);
+            let mut lines = vec![formatter::invite(invited_by, my_nick, &irc_channel)];
+            if let Some(code) = code {
+                lines.push(formatter::server_notice(
+                    my_nick,
+                    &format!("Your invite code for {irc_channel} is {code}. Join with: /join {irc_channel} {code}"),
+                ));
+            }
+            lines
+        }
+        ChatEvent::GroupDmUpdate {
+            conversation,
+            change,
+        } => group_dm::change_lines(my_nick, caps, conversation, change),
+        ChatEvent::Wallops { from, message } => vec![formatter::wallops(from, message)],
+        ChatEvent::MonitorStatus { nickname, online } => {
+            let targets = [nickname.clone()];
+            if *online {
+                vec![formatter::rpl_mononline(my_nick, &targets)]
+            } else {
+                vec![formatter::rpl_monoffline(my_nick, &targets)]
+            }
+        }
+        ChatEvent::Killed { killed_by, reason } => vec![format!(
+            "ERROR :Closing Link: {my_nick} (Killed ({killed_by} ({reason})))"
+        )],
+        ChatEvent::ChannelKnock {
+            server_id,
+            channel,
+            nickname,
+            message,
+        } => {
+            let irc_channel = to_irc_channel(engine, server_id, channel);
+            vec![formatter::rpl_knock(my_nick, &irc_channel, nickname, message.as_deref())]
+        }
+        ChatEvent::PresenceUpdate { presence, .. } => {
+            // Going offline is already visible as a QUIT or PART
+            if !caps.has("away-notify") || presence.status == "offline" {
+                return vec![];
+            }
+            let message = commands::away_message(presence);
+            vec![formatter::away(&presence.nickname, message.as_deref())]
+        }
+        ChatEvent::MemberPrefixUpdate {
+            server_id,
+            channel,
+            nickname,
+            added,
+            removed,
+        } => {
+            let modes = commands::prefix_mode_change(added, removed);
+            let letters = modes.chars().filter(|c| *c != '+' && *c != '-').count();
+            if letters == 0 {
+                return vec![];
+            }
+            let args = vec![nickname.as_str(); letters];
+            vec![formatter::server_mode(
+                &to_irc_channel(engine, server_id, channel),
+                &modes,
+                &args,
+            )]
+        }
+        ChatEvent::PrivacyUpdate {
+            server_id,
+            channel,
+            is_private,
+        } => {
+            let modes = if *is_private { "+s" } else { "-s" };
+            channel_mode_line(engine, my_nick, server_id, channel, modes)
+        }
+        ChatEvent::ModeratedUpdate {
+            server_id,
+            channel,
+            moderated,
+        } => {
+            let modes = if *moderated { "+m" } else { "-m" };
+            channel_mode_line(engine, my_nick, server_id, channel, modes)
+        }
         ChatEvent::MemberUnban { .. } => vec![],
         ChatEvent::MemberTimeout { .. } => vec![],
         ChatEvent::SlowModeUpdate { .. } => vec![],
@@ -565,7 +1398,6 @@ fn event_to_irc_lines(engine: &ChatEngine, my_nick: &str, event: &ChatEvent) ->
         | ChatEvent::CategoryUpdate { .. }
         | ChatEvent::CategoryDelete { .. }
         | ChatEvent::ChannelReorder { .. }
-        | ChatEvent::PresenceUpdate { .. }
         | ChatEvent::PresenceList { .. }
         | ChatEvent::UserProfile { .. }
         | ChatEvent::ServerNicknameUpdate { .. }
@@ -576,9 +1408,13 @@ fn event_to_irc_lines(engine: &ChatEngine, my_nick: &str, event: &ChatEvent) ->
         | ChatEvent::ForumTagList { .. }
         | ChatEvent::ForumTagUpdate { .. }
         | ChatEvent::ForumTagDelete { .. }
+        | ChatEvent::MessageEditHistory { .. }
         | ChatEvent::BookmarkList { .. }
         | ChatEvent::BookmarkAdd { .. }
         | ChatEvent::BookmarkRemove { .. }
+        // The message itself already highlights the user in their client
+        | ChatEvent::Mention { .. }
+        | ChatEvent::MentionList { .. }
         | ChatEvent::InviteList { .. }
         | ChatEvent::InviteCreate { .. }
         | ChatEvent::InviteDelete { .. }
@@ -587,6 +1423,7 @@ fn event_to_irc_lines(engine: &ChatEngine, my_nick: &str, event: &ChatEvent) ->
         | ChatEvent::EventDelete { .. }
         | ChatEvent::EventRsvpList { .. }
         | ChatEvent::ServerCommunity { .. }
+        | ChatEvent::ServerMotd { .. }
         | ChatEvent::DiscoverServers { .. }
         | ChatEvent::ChannelFollowList { .. }
         | ChatEvent::ChannelFollowCreate { .. }
@@ -609,21 +1446,250 @@ fn event_to_irc_lines(engine: &ChatEngine, my_nick: &str, event: &ChatEvent) ->
     }
 }
 
-fn send_line(tx: &mpsc::UnboundedSender<String>, line: &str) {
-    let _ = tx.send(line.to_string());
+/// A channel MODE change made outside IRC, shown only to members of the channel.
+fn channel_mode_line(
+    engine: &ChatEngine,
+    my_nick: &str,
+    server_id: &str,
+    channel: &str,
+    modes: &str,
+) -> Vec<String> {
+    let in_channel = engine
+        .get_members(server_id, channel)
+        .is_ok_and(|members| members.iter().any(|m| m.nickname == my_nick));
+    if !in_channel {
+        return vec![];
+    }
+    vec![formatter::server_mode(
+        &to_irc_channel(engine, server_id, channel),
+        modes,
+        &[],
+    )]
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::engine::events::{MemberInfo, PinnedMessageInfo, ThreadInfo};
-    use chrono::Utc;
-    use std::sync::Arc;
-    use uuid::Uuid;
-
-    /// Create a minimal ChatEngine with no database for unit tests.
-    fn test_engine() -> Arc<ChatEngine> {
-        Arc::new(ChatEngine::new(None))
+/// IRCv3 tags for an outgoing line: `time` with server-time, `msgid` with message-tags.
+pub(super) fn event_tags(
+    caps: &CapState,
+    time: DateTime<Utc>,
+    msgid: &str,
+) -> Vec<(String, String)> {
+    let mut tags = Vec::new();
+    if caps.has("server-time") {
+        tags.push((
+            "time".to_string(),
+            time.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string(),
+        ));
+    }
+    if caps.has("message-tags") {
+        tags.push(("msgid".to_string(), msgid.to_string()));
+    }
+    tags
+}
+
+/// Tags for a stored message: `event_tags` plus `+draft/reply` when it answers
+/// another message and the client accepts client-only tags.
+pub(super) fn message_tags(
+    caps: &CapState,
+    time: DateTime<Utc>,
+    msgid: &str,
+    reply_to: Option<&str>,
+) -> Vec<(String, String)> {
+    let mut tags = event_tags(caps, time, msgid);
+    if let Some(reply_to) = reply_to
+        && caps.has("message-tags")
+    {
+        tags.push(("+draft/reply".to_string(), reply_to.to_string()));
+    }
+    tags
+}
+
+/// Render a stored message as PRIVMSG/NOTICE lines, one per line of content.
+pub(super) fn message_lines(
+    tags: Vec<(String, String)>,
+    from: &str,
+    target: &str,
+    content: &str,
+    kind: MessageKind,
+) -> Vec<String> {
+    let (command, lines) = formatting::outgoing(content, kind);
+    tagged_lines(tags, command, from, target, lines)
+}
+
+/// Render lines of one message from `from`. Only the first line carries the
+/// full tags; continuation lines keep just the time, so the msgid, replies
+/// and redactions refer to the message as a whole.
+pub(super) fn tagged_lines(
+    tags: Vec<(String, String)>,
+    command: &str,
+    from: &str,
+    target: &str,
+    lines: Vec<String>,
+) -> Vec<String> {
+    let continuation: Vec<_> = tags
+        .iter()
+        .filter(|(key, _)| key == "time")
+        .cloned()
+        .collect();
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let tags = if i == 0 { &tags } else { &continuation };
+            formatter::with_tags(tags, &formatter::user_message(command, from, target, line))
+        })
+        .collect()
+}
+
+/// Rate limit for typing notifications on one connection. `active` is
+/// forwarded at most once per `TYPING_THROTTLE` for each typist and channel;
+/// stopping resets the clock so the next `active` goes straight out.
+#[derive(Default)]
+struct TypingThrottle {
+    last_active: HashMap<(String, String, String), Instant>,
+}
+
+impl TypingThrottle {
+    /// Whether `event` should be delivered. Non-typing events always are.
+    fn allow(&mut self, event: &ChatEvent, now: Instant) -> bool {
+        match event {
+            ChatEvent::TypingStart {
+                server_id,
+                channel,
+                nickname,
+            } => {
+                let key = (nickname.clone(), server_id.clone(), channel.clone());
+                if self
+                    .last_active
+                    .get(&key)
+                    .is_some_and(|last| now.duration_since(*last) < TYPING_THROTTLE)
+                {
+                    return false;
+                }
+                // Forget typists whose throttle has lapsed, so the map stays small
+                self.last_active
+                    .retain(|_, last| now.duration_since(*last) < TYPING_THROTTLE);
+                self.last_active.insert(key, now);
+                true
+            }
+            ChatEvent::TypingStop {
+                server_id,
+                channel,
+                nickname,
+                ..
+            } => {
+                let key = (nickname.clone(), server_id.clone(), channel.clone());
+                self.last_active.remove(&key);
+                true
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Away state announced on one connection. Presence arrives once per shared
+/// server, so a change is only forwarded the first time it's seen. Only nicks
+/// that are away are remembered; coming back or quitting forgets them.
+#[derive(Default)]
+struct AwayTracker {
+    away: HashMap<String, String>,
+}
+
+impl AwayTracker {
+    /// Whether `event` should be delivered. Non-presence events always are.
+    fn allow(&mut self, event: &ChatEvent) -> bool {
+        match event {
+            ChatEvent::PresenceUpdate { presence, .. } => {
+                let message = commands::away_message(presence);
+                let previous = match &message {
+                    Some(m) => self.away.insert(presence.nickname.clone(), m.clone()),
+                    None => self.away.remove(&presence.nickname),
+                };
+                previous != message
+            }
+            ChatEvent::Quit { nickname, .. } => {
+                self.away.remove(nickname);
+                true
+            }
+            _ => true,
+        }
+    }
+}
+
+/// When a registered client was last heard from, so it can be sent a PING
+/// once it goes quiet and dropped if that PING goes unanswered.
+struct Keepalive {
+    interval: Duration,
+    timeout: Duration,
+    last_heard: tokio::time::Instant,
+    /// When the outstanding PING went out, if there is one.
+    ping_sent: Option<tokio::time::Instant>,
+}
+
+impl Keepalive {
+    fn new(interval: Duration, timeout: Duration) -> Self {
+        Self {
+            interval,
+            timeout,
+            last_heard: tokio::time::Instant::now(),
+            ping_sent: None,
+        }
+    }
+
+    /// Any line from the client counts as an answer.
+    fn heard(&mut self, now: tokio::time::Instant) {
+        self.last_heard = now;
+        self.ping_sent = None;
+    }
+
+    /// When to next act: send a PING, or give up on the one already sent.
+    fn deadline(&self) -> tokio::time::Instant {
+        match self.ping_sent {
+            Some(sent) => sent + self.timeout,
+            None => self.last_heard + self.interval,
+        }
+    }
+
+    /// Called once the deadline passes. True if a PING should go out now,
+    /// false if the last one went unanswered and the client should be dropped.
+    fn expire(&mut self, now: tokio::time::Instant) -> bool {
+        if self.ping_sent.is_some() {
+            return false;
+        }
+        self.ping_sent = Some(now);
+        true
+    }
+}
+
+/// `+typing=<state>` as a TAGMSG from the typist.
+fn typing_tagmsg(caps: &CapState, nick: &str, target: &str, state: &str) -> String {
+    let mut tags = live_event_tags(caps);
+    tags.push(("+typing".to_string(), state.to_string()));
+    formatter::with_tags(&tags, &formatter::tagmsg(nick, target))
+}
+
+/// Tags for membership/topic events, which the engine doesn't persist:
+/// stamped with the delivery time and a fresh msgid.
+pub(super) fn live_event_tags(caps: &CapState) -> Vec<(String, String)> {
+    event_tags(caps, Utc::now(), &Uuid::new_v4().to_string())
+}
+
+fn send_line(tx: &mpsc::UnboundedSender<String>, line: &str) {
+    let _ = tx.send(line.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::events::{
+        MemberInfo, PinnedMessageInfo, PresenceInfo, ReplyInfo, ThreadInfo,
+    };
+    use chrono::Utc;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    /// Create a minimal ChatEngine with no database for unit tests.
+    fn test_engine() -> Arc<ChatEngine> {
+        Arc::new(ChatEngine::new(None))
     }
 
     // ── Message event ──
@@ -634,6 +1700,7 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::Message {
                 id: Uuid::new_v4(),
                 server_id: Some(DEFAULT_SERVER_ID.to_string()),
@@ -644,6 +1711,7 @@ mod tests {
                 avatar_url: None,
                 reply_to: None,
                 attachments: None,
+                kind: MessageKind::Normal,
             },
         );
         assert_eq!(lines.len(), 1);
@@ -657,6 +1725,7 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "bob",
+            &CapState::default(),
             &ChatEvent::Message {
                 id: Uuid::new_v4(),
                 server_id: None,
@@ -667,12 +1736,67 @@ mod tests {
                 avatar_url: None,
                 reply_to: None,
                 attachments: None,
+                kind: MessageKind::Normal,
             },
         );
         assert_eq!(lines.len(), 1);
         assert!(lines[0].contains("PRIVMSG bob :Hey there"));
     }
 
+    fn message_event(content: &str, kind: MessageKind) -> ChatEvent {
+        ChatEvent::Message {
+            id: Uuid::nil(),
+            server_id: Some(DEFAULT_SERVER_ID.to_string()),
+            from: "alice".into(),
+            target: "#general".into(),
+            content: content.into(),
+            timestamp: Utc::now(),
+            avatar_url: None,
+            reply_to: None,
+            attachments: None,
+            kind,
+        }
+    }
+
+    #[test]
+    fn test_message_event_action_and_notice() {
+        let engine = test_engine();
+        let caps = CapState::default();
+        let action = message_event("*waves*", MessageKind::Action);
+        assert_eq!(
+            event_to_irc_lines(&engine, "viewer", &caps, &action),
+            vec![":alice!alice@concord PRIVMSG #general :\x01ACTION \x1Dwaves\x1D\x01"]
+        );
+        let notice = message_event("deploy done", MessageKind::Notice);
+        assert_eq!(
+            event_to_irc_lines(&engine, "viewer", &caps, &notice),
+            vec![":alice!alice@concord NOTICE #general :deploy done"]
+        );
+    }
+
+    #[test]
+    fn test_message_event_multiline_tags_first_line_only() {
+        let engine = test_engine();
+        let mut caps = CapState::default();
+        caps.handle("viewer", &cap_cmd("CAP REQ :message-tags"), true);
+        let lines = event_to_irc_lines(
+            &engine,
+            "viewer",
+            &caps,
+            &message_event("**one**\ntwo &amp; three", MessageKind::Normal),
+        );
+        assert_eq!(
+            lines,
+            vec![
+                format!(
+                    "@msgid={} :alice!alice@concord PRIVMSG #general \x02one\x02",
+                    Uuid::nil()
+                ),
+                ":alice!alice@concord PRIVMSG #general :two & three".to_string(),
+            ]
+        );
+    }
+
     // ── Join/Part/Quit/Nick events ──
 
     #[test]
@@ -681,6 +1805,7 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::Join {
                 nickname: "alice".into(),
                 server_id: DEFAULT_SERVER_ID.into(),
@@ -693,12 +1818,58 @@ mod tests {
         assert!(lines[0].starts_with(":alice!"));
     }
 
+    #[test]
+    fn test_join_event_extended_join() {
+        // The account is the one the session logged in as, even under another nick
+        let engine = test_engine();
+        let (sid, _rx) = engine
+            .connect(Some("u-alice".into()), "ally".into(), Protocol::Irc, None)
+            .unwrap();
+        engine.set_account(sid, "alice".into());
+        let mut caps = CapState::default();
+        caps.handle("viewer", &cap_cmd("CAP REQ :extended-join"), true);
+
+        let lines = event_to_irc_lines(
+            &engine,
+            "viewer",
+            &caps,
+            &ChatEvent::Join {
+                nickname: "ally".into(),
+                server_id: DEFAULT_SERVER_ID.into(),
+                channel: "#general".into(),
+                avatar_url: None,
+            },
+        );
+        assert_eq!(lines, vec![":ally!ally@concord JOIN #general alice ally"]);
+    }
+
+    #[test]
+    fn test_join_event_extended_join_guest() {
+        let engine = test_engine();
+        let mut caps = CapState::default();
+        caps.handle("viewer", &cap_cmd("CAP REQ :extended-join"), true);
+
+        let lines = event_to_irc_lines(
+            &engine,
+            "viewer",
+            &caps,
+            &ChatEvent::Join {
+                nickname: "ghost".into(),
+                server_id: DEFAULT_SERVER_ID.into(),
+                channel: "#general".into(),
+                avatar_url: None,
+            },
+        );
+        assert_eq!(lines, vec![":ghost!ghost@concord JOIN #general * ghost"]);
+    }
+
     #[test]
     fn test_part_event_with_reason() {
         let engine = test_engine();
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::Part {
                 nickname: "bob".into(),
                 server_id: DEFAULT_SERVER_ID.into(),
@@ -717,6 +1888,7 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::Part {
                 nickname: "bob".into(),
                 server_id: DEFAULT_SERVER_ID.into(),
@@ -734,6 +1906,7 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::Quit {
                 nickname: "alice".into(),
                 reason: Some("Leaving".into()),
@@ -750,6 +1923,7 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::Quit {
                 nickname: "alice".into(),
                 reason: None,
@@ -765,6 +1939,7 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::NickChange {
                 old_nick: "alice".into(),
                 new_nick: "alice_".into(),
@@ -783,6 +1958,7 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::TopicChange {
                 server_id: DEFAULT_SERVER_ID.into(),
                 channel: "#general".into(),
@@ -801,6 +1977,7 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::Topic {
                 server_id: DEFAULT_SERVER_ID.into(),
                 channel: "#dev".into(),
@@ -818,6 +1995,7 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::Topic {
                 server_id: DEFAULT_SERVER_ID.into(),
                 channel: "#dev".into(),
@@ -837,6 +2015,7 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::Names {
                 server_id: DEFAULT_SERVER_ID.into(),
                 channel: "#general".into(),
@@ -848,6 +2027,7 @@ mod tests {
                         custom_status: None,
                         status_emoji: None,
                         user_id: None,
+                        prefixes: String::new(),
                     },
                     MemberInfo {
                         nickname: "bob".into(),
@@ -856,6 +2036,7 @@ mod tests {
                         custom_status: None,
                         status_emoji: None,
                         user_id: None,
+                        prefixes: String::new(),
                     },
                 ],
             },
@@ -868,6 +2049,77 @@ mod tests {
         assert!(lines[1].contains("366"));
     }
 
+    #[test]
+    fn test_names_event_with_prefixes() {
+        let engine = test_engine();
+        let mut caps = CapState::default();
+        caps.handle("viewer", &cap_cmd("CAP REQ multi-prefix"), true);
+        let lines = event_to_irc_lines(
+            &engine,
+            "viewer",
+            &caps,
+            &ChatEvent::Names {
+                server_id: DEFAULT_SERVER_ID.into(),
+                channel: "#general".into(),
+                members: vec![MemberInfo {
+                    nickname: "alice".into(),
+                    avatar_url: None,
+                    status: None,
+                    custom_status: None,
+                    status_emoji: None,
+                    user_id: None,
+                    prefixes: "~&@%".into(),
+                }],
+            },
+        );
+        assert_eq!(lines[0], ":concord 353 viewer = #general ~&@%alice");
+    }
+
+    #[test]
+    fn test_presence_update_renders_away_notify() {
+        let engine = test_engine();
+        let event = ChatEvent::PresenceUpdate {
+            server_id: DEFAULT_SERVER_ID.into(),
+            presence: PresenceInfo {
+                user_id: "u1".into(),
+                nickname: "alice".into(),
+                avatar_url: None,
+                status: "dnd".into(),
+                custom_status: None,
+                status_emoji: None,
+            },
+        };
+        assert!(event_to_irc_lines(&engine, "viewer", &CapState::default(), &event).is_empty());
+
+        let mut caps = CapState::default();
+        caps.handle("viewer", &cap_cmd("CAP REQ away-notify"), true);
+        assert_eq!(
+            event_to_irc_lines(&engine, "viewer", &caps, &event),
+            vec![":alice!alice@concord AWAY :Do not disturb".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_member_prefix_update_renders_mode() {
+        let engine = test_engine();
+        let lines = event_to_irc_lines(
+            &engine,
+            "viewer",
+            &CapState::default(),
+            &ChatEvent::MemberPrefixUpdate {
+                server_id: DEFAULT_SERVER_ID.into(),
+                channel: "#general".into(),
+                nickname: "alice".into(),
+                added: "@%".into(),
+                removed: "+".into(),
+            },
+        );
+        assert_eq!(
+            lines,
+            vec![":concord MODE #general +oh-v alice alice alice".to_string()]
+        );
+    }
+
     // ── ServerNotice / Error events ──
 
     #[test]
@@ -876,6 +2128,7 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::ServerNotice {
                 message: "Welcome to Concord".into(),
             },
@@ -890,6 +2143,7 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::Error {
                 code: "NOT_FOUND".into(),
                 message: "Channel not found".into(),
@@ -909,18 +2163,44 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::MessageEdit {
                 id: Uuid::new_v4(),
                 server_id: DEFAULT_SERVER_ID.into(),
                 channel: "#general".into(),
+                from: "alice".into(),
                 content: "edited content".into(),
                 edited_at: Utc::now(),
             },
         );
+        assert_eq!(
+            lines,
+            vec![":alice!alice@concord PRIVMSG #general :* correction: edited content"]
+        );
+    }
+
+    #[test]
+    fn test_message_edit_event_tagged() {
+        let engine = test_engine();
+        let mut caps = CapState::default();
+        caps.handle("viewer", &cap_cmd("CAP REQ :message-tags"), true);
+        let id = Uuid::new_v4();
+        let lines = event_to_irc_lines(
+            &engine,
+            "viewer",
+            &caps,
+            &ChatEvent::MessageEdit {
+                id,
+                server_id: DEFAULT_SERVER_ID.into(),
+                channel: "#general".into(),
+                from: "alice".into(),
+                content: "**fixed**".into(),
+                edited_at: Utc::now(),
+            },
+        );
         assert_eq!(lines.len(), 1);
-        assert!(lines[0].contains("NOTICE viewer"));
-        assert!(lines[0].contains("edited"));
-        assert!(lines[0].contains("#general"));
+        assert!(lines[0].contains(&format!(";+draft/edit={id} ")));
+        assert!(lines[0].ends_with("PRIVMSG #general :* correction: \x02fixed\x02"));
     }
 
     #[test]
@@ -929,10 +2209,12 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::MessageDelete {
                 id: Uuid::new_v4(),
                 server_id: DEFAULT_SERVER_ID.into(),
                 channel: "#general".into(),
+                deleted_by: "alice".into(),
             },
         );
         assert_eq!(lines.len(), 1);
@@ -941,6 +2223,29 @@ mod tests {
         assert!(lines[0].contains("#general"));
     }
 
+    #[test]
+    fn test_message_delete_event_redact() {
+        let engine = test_engine();
+        let mut caps = CapState::default();
+        caps.handle("viewer", &cap_cmd("CAP REQ :draft/message-redaction"), true);
+        let id = Uuid::new_v4();
+        let lines = event_to_irc_lines(
+            &engine,
+            "viewer",
+            &caps,
+            &ChatEvent::MessageDelete {
+                id,
+                server_id: DEFAULT_SERVER_ID.into(),
+                channel: "#general".into(),
+                deleted_by: "mod".into(),
+            },
+        );
+        assert_eq!(
+            lines,
+            vec![format!(":mod!mod@concord REDACT #general {id}")]
+        );
+    }
+
     // ── Reaction events ──
 
     #[test]
@@ -949,6 +2254,7 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::ReactionAdd {
                 message_id: Uuid::new_v4(),
                 server_id: DEFAULT_SERVER_ID.into(),
@@ -964,12 +2270,62 @@ mod tests {
         assert!(lines[0].contains("#general"));
     }
 
+    #[test]
+    fn test_reaction_events_as_tagmsg() {
+        let engine = test_engine();
+        let mut caps = CapState::default();
+        caps.handle("viewer", &cap_cmd("CAP REQ :message-tags"), true);
+        let id = Uuid::new_v4();
+        let add = ChatEvent::ReactionAdd {
+            message_id: id,
+            server_id: DEFAULT_SERVER_ID.into(),
+            channel: "#general".into(),
+            user_id: "uid1".into(),
+            nickname: "alice".into(),
+            emoji: "\u{1f44d}".into(),
+        };
+        let lines = event_to_irc_lines(&engine, "viewer", &caps, &add);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].contains(&format!(";+draft/react=\u{1f44d};+draft/reply={id} ")));
+        assert!(lines[0].ends_with(":alice!alice@concord TAGMSG #general"));
+
+        let remove = ChatEvent::ReactionRemove {
+            message_id: id,
+            server_id: DEFAULT_SERVER_ID.into(),
+            channel: "#general".into(),
+            user_id: "uid1".into(),
+            nickname: "alice".into(),
+            emoji: "\u{1f44d}".into(),
+        };
+        let lines = event_to_irc_lines(&engine, "viewer", &caps, &remove);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].contains(";+draft/unreact=\u{1f44d};"));
+    }
+
+    #[test]
+    fn test_message_event_reply_tag() {
+        let engine = test_engine();
+        let mut caps = CapState::default();
+        caps.handle("viewer", &cap_cmd("CAP REQ :message-tags"), true);
+        let mut event = message_event("sure", MessageKind::Normal);
+        if let ChatEvent::Message { reply_to, .. } = &mut event {
+            *reply_to = Some(ReplyInfo {
+                id: "parent-id".into(),
+                from: "bob".into(),
+                content_preview: "lunch?".into(),
+            });
+        }
+        let lines = event_to_irc_lines(&engine, "viewer", &caps, &event);
+        assert!(lines[0].starts_with(&format!("@msgid={};+draft/reply=parent-id ", Uuid::nil())));
+    }
+
     #[test]
     fn test_reaction_remove_event_is_empty() {
         let engine = test_engine();
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::ReactionRemove {
                 message_id: Uuid::new_v4(),
                 server_id: DEFAULT_SERVER_ID.into(),
@@ -990,6 +2346,7 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::TypingStart {
                 server_id: DEFAULT_SERVER_ID.into(),
                 channel: "#general".into(),
@@ -999,12 +2356,100 @@ mod tests {
         assert!(lines.is_empty());
     }
 
+    #[test]
+    fn test_typing_events_as_tagmsg() {
+        let engine = test_engine();
+        let mut caps = CapState::default();
+        caps.handle("viewer", &cap_cmd("CAP REQ :message-tags"), true);
+        let start = ChatEvent::TypingStart {
+            server_id: DEFAULT_SERVER_ID.into(),
+            channel: "#general".into(),
+            nickname: "alice".into(),
+        };
+        let lines = event_to_irc_lines(&engine, "viewer", &caps, &start);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].contains(";+typing=active "));
+        assert!(lines[0].ends_with(":alice!alice@concord TAGMSG #general"));
+
+        let paused = ChatEvent::TypingStop {
+            server_id: DEFAULT_SERVER_ID.into(),
+            channel: "#general".into(),
+            nickname: "alice".into(),
+            paused: true,
+        };
+        let lines = event_to_irc_lines(&engine, "viewer", &caps, &paused);
+        assert!(lines[0].contains(";+typing=paused "));
+        let lines = event_to_irc_lines(&engine, "viewer", &CapState::default(), &paused);
+        assert!(lines.is_empty());
+    }
+
+    #[test]
+    fn test_typing_throttle() {
+        let typing = |nickname: &str| ChatEvent::TypingStart {
+            server_id: DEFAULT_SERVER_ID.into(),
+            channel: "#general".into(),
+            nickname: nickname.into(),
+        };
+        let done = ChatEvent::TypingStop {
+            server_id: DEFAULT_SERVER_ID.into(),
+            channel: "#general".into(),
+            nickname: "alice".into(),
+            paused: false,
+        };
+        let mut throttle = TypingThrottle::default();
+        let t0 = Instant::now();
+
+        assert!(throttle.allow(&typing("alice"), t0));
+        assert!(!throttle.allow(&typing("alice"), t0 + Duration::from_secs(1)));
+        assert!(throttle.allow(&typing("bob"), t0 + Duration::from_secs(1)));
+        assert!(throttle.allow(&typing("alice"), t0 + TYPING_THROTTLE));
+        // Stopping resets the throttle
+        assert!(throttle.allow(&done, t0 + TYPING_THROTTLE));
+        assert!(throttle.allow(&typing("alice"), t0 + TYPING_THROTTLE));
+        // Typists who never sent a stop are forgotten once their throttle lapses
+        assert!(throttle.allow(&typing("carol"), t0 + TYPING_THROTTLE * 2));
+        assert_eq!(throttle.last_active.len(), 1);
+    }
+
+    #[test]
+    fn test_away_tracker() {
+        let presence = |nickname: &str, status: &str| ChatEvent::PresenceUpdate {
+            server_id: DEFAULT_SERVER_ID.into(),
+            presence: PresenceInfo {
+                user_id: format!("u-{nickname}"),
+                nickname: nickname.into(),
+                avatar_url: None,
+                status: status.into(),
+                custom_status: None,
+                status_emoji: None,
+            },
+        };
+        let mut away = AwayTracker::default();
+
+        // The same change arriving from each shared server goes out once
+        assert!(away.allow(&presence("alice", "idle")));
+        assert!(!away.allow(&presence("alice", "idle")));
+        assert!(away.allow(&presence("alice", "dnd")));
+        assert!(away.allow(&presence("alice", "online")));
+        assert!(!away.allow(&presence("alice", "online")));
+        // Only nicks that are away are remembered
+        assert!(away.allow(&presence("bob", "idle")));
+        assert!(!away.allow(&presence("carol", "online")));
+        assert_eq!(away.away.len(), 1);
+        assert!(away.allow(&ChatEvent::Quit {
+            nickname: "bob".into(),
+            reason: None,
+        }));
+        assert!(away.away.is_empty());
+    }
+
     #[test]
     fn test_message_embed_is_silent() {
         let engine = test_engine();
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::MessageEmbed {
                 message_id: Uuid::new_v4(),
                 server_id: DEFAULT_SERVER_ID.into(),
@@ -1021,6 +2466,7 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::ChannelList {
                 server_id: DEFAULT_SERVER_ID.into(),
                 channels: vec![],
@@ -1037,6 +2483,7 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::MessagePin {
                 server_id: DEFAULT_SERVER_ID.into(),
                 channel: "#general".into(),
@@ -1064,6 +2511,7 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::MessageUnpin {
                 server_id: DEFAULT_SERVER_ID.into(),
                 channel: "#general".into(),
@@ -1080,6 +2528,7 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::ThreadCreate {
                 server_id: DEFAULT_SERVER_ID.into(),
                 parent_channel: "#general".into(),
@@ -1098,6 +2547,7 @@ mod tests {
         assert_eq!(lines.len(), 1);
         assert!(lines[0].contains("Discussion"));
         assert!(lines[0].contains("thread"));
+        assert!(lines[0].ends_with(" (/join #general/discussion)"));
     }
 
     #[test]
@@ -1106,6 +2556,7 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::ThreadUpdate {
                 server_id: DEFAULT_SERVER_ID.into(),
                 thread: ThreadInfo {
@@ -1131,6 +2582,7 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::ThreadUpdate {
                 server_id: DEFAULT_SERVER_ID.into(),
                 thread: ThreadInfo {
@@ -1157,6 +2609,7 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::MemberKick {
                 server_id: DEFAULT_SERVER_ID.into(),
                 user_id: "uid1".into(),
@@ -1176,6 +2629,7 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::MemberKick {
                 server_id: DEFAULT_SERVER_ID.into(),
                 user_id: "uid1".into(),
@@ -1193,6 +2647,7 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::MemberBan {
                 server_id: DEFAULT_SERVER_ID.into(),
                 user_id: "uid1".into(),
@@ -1205,12 +2660,101 @@ mod tests {
         assert!(lines[0].contains("Spam"));
     }
 
+    #[test]
+    fn test_channel_kick_event() {
+        let engine = test_engine();
+        let lines = event_to_irc_lines(
+            &engine,
+            "viewer",
+            &CapState::default(),
+            &ChatEvent::ChannelKick {
+                server_id: DEFAULT_SERVER_ID.into(),
+                channel: "#general".into(),
+                nickname: "troll".into(),
+                kicked_by: "admin".into(),
+                reason: Some("Rule violation".into()),
+            },
+        );
+        assert_eq!(
+            lines,
+            vec![":admin!admin@concord KICK #general troll :Rule violation".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_channel_invite_and_knock_events() {
+        let engine = test_engine();
+        let lines = event_to_irc_lines(
+            &engine,
+            "bob",
+            &CapState::default(),
+            &ChatEvent::ChannelInvite {
+                server_id: DEFAULT_SERVER_ID.into(),
+                channel: "#secret".into(),
+                invited_by: "op".into(),
+                code: Some("abc123".into()),
+            },
+        );
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], ":op!op@concord INVITE bob #secret");
+        assert!(lines[1].contains("/join #secret abc123"));
+
+        let lines = event_to_irc_lines(
+            &engine,
+            "op",
+            &CapState::default(),
+            &ChatEvent::ChannelKnock {
+                server_id: DEFAULT_SERVER_ID.into(),
+                channel: "#secret".into(),
+                nickname: "bob".into(),
+                message: None,
+            },
+        );
+        assert_eq!(
+            lines,
+            vec![":concord 710 op #secret bob!bob@concord :has asked for an invite".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_channel_mode_updates_only_reach_channel_members() {
+        let engine = test_engine();
+        let event = ChatEvent::ModeratedUpdate {
+            server_id: DEFAULT_SERVER_ID.into(),
+            channel: "#general".into(),
+            moderated: true,
+        };
+        assert!(event_to_irc_lines(&engine, "viewer", &CapState::default(), &event).is_empty());
+
+        let (sid, _rx) = engine
+            .connect(None, "viewer".into(), Protocol::Irc, None)
+            .unwrap();
+        engine
+            .join_channel(sid, DEFAULT_SERVER_ID, "#general")
+            .unwrap();
+        let lines = event_to_irc_lines(&engine, "viewer", &CapState::default(), &event);
+        assert_eq!(lines, vec![":concord MODE #general +m".to_string()]);
+
+        let lines = event_to_irc_lines(
+            &engine,
+            "viewer",
+            &CapState::default(),
+            &ChatEvent::PrivacyUpdate {
+                server_id: DEFAULT_SERVER_ID.into(),
+                channel: "#general".into(),
+                is_private: false,
+            },
+        );
+        assert_eq!(lines, vec![":concord MODE #general -s".to_string()]);
+    }
+
     #[test]
     fn test_member_unban_is_silent() {
         let engine = test_engine();
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::MemberUnban {
                 server_id: DEFAULT_SERVER_ID.into(),
                 user_id: "uid1".into(),
@@ -1225,6 +2769,7 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::SlowModeUpdate {
                 server_id: DEFAULT_SERVER_ID.into(),
                 channel: "#general".into(),
@@ -1240,6 +2785,7 @@ mod tests {
         let lines = event_to_irc_lines(
             &engine,
             "viewer",
+            &CapState::default(),
             &ChatEvent::BulkMessageDelete {
                 server_id: DEFAULT_SERVER_ID.into(),
                 channel: "#general".into(),
@@ -1275,7 +2821,15 @@ mod tests {
                 server_id: DEFAULT_SERVER_ID.into(),
                 presences: vec![],
             },
+            ChatEvent::MessageEditHistory {
+                message_id: "msg-1".into(),
+                revisions: vec![],
+            },
             ChatEvent::BookmarkList { bookmarks: vec![] },
+            ChatEvent::MentionList {
+                mentions: vec![],
+                has_more: false,
+            },
             ChatEvent::InviteList {
                 server_id: DEFAULT_SERVER_ID.into(),
                 invites: vec![],
@@ -1291,7 +2845,7 @@ mod tests {
         ];
 
         for event in &ws_events {
-            let lines = event_to_irc_lines(&engine, "viewer", event);
+            let lines = event_to_irc_lines(&engine, "viewer", &CapState::default(), event);
             assert!(
                 lines.is_empty(),
                 "Expected no IRC output for {:?} but got {:?}",
@@ -1301,6 +2855,536 @@ mod tests {
         }
     }
 
+    // ── server-time / message-tags ──
+
+    fn caps_with(requested: &str) -> CapState {
+        let mut caps = CapState::default();
+        caps.handle("viewer", &cap_cmd(&format!("CAP REQ :{requested}")), true);
+        caps
+    }
+
+    #[test]
+    fn test_message_event_with_tags() {
+        let engine = test_engine();
+        let id = Uuid::new_v4();
+        let timestamp = DateTime::parse_from_rfc3339("2025-03-04T05:06:07.890Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let lines = event_to_irc_lines(
+            &engine,
+            "viewer",
+            &caps_with("server-time message-tags"),
+            &ChatEvent::Message {
+                id,
+                server_id: Some(DEFAULT_SERVER_ID.to_string()),
+                from: "alice".into(),
+                target: "#general".into(),
+                content: "Hello everyone".into(),
+                timestamp,
+                avatar_url: None,
+                reply_to: None,
+                attachments: None,
+                kind: MessageKind::Normal,
+            },
+        );
+        assert_eq!(
+            lines,
+            vec![format!(
+                "@time=2025-03-04T05:06:07.890Z;msgid={id} :alice!alice@concord PRIVMSG #general :Hello everyone"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_message_event_server_time_only() {
+        let engine = test_engine();
+        let lines = event_to_irc_lines(
+            &engine,
+            "viewer",
+            &caps_with("server-time"),
+            &ChatEvent::Message {
+                id: Uuid::new_v4(),
+                server_id: None,
+                from: "alice".into(),
+                target: "viewer".into(),
+                content: "psst".into(),
+                timestamp: Utc::now(),
+                avatar_url: None,
+                reply_to: None,
+                attachments: None,
+                kind: MessageKind::Normal,
+            },
+        );
+        assert!(lines[0].starts_with("@time="));
+        assert!(!lines[0].contains("msgid="));
+    }
+
+    #[test]
+    fn test_join_part_topic_tagged() {
+        let engine = test_engine();
+        let caps = caps_with("server-time message-tags");
+        let events = vec![
+            ChatEvent::Join {
+                nickname: "alice".into(),
+                server_id: DEFAULT_SERVER_ID.into(),
+                channel: "#general".into(),
+                avatar_url: None,
+            },
+            ChatEvent::Part {
+                nickname: "alice".into(),
+                server_id: DEFAULT_SERVER_ID.into(),
+                channel: "#general".into(),
+                reason: None,
+            },
+            ChatEvent::TopicChange {
+                server_id: DEFAULT_SERVER_ID.into(),
+                channel: "#general".into(),
+                set_by: "alice".into(),
+                topic: "New topic".into(),
+            },
+        ];
+        for event in &events {
+            let lines = event_to_irc_lines(&engine, "viewer", &caps, event);
+            let msg = IrcMessage::parse(&lines[0]).unwrap();
+            assert!(msg.tag("time").is_some(), "missing time on {}", lines[0]);
+            assert!(
+                Uuid::parse_str(msg.tag("msgid").unwrap()).is_ok(),
+                "bad msgid on {}",
+                lines[0]
+            );
+        }
+    }
+
+    #[test]
+    fn test_untagged_without_caps() {
+        let engine = test_engine();
+        let lines = event_to_irc_lines(
+            &engine,
+            "viewer",
+            &CapState::default(),
+            &ChatEvent::Part {
+                nickname: "alice".into(),
+                server_id: DEFAULT_SERVER_ID.into(),
+                channel: "#general".into(),
+                reason: None,
+            },
+        );
+        assert_eq!(lines, vec![":alice!alice@concord PART #general"]);
+    }
+
+    // ── CAP negotiation ──
+
+    fn cap_cmd(line: &str) -> IrcMessage {
+        IrcMessage::parse(line).unwrap()
+    }
+
+    #[test]
+    fn test_cap_ls_legacy() {
+        let mut caps = CapState::default();
+        let lines = caps.handle("*", &cap_cmd("CAP LS"), false);
+        assert_eq!(
+            lines,
+            vec![
+                ":concord CAP * LS :away-notify batch cap-notify draft/chathistory draft/message-redaction echo-message extended-join message-tags multi-prefix sasl server-time userhost-in-names"
+            ]
+        );
+        assert!(caps.negotiating());
+        assert!(!caps.has("cap-notify"));
+    }
+
+    #[test]
+    fn test_cap_ls_302_enables_cap_notify() {
+        let mut caps = CapState::default();
+        let lines = caps.handle("*", &cap_cmd("CAP LS 302"), false);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].starts_with(":concord CAP * LS :"));
+        assert!(caps.has("cap-notify"));
+    }
+
+    #[test]
+    fn test_cap_ls_302_splits_long_lists() {
+        let caps = CapState {
+            version: 302,
+            ..Default::default()
+        };
+        let tokens: Vec<String> = (0..100).map(|i| format!("vendor/cap-{i}")).collect();
+        let lines = caps.cap_list_lines("*", "LS", &tokens);
+        assert!(lines.len() > 1);
+        for line in &lines[..lines.len() - 1] {
+            assert!(line.starts_with(":concord CAP * LS * :"));
+        }
+        assert!(!lines.last().unwrap().contains(" LS * "));
+        let total: usize = lines
+            .iter()
+            .map(|l| l.rsplit(':').next().unwrap().split(' ').count())
+            .sum();
+        assert_eq!(total, 100);
+    }
+
+    #[test]
+    fn test_cap_req_ack() {
+        let mut caps = CapState::default();
+        let lines = caps.handle("*", &cap_cmd("CAP REQ :extended-join"), false);
+        assert_eq!(lines, vec![":concord CAP * ACK extended-join"]);
+        assert!(caps.has("extended-join"));
+        assert!(caps.negotiating());
+    }
+
+    #[test]
+    fn test_cap_req_unknown_naks_whole_request() {
+        let mut caps = CapState::default();
+        let lines = caps.handle("*", &cap_cmd("CAP REQ :extended-join bogus-cap"), false);
+        assert_eq!(lines, vec![":concord CAP * NAK :extended-join bogus-cap"]);
+        assert!(!caps.has("extended-join"));
+    }
+
+    #[test]
+    fn test_cap_req_disable() {
+        let mut caps = CapState::default();
+        caps.handle("alice", &cap_cmd("CAP REQ extended-join"), true);
+        let lines = caps.handle("alice", &cap_cmd("CAP REQ -extended-join"), true);
+        assert_eq!(lines, vec![":concord CAP alice ACK -extended-join"]);
+        assert!(!caps.has("extended-join"));
+    }
+
+    #[test]
+    fn test_cap_notify_cannot_be_disabled_by_302_client() {
+        let mut caps = CapState::default();
+        caps.handle("*", &cap_cmd("CAP LS 302"), false);
+        let lines = caps.handle("*", &cap_cmd("CAP REQ -cap-notify"), false);
+        assert_eq!(lines, vec![":concord CAP * NAK -cap-notify"]);
+        assert!(caps.has("cap-notify"));
+    }
+
+    #[test]
+    fn test_cap_list() {
+        let mut caps = CapState::default();
+        caps.handle("alice", &cap_cmd("CAP REQ :extended-join cap-notify"), true);
+        let lines = caps.handle("alice", &cap_cmd("CAP LIST"), true);
+        assert_eq!(lines, vec![":concord CAP alice LIST :cap-notify extended-join"]);
+    }
+
+    #[test]
+    fn test_cap_end_releases_registration() {
+        let mut caps = CapState::default();
+        caps.handle("*", &cap_cmd("CAP LS 302"), false);
+        assert!(caps.negotiating());
+        let lines = caps.handle("*", &cap_cmd("CAP END"), false);
+        assert!(lines.is_empty());
+        assert!(!caps.negotiating());
+    }
+
+    #[test]
+    fn test_cap_after_registration_does_not_hold() {
+        let mut caps = CapState::default();
+        caps.handle("alice", &cap_cmd("CAP LS"), true);
+        caps.handle("alice", &cap_cmd("CAP REQ extended-join"), true);
+        assert!(!caps.negotiating());
+    }
+
+    #[test]
+    fn test_cap_invalid_subcommand() {
+        let mut caps = CapState::default();
+        let lines = caps.handle("alice", &cap_cmd("CAP FROB"), true);
+        assert_eq!(lines, vec![":concord 410 alice FROB :Invalid CAP command"]);
+    }
+
+    #[test]
+    fn test_cap_missing_subcommand() {
+        let mut caps = CapState::default();
+        let lines = caps.handle("*", &cap_cmd("CAP"), false);
+        assert_eq!(lines, vec![":concord 461 * CAP :Not enough parameters"]);
+    }
+
+    // ── SASL ──
+
+    async fn sasl_db() -> SqlitePool {
+        use crate::auth::token::hash_irc_token;
+        use crate::db::pool::{create_pool, run_migrations};
+        use crate::db::queries::users::{CreateOAuthUser, create_irc_cert, create_with_oauth};
+
+        let pool = create_pool("sqlite::memory:").await.unwrap();
+        run_migrations(&pool).await.unwrap();
+        create_with_oauth(
+            &pool,
+            &CreateOAuthUser {
+                user_id: "u-alice",
+                username: "alice",
+                email: None,
+                avatar_url: None,
+                oauth_id: "oauth-alice",
+                provider: "github",
+                provider_id: "gh-alice",
+            },
+        )
+        .await
+        .unwrap();
+        let hash = hash_irc_token("secret-token").unwrap();
+        users::create_irc_token(&pool, "t1", "u-alice", &hash, None)
+            .await
+            .unwrap();
+        create_irc_cert(&pool, "c1", "u-alice", "f00d", None)
+            .await
+            .unwrap();
+        pool
+    }
+
+    async fn authenticate(
+        db: &SqlitePool,
+        cert_fp: Option<&str>,
+        sasl: &mut SaslState,
+        arg: &str,
+    ) -> (Vec<String>, Option<(String, String)>) {
+        handle_authenticate(db, cert_fp, sasl, &mut 0, "*", arg).await
+    }
+
+    #[tokio::test]
+    async fn test_sasl_plain_success() {
+        let db = sasl_db().await;
+        let mut sasl = SaslState::default();
+
+        let (lines, _) = authenticate(&db, None, &mut sasl, "PLAIN").await;
+        assert_eq!(lines, vec!["AUTHENTICATE +"]);
+
+        let payload = BASE64.encode("\0alice\0secret-token");
+        let (lines, account) = authenticate(&db, None, &mut sasl, &payload).await;
+        assert_eq!(
+            lines,
+            vec![
+                ":concord 900 * *!*@concord alice :You are now logged in as alice",
+                ":concord 903 * :SASL authentication successful",
+            ]
+        );
+        assert_eq!(account, Some(("u-alice".to_string(), "alice".to_string())));
+        assert!(matches!(sasl, SaslState::Idle));
+    }
+
+    #[tokio::test]
+    async fn test_sasl_logs_atproto_users_in_under_their_did() {
+        use crate::auth::token::hash_irc_token;
+        use crate::db::queries::users::{CreateOAuthUser, create_irc_cert, create_with_oauth};
+
+        let db = sasl_db().await;
+        create_with_oauth(
+            &db,
+            &CreateOAuthUser {
+                user_id: "u-sky",
+                username: "sky",
+                email: None,
+                avatar_url: None,
+                oauth_id: "oauth-sky",
+                provider: "atproto",
+                provider_id: "did:plc:sky",
+            },
+        )
+        .await
+        .unwrap();
+        let hash = hash_irc_token("sky-token").unwrap();
+        users::create_irc_token(&db, "t2", "u-sky", &hash, None)
+            .await
+            .unwrap();
+        create_irc_cert(&db, "c2", "u-sky", "beef", None)
+            .await
+            .unwrap();
+        let expected = Some(("u-sky".to_string(), "did:plc:sky".to_string()));
+
+        // The same account name WHOIS and WHOX give, whichever way they log in
+        let mut sasl = SaslState::default();
+        authenticate(&db, None, &mut sasl, "PLAIN").await;
+        let payload = BASE64.encode("\0sky\0sky-token");
+        let (lines, account) = authenticate(&db, None, &mut sasl, &payload).await;
+        assert_eq!(
+            lines[0],
+            ":concord 900 * *!*@concord did:plc:sky :You are now logged in as did:plc:sky"
+        );
+        assert_eq!(account, expected);
+
+        authenticate(&db, Some("beef"), &mut sasl, "EXTERNAL").await;
+        let (_, account) = authenticate(&db, Some("beef"), &mut sasl, "+").await;
+        assert_eq!(account, expected);
+    }
+
+    #[tokio::test]
+    async fn test_sasl_plain_wrong_token() {
+        let db = sasl_db().await;
+        let mut sasl = SaslState::default();
+        authenticate(&db, None, &mut sasl, "PLAIN").await;
+
+        let payload = BASE64.encode("alice\0alice\0wrong");
+        let (lines, account) = authenticate(&db, None, &mut sasl, &payload).await;
+        assert_eq!(lines, vec![":concord 904 * :SASL authentication failed"]);
+        assert!(account.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sasl_plain_mismatched_authzid() {
+        let db = sasl_db().await;
+        let mut sasl = SaslState::default();
+        authenticate(&db, None, &mut sasl, "PLAIN").await;
+
+        let payload = BASE64.encode("bob\0alice\0secret-token");
+        let (lines, account) = authenticate(&db, None, &mut sasl, &payload).await;
+        assert_eq!(lines, vec![":concord 904 * :SASL authentication failed"]);
+        assert!(account.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sasl_plain_invalid_base64() {
+        let db = sasl_db().await;
+        let mut sasl = SaslState::default();
+        authenticate(&db, None, &mut sasl, "PLAIN").await;
+
+        let (lines, account) = authenticate(&db, None, &mut sasl, "!!!not-base64").await;
+        assert_eq!(lines, vec![":concord 904 * :SASL authentication failed"]);
+        assert!(account.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sasl_unknown_mechanism() {
+        let db = sasl_db().await;
+        let mut sasl = SaslState::default();
+        let (lines, _) = authenticate(&db, None, &mut sasl, "SCRAM-SHA-256").await;
+        assert_eq!(
+            lines,
+            vec![
+                ":concord 908 * PLAIN,EXTERNAL :are available SASL mechanisms",
+                ":concord 904 * :SASL authentication failed",
+            ]
+        );
+        assert!(matches!(sasl, SaslState::Idle));
+    }
+
+    #[tokio::test]
+    async fn test_sasl_external_requires_certificate() {
+        let db = sasl_db().await;
+        let mut sasl = SaslState::default();
+        let (lines, _) = authenticate(&db, None, &mut sasl, "EXTERNAL").await;
+        assert_eq!(lines, vec![":concord 904 * :SASL authentication failed"]);
+    }
+
+    #[tokio::test]
+    async fn test_sasl_external_success() {
+        let db = sasl_db().await;
+        let mut sasl = SaslState::default();
+        let (lines, _) = authenticate(&db, Some("f00d"), &mut sasl, "EXTERNAL").await;
+        assert_eq!(lines, vec!["AUTHENTICATE +"]);
+
+        let (lines, account) = authenticate(&db, Some("f00d"), &mut sasl, "+").await;
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].contains(" 903 "));
+        assert_eq!(account, Some(("u-alice".to_string(), "alice".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_sasl_external_unbound_certificate() {
+        let db = sasl_db().await;
+        let mut sasl = SaslState::default();
+        authenticate(&db, Some("beef"), &mut sasl, "EXTERNAL").await;
+        let (lines, account) = authenticate(&db, Some("beef"), &mut sasl, "+").await;
+        assert_eq!(lines, vec![":concord 904 * :SASL authentication failed"]);
+        assert!(account.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sasl_abort() {
+        let db = sasl_db().await;
+        let mut sasl = SaslState::default();
+        authenticate(&db, None, &mut sasl, "PLAIN").await;
+        let (lines, _) = authenticate(&db, None, &mut sasl, "*").await;
+        assert_eq!(lines, vec![":concord 906 * :SASL authentication aborted"]);
+        assert!(matches!(sasl, SaslState::Idle));
+    }
+
+    #[tokio::test]
+    async fn test_sasl_chunked_payload() {
+        let db = sasl_db().await;
+        let mut sasl = SaslState::default();
+        authenticate(&db, None, &mut sasl, "PLAIN").await;
+
+        // A full 400-byte chunk waits for more
+        let chunk = "A".repeat(SASL_CHUNK_SIZE);
+        let (lines, _) = authenticate(&db, None, &mut sasl, &chunk).await;
+        assert!(lines.is_empty());
+        assert!(matches!(sasl, SaslState::Awaiting { .. }));
+
+        // `+` terminates a payload that was an exact multiple of 400 bytes
+        let (lines, account) = authenticate(&db, None, &mut sasl, "+").await;
+        assert_eq!(lines, vec![":concord 904 * :SASL authentication failed"]);
+        assert!(account.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sasl_payload_too_long() {
+        let db = sasl_db().await;
+        let mut sasl = SaslState::default();
+        authenticate(&db, None, &mut sasl, "PLAIN").await;
+
+        let chunk = "A".repeat(SASL_CHUNK_SIZE);
+        let mut last = Vec::new();
+        for _ in 0..=(SASL_MAX_PAYLOAD / SASL_CHUNK_SIZE) {
+            last = authenticate(&db, None, &mut sasl, &chunk).await.0;
+        }
+        assert_eq!(last, vec![":concord 905 * :SASL message too long"]);
+    }
+
+    // ── Read-marker client names ──
+
+    #[test]
+    fn test_client_name() {
+        assert_eq!(client_name("alice@laptop"), Some("laptop"));
+        assert_eq!(client_name("alice@work-pc_2"), Some("work-pc_2"));
+        assert_eq!(client_name("alice"), None);
+        assert_eq!(client_name("alice@"), None);
+        assert_eq!(client_name("alice@bad name"), None);
+        assert_eq!(client_name(&format!("alice@{}", "x".repeat(33))), None);
+    }
+
+    // ── Login nick resolution ──
+
+    #[test]
+    fn test_resolve_login_nick_keeps_free_nick() {
+        let engine = test_engine();
+        assert_eq!(resolve_login_nick(&engine, "ali", "u-alice", "alice"), "ali");
+    }
+
+    #[tokio::test]
+    async fn test_resolve_login_nick_reclaims_own_session() {
+        let engine = test_engine();
+        let _s = engine
+            .connect(Some("u-alice".into()), "alice".into(), Protocol::WebSocket, None)
+            .unwrap();
+        assert_eq!(resolve_login_nick(&engine, "alice", "u-alice", "alice"), "alice");
+    }
+
+    #[tokio::test]
+    async fn test_resolve_login_nick_falls_back_to_account() {
+        let engine = test_engine();
+        let _s = engine.connect(None, "ali".into(), Protocol::Irc, None).unwrap();
+        assert_eq!(resolve_login_nick(&engine, "ali", "u-alice", "alice"), "alice");
+    }
+
+    #[tokio::test]
+    async fn test_resolve_login_nick_numbers_taken_account_name() {
+        let engine = test_engine();
+        let _s1 = engine.connect(None, "alice".into(), Protocol::Irc, None).unwrap();
+        let _s2 = engine
+            .connect(Some("u-other".into()), "alice1".into(), Protocol::Irc, None)
+            .unwrap();
+        assert_eq!(resolve_login_nick(&engine, "alice", "u-alice", "alice"), "alice2");
+    }
+
+    #[tokio::test]
+    async fn test_resolve_login_nick_numbers_requested_nick_for_did_accounts() {
+        let engine = test_engine();
+        let _s = engine
+            .connect(None, "sky".into(), Protocol::Irc, None)
+            .unwrap();
+        assert_eq!(
+            resolve_login_nick(&engine, "sky", "u-sky", "did:plc:sky"),
+            "sky1"
+        );
+    }
+
     // ── send_line helper test ──
 
     #[test]
@@ -1318,4 +3402,168 @@ mod tests {
         // Should not panic
         send_line(&tx, "PRIVMSG #test :Hello");
     }
+
+    /// Run a connection over in-memory channels, returning the client's ends of them.
+    fn line_transport(
+        engine: Arc<ChatEngine>,
+        config: IrcSection,
+    ) -> (
+        mpsc::UnboundedSender<String>,
+        mpsc::UnboundedReceiver<String>,
+        tokio::task::JoinHandle<()>,
+    ) {
+        let db = SqlitePool::connect_lazy("sqlite::memory:").unwrap();
+        let (in_tx, in_rx) = mpsc::unbounded_channel::<String>();
+        let (out_tx, out_rx) = mpsc::unbounded_channel::<String>();
+
+        let lines = futures_util::stream::unfold(in_rx, |mut rx| async move {
+            rx.recv().await.map(|line| (Ok(line), rx))
+        });
+        let sink = futures_util::sink::unfold(out_tx, |tx, line: String| async move {
+            tx.send(line).map_err(|_| ())?;
+            Ok::<_, ()>(tx)
+        });
+        let conn = tokio::spawn(handle_connection(
+            lines,
+            sink,
+            "test".into(),
+            engine,
+            db,
+            Arc::new(config),
+            None,
+        ));
+        (in_tx, out_rx, conn)
+    }
+
+    /// Receive lines until one contains `needle`, returning it.
+    async fn recv_until(out_rx: &mut mpsc::UnboundedReceiver<String>, needle: &str) -> String {
+        loop {
+            let line = out_rx.recv().await.expect("connection closed");
+            if line.contains(needle) {
+                return line;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connection_over_line_transport() {
+        let engine = test_engine();
+        let (in_tx, mut out_rx, conn) = line_transport(engine.clone(), IrcSection::default());
+
+        in_tx.send("NICK wsguest".into()).unwrap();
+        in_tx.send("USER wsguest 0 * :Guest".into()).unwrap();
+        let welcome = out_rx.recv().await.unwrap();
+        assert!(welcome.starts_with(":concord 001 wsguest "));
+        assert!(!welcome.ends_with('\n'));
+        assert!(engine.get_session_by_nick("wsguest").is_some());
+
+        // Ending the inbound stream disconnects the session
+        drop(in_tx);
+        conn.await.unwrap();
+        assert!(engine.get_session_by_nick("wsguest").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_repeated_sasl_failures_close_connection() {
+        let (in_tx, mut out_rx, conn) = line_transport(test_engine(), IrcSection::default());
+        in_tx.send("CAP REQ :sasl".into()).unwrap();
+        recv_until(&mut out_rx, " ACK ").await;
+
+        let payload = BASE64.encode("\0alice\0wrong");
+        for _ in 0..MAX_SASL_FAILURES {
+            in_tx.send("AUTHENTICATE PLAIN".into()).unwrap();
+            recv_until(&mut out_rx, "AUTHENTICATE +").await;
+            in_tx.send(format!("AUTHENTICATE {payload}")).unwrap();
+            recv_until(&mut out_rx, " 904 ").await;
+        }
+        assert_eq!(
+            out_rx.recv().await.unwrap(),
+            "ERROR :Closing Link: * (Too many failed SASL attempts)"
+        );
+        conn.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_registration_timeout() {
+        let config = IrcSection {
+            registration_timeout_secs: 10,
+            ..IrcSection::default()
+        };
+        let (in_tx, mut out_rx, conn) = line_transport(test_engine(), config);
+        let started = tokio::time::Instant::now();
+
+        in_tx.send("NICK slowpoke".into()).unwrap();
+        conn.await.unwrap();
+        assert!(started.elapsed() >= Duration::from_secs(10));
+        assert_eq!(
+            out_rx.recv().await.unwrap(),
+            "ERROR :Closing Link: * (Registration timed out)"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ping_keepalive() {
+        let config = IrcSection {
+            ping_interval_secs: 30,
+            ping_timeout_secs: 20,
+            ..IrcSection::default()
+        };
+        let engine = test_engine();
+        let (in_tx, mut out_rx, conn) = line_transport(engine.clone(), config);
+        in_tx.send("NICK quiet".into()).unwrap();
+        in_tx.send("USER quiet 0 * :Quiet".into()).unwrap();
+        recv_until(&mut out_rx, " 001 quiet ").await;
+
+        // A quiet client is pinged, and answering keeps it connected
+        let started = tokio::time::Instant::now();
+        assert_eq!(recv_until(&mut out_rx, "PING :").await, "PING :concord");
+        assert!(started.elapsed() >= Duration::from_secs(30));
+        in_tx.send("PONG :concord".into()).unwrap();
+
+        // A PING left unanswered drops it
+        recv_until(&mut out_rx, "PING :").await;
+        assert_eq!(
+            recv_until(&mut out_rx, "ERROR").await,
+            "ERROR :Closing Link: quiet (Ping timeout: 20 seconds)"
+        );
+        conn.await.unwrap();
+        assert!(engine.get_session_by_nick("quiet").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_join_channel_limit() {
+        let engine = Arc::new(ChatEngine::new(None).with_irc_channel_limit(1));
+        let (in_tx, mut out_rx, _conn) = line_transport(engine.clone(), IrcSection::default());
+        in_tx.send("NICK joiner".into()).unwrap();
+        in_tx.send("USER joiner 0 * :Joiner".into()).unwrap();
+        assert!(
+            recv_until(&mut out_rx, " 005 joiner ")
+                .await
+                .contains(" CHANLIMIT=#:1 ")
+        );
+
+        in_tx.send("JOIN #first".into()).unwrap();
+        recv_until(&mut out_rx, "JOIN #first").await;
+        in_tx.send("JOIN #second".into()).unwrap();
+        assert_eq!(
+            recv_until(&mut out_rx, " 405 ").await,
+            ":concord 405 joiner #second :You have joined too many channels"
+        );
+        assert!(!engine.in_channel(
+            engine.get_session_by_nick("joiner").unwrap().id,
+            DEFAULT_SERVER_ID,
+            "#second"
+        ));
+
+        // Rejoining a channel it's already in doesn't count against the limit
+        in_tx.send("JOIN #first".into()).unwrap();
+        in_tx.send("PING :check".into()).unwrap();
+        loop {
+            let line = out_rx.recv().await.unwrap();
+            assert!(!line.contains(" 405 "), "{line}");
+            if line.contains("PONG") {
+                break;
+            }
+        }
+    }
 }
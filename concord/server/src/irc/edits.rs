@@ -0,0 +1,136 @@
+//! Message edits, deletions and reactions over IRC.
+//!
+//! Deletions use the `draft/message-redaction` REDACT command. Reactions ride
+//! on TAGMSG (see `commands::handle_tagmsg`) with the `+draft/react` /
+//! `+draft/unreact` and `+draft/reply` client tags. IRC has no settled way to
+//! edit, so edits are sent as a PRIVMSG tagged `+draft/edit=<msgid>`; other
+//! clients see a `* correction:` line from the author.
+
+use chrono::{DateTime, Utc};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::engine::chat_engine::ChatEngine;
+use crate::engine::events::{MessageKind, SessionId};
+
+use super::connection::{CapState, event_tags, tagged_lines};
+use super::formatter;
+use super::formatting::{self, IrcText};
+use super::parser::IrcMessage;
+
+/// Prefix of the line that stands in for an edit.
+const CORRECTION_PREFIX: &str = "* correction: ";
+
+/// Handle `REDACT <target> <msgid> [:reason]` by deleting the message. The
+/// deletion is echoed back as REDACT to everyone in the channel, including
+/// the sender. Concord doesn't store reasons, so any reason is dropped.
+pub async fn handle_redact(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    msg: &IrcMessage,
+) -> Vec<String> {
+    let [target, msgid, ..] = msg.params.as_slice() else {
+        return vec![formatter::err_needmoreparams(nick, "REDACT")];
+    };
+
+    match engine.delete_message(session_id, msgid).await {
+        Ok(()) => vec![],
+        Err(e) => {
+            warn!(error = %e, %target, %msgid, "REDACT failed");
+            let code = if e == "Message not found" {
+                "UNKNOWN_MSGID"
+            } else {
+                "REDACT_FORBIDDEN"
+            };
+            vec![formatter::fail("REDACT", code, &[target, msgid], &e)]
+        }
+    }
+}
+
+/// Apply the reaction tags of a TAGMSG: `+draft/react` or `+draft/unreact`
+/// together with `+draft/reply` adds or removes a reaction on the replied-to
+/// message. Failures are only logged, since TAGMSG is never answered.
+pub async fn apply_reaction_tags(engine: &ChatEngine, session_id: SessionId, msg: &IrcMessage) {
+    let Some(message_id) = msg.tag("+draft/reply") else {
+        return;
+    };
+    let non_empty = |key| msg.tag(key).filter(|emoji| !emoji.is_empty());
+
+    let result = if let Some(emoji) = non_empty("+draft/react") {
+        engine.add_reaction(session_id, message_id, emoji).await
+    } else if let Some(emoji) = non_empty("+draft/unreact") {
+        engine.remove_reaction(session_id, message_id, emoji).await
+    } else {
+        return;
+    };
+
+    if let Err(e) = result {
+        warn!(error = %e, %message_id, "TAGMSG reaction failed");
+    }
+}
+
+/// Handle `@+draft/edit=<msgid> PRIVMSG <target> :<new text>` by replacing the
+/// text of one of the sender's own messages.
+pub async fn handle_edit(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    msg: &IrcMessage,
+) -> Vec<String> {
+    let (Some(message_id), [_target, text, ..]) = (msg.tag("+draft/edit"), msg.params.as_slice())
+    else {
+        return vec![formatter::err_needmoreparams(nick, "PRIVMSG")];
+    };
+    let text = match formatting::classify(text) {
+        IrcText::Plain(text) | IrcText::Action(text) => text,
+        IrcText::Ctcp => return vec![],
+    };
+
+    match engine
+        .edit_message(session_id, message_id, &formatting::irc_to_markdown(text))
+        .await
+    {
+        Ok(()) => vec![],
+        Err(e) => {
+            warn!(error = %e, %message_id, "edit failed");
+            vec![formatter::server_notice(nick, &e)]
+        }
+    }
+}
+
+/// Lines announcing an edit: the new text as a `* correction:` PRIVMSG from
+/// the author, tagged `+draft/edit` for clients that accept client-only tags.
+pub(super) fn edit_lines(
+    caps: &CapState,
+    from: &str,
+    target: &str,
+    msgid: &str,
+    content: &str,
+    edited_at: DateTime<Utc>,
+) -> Vec<String> {
+    let (_, mut lines) = formatting::outgoing(content, MessageKind::Normal);
+    lines[0] = format!("{CORRECTION_PREFIX}{}", lines[0]);
+
+    let mut tags = event_tags(caps, edited_at, &Uuid::new_v4().to_string());
+    if caps.has("message-tags") {
+        tags.push(("+draft/edit".to_string(), msgid.to_string()));
+    }
+    tagged_lines(tags, "PRIVMSG", from, target, lines)
+}
+
+/// A reaction (`tag` is `+draft/react` or `+draft/unreact`) as a TAGMSG from
+/// the reacting user.
+pub(super) fn reaction_tagmsg(
+    caps: &CapState,
+    nick: &str,
+    target: &str,
+    tag: &str,
+    emoji: &str,
+    msgid: &str,
+) -> String {
+    let mut tags = event_tags(caps, Utc::now(), &Uuid::new_v4().to_string());
+    tags.push((tag.to_string(), emoji.to_string()));
+    tags.push(("+draft/reply".to_string(), msgid.to_string()));
+    formatter::with_tags(&tags, &formatter::tagmsg(nick, target))
+}
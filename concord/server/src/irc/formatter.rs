@@ -1,20 +1,53 @@
+use std::sync::OnceLock;
+
 use super::numerics::*;
-use super::parser::IrcMessage;
+use super::parser::{IrcMessage, format_tags};
 
 /// Helper to build IRC reply lines. All functions return formatted strings
 /// ready to send (caller appends \r\n).
-const SERVER_NAME: &str = "concord";
+const DEFAULT_SERVER_NAME: &str = "concord";
+const DEFAULT_NETWORK_NAME: &str = "Concord";
+
+/// The server's hostname and its network's name, from the `[irc]` config.
+static IDENTITY: OnceLock<(String, String)> = OnceLock::new();
+
+/// Set the server and network names once at startup; later calls are ignored.
+/// Until then (and in tests) they're `concord` and `Concord`.
+pub fn set_identity(server_name: &str, network_name: &str) {
+    let _ = IDENTITY.set((server_name.to_string(), network_name.to_string()));
+}
 
+/// The hostname the server sends its own messages from.
 pub fn server_name() -> &'static str {
-    SERVER_NAME
+    IDENTITY
+        .get()
+        .map_or(DEFAULT_SERVER_NAME, |(server, _)| server)
+}
+
+/// The network name shown in the welcome and RPL_ISUPPORT.
+pub fn network_name() -> &'static str {
+    IDENTITY
+        .get()
+        .map_or(DEFAULT_NETWORK_NAME, |(_, network)| network)
+}
+
+/// @tags <line> — prefix an already formatted line with IRCv3 message tags.
+pub fn with_tags(tags: &[(String, String)], line: &str) -> String {
+    if tags.is_empty() {
+        return line.to_string();
+    }
+    format!("@{} {}", format_tags(tags), line)
 }
 
 /// :concord 001 nick :Welcome to Concord, nick!
 pub fn rpl_welcome(nick: &str) -> String {
     IrcMessage::server_reply(
-        SERVER_NAME,
+        server_name(),
         RPL_WELCOME,
-        vec![nick.into(), format!("Welcome to Concord, {}!", nick)],
+        vec![
+            nick.into(),
+            format!("Welcome to {}, {}!", network_name(), nick),
+        ],
     )
     .format()
 }
@@ -22,11 +55,11 @@ pub fn rpl_welcome(nick: &str) -> String {
 /// :concord 002 nick :Your host is concord, running version 0.1.0
 pub fn rpl_yourhost(nick: &str) -> String {
     IrcMessage::server_reply(
-        SERVER_NAME,
+        server_name(),
         RPL_YOURHOST,
         vec![
             nick.into(),
-            format!("Your host is {}, running version 0.1.0", SERVER_NAME),
+            format!("Your host is {}, running version 0.1.0", server_name()),
         ],
     )
     .format()
@@ -35,7 +68,7 @@ pub fn rpl_yourhost(nick: &str) -> String {
 /// :concord 003 nick :This server was created ...
 pub fn rpl_created(nick: &str) -> String {
     IrcMessage::server_reply(
-        SERVER_NAME,
+        server_name(),
         RPL_CREATED,
         vec![nick.into(), "This server was created today".into()],
     )
@@ -45,11 +78,11 @@ pub fn rpl_created(nick: &str) -> String {
 /// :concord 004 nick concord 0.1.0 o o
 pub fn rpl_myinfo(nick: &str) -> String {
     IrcMessage::server_reply(
-        SERVER_NAME,
+        server_name(),
         RPL_MYINFO,
         vec![
             nick.into(),
-            SERVER_NAME.into(),
+            server_name().into(),
             "0.1.0".into(),
             "o".into(),
             "o".into(),
@@ -58,10 +91,87 @@ pub fn rpl_myinfo(nick: &str) -> String {
     .format()
 }
 
+/// :concord 005 nick TOKEN... :are supported by this server
+pub fn rpl_isupport(nick: &str, tokens: &[&str]) -> String {
+    let mut params = vec![nick.to_string()];
+    params.extend(tokens.iter().map(|t| t.to_string()));
+    params.push("are supported by this server".into());
+    IrcMessage::server_reply(server_name(), RPL_ISUPPORT, params).format()
+}
+
+/// :concord 251 nick :There are N users and 0 invisible on 1 servers
+pub fn rpl_luserclient(nick: &str, users: usize) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_LUSERCLIENT,
+        vec![
+            nick.into(),
+            format!("There are {users} users and 0 invisible on 1 servers"),
+        ],
+    )
+    .format()
+}
+
+/// :concord 254 nick N :channels formed
+pub fn rpl_luserchannels(nick: &str, channels: usize) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_LUSERCHANNELS,
+        vec![nick.into(), channels.to_string(), "channels formed".into()],
+    )
+    .format()
+}
+
+/// :concord 255 nick :I have N clients and 0 servers
+pub fn rpl_luserme(nick: &str, clients: usize) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_LUSERME,
+        vec![
+            nick.into(),
+            format!("I have {clients} clients and 0 servers"),
+        ],
+    )
+    .format()
+}
+
+/// :concord 375 nick :- concord Message of the day -
+pub fn rpl_motdstart(nick: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_MOTDSTART,
+        vec![
+            nick.into(),
+            format!("- {} Message of the day -", server_name()),
+        ],
+    )
+    .format()
+}
+
+/// :concord 372 nick :- line
+pub fn rpl_motd(nick: &str, line: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_MOTD,
+        vec![nick.into(), format!("- {line}")],
+    )
+    .format()
+}
+
+/// :concord 376 nick :End of /MOTD command.
+pub fn rpl_endofmotd(nick: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_ENDOFMOTD,
+        vec![nick.into(), "End of /MOTD command.".into()],
+    )
+    .format()
+}
+
 /// :concord 422 nick :MOTD File is missing
 pub fn err_nomotd(nick: &str) -> String {
     IrcMessage::server_reply(
-        SERVER_NAME,
+        server_name(),
         ERR_NOMOTD,
         vec![nick.into(), "MOTD File is missing".into()],
     )
@@ -71,13 +181,27 @@ pub fn err_nomotd(nick: &str) -> String {
 /// :nick!nick@concord JOIN #channel
 pub fn join(nick: &str, channel: &str) -> String {
     IrcMessage {
-        prefix: Some(format!("{}!{}@{}", nick, nick, SERVER_NAME)),
+        tags: Vec::new(),
+        prefix: Some(format!("{}!{}@{}", nick, nick, server_name())),
         command: "JOIN".into(),
         params: vec![channel.into()],
     }
     .format()
 }
 
+/// :nick!nick@concord JOIN #channel account :realname
+///
+/// The `extended-join` form; `account` is `*` for unauthenticated users.
+pub fn extended_join(nick: &str, channel: &str, account: &str, realname: &str) -> String {
+    IrcMessage {
+        tags: Vec::new(),
+        prefix: Some(format!("{}!{}@{}", nick, nick, server_name())),
+        command: "JOIN".into(),
+        params: vec![channel.into(), account.into(), realname.into()],
+    }
+    .format()
+}
+
 /// :nick!nick@concord PART #channel [:reason]
 pub fn part(nick: &str, channel: &str, reason: Option<&str>) -> String {
     let mut params = vec![channel.to_string()];
@@ -85,7 +209,8 @@ pub fn part(nick: &str, channel: &str, reason: Option<&str>) -> String {
         params.push(r.to_string());
     }
     IrcMessage {
-        prefix: Some(format!("{}!{}@{}", nick, nick, SERVER_NAME)),
+        tags: Vec::new(),
+        prefix: Some(format!("{}!{}@{}", nick, nick, server_name())),
         command: "PART".into(),
         params,
     }
@@ -94,9 +219,42 @@ pub fn part(nick: &str, channel: &str, reason: Option<&str>) -> String {
 
 /// :nick!nick@concord PRIVMSG target :message
 pub fn privmsg(nick: &str, target: &str, message: &str) -> String {
+    user_message("PRIVMSG", nick, target, message)
+}
+
+/// :nick!nick@concord NOTICE target :message
+pub fn notice(nick: &str, target: &str, message: &str) -> String {
+    user_message("NOTICE", nick, target, message)
+}
+
+/// :nick!nick@concord TAGMSG target (the tags carry the content)
+pub fn tagmsg(nick: &str, target: &str) -> String {
+    IrcMessage {
+        tags: Vec::new(),
+        prefix: Some(format!("{}!{}@{}", nick, nick, server_name())),
+        command: "TAGMSG".into(),
+        params: vec![target.into()],
+    }
+    .format()
+}
+
+/// :nick!nick@concord REDACT target msgid
+pub fn redact(nick: &str, target: &str, msgid: &str) -> String {
+    IrcMessage {
+        tags: Vec::new(),
+        prefix: Some(format!("{}!{}@{}", nick, nick, server_name())),
+        command: "REDACT".into(),
+        params: vec![target.into(), msgid.into()],
+    }
+    .format()
+}
+
+/// A PRIVMSG or NOTICE from a user.
+pub fn user_message(command: &str, nick: &str, target: &str, message: &str) -> String {
     IrcMessage {
-        prefix: Some(format!("{}!{}@{}", nick, nick, SERVER_NAME)),
-        command: "PRIVMSG".into(),
+        tags: Vec::new(),
+        prefix: Some(format!("{}!{}@{}", nick, nick, server_name())),
+        command: command.into(),
         params: vec![target.into(), message.into()],
     }
     .format()
@@ -109,7 +267,8 @@ pub fn quit(nick: &str, reason: Option<&str>) -> String {
         params.push(r.to_string());
     }
     IrcMessage {
-        prefix: Some(format!("{}!{}@{}", nick, nick, SERVER_NAME)),
+        tags: Vec::new(),
+        prefix: Some(format!("{}!{}@{}", nick, nick, server_name())),
         command: "QUIT".into(),
         params,
     }
@@ -119,7 +278,8 @@ pub fn quit(nick: &str, reason: Option<&str>) -> String {
 /// :nick!nick@concord NICK newnick
 pub fn nick_change(old_nick: &str, new_nick: &str) -> String {
     IrcMessage {
-        prefix: Some(format!("{}!{}@{}", old_nick, old_nick, SERVER_NAME)),
+        tags: Vec::new(),
+        prefix: Some(format!("{}!{}@{}", old_nick, old_nick, server_name())),
         command: "NICK".into(),
         params: vec![new_nick.into()],
     }
@@ -129,7 +289,7 @@ pub fn nick_change(old_nick: &str, new_nick: &str) -> String {
 /// :concord 332 nick #channel :topic text
 pub fn rpl_topic(nick: &str, channel: &str, topic: &str) -> String {
     IrcMessage::server_reply(
-        SERVER_NAME,
+        server_name(),
         RPL_TOPIC,
         vec![nick.into(), channel.into(), topic.into()],
     )
@@ -139,7 +299,7 @@ pub fn rpl_topic(nick: &str, channel: &str, topic: &str) -> String {
 /// :concord 331 nick #channel :No topic is set
 pub fn rpl_notopic(nick: &str, channel: &str) -> String {
     IrcMessage::server_reply(
-        SERVER_NAME,
+        server_name(),
         RPL_NOTOPIC,
         vec![nick.into(), channel.into(), "No topic is set".into()],
     )
@@ -149,7 +309,8 @@ pub fn rpl_notopic(nick: &str, channel: &str) -> String {
 /// :nick!nick@concord TOPIC #channel :new topic
 pub fn topic_change(nick: &str, channel: &str, topic: &str) -> String {
     IrcMessage {
-        prefix: Some(format!("{}!{}@{}", nick, nick, SERVER_NAME)),
+        tags: Vec::new(),
+        prefix: Some(format!("{}!{}@{}", nick, nick, server_name())),
         command: "TOPIC".into(),
         params: vec![channel.into(), topic.into()],
     }
@@ -159,7 +320,7 @@ pub fn topic_change(nick: &str, channel: &str, topic: &str) -> String {
 /// :concord 353 nick = #channel :nick1 nick2 nick3
 pub fn rpl_namreply(nick: &str, channel: &str, members: &[String]) -> String {
     IrcMessage::server_reply(
-        SERVER_NAME,
+        server_name(),
         RPL_NAMREPLY,
         vec![nick.into(), "=".into(), channel.into(), members.join(" ")],
     )
@@ -169,7 +330,7 @@ pub fn rpl_namreply(nick: &str, channel: &str, members: &[String]) -> String {
 /// :concord 366 nick #channel :End of /NAMES list
 pub fn rpl_endofnames(nick: &str, channel: &str) -> String {
     IrcMessage::server_reply(
-        SERVER_NAME,
+        server_name(),
         RPL_ENDOFNAMES,
         vec![nick.into(), channel.into(), "End of /NAMES list".into()],
     )
@@ -179,7 +340,7 @@ pub fn rpl_endofnames(nick: &str, channel: &str) -> String {
 /// :concord 322 nick #channel member_count :topic
 pub fn rpl_list(nick: &str, channel: &str, member_count: usize, topic: &str) -> String {
     IrcMessage::server_reply(
-        SERVER_NAME,
+        server_name(),
         RPL_LIST,
         vec![
             nick.into(),
@@ -194,7 +355,7 @@ pub fn rpl_list(nick: &str, channel: &str, member_count: usize, topic: &str) ->
 /// :concord 323 nick :End of /LIST
 pub fn rpl_listend(nick: &str) -> String {
     IrcMessage::server_reply(
-        SERVER_NAME,
+        server_name(),
         RPL_LISTEND,
         vec![nick.into(), "End of /LIST".into()],
     )
@@ -204,13 +365,13 @@ pub fn rpl_listend(nick: &str) -> String {
 /// :concord 311 requestor nick user host * :realname
 pub fn rpl_whoisuser(requestor: &str, nick: &str) -> String {
     IrcMessage::server_reply(
-        SERVER_NAME,
+        server_name(),
         RPL_WHOISUSER,
         vec![
             requestor.into(),
             nick.into(),
             nick.into(),
-            SERVER_NAME.into(),
+            server_name().into(),
             "*".into(),
             nick.into(),
         ],
@@ -221,34 +382,92 @@ pub fn rpl_whoisuser(requestor: &str, nick: &str) -> String {
 /// :concord 312 requestor nick server :server info
 pub fn rpl_whoisserver(requestor: &str, nick: &str) -> String {
     IrcMessage::server_reply(
-        SERVER_NAME,
+        server_name(),
         RPL_WHOISSERVER,
         vec![
             requestor.into(),
             nick.into(),
-            SERVER_NAME.into(),
+            server_name().into(),
             "Concord IRC-compatible chat server".into(),
         ],
     )
     .format()
 }
 
+/// :concord 330 requestor nick account :is logged in as
+pub fn rpl_whoisaccount(requestor: &str, nick: &str, account: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_WHOISACCOUNT,
+        vec![
+            requestor.into(),
+            nick.into(),
+            account.into(),
+            "is logged in as".into(),
+        ],
+    )
+    .format()
+}
+
 /// :concord 318 requestor nick :End of /WHOIS list
 pub fn rpl_endofwhois(requestor: &str, nick: &str) -> String {
     IrcMessage::server_reply(
-        SERVER_NAME,
+        server_name(),
         RPL_ENDOFWHOIS,
         vec![requestor.into(), nick.into(), "End of /WHOIS list".into()],
     )
     .format()
 }
 
+// Away
+
+/// :concord 301 requestor nick :away message
+pub fn rpl_away(requestor: &str, nick: &str, message: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_AWAY,
+        vec![requestor.into(), nick.into(), message.into()],
+    )
+    .format()
+}
+
+/// :concord 305 nick :You are no longer marked as being away
+pub fn rpl_unaway(nick: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_UNAWAY,
+        vec![nick.into(), "You are no longer marked as being away".into()],
+    )
+    .format()
+}
+
+/// :concord 306 nick :You have been marked as being away
+pub fn rpl_nowaway(nick: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_NOWAWAY,
+        vec![nick.into(), "You have been marked as being away".into()],
+    )
+    .format()
+}
+
+/// :nick!nick@concord AWAY [:message] — away-notify; no message means back.
+pub fn away(nick: &str, message: Option<&str>) -> String {
+    IrcMessage {
+        tags: Vec::new(),
+        prefix: Some(format!("{}!{}@{}", nick, nick, server_name())),
+        command: "AWAY".into(),
+        params: message.map(|m| vec![m.to_string()]).unwrap_or_default(),
+    }
+    .format()
+}
+
 // Error replies
 
 /// :concord 401 nick target :No such nick/channel
 pub fn err_nosuchnick(nick: &str, target: &str) -> String {
     IrcMessage::server_reply(
-        SERVER_NAME,
+        server_name(),
         ERR_NOSUCHNICK,
         vec![nick.into(), target.into(), "No such nick/channel".into()],
     )
@@ -258,17 +477,27 @@ pub fn err_nosuchnick(nick: &str, target: &str) -> String {
 /// :concord 403 nick channel :No such channel
 pub fn err_nosuchchannel(nick: &str, channel: &str) -> String {
     IrcMessage::server_reply(
-        SERVER_NAME,
+        server_name(),
         ERR_NOSUCHCHANNEL,
         vec![nick.into(), channel.into(), "No such channel".into()],
     )
     .format()
 }
 
+/// :concord 404 nick channel :Cannot send to channel
+pub fn err_cannotsendtochan(nick: &str, channel: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        ERR_CANNOTSENDTOCHAN,
+        vec![nick.into(), channel.into(), "Cannot send to channel".into()],
+    )
+    .format()
+}
+
 /// :concord 421 nick command :Unknown command
 pub fn err_unknowncommand(nick: &str, command: &str) -> String {
     IrcMessage::server_reply(
-        SERVER_NAME,
+        server_name(),
         ERR_UNKNOWNCOMMAND,
         vec![nick.into(), command.into(), "Unknown command".into()],
     )
@@ -278,7 +507,7 @@ pub fn err_unknowncommand(nick: &str, command: &str) -> String {
 /// :concord 431 nick :No nickname given
 pub fn err_nonicknamegiven(nick: &str) -> String {
     IrcMessage::server_reply(
-        SERVER_NAME,
+        server_name(),
         ERR_NONICKNAMEGIVEN,
         vec![nick.into(), "No nickname given".into()],
     )
@@ -288,7 +517,7 @@ pub fn err_nonicknamegiven(nick: &str) -> String {
 /// :concord 433 nick newnick :Nickname is already in use
 pub fn err_nicknameinuse(nick: &str, wanted: &str) -> String {
     IrcMessage::server_reply(
-        SERVER_NAME,
+        server_name(),
         ERR_NICKNAMEINUSE,
         vec![
             nick.into(),
@@ -302,7 +531,7 @@ pub fn err_nicknameinuse(nick: &str, wanted: &str) -> String {
 /// :concord 442 nick channel :You're not on that channel
 pub fn err_notonchannel(nick: &str, channel: &str) -> String {
     IrcMessage::server_reply(
-        SERVER_NAME,
+        server_name(),
         ERR_NOTONCHANNEL,
         vec![
             nick.into(),
@@ -316,7 +545,7 @@ pub fn err_notonchannel(nick: &str, channel: &str) -> String {
 /// :concord 451 * :You have not registered
 pub fn err_notregistered() -> String {
     IrcMessage::server_reply(
-        SERVER_NAME,
+        server_name(),
         ERR_NOTREGISTERED,
         vec!["*".into(), "You have not registered".into()],
     )
@@ -326,7 +555,7 @@ pub fn err_notregistered() -> String {
 /// :concord 461 nick command :Not enough parameters
 pub fn err_needmoreparams(nick: &str, command: &str) -> String {
     IrcMessage::server_reply(
-        SERVER_NAME,
+        server_name(),
         ERR_NEEDMOREPARAMS,
         vec![nick.into(), command.into(), "Not enough parameters".into()],
     )
@@ -336,125 +565,903 @@ pub fn err_needmoreparams(nick: &str, command: &str) -> String {
 /// :concord 462 nick :You may not reregister
 pub fn err_alreadyregistered(nick: &str) -> String {
     IrcMessage::server_reply(
-        SERVER_NAME,
+        server_name(),
         ERR_ALREADYREGISTERED,
         vec![nick.into(), "You may not reregister".into()],
     )
     .format()
 }
 
-/// PING :token
-pub fn ping(token: &str) -> String {
-    IrcMessage {
-        prefix: None,
-        command: "PING".into(),
-        params: vec![token.into()],
+// Capability negotiation
+
+/// :concord CAP nick SUBCOMMAND [*] :cap1 cap2
+///
+/// `more` adds the `*` continuation marker used by multi-line CAP LS/LIST replies.
+pub fn cap(nick: &str, subcommand: &str, caps: &str, more: bool) -> String {
+    let mut params = vec![nick.into(), subcommand.into()];
+    if more {
+        params.push("*".into());
     }
+    params.push(caps.into());
+    IrcMessage::server_reply(server_name(), "CAP", params).format()
+}
+
+/// :concord 410 nick subcommand :Invalid CAP command
+pub fn err_invalidcapcmd(nick: &str, subcommand: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        ERR_INVALIDCAPCMD,
+        vec![nick.into(), subcommand.into(), "Invalid CAP command".into()],
+    )
     .format()
 }
 
-/// :concord PONG concord :token
-pub fn pong(token: &str) -> String {
+// SASL
+
+/// AUTHENTICATE payload (`+` for an empty challenge)
+pub fn authenticate(payload: &str) -> String {
     IrcMessage {
-        prefix: Some(SERVER_NAME.into()),
-        command: "PONG".into(),
-        params: vec![SERVER_NAME.into(), token.into()],
+        tags: Vec::new(),
+        prefix: None,
+        command: "AUTHENTICATE".into(),
+        params: vec![payload.into()],
     }
     .format()
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    // ── Server name helper ──
+/// :concord 900 nick nick!nick@concord account :You are now logged in as account
+pub fn rpl_loggedin(nick: &str, account: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_LOGGEDIN,
+        vec![
+            nick.into(),
+            format!("{}!{}@{}", nick, nick, server_name()),
+            account.into(),
+            format!("You are now logged in as {}", account),
+        ],
+    )
+    .format()
+}
 
-    #[test]
-    fn test_server_name() {
-        assert_eq!(server_name(), "concord");
-    }
+/// :concord 903 nick :SASL authentication successful
+pub fn rpl_saslsuccess(nick: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_SASLSUCCESS,
+        vec![nick.into(), "SASL authentication successful".into()],
+    )
+    .format()
+}
 
-    // ── Welcome burst (001-004) ──
+/// :concord 904 nick :SASL authentication failed
+pub fn err_saslfail(nick: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        ERR_SASLFAIL,
+        vec![nick.into(), "SASL authentication failed".into()],
+    )
+    .format()
+}
 
-    #[test]
-    fn test_rpl_welcome() {
-        let result = rpl_welcome("alice");
-        assert_eq!(result, ":concord 001 alice :Welcome to Concord, alice!");
-    }
+/// :concord 905 nick :SASL message too long
+pub fn err_sasltoolong(nick: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        ERR_SASLTOOLONG,
+        vec![nick.into(), "SASL message too long".into()],
+    )
+    .format()
+}
 
-    #[test]
-    fn test_rpl_yourhost() {
-        let result = rpl_yourhost("alice");
-        assert_eq!(
-            result,
-            ":concord 002 alice :Your host is concord, running version 0.1.0"
-        );
-    }
+/// :concord 906 nick :SASL authentication aborted
+pub fn err_saslaborted(nick: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        ERR_SASLABORTED,
+        vec![nick.into(), "SASL authentication aborted".into()],
+    )
+    .format()
+}
 
-    #[test]
-    fn test_rpl_created() {
-        let result = rpl_created("alice");
-        assert_eq!(result, ":concord 003 alice :This server was created today");
-    }
+/// :concord 907 nick :You have already authenticated using SASL
+pub fn err_saslalready(nick: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        ERR_SASLALREADY,
+        vec![
+            nick.into(),
+            "You have already authenticated using SASL".into(),
+        ],
+    )
+    .format()
+}
 
-    #[test]
-    fn test_rpl_myinfo() {
-        let result = rpl_myinfo("alice");
-        assert_eq!(result, ":concord 004 alice concord 0.1.0 o o");
-    }
+/// :concord 908 nick PLAIN,EXTERNAL :are available SASL mechanisms
+pub fn rpl_saslmechs(nick: &str, mechanisms: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_SASLMECHS,
+        vec![
+            nick.into(),
+            mechanisms.into(),
+            "are available SASL mechanisms".into(),
+        ],
+    )
+    .format()
+}
 
-    // ── MOTD ──
+// Channel modes and moderation
 
-    #[test]
-    fn test_err_nomotd() {
-        let result = err_nomotd("alice");
-        assert_eq!(result, ":concord 422 alice :MOTD File is missing");
+/// :nick!nick@concord MODE channel modes [args...]
+pub fn mode(nick: &str, channel: &str, modes: &str, args: &[&str]) -> String {
+    let mut params = vec![channel.to_string(), modes.to_string()];
+    params.extend(args.iter().map(|a| a.to_string()));
+    IrcMessage {
+        tags: Vec::new(),
+        prefix: Some(format!("{}!{}@{}", nick, nick, server_name())),
+        command: "MODE".into(),
+        params,
     }
+    .format()
+}
 
-    // ── JOIN / PART / QUIT / NICK ──
+/// :concord MODE channel modes [args...] — a mode change not attributed to an IRC user.
+pub fn server_mode(channel: &str, modes: &str, args: &[&str]) -> String {
+    let mut params = vec![channel.to_string(), modes.to_string()];
+    params.extend(args.iter().map(|a| a.to_string()));
+    IrcMessage::server_reply(server_name(), "MODE", params).format()
+}
 
-    #[test]
-    fn test_join_format() {
-        let result = join("alice", "#general");
-        assert_eq!(result, ":alice!alice@concord JOIN #general");
+/// :nick!nick@concord KICK channel target :reason
+pub fn kick(nick: &str, channel: &str, target: &str, reason: Option<&str>) -> String {
+    let mut params = vec![channel.to_string(), target.to_string()];
+    if let Some(r) = reason {
+        params.push(r.to_string());
     }
-
-    #[test]
-    fn test_part_without_reason() {
-        let result = part("alice", "#general", None);
-        assert_eq!(result, ":alice!alice@concord PART #general");
+    IrcMessage {
+        tags: Vec::new(),
+        prefix: Some(format!("{}!{}@{}", nick, nick, server_name())),
+        command: "KICK".into(),
+        params,
     }
+    .format()
+}
 
-    #[test]
-    fn test_part_with_reason() {
-        let result = part("alice", "#general", Some("Goodbye"));
-        // Single-word reason has no colon prefix (format() only adds : for spaces/empty)
-        assert_eq!(result, ":alice!alice@concord PART #general Goodbye");
-    }
+/// :concord NOTICE nick :text
+pub fn server_notice(nick: &str, text: &str) -> String {
+    IrcMessage::server_reply(server_name(), "NOTICE", vec![nick.into(), text.into()]).format()
+}
 
-    #[test]
-    fn test_privmsg_format() {
-        let result = privmsg("alice", "#general", "Hello world");
-        assert_eq!(result, ":alice!alice@concord PRIVMSG #general :Hello world");
-    }
+/// :concord 221 nick modes
+pub fn rpl_umodeis(nick: &str, modes: &str) -> String {
+    IrcMessage::server_reply(server_name(), RPL_UMODEIS, vec![nick.into(), modes.into()]).format()
+}
 
-    #[test]
-    fn test_privmsg_single_word() {
-        let result = privmsg("alice", "bob", "hello");
-        // Single word trailing should NOT get colon since it has no spaces
-        // But the format method adds colon only for last param with spaces or empty
-        // "hello" has no spaces, so no colon prefix
-        assert_eq!(result, ":alice!alice@concord PRIVMSG bob hello");
-    }
+/// :concord 324 nick channel modes
+pub fn rpl_channelmodeis(nick: &str, channel: &str, modes: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_CHANNELMODEIS,
+        vec![nick.into(), channel.into(), modes.into()],
+    )
+    .format()
+}
 
-    #[test]
-    fn test_quit_without_reason() {
-        let result = quit("alice", None);
-        assert_eq!(result, ":alice!alice@concord QUIT");
-    }
+/// :concord 329 nick channel created_at
+pub fn rpl_creationtime(nick: &str, channel: &str, created_at: i64) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_CREATIONTIME,
+        vec![nick.into(), channel.into(), created_at.to_string()],
+    )
+    .format()
+}
 
-    #[test]
-    fn test_quit_with_reason() {
+/// :concord 367 nick channel mask set_by set_at
+pub fn rpl_banlist(nick: &str, channel: &str, mask: &str, set_by: &str, set_at: i64) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_BANLIST,
+        vec![
+            nick.into(),
+            channel.into(),
+            mask.into(),
+            set_by.into(),
+            set_at.to_string(),
+        ],
+    )
+    .format()
+}
+
+/// :concord 368 nick channel :End of channel ban list
+pub fn rpl_endofbanlist(nick: &str, channel: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_ENDOFBANLIST,
+        vec![
+            nick.into(),
+            channel.into(),
+            "End of channel ban list".into(),
+        ],
+    )
+    .format()
+}
+
+/// :concord 728 nick channel q mask set_by set_at
+pub fn rpl_quietlist(nick: &str, channel: &str, mask: &str, set_by: &str, set_at: i64) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_QUIETLIST,
+        vec![
+            nick.into(),
+            channel.into(),
+            "q".into(),
+            mask.into(),
+            set_by.into(),
+            set_at.to_string(),
+        ],
+    )
+    .format()
+}
+
+/// :concord 729 nick channel q :End of channel quiet list
+pub fn rpl_endofquietlist(nick: &str, channel: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_ENDOFQUIETLIST,
+        vec![
+            nick.into(),
+            channel.into(),
+            "q".into(),
+            "End of channel quiet list".into(),
+        ],
+    )
+    .format()
+}
+
+/// :concord 441 nick target channel :They aren't on that channel
+pub fn err_usernotinchannel(nick: &str, target: &str, channel: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        ERR_USERNOTINCHANNEL,
+        vec![
+            nick.into(),
+            target.into(),
+            channel.into(),
+            "They aren't on that channel".into(),
+        ],
+    )
+    .format()
+}
+
+/// :concord 472 nick char :is unknown mode char to me
+pub fn err_unknownmode(nick: &str, mode: char) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        ERR_UNKNOWNMODE,
+        vec![
+            nick.into(),
+            mode.to_string(),
+            "is unknown mode char to me".into(),
+        ],
+    )
+    .format()
+}
+
+/// :concord 482 nick channel :You're not channel operator
+pub fn err_chanoprivsneeded(nick: &str, channel: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        ERR_CHANOPRIVSNEEDED,
+        vec![
+            nick.into(),
+            channel.into(),
+            "You're not channel operator".into(),
+        ],
+    )
+    .format()
+}
+
+// Invites and knocks
+
+/// :nick!nick@concord INVITE target #channel
+pub fn invite(nick: &str, target: &str, channel: &str) -> String {
+    IrcMessage {
+        tags: Vec::new(),
+        prefix: Some(format!("{}!{}@{}", nick, nick, server_name())),
+        command: "INVITE".into(),
+        params: vec![target.into(), channel.into()],
+    }
+    .format()
+}
+
+/// :concord 341 nick target channel
+pub fn rpl_inviting(nick: &str, target: &str, channel: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_INVITING,
+        vec![nick.into(), target.into(), channel.into()],
+    )
+    .format()
+}
+
+/// :concord 443 nick target channel :is already on channel
+pub fn err_useronchannel(nick: &str, target: &str, channel: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        ERR_USERONCHANNEL,
+        vec![
+            nick.into(),
+            target.into(),
+            channel.into(),
+            "is already on channel".into(),
+        ],
+    )
+    .format()
+}
+
+/// :concord 473 nick channel :Cannot join channel (+i)
+pub fn err_inviteonlychan(nick: &str, channel: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        ERR_INVITEONLYCHAN,
+        vec![
+            nick.into(),
+            channel.into(),
+            "Cannot join channel (+i)".into(),
+        ],
+    )
+    .format()
+}
+
+/// :concord 405 nick channel :You have joined too many channels
+pub fn err_toomanychannels(nick: &str, channel: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        ERR_TOOMANYCHANNELS,
+        vec![
+            nick.into(),
+            channel.into(),
+            "You have joined too many channels".into(),
+        ],
+    )
+    .format()
+}
+
+/// :concord 475 nick channel :Cannot join channel (+k)
+///
+/// Sent when the channel's server needs an invite code, which JOIN takes as the key.
+pub fn err_badchannelkey(nick: &str, channel: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        ERR_BADCHANNELKEY,
+        vec![
+            nick.into(),
+            channel.into(),
+            "Cannot join channel (+k)".into(),
+        ],
+    )
+    .format()
+}
+
+/// :concord 710 nick channel mask :has asked for an invite
+pub fn rpl_knock(nick: &str, channel: &str, knocker: &str, message: Option<&str>) -> String {
+    let text = match message {
+        Some(m) => format!("has asked for an invite: {m}"),
+        None => "has asked for an invite".into(),
+    };
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_KNOCK,
+        vec![
+            nick.into(),
+            channel.into(),
+            format!("{}!{}@{}", knocker, knocker, server_name()),
+            text,
+        ],
+    )
+    .format()
+}
+
+/// :concord 711 nick channel :Your KNOCK has been delivered
+pub fn rpl_knockdlvr(nick: &str, channel: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_KNOCKDLVR,
+        vec![
+            nick.into(),
+            channel.into(),
+            "Your KNOCK has been delivered".into(),
+        ],
+    )
+    .format()
+}
+
+/// :concord 712 nick channel :Too many KNOCKs (channel)
+pub fn err_toomanyknock(nick: &str, channel: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        ERR_TOOMANYKNOCK,
+        vec![
+            nick.into(),
+            channel.into(),
+            "Too many KNOCKs (channel)".into(),
+        ],
+    )
+    .format()
+}
+
+/// :concord 713 nick channel :Channel is open
+pub fn err_chanopen(nick: &str, channel: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        ERR_CHANOPEN,
+        vec![nick.into(), channel.into(), "Channel is open".into()],
+    )
+    .format()
+}
+
+/// :concord 714 nick channel :You are already on that channel
+pub fn err_knockonchan(nick: &str, channel: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        ERR_KNOCKONCHAN,
+        vec![
+            nick.into(),
+            channel.into(),
+            "You are already on that channel".into(),
+        ],
+    )
+    .format()
+}
+
+// Operators
+
+/// :nick!nick@concord WALLOPS :message
+pub fn wallops(nick: &str, message: &str) -> String {
+    IrcMessage {
+        tags: Vec::new(),
+        prefix: Some(format!("{}!{}@{}", nick, nick, server_name())),
+        command: "WALLOPS".into(),
+        params: vec![message.into()],
+    }
+    .format()
+}
+
+/// :concord 313 requestor nick :is an IRC operator
+pub fn rpl_whoisoperator(requestor: &str, nick: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_WHOISOPERATOR,
+        vec![requestor.into(), nick.into(), "is an IRC operator".into()],
+    )
+    .format()
+}
+
+/// :concord 381 nick :You are now an IRC operator
+pub fn rpl_youreoper(nick: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_YOUREOPER,
+        vec![nick.into(), "You are now an IRC operator".into()],
+    )
+    .format()
+}
+
+/// :concord 402 nick server :No such server
+pub fn err_nosuchserver(nick: &str, server: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        ERR_NOSUCHSERVER,
+        vec![nick.into(), server.into(), "No such server".into()],
+    )
+    .format()
+}
+
+/// :concord 464 nick :Password incorrect
+pub fn err_passwdmismatch(nick: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        ERR_PASSWDMISMATCH,
+        vec![nick.into(), "Password incorrect".into()],
+    )
+    .format()
+}
+
+/// :concord 481 nick :Permission Denied- You're not an IRC operator
+pub fn err_noprivileges(nick: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        ERR_NOPRIVILEGES,
+        vec![
+            nick.into(),
+            "Permission Denied- You're not an IRC operator".into(),
+        ],
+    )
+    .format()
+}
+
+/// :concord 491 nick :No O-lines for your host
+pub fn err_nooperhost(nick: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        ERR_NOOPERHOST,
+        vec![nick.into(), "No O-lines for your host".into()],
+    )
+    .format()
+}
+
+/// :concord 242 nick :Server Up D days H:MM:SS
+pub fn rpl_statsuptime(nick: &str, uptime_secs: i64) -> String {
+    let (days, rest) = (uptime_secs / 86400, uptime_secs % 86400);
+    let text = format!(
+        "Server Up {} days {}:{:02}:{:02}",
+        days,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60
+    );
+    IrcMessage::server_reply(server_name(), RPL_STATSUPTIME, vec![nick.into(), text]).format()
+}
+
+/// :concord 249 nick query :text
+pub fn rpl_statsdebug(nick: &str, query: &str, text: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_STATSDEBUG,
+        vec![nick.into(), query.into(), text.into()],
+    )
+    .format()
+}
+
+/// :concord 219 nick query :End of /STATS report
+pub fn rpl_endofstats(nick: &str, query: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_ENDOFSTATS,
+        vec![nick.into(), query.into(), "End of /STATS report".into()],
+    )
+    .format()
+}
+
+// WHO, USERHOST and ISON
+
+/// :concord 352 nick channel user host server target flags :0 realname
+pub fn rpl_whoreply(nick: &str, channel: &str, target: &str, flags: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_WHOREPLY,
+        vec![
+            nick.into(),
+            channel.into(),
+            target.into(),
+            server_name().into(),
+            server_name().into(),
+            target.into(),
+            flags.into(),
+            format!("0 {target}"),
+        ],
+    )
+    .format()
+}
+
+/// :concord 354 nick field... — a WHOX reply carrying the requested fields in order
+pub fn rpl_whospcrpl(nick: &str, fields: &[String]) -> String {
+    let mut params = vec![nick.to_string()];
+    params.extend(fields.iter().cloned());
+    IrcMessage::server_reply(server_name(), RPL_WHOSPCRPL, params).format()
+}
+
+/// :concord 315 nick mask :End of /WHO list
+pub fn rpl_endofwho(nick: &str, mask: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_ENDOFWHO,
+        vec![nick.into(), mask.into(), "End of /WHO list".into()],
+    )
+    .format()
+}
+
+/// :concord 302 nick :alice=+alice@concord bob*=-bob@concord
+pub fn rpl_userhost(nick: &str, replies: &[String]) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_USERHOST,
+        vec![nick.into(), replies.join(" ")],
+    )
+    .format()
+}
+
+/// :concord 303 nick :alice bob
+pub fn rpl_ison(nick: &str, online: &[String]) -> String {
+    IrcMessage::server_reply(server_name(), RPL_ISON, vec![nick.into(), online.join(" ")]).format()
+}
+
+// Monitoring
+
+/// :concord 730 nick :alice!alice@concord,bob!bob@concord
+pub fn rpl_mononline(nick: &str, targets: &[String]) -> String {
+    let masks: Vec<String> = targets
+        .iter()
+        .map(|t| format!("{t}!{t}@{}", server_name()))
+        .collect();
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_MONONLINE,
+        vec![nick.into(), masks.join(",")],
+    )
+    .format()
+}
+
+/// :concord 731 nick :alice,bob
+pub fn rpl_monoffline(nick: &str, targets: &[String]) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_MONOFFLINE,
+        vec![nick.into(), targets.join(",")],
+    )
+    .format()
+}
+
+/// :concord 732 nick :alice,bob
+pub fn rpl_monlist(nick: &str, targets: &[String]) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_MONLIST,
+        vec![nick.into(), targets.join(",")],
+    )
+    .format()
+}
+
+/// :concord 733 nick :End of MONITOR list
+pub fn rpl_endofmonlist(nick: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        RPL_ENDOFMONLIST,
+        vec![nick.into(), "End of MONITOR list".into()],
+    )
+    .format()
+}
+
+/// :concord 734 nick limit targets :Monitor list is full.
+pub fn err_monlistfull(nick: &str, limit: usize, targets: &[String]) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        ERR_MONLISTFULL,
+        vec![
+            nick.into(),
+            limit.to_string(),
+            targets.join(","),
+            "Monitor list is full.".into(),
+        ],
+    )
+    .format()
+}
+
+// Batches and history
+
+/// :concord BATCH +reference type [params...]
+pub fn batch_start(reference: &str, batch_type: &str, params: &[&str]) -> String {
+    let mut all = vec![format!("+{}", reference), batch_type.into()];
+    all.extend(params.iter().map(|p| p.to_string()));
+    IrcMessage::server_reply(server_name(), "BATCH", all).format()
+}
+
+/// :concord BATCH -reference
+pub fn batch_end(reference: &str) -> String {
+    IrcMessage::server_reply(server_name(), "BATCH", vec![format!("-{}", reference)]).format()
+}
+
+/// :concord CHATHISTORY TARGETS target timestamp
+pub fn chathistory_target(target: &str, timestamp: &str) -> String {
+    IrcMessage::server_reply(
+        server_name(),
+        "CHATHISTORY",
+        vec!["TARGETS".into(), target.into(), timestamp.into()],
+    )
+    .format()
+}
+
+/// :concord FAIL command CODE [context...] :description — an IRCv3 standard reply.
+pub fn fail(command: &str, code: &str, context: &[&str], description: &str) -> String {
+    let mut params = vec![command.to_string(), code.to_string()];
+    params.extend(context.iter().map(|c| c.to_string()));
+    params.push(description.into());
+    IrcMessage::server_reply(server_name(), "FAIL", params).format()
+}
+
+/// PING :token
+pub fn ping(token: &str) -> String {
+    IrcMessage {
+        tags: Vec::new(),
+        prefix: None,
+        command: "PING".into(),
+        params: vec![token.into()],
+    }
+    .format()
+}
+
+/// :concord PONG concord :token
+pub fn pong(token: &str) -> String {
+    IrcMessage {
+        tags: Vec::new(),
+        prefix: Some(server_name().into()),
+        command: "PONG".into(),
+        params: vec![server_name().into(), token.into()],
+    }
+    .format()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ── Server name helper ──
+
+    #[test]
+    fn test_server_name() {
+        assert_eq!(server_name(), "concord");
+    }
+
+    // ── Message tags ──
+
+    #[test]
+    fn test_with_tags() {
+        let tags = vec![
+            ("time".to_string(), "2025-01-01T00:00:00.000Z".to_string()),
+            ("msgid".to_string(), "abc".to_string()),
+        ];
+        let result = with_tags(&tags, &privmsg("alice", "#general", "hi there"));
+        assert_eq!(
+            result,
+            "@time=2025-01-01T00:00:00.000Z;msgid=abc :alice!alice@concord PRIVMSG #general :hi there"
+        );
+    }
+
+    #[test]
+    fn test_with_no_tags_is_unchanged() {
+        let line = join("alice", "#general");
+        assert_eq!(with_tags(&[], &line), line);
+    }
+
+    // ── Welcome burst (001-004) ──
+
+    #[test]
+    fn test_rpl_welcome() {
+        let result = rpl_welcome("alice");
+        assert_eq!(result, ":concord 001 alice :Welcome to Concord, alice!");
+    }
+
+    #[test]
+    fn test_rpl_yourhost() {
+        let result = rpl_yourhost("alice");
+        assert_eq!(
+            result,
+            ":concord 002 alice :Your host is concord, running version 0.1.0"
+        );
+    }
+
+    #[test]
+    fn test_rpl_created() {
+        let result = rpl_created("alice");
+        assert_eq!(result, ":concord 003 alice :This server was created today");
+    }
+
+    #[test]
+    fn test_rpl_myinfo() {
+        let result = rpl_myinfo("alice");
+        assert_eq!(result, ":concord 004 alice concord 0.1.0 o o");
+    }
+
+    #[test]
+    fn test_rpl_isupport() {
+        let result = rpl_isupport("alice", &["PREFIX=(Yaohv)~&@%+"]);
+        assert_eq!(
+            result,
+            ":concord 005 alice PREFIX=(Yaohv)~&@%+ :are supported by this server"
+        );
+    }
+
+    // ── MOTD ──
+
+    #[test]
+    fn test_motd_lines() {
+        assert_eq!(
+            rpl_motdstart("alice"),
+            ":concord 375 alice :- concord Message of the day -"
+        );
+        assert_eq!(
+            rpl_motd("alice", "Be nice"),
+            ":concord 372 alice :- Be nice"
+        );
+        assert_eq!(
+            rpl_endofmotd("alice"),
+            ":concord 376 alice :End of /MOTD command."
+        );
+    }
+
+    #[test]
+    fn test_lusers_lines() {
+        assert_eq!(
+            rpl_luserclient("alice", 3),
+            ":concord 251 alice :There are 3 users and 0 invisible on 1 servers"
+        );
+        assert_eq!(
+            rpl_luserchannels("alice", 7),
+            ":concord 254 alice 7 :channels formed"
+        );
+        assert_eq!(
+            rpl_luserme("alice", 4),
+            ":concord 255 alice :I have 4 clients and 0 servers"
+        );
+    }
+
+    #[test]
+    fn test_err_nomotd() {
+        let result = err_nomotd("alice");
+        assert_eq!(result, ":concord 422 alice :MOTD File is missing");
+    }
+
+    // ── JOIN / PART / QUIT / NICK ──
+
+    #[test]
+    fn test_join_format() {
+        let result = join("alice", "#general");
+        assert_eq!(result, ":alice!alice@concord JOIN #general");
+    }
+
+    #[test]
+    fn test_extended_join_format() {
+        let result = extended_join("alice", "#general", "alice", "Alice Liddell");
+        assert_eq!(
+            result,
+            ":alice!alice@concord JOIN #general alice :Alice Liddell"
+        );
+    }
+
+    #[test]
+    fn test_extended_join_guest() {
+        let result = extended_join("guest", "#general", "*", "guest");
+        assert_eq!(result, ":guest!guest@concord JOIN #general * guest");
+    }
+
+    #[test]
+    fn test_part_without_reason() {
+        let result = part("alice", "#general", None);
+        assert_eq!(result, ":alice!alice@concord PART #general");
+    }
+
+    #[test]
+    fn test_part_with_reason() {
+        let result = part("alice", "#general", Some("Goodbye"));
+        // Single-word reason has no colon prefix (format() only adds : for spaces/empty)
+        assert_eq!(result, ":alice!alice@concord PART #general Goodbye");
+    }
+
+    #[test]
+    fn test_privmsg_format() {
+        let result = privmsg("alice", "#general", "Hello world");
+        assert_eq!(result, ":alice!alice@concord PRIVMSG #general :Hello world");
+    }
+
+    #[test]
+    fn test_privmsg_single_word() {
+        let result = privmsg("alice", "bob", "hello");
+        // Single word trailing should NOT get colon since it has no spaces
+        // But the format method adds colon only for last param with spaces or empty
+        // "hello" has no spaces, so no colon prefix
+        assert_eq!(result, ":alice!alice@concord PRIVMSG bob hello");
+    }
+
+    #[test]
+    fn test_quit_without_reason() {
+        let result = quit("alice", None);
+        assert_eq!(result, ":alice!alice@concord QUIT");
+    }
+
+    #[test]
+    fn test_quit_with_reason() {
         let result = quit("alice", Some("Gone to lunch"));
         assert_eq!(result, ":alice!alice@concord QUIT :Gone to lunch");
     }
@@ -554,6 +1561,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_away_replies() {
+        assert_eq!(
+            rpl_away("alice", "bob", "Gone fishing"),
+            ":concord 301 alice bob :Gone fishing"
+        );
+        assert_eq!(
+            rpl_unaway("alice"),
+            ":concord 305 alice :You are no longer marked as being away"
+        );
+        assert_eq!(
+            rpl_nowaway("alice"),
+            ":concord 306 alice :You have been marked as being away"
+        );
+        assert_eq!(
+            away("bob", Some("Gone fishing")),
+            ":bob!bob@concord AWAY :Gone fishing"
+        );
+        assert_eq!(away("bob", None), ":bob!bob@concord AWAY");
+    }
+
     #[test]
     fn test_rpl_endofwhois() {
         let result = rpl_endofwhois("alice", "bob");
@@ -619,6 +1647,333 @@ mod tests {
         assert_eq!(result, ":concord 462 alice :You may not reregister");
     }
 
+    // ── CAP ──
+
+    #[test]
+    fn test_cap_ls_format() {
+        let result = cap("*", "LS", "cap-notify sasl", false);
+        assert_eq!(result, ":concord CAP * LS :cap-notify sasl");
+    }
+
+    #[test]
+    fn test_cap_ls_continuation() {
+        let result = cap("*", "LS", "cap-notify sasl", true);
+        assert_eq!(result, ":concord CAP * LS * :cap-notify sasl");
+    }
+
+    #[test]
+    fn test_cap_empty_list() {
+        let result = cap("alice", "LIST", "", false);
+        assert_eq!(result, ":concord CAP alice LIST :");
+    }
+
+    #[test]
+    fn test_err_invalidcapcmd() {
+        let result = err_invalidcapcmd("alice", "FOO");
+        assert_eq!(result, ":concord 410 alice FOO :Invalid CAP command");
+    }
+
+    // ── SASL ──
+
+    #[test]
+    fn test_authenticate_continue() {
+        assert_eq!(authenticate("+"), "AUTHENTICATE +");
+    }
+
+    #[test]
+    fn test_rpl_loggedin() {
+        let result = rpl_loggedin("alice", "alice");
+        assert_eq!(
+            result,
+            ":concord 900 alice alice!alice@concord alice :You are now logged in as alice"
+        );
+    }
+
+    #[test]
+    fn test_rpl_saslsuccess() {
+        let result = rpl_saslsuccess("*");
+        assert_eq!(result, ":concord 903 * :SASL authentication successful");
+    }
+
+    #[test]
+    fn test_err_saslfail() {
+        let result = err_saslfail("*");
+        assert_eq!(result, ":concord 904 * :SASL authentication failed");
+    }
+
+    #[test]
+    fn test_rpl_saslmechs() {
+        let result = rpl_saslmechs("*", "PLAIN,EXTERNAL");
+        assert_eq!(
+            result,
+            ":concord 908 * PLAIN,EXTERNAL :are available SASL mechanisms"
+        );
+    }
+
+    // ── BATCH / CHATHISTORY ──
+
+    #[test]
+    fn test_batch_start_and_end() {
+        assert_eq!(
+            batch_start("abc", "chathistory", &["#general"]),
+            ":concord BATCH +abc chathistory #general"
+        );
+        assert_eq!(
+            batch_start("t1", "draft/chathistory-targets", &[]),
+            ":concord BATCH +t1 draft/chathistory-targets"
+        );
+        assert_eq!(batch_end("abc"), ":concord BATCH -abc");
+    }
+
+    #[test]
+    fn test_chathistory_target() {
+        assert_eq!(
+            chathistory_target("#general", "2024-01-01T00:00:00.000Z"),
+            ":concord CHATHISTORY TARGETS #general 2024-01-01T00:00:00.000Z"
+        );
+    }
+
+    #[test]
+    fn test_fail_standard_reply() {
+        assert_eq!(
+            fail(
+                "CHATHISTORY",
+                "INVALID_TARGET",
+                &["LATEST", "#x"],
+                "No such target"
+            ),
+            ":concord FAIL CHATHISTORY INVALID_TARGET LATEST #x :No such target"
+        );
+    }
+
+    // ── MODE / KICK ──
+
+    #[test]
+    fn test_mode_and_kick_lines() {
+        assert_eq!(
+            mode("op", "#general", "+b", &["troll!*@*"]),
+            ":op!op@concord MODE #general +b troll!*@*"
+        );
+        assert_eq!(
+            server_mode("#general", "+m", &[]),
+            ":concord MODE #general +m"
+        );
+        assert_eq!(
+            server_mode("#general", "+oh", &["alice", "alice"]),
+            ":concord MODE #general +oh alice alice"
+        );
+        assert_eq!(
+            kick("op", "#general", "troll", Some("be nice")),
+            ":op!op@concord KICK #general troll :be nice"
+        );
+        assert_eq!(
+            kick("op", "#general", "troll", None),
+            ":op!op@concord KICK #general troll"
+        );
+    }
+
+    #[test]
+    fn test_channel_mode_replies() {
+        assert_eq!(
+            rpl_channelmodeis("u", "#general", "+ms"),
+            ":concord 324 u #general +ms"
+        );
+        assert_eq!(
+            rpl_creationtime("u", "#general", 1700000000),
+            ":concord 329 u #general 1700000000"
+        );
+        assert_eq!(
+            rpl_banlist("u", "#general", "troll!*@*", "op", 1700000000),
+            ":concord 367 u #general troll!*@* op 1700000000"
+        );
+        assert_eq!(
+            rpl_endofbanlist("u", "#general"),
+            ":concord 368 u #general :End of channel ban list"
+        );
+        assert_eq!(
+            rpl_quietlist("u", "#general", "troll!*@*", "concord", 1700000000),
+            ":concord 728 u #general q troll!*@* concord 1700000000"
+        );
+        assert_eq!(
+            rpl_endofquietlist("u", "#general"),
+            ":concord 729 u #general q :End of channel quiet list"
+        );
+    }
+
+    #[test]
+    fn test_mode_errors() {
+        assert_eq!(
+            err_unknownmode("u", 'z'),
+            ":concord 472 u z :is unknown mode char to me"
+        );
+        assert_eq!(
+            err_chanoprivsneeded("u", "#general"),
+            ":concord 482 u #general :You're not channel operator"
+        );
+        assert_eq!(
+            err_usernotinchannel("u", "bob", "#general"),
+            ":concord 441 u bob #general :They aren't on that channel"
+        );
+    }
+
+    // ── INVITE / KNOCK ──
+
+    #[test]
+    fn test_invite_lines() {
+        assert_eq!(
+            invite("op", "bob", "#guild/secret"),
+            ":op!op@concord INVITE bob #guild/secret"
+        );
+        assert_eq!(
+            rpl_inviting("op", "bob", "#general"),
+            ":concord 341 op bob #general"
+        );
+        assert_eq!(
+            err_useronchannel("op", "bob", "#general"),
+            ":concord 443 op bob #general :is already on channel"
+        );
+        assert_eq!(
+            err_inviteonlychan("u", "#secret"),
+            ":concord 473 u #secret :Cannot join channel (+i)"
+        );
+        assert_eq!(
+            err_badchannelkey("u", "#guild/general"),
+            ":concord 475 u #guild/general :Cannot join channel (+k)"
+        );
+    }
+
+    #[test]
+    fn test_knock_lines() {
+        assert_eq!(
+            rpl_knock("op", "#secret", "bob", None),
+            ":concord 710 op #secret bob!bob@concord :has asked for an invite"
+        );
+        assert_eq!(
+            rpl_knock("op", "#secret", "bob", Some("let me in")),
+            ":concord 710 op #secret bob!bob@concord :has asked for an invite: let me in"
+        );
+        assert_eq!(
+            rpl_knockdlvr("bob", "#secret"),
+            ":concord 711 bob #secret :Your KNOCK has been delivered"
+        );
+        assert_eq!(
+            err_toomanyknock("bob", "#secret"),
+            ":concord 712 bob #secret :Too many KNOCKs (channel)"
+        );
+        assert_eq!(
+            err_chanopen("bob", "#general"),
+            ":concord 713 bob #general :Channel is open"
+        );
+        assert_eq!(
+            err_knockonchan("bob", "#general"),
+            ":concord 714 bob #general :You are already on that channel"
+        );
+    }
+
+    // ── Operators ──
+
+    #[test]
+    fn test_who_lines() {
+        assert_eq!(
+            rpl_whoreply("u", "#general", "alice", "G@"),
+            ":concord 352 u #general alice concord concord alice G@ :0 alice"
+        );
+        let fields = vec![
+            "#general".to_string(),
+            "alice".into(),
+            "H".into(),
+            "0".into(),
+        ];
+        assert_eq!(
+            rpl_whospcrpl("u", &fields),
+            ":concord 354 u #general alice H 0"
+        );
+        assert_eq!(
+            rpl_whoisaccount("u", "bob", "did:plc:bob"),
+            ":concord 330 u bob did:plc:bob :is logged in as"
+        );
+        assert_eq!(
+            rpl_endofwho("u", "#general"),
+            ":concord 315 u #general :End of /WHO list"
+        );
+        let replies = vec![
+            "alice=+alice@concord".to_string(),
+            "bob*=-bob@concord".into(),
+        ];
+        assert_eq!(
+            rpl_userhost("u", &replies),
+            ":concord 302 u :alice=+alice@concord bob*=-bob@concord"
+        );
+        assert_eq!(
+            rpl_ison("u", &["alice".to_string(), "bob".into()]),
+            ":concord 303 u :alice bob"
+        );
+    }
+
+    #[test]
+    fn test_monitor_lines() {
+        let targets = vec!["alice".to_string(), "bob".into()];
+        assert_eq!(
+            rpl_mononline("u", &targets),
+            ":concord 730 u alice!alice@concord,bob!bob@concord"
+        );
+        assert_eq!(rpl_monoffline("u", &targets), ":concord 731 u alice,bob");
+        assert_eq!(rpl_monlist("u", &targets), ":concord 732 u alice,bob");
+        assert_eq!(rpl_endofmonlist("u"), ":concord 733 u :End of MONITOR list");
+        assert_eq!(
+            err_monlistfull("u", 100, &targets),
+            ":concord 734 u 100 alice,bob :Monitor list is full."
+        );
+    }
+
+    #[test]
+    fn test_operator_lines() {
+        assert_eq!(
+            wallops("root", "rebooting soon"),
+            ":root!root@concord WALLOPS :rebooting soon"
+        );
+        assert_eq!(
+            rpl_whoisoperator("u", "root"),
+            ":concord 313 u root :is an IRC operator"
+        );
+        assert_eq!(
+            rpl_youreoper("root"),
+            ":concord 381 root :You are now an IRC operator"
+        );
+        assert_eq!(
+            err_noprivileges("u"),
+            ":concord 481 u :Permission Denied- You're not an IRC operator"
+        );
+        assert_eq!(
+            err_nooperhost("u"),
+            ":concord 491 u :No O-lines for your host"
+        );
+        assert_eq!(
+            err_passwdmismatch("u"),
+            ":concord 464 u :Password incorrect"
+        );
+        assert_eq!(
+            err_nosuchserver("u", "nowhere"),
+            ":concord 402 u nowhere :No such server"
+        );
+    }
+
+    #[test]
+    fn test_stats_lines() {
+        assert_eq!(
+            rpl_statsuptime("root", 90061),
+            ":concord 242 root :Server Up 1 days 1:01:01"
+        );
+        assert_eq!(
+            rpl_statsdebug("root", "c", "3 channels"),
+            ":concord 249 root c :3 channels"
+        );
+        assert_eq!(
+            rpl_endofstats("root", "u"),
+            ":concord 219 root u :End of /STATS report"
+        );
+    }
+
     // ── PING / PONG ──
 
     #[test]
@@ -656,6 +2011,8 @@ mod tests {
         let n = nick_change("user1", "user2");
         let m = privmsg("user1", "#test", "hi");
         let t = topic_change("user1", "#test", "topic");
+        let md = mode("user1", "#test", "+m", &[]);
+        let k = kick("user1", "#test", "user2", None);
 
         let prefix = ":user1!user1@concord ";
         assert!(j.starts_with(prefix), "JOIN prefix mismatch: {}", j);
@@ -664,6 +2021,8 @@ mod tests {
         assert!(n.starts_with(prefix), "NICK prefix mismatch: {}", n);
         assert!(m.starts_with(prefix), "PRIVMSG prefix mismatch: {}", m);
         assert!(t.starts_with(prefix), "TOPIC prefix mismatch: {}", t);
+        assert!(md.starts_with(prefix), "MODE prefix mismatch: {}", md);
+        assert!(k.starts_with(prefix), "KICK prefix mismatch: {}", k);
     }
 
     #[test]
@@ -673,7 +2032,14 @@ mod tests {
             rpl_yourhost("u"),
             rpl_created("u"),
             rpl_myinfo("u"),
+            rpl_isupport("u", &["PREFIX=(o)@"]),
             err_nomotd("u"),
+            rpl_luserclient("u", 1),
+            rpl_luserchannels("u", 1),
+            rpl_luserme("u", 1),
+            rpl_motdstart("u"),
+            rpl_motd("u", "x"),
+            rpl_endofmotd("u"),
             rpl_topic("u", "#c", "t"),
             rpl_notopic("u", "#c"),
             rpl_namreply("u", "#c", &["a".into()]),
@@ -688,6 +2054,31 @@ mod tests {
             err_notregistered(),
             err_needmoreparams("u", "CMD"),
             err_alreadyregistered("u"),
+            cap("u", "LS", "", false),
+            err_invalidcapcmd("u", "X"),
+            rpl_loggedin("u", "u"),
+            rpl_saslsuccess("u"),
+            err_saslfail("u"),
+            err_sasltoolong("u"),
+            err_saslaborted("u"),
+            err_saslalready("u"),
+            rpl_saslmechs("u", "PLAIN"),
+            batch_start("r", "chathistory", &["#c"]),
+            batch_end("r"),
+            chathistory_target("#c", "t"),
+            fail("CMD", "CODE", &[], "d"),
+            server_mode("#c", "+m", &[]),
+            server_notice("u", "t"),
+            rpl_umodeis("u", "+"),
+            rpl_channelmodeis("u", "#c", "+"),
+            rpl_creationtime("u", "#c", 0),
+            rpl_banlist("u", "#c", "m", "s", 0),
+            rpl_endofbanlist("u", "#c"),
+            rpl_quietlist("u", "#c", "m", "s", 0),
+            rpl_endofquietlist("u", "#c"),
+            err_usernotinchannel("u", "t", "#c"),
+            err_unknownmode("u", 'z'),
+            err_chanoprivsneeded("u", "#c"),
         ];
 
         for reply in &replies {
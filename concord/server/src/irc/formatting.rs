@@ -0,0 +1,394 @@
+//! Translation between mIRC formatting codes and the web client's Markdown
+//! subset (`**bold**`, `*italic*`, `~~strike~~`, `` `code` ``, `||spoiler||`
+//! and ``` fenced blocks), plus CTCP ACTION framing for `/me`.
+
+use crate::engine::events::MessageKind;
+
+const BOLD: char = '\x02';
+const COLOR: char = '\x03';
+const MONOSPACE: char = '\x11';
+const RESET: char = '\x0F';
+const REVERSE: char = '\x16';
+const ITALIC: char = '\x1D';
+const STRIKETHROUGH: char = '\x1E';
+const UNDERLINE: char = '\x1F';
+const CTCP: char = '\x01';
+
+/// Spoilers travel over IRC as black-on-black text, the usual IRC convention.
+const SPOILER_COLOR: &str = "01,01";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Style {
+    Bold,
+    Italic,
+    Strikethrough,
+    Code,
+    Spoiler,
+}
+
+impl Style {
+    fn markdown(self) -> &'static str {
+        match self {
+            Style::Bold => "**",
+            Style::Italic => "*",
+            Style::Strikethrough => "~~",
+            Style::Code => "`",
+            Style::Spoiler => "||",
+        }
+    }
+}
+
+/// Open Markdown spans while converting IRC text. IRC toggles may overlap
+/// freely, so closing a span that isn't innermost closes and reopens the
+/// spans inside it to keep the Markdown properly nested.
+struct MarkdownWriter {
+    out: String,
+    /// Open styles, innermost last, with the output length right after each opener.
+    open: Vec<(Style, usize)>,
+}
+
+impl MarkdownWriter {
+    fn toggle(&mut self, style: Style) {
+        if self.open.iter().any(|(s, _)| *s == style) {
+            self.close(style);
+        } else {
+            self.out.push_str(style.markdown());
+            self.open.push((style, self.out.len()));
+        }
+    }
+
+    fn close(&mut self, style: Style) {
+        let Some(pos) = self.open.iter().position(|(s, _)| *s == style) else {
+            return;
+        };
+        let reopen: Vec<Style> = self.open[pos + 1..].iter().map(|(s, _)| *s).collect();
+        while self.open.len() > pos {
+            self.close_innermost();
+        }
+        for style in reopen {
+            self.toggle(style);
+        }
+    }
+
+    fn close_innermost(&mut self) {
+        if let Some((style, start)) = self.open.pop() {
+            if self.out.len() == start {
+                // Nothing inside: drop the opener rather than emit `****`
+                self.out.truncate(start - style.markdown().len());
+            } else {
+                self.out.push_str(style.markdown());
+            }
+        }
+    }
+
+    fn close_all(&mut self) {
+        while !self.open.is_empty() {
+            self.close_innermost();
+        }
+    }
+}
+
+/// Convert IRC text with mIRC control codes to Markdown. Underline, reverse
+/// and colors have no Markdown form and are dropped, except matching
+/// foreground/background colors, which mark a spoiler.
+pub fn irc_to_markdown(text: &str) -> String {
+    let mut writer = MarkdownWriter {
+        out: String::with_capacity(text.len()),
+        open: Vec::new(),
+    };
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            BOLD => writer.toggle(Style::Bold),
+            ITALIC => writer.toggle(Style::Italic),
+            STRIKETHROUGH => writer.toggle(Style::Strikethrough),
+            MONOSPACE => writer.toggle(Style::Code),
+            UNDERLINE | REVERSE => {}
+            RESET => writer.close_all(),
+            COLOR => {
+                let fg = take_color_number(&mut chars);
+                let mut bg = None;
+                if fg.is_some() && chars.peek() == Some(&',') {
+                    let mut lookahead = chars.clone();
+                    lookahead.next();
+                    if lookahead.peek().is_some_and(char::is_ascii_digit) {
+                        chars.next();
+                        bg = take_color_number(&mut chars);
+                    }
+                }
+                let in_spoiler = writer.open.iter().any(|(s, _)| *s == Style::Spoiler);
+                match (fg, bg) {
+                    (Some(fg), Some(bg)) if fg == bg && !in_spoiler => {
+                        writer.toggle(Style::Spoiler)
+                    }
+                    // A bare color code ends any colored span
+                    (None, _) => writer.close(Style::Spoiler),
+                    _ => {}
+                }
+            }
+            _ => writer.out.push(c),
+        }
+    }
+
+    writer.close_all();
+    writer.out
+}
+
+/// Read a one- or two-digit mIRC color number.
+fn take_color_number(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> Option<u8> {
+    let mut digits = String::new();
+    while digits.len() < 2 && chars.peek().is_some_and(char::is_ascii_digit) {
+        digits.push(chars.next().unwrap_or_default());
+    }
+    digits.parse().ok()
+}
+
+/// Convert web Markdown to IRC text. Fenced code blocks become monospace
+/// lines; the HTML escaping applied to stored messages is undone, since IRC
+/// clients show text verbatim.
+pub fn markdown_to_irc(text: &str) -> String {
+    let text = unescape_html(text);
+    let mut lines = Vec::new();
+    let mut in_code_block = false;
+
+    for line in text.split('\n') {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            lines.push(format!("{MONOSPACE}{line}{MONOSPACE}"));
+        } else {
+            lines.push(inline_to_irc(line));
+        }
+    }
+
+    lines.join("\n")
+}
+
+/// Inline Markdown to IRC codes, mirroring the web client's parser: markers
+/// only count when a matching closer follows.
+fn inline_to_irc(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+
+    while i < text.len() {
+        let rest = &text[i..];
+
+        if let Some(inner) = rest.strip_prefix('`')
+            && let Some(end) = inner.find('`')
+        {
+            out.push(MONOSPACE);
+            out.push_str(&inner[..end]);
+            out.push(MONOSPACE);
+            i += end + 2;
+            continue;
+        }
+
+        if let Some((inner, len)) = delimited(rest, "**") {
+            out.push(BOLD);
+            out.push_str(&inline_to_irc(inner));
+            out.push(BOLD);
+            i += len;
+            continue;
+        }
+
+        if rest.starts_with('*')
+            && !rest.starts_with("**")
+            && let Some(end) = rest[1..].find('*')
+            && !rest[1 + end + 1..].starts_with('*')
+        {
+            out.push(ITALIC);
+            out.push_str(&inline_to_irc(&rest[1..1 + end]));
+            out.push(ITALIC);
+            i += end + 2;
+            continue;
+        }
+
+        if let Some((inner, len)) = delimited(rest, "~~") {
+            out.push(STRIKETHROUGH);
+            out.push_str(&inline_to_irc(inner));
+            out.push(STRIKETHROUGH);
+            i += len;
+            continue;
+        }
+
+        if let Some((inner, len)) = delimited(rest, "||") {
+            out.push(COLOR);
+            out.push_str(SPOILER_COLOR);
+            out.push_str(&inline_to_irc(inner));
+            out.push(COLOR);
+            i += len;
+            continue;
+        }
+
+        let c = rest.chars().next().unwrap_or_default();
+        out.push(c);
+        i += c.len_utf8();
+    }
+
+    out
+}
+
+/// If `text` starts with `marker`, the text up to the next `marker` and the
+/// total length consumed including both markers.
+fn delimited<'a>(text: &'a str, marker: &str) -> Option<(&'a str, usize)> {
+    let inner = text.strip_prefix(marker)?;
+    let end = inner.find(marker)?;
+    Some((&inner[..end], end + 2 * marker.len()))
+}
+
+fn unescape_html(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// What an inbound PRIVMSG or NOTICE body carries.
+#[derive(Debug, PartialEq, Eq)]
+pub enum IrcText<'a> {
+    /// Ordinary text.
+    Plain(&'a str),
+    /// `\x01ACTION text\x01` — a `/me`.
+    Action(&'a str),
+    /// Any other CTCP request or reply, which Concord doesn't relay.
+    Ctcp,
+}
+
+/// Classify an inbound message body, unwrapping CTCP ACTION.
+pub fn classify(text: &str) -> IrcText<'_> {
+    let Some(ctcp) = text.strip_prefix(CTCP) else {
+        return IrcText::Plain(text);
+    };
+    let ctcp = ctcp.strip_suffix(CTCP).unwrap_or(ctcp);
+    match ctcp.split_once(' ') {
+        Some((command, body)) if command.eq_ignore_ascii_case("ACTION") => IrcText::Action(body),
+        None if ctcp.eq_ignore_ascii_case("ACTION") => IrcText::Action(""),
+        _ => IrcText::Ctcp,
+    }
+}
+
+/// The IRC command and body lines for a stored message: Markdown becomes mIRC
+/// codes, multi-line content becomes one line per row, and actions are framed
+/// as CTCP ACTION.
+pub fn outgoing(content: &str, kind: MessageKind) -> (&'static str, Vec<String>) {
+    // System messages are never answered either
+    let command = if matches!(kind, MessageKind::Notice | MessageKind::System) {
+        "NOTICE"
+    } else {
+        "PRIVMSG"
+    };
+    let converted = markdown_to_irc(content);
+    let mut lines: Vec<String> = converted
+        .split('\n')
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect();
+    if lines.is_empty() {
+        lines.push(String::new());
+    }
+    if kind == MessageKind::Action {
+        for line in &mut lines {
+            *line = format!("{CTCP}ACTION {line}{CTCP}");
+        }
+    }
+    (command, lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_irc_to_markdown_basic_styles() {
+        assert_eq!(irc_to_markdown("\x02bold\x02 text"), "**bold** text");
+        assert_eq!(irc_to_markdown("\x1Ditalic\x1D"), "*italic*");
+        assert_eq!(irc_to_markdown("\x1Egone\x1E"), "~~gone~~");
+        assert_eq!(irc_to_markdown("\x11code\x11"), "`code`");
+        assert_eq!(irc_to_markdown("\x1Funderlined\x1F"), "underlined");
+    }
+
+    #[test]
+    fn test_irc_to_markdown_unclosed_and_reset() {
+        assert_eq!(irc_to_markdown("\x02loud"), "**loud**");
+        assert_eq!(
+            irc_to_markdown("\x02\x1Dboth\x0F plain"),
+            "***both*** plain"
+        );
+        assert_eq!(irc_to_markdown("empty\x02\x02"), "empty");
+    }
+
+    #[test]
+    fn test_irc_to_markdown_colors() {
+        assert_eq!(irc_to_markdown("\x0304red\x03 text"), "red text");
+        assert_eq!(irc_to_markdown("\x034,12blue\x03"), "blue");
+        assert_eq!(
+            irc_to_markdown("\x0301,01secret\x03 done"),
+            "||secret|| done"
+        );
+        assert_eq!(irc_to_markdown("\x034,done"), ",done");
+    }
+
+    #[test]
+    fn test_markdown_to_irc_basic_styles() {
+        assert_eq!(markdown_to_irc("**bold** text"), "\x02bold\x02 text");
+        assert_eq!(markdown_to_irc("*italic*"), "\x1Ditalic\x1D");
+        assert_eq!(markdown_to_irc("~~gone~~"), "\x1Egone\x1E");
+        assert_eq!(markdown_to_irc("`a*b*c`"), "\x11a*b*c\x11");
+        assert_eq!(markdown_to_irc("||secret||"), "\x0301,01secret\x03");
+        assert_eq!(
+            markdown_to_irc("**outer *inner* end**"),
+            "\x02outer \x1Dinner\x1D end\x02"
+        );
+    }
+
+    #[test]
+    fn test_markdown_to_irc_leaves_unmatched_markers() {
+        assert_eq!(markdown_to_irc("2 * 3 = 6"), "2 * 3 = 6");
+        assert_eq!(markdown_to_irc("a || b"), "a || b");
+    }
+
+    #[test]
+    fn test_markdown_to_irc_code_block_and_entities() {
+        assert_eq!(
+            markdown_to_irc("look:\n```rust\nlet x = 1;\n```"),
+            "look:\n\x11let x = 1;\x11"
+        );
+        assert_eq!(markdown_to_irc("a &lt;b&gt; &amp; c"), "a <b> & c");
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        for text in ["**bold** and *italic*", "~~x~~ `y` ||z||"] {
+            assert_eq!(irc_to_markdown(&markdown_to_irc(text)), text);
+        }
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify("hello"), IrcText::Plain("hello"));
+        assert_eq!(classify("\x01ACTION waves\x01"), IrcText::Action("waves"));
+        assert_eq!(classify("\x01ACTION waves"), IrcText::Action("waves"));
+        assert_eq!(classify("\x01VERSION\x01"), IrcText::Ctcp);
+    }
+
+    #[test]
+    fn test_outgoing() {
+        assert_eq!(
+            outgoing("**hi**", MessageKind::Normal),
+            ("PRIVMSG", vec!["\x02hi\x02".to_string()])
+        );
+        assert_eq!(
+            outgoing("waves", MessageKind::Action),
+            ("PRIVMSG", vec!["\x01ACTION waves\x01".to_string()])
+        );
+        assert_eq!(
+            outgoing("line one\n\nline two", MessageKind::Notice),
+            (
+                "NOTICE",
+                vec!["line one".to_string(), "line two".to_string()]
+            )
+        );
+    }
+}
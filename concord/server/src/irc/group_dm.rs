@@ -0,0 +1,333 @@
+use tracing::warn;
+
+use crate::engine::chat_engine::ChatEngine;
+use crate::engine::events::{GroupDmChange, GroupDmInfo, SessionId};
+
+use super::connection::{CapState, live_event_tags};
+use super::formatter;
+use super::parser::IrcMessage;
+
+/// Whether an IRC target names a group DM. Group DMs appear to IRC clients as
+/// `&<conversation id>` channels that can't be joined by name: JOIN only
+/// reopens one the user is already in.
+pub fn is_group_target(target: &str) -> bool {
+    target.starts_with('&')
+}
+
+/// Handle a channel command addressed to group DMs: JOIN, PART, TOPIC,
+/// NAMES, MODE, KICK and INVITE. INVITE adds someone, KICK (by the owner)
+/// removes them, PART leaves, TOPIC renames and `MODE +o` hands the group to
+/// another participant.
+pub async fn handle_command(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    caps: &CapState,
+    msg: &IrcMessage,
+) -> Vec<String> {
+    let needed = if matches!(msg.command.as_str(), "INVITE" | "KICK") {
+        2
+    } else {
+        1
+    };
+    if msg.params.len() < needed {
+        return vec![formatter::err_needmoreparams(nick, &msg.command)];
+    }
+
+    match msg.command.as_str() {
+        "JOIN" => {
+            let mut replies = Vec::new();
+            for target in msg.params[0].split(',').filter(|t| !t.is_empty()) {
+                match group(engine, session_id, target).await {
+                    Some(info) => replies.extend(join_lines(nick, caps, &info)),
+                    None => replies.push(formatter::err_inviteonlychan(nick, target)),
+                }
+            }
+            replies
+        }
+        "PART" => {
+            let mut replies = Vec::new();
+            for target in msg.params[0].split(',').filter(|t| !t.is_empty()) {
+                let result = match conversation_id(target) {
+                    Some(id) => engine.leave_group_dm(session_id, id).await,
+                    None => Err("Not a group DM".into()),
+                };
+                if let Err(e) = result {
+                    warn!(error = %e, %target, "group DM PART failed");
+                    replies.push(formatter::err_notonchannel(nick, target));
+                }
+            }
+            replies
+        }
+        "TOPIC" => handle_topic(engine, session_id, nick, msg).await,
+        "NAMES" => {
+            let target = &msg.params[0];
+            match group(engine, session_id, target).await {
+                Some(info) => names_lines(nick, caps, &info),
+                None => vec![formatter::rpl_endofnames(nick, target)],
+            }
+        }
+        "MODE" => handle_mode(engine, session_id, nick, msg).await,
+        "KICK" => {
+            let target = &msg.params[0];
+            let Some(id) = conversation_id(target) else {
+                return vec![formatter::err_nosuchchannel(nick, target)];
+            };
+            let mut replies = Vec::new();
+            for member in msg.params[1].split(',').filter(|m| !m.is_empty()) {
+                if let Err(e) = engine
+                    .remove_group_dm_participant(session_id, id, member)
+                    .await
+                {
+                    replies.push(group_error(nick, target, &e));
+                }
+            }
+            replies
+        }
+        "INVITE" => {
+            let (member, target) = (&msg.params[0], &msg.params[1]);
+            let Some(id) = conversation_id(target) else {
+                return vec![formatter::err_nosuchchannel(nick, target)];
+            };
+            match engine
+                .add_group_dm_participant(session_id, id, member)
+                .await
+            {
+                Ok(()) => vec![formatter::rpl_inviting(nick, member, target)],
+                Err(e) if e.ends_with("is already in this group") => {
+                    vec![formatter::err_useronchannel(nick, member, target)]
+                }
+                Err(e) if e.starts_with("No such user") => {
+                    vec![formatter::err_nosuchnick(nick, member)]
+                }
+                Err(e) => vec![group_error(nick, target, &e)],
+            }
+        }
+        _ => vec![formatter::err_unknowncommand(nick, &msg.command)],
+    }
+}
+
+/// `TOPIC &id` shows the group's name; `TOPIC &id :name` renames it.
+async fn handle_topic(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    msg: &IrcMessage,
+) -> Vec<String> {
+    let target = &msg.params[0];
+    let Some(id) = conversation_id(target) else {
+        return vec![formatter::err_nosuchchannel(nick, target)];
+    };
+    if let Some(name) = msg.params.get(1) {
+        return match engine.rename_group_dm(session_id, id, Some(name)).await {
+            Ok(()) => vec![],
+            Err(e) => vec![group_error(nick, target, &e)],
+        };
+    }
+    match group(engine, session_id, target).await {
+        Some(GroupDmInfo {
+            name: Some(name), ..
+        }) => vec![formatter::rpl_topic(nick, target, &name)],
+        Some(_) => vec![formatter::rpl_notopic(nick, target)],
+        None => vec![formatter::err_notonchannel(nick, target)],
+    }
+}
+
+/// Group DMs are always invite-only and secret. `+o` on a participant makes
+/// them the owner; no other mode can be changed.
+async fn handle_mode(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    msg: &IrcMessage,
+) -> Vec<String> {
+    let target = &msg.params[0];
+    let Some(id) = conversation_id(target) else {
+        return vec![formatter::err_nosuchchannel(nick, target)];
+    };
+    match msg.params.get(1).map(String::as_str) {
+        None => match group(engine, session_id, target).await {
+            Some(_) => vec![formatter::rpl_channelmodeis(nick, target, "+is")],
+            None => vec![formatter::err_notonchannel(nick, target)],
+        },
+        Some("+o") => match msg.params.get(2) {
+            Some(member) => match engine
+                .transfer_group_dm_ownership(session_id, id, member)
+                .await
+            {
+                Ok(()) => vec![],
+                Err(e) => vec![group_error(nick, target, &e)],
+            },
+            None => vec![formatter::err_needmoreparams(nick, "MODE")],
+        },
+        Some(modes) => {
+            let mode = modes.trim_start_matches(['+', '-']).chars().next();
+            vec![formatter::err_unknownmode(nick, mode.unwrap_or('-'))]
+        }
+    }
+}
+
+/// The lines that open a group DM in an IRC client: JOIN, its name as the
+/// topic, and its participants.
+pub fn join_lines(my_nick: &str, caps: &CapState, info: &GroupDmInfo) -> Vec<String> {
+    let mut lines = vec![formatter::with_tags(
+        &live_event_tags(caps),
+        &formatter::join(my_nick, &info.target),
+    )];
+    if let Some(name) = &info.name {
+        lines.push(formatter::rpl_topic(my_nick, &info.target, name));
+    }
+    lines.extend(names_lines(my_nick, caps, info));
+    lines
+}
+
+/// How a change to a group DM looks to one of its IRC participants.
+pub fn change_lines(
+    my_nick: &str,
+    caps: &CapState,
+    info: &GroupDmInfo,
+    change: &GroupDmChange,
+) -> Vec<String> {
+    let target = &info.target;
+    let line = match change {
+        GroupDmChange::Created { .. } => return join_lines(my_nick, caps, info),
+        GroupDmChange::Added { nickname, .. } if nickname == my_nick => {
+            return join_lines(my_nick, caps, info);
+        }
+        GroupDmChange::Added { nickname, .. } => formatter::join(nickname, target),
+        GroupDmChange::Removed { by, nickname } => formatter::kick(by, target, nickname, None),
+        GroupDmChange::Left { nickname } => formatter::part(nickname, target, None),
+        GroupDmChange::Renamed { by, name } => {
+            formatter::topic_change(by, target, name.as_deref().unwrap_or(""))
+        }
+        GroupDmChange::OwnerChanged { by, nickname } => {
+            if info.participants.iter().any(|p| &p.nickname == by) {
+                formatter::server_mode(target, "-o+o", &[by, nickname])
+            } else {
+                formatter::server_mode(target, "+o", &[nickname])
+            }
+        }
+    };
+    vec![formatter::with_tags(&live_event_tags(caps), &line)]
+}
+
+fn names_lines(my_nick: &str, caps: &CapState, info: &GroupDmInfo) -> Vec<String> {
+    let nicks: Vec<String> = info
+        .participants
+        .iter()
+        .map(|p| {
+            let prefix = if p.user_id == info.owner_id { "@" } else { "" };
+            if caps.has("userhost-in-names") {
+                let nick = &p.nickname;
+                format!("{prefix}{nick}!{nick}@{}", formatter::server_name())
+            } else {
+                format!("{prefix}{}", p.nickname)
+            }
+        })
+        .collect();
+    vec![
+        formatter::rpl_namreply(my_nick, &info.target, &nicks),
+        formatter::rpl_endofnames(my_nick, &info.target),
+    ]
+}
+
+fn conversation_id(target: &str) -> Option<&str> {
+    target.strip_prefix('&').filter(|id| !id.is_empty())
+}
+
+async fn group(engine: &ChatEngine, session_id: SessionId, target: &str) -> Option<GroupDmInfo> {
+    engine
+        .group_dm(session_id, conversation_id(target)?)
+        .await
+        .ok()
+}
+
+fn group_error(nick: &str, target: &str, error: &str) -> String {
+    if error.starts_with("FORBIDDEN") {
+        formatter::err_chanoprivsneeded(nick, target)
+    } else if error.starts_with("No such group DM") {
+        formatter::err_notonchannel(nick, target)
+    } else {
+        formatter::server_notice(nick, &format!("{target}: {error}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::events::DmParticipant;
+
+    fn participant(user_id: &str, nickname: &str) -> DmParticipant {
+        DmParticipant {
+            user_id: user_id.into(),
+            nickname: nickname.into(),
+            avatar_url: None,
+        }
+    }
+
+    fn group_info() -> GroupDmInfo {
+        GroupDmInfo {
+            id: "g1".into(),
+            target: "&g1".into(),
+            name: Some("weekend".into()),
+            owner_id: "u1".into(),
+            participants: vec![participant("u1", "alice"), participant("u2", "bob")],
+        }
+    }
+
+    #[test]
+    fn test_join_lines_show_name_and_owner() {
+        let lines = join_lines("bob", &CapState::default(), &group_info());
+        assert!(lines[0].ends_with("JOIN &g1"));
+        assert!(lines[1].ends_with(" 332 bob &g1 weekend"));
+        assert!(lines[2].contains(" 353 bob = &g1 :@alice bob"));
+        assert!(lines[3].contains(" 366 bob &g1 "));
+    }
+
+    #[test]
+    fn test_change_lines() {
+        let caps = CapState::default();
+        let info = group_info();
+        let added = GroupDmChange::Added {
+            by: "alice".into(),
+            nickname: "carol".into(),
+        };
+        assert_eq!(
+            change_lines("bob", &caps, &info, &added),
+            vec![":carol!carol@concord JOIN &g1"]
+        );
+        // Being added opens the group
+        assert_eq!(change_lines("carol", &caps, &info, &added).len(), 4);
+
+        let removed = GroupDmChange::Removed {
+            by: "alice".into(),
+            nickname: "carol".into(),
+        };
+        assert_eq!(
+            change_lines("bob", &caps, &info, &removed),
+            vec![":alice!alice@concord KICK &g1 carol"]
+        );
+        let renamed = GroupDmChange::Renamed {
+            by: "bob".into(),
+            name: None,
+        };
+        assert!(change_lines("bob", &caps, &info, &renamed)[0].ends_with("TOPIC &g1 :"));
+
+        let handed_over = GroupDmChange::OwnerChanged {
+            by: "alice".into(),
+            nickname: "bob".into(),
+        };
+        assert_eq!(
+            change_lines("bob", &caps, &info, &handed_over),
+            vec![":concord MODE &g1 -o+o alice bob"]
+        );
+        let left_owner = GroupDmChange::OwnerChanged {
+            by: "dave".into(),
+            nickname: "bob".into(),
+        };
+        assert_eq!(
+            change_lines("bob", &caps, &info, &left_owner),
+            vec![":concord MODE &g1 +o bob"]
+        );
+    }
+}
@@ -0,0 +1,644 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::engine::chat_engine::{
+    ChatEngine, DEFAULT_SERVER_ID, HistoryAnchor, HistoryQuery, MarkerTarget,
+};
+use crate::engine::events::{ChatEvent, HistoryMessage, SessionId};
+
+use super::commands::{parse_irc_channel, to_irc_channel};
+use super::connection::{CapState, message_lines, message_tags};
+use super::formatter;
+use super::parser::IrcMessage;
+
+/// Most messages a single CHATHISTORY request may return (advertised as
+/// `CHATHISTORY=<n>` in ISUPPORT).
+pub const CHATHISTORY_MAX_LIMIT: usize = 100;
+
+/// Lines replayed after JOIN to clients that haven't enabled `draft/chathistory`.
+pub const JOIN_REPLAY_LINES: i64 = 20;
+
+/// Most missed lines replayed per conversation to a returning client.
+pub const BACKLOG_REPLAY_LINES: i64 = 100;
+
+/// Handle `CHATHISTORY <subcommand> ...` and return the reply lines.
+///
+/// Message results are delivered in a `chathistory` batch when the client has
+/// enabled `batch`; failures use IRCv3 standard replies (`FAIL CHATHISTORY`).
+pub async fn handle_chathistory(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    caps: &CapState,
+    msg: &IrcMessage,
+) -> Vec<String> {
+    let Some(subcommand) = msg.params.first().map(|s| s.to_ascii_uppercase()) else {
+        return vec![formatter::fail(
+            "CHATHISTORY",
+            "NEED_MORE_PARAMS",
+            &[],
+            "Missing parameters",
+        )];
+    };
+    let args = &msg.params[1..];
+
+    if subcommand == "TARGETS" {
+        return handle_targets(engine, session_id, caps, args).await;
+    }
+
+    let needed = match subcommand.as_str() {
+        "LATEST" | "BEFORE" | "AFTER" | "AROUND" => 3,
+        "BETWEEN" => 4,
+        _ => {
+            return vec![formatter::fail(
+                "CHATHISTORY",
+                "UNKNOWN_COMMAND",
+                &[&subcommand],
+                "Unknown subcommand",
+            )];
+        }
+    };
+    if args.len() < needed {
+        return vec![formatter::fail(
+            "CHATHISTORY",
+            "NEED_MORE_PARAMS",
+            &[&subcommand],
+            "Missing parameters",
+        )];
+    }
+
+    let target = args[0].as_str();
+    let invalid_params = |reason: &str| {
+        vec![formatter::fail(
+            "CHATHISTORY",
+            "INVALID_PARAMS",
+            &[&subcommand],
+            reason,
+        )]
+    };
+    let Some(limit) = parse_limit(&args[needed - 1]) else {
+        return invalid_params("Invalid limit");
+    };
+
+    let query = match subcommand.as_str() {
+        "LATEST" if args[1] == "*" => HistoryQuery::Latest(None),
+        "LATEST" => match parse_anchor(&args[1]) {
+            Some(anchor) => HistoryQuery::Latest(Some(anchor)),
+            None => return invalid_params("Invalid message reference"),
+        },
+        _ => {
+            let Some(first) = parse_anchor(&args[1]) else {
+                return invalid_params("Invalid message reference");
+            };
+            match subcommand.as_str() {
+                "BEFORE" => HistoryQuery::Before(first),
+                "AFTER" => HistoryQuery::After(first),
+                "AROUND" => HistoryQuery::Around(first),
+                _ => match parse_anchor(&args[2]) {
+                    Some(second) => HistoryQuery::Between(first, second),
+                    None => return invalid_params("Invalid message reference"),
+                },
+            }
+        }
+    };
+
+    let (server_id, engine_target) = if target.starts_with('#') {
+        parse_irc_channel(engine, target)
+    } else {
+        (DEFAULT_SERVER_ID.to_string(), target.to_string())
+    };
+
+    match engine
+        .fetch_history_window(session_id, &server_id, &engine_target, &query, limit as i64)
+        .await
+    {
+        Ok(messages) => {
+            let irc_target = if target.starts_with('#') {
+                to_irc_channel(engine, &server_id, &engine_target)
+            } else {
+                target.to_string()
+            };
+            let lines = messages
+                .iter()
+                .flat_map(|m| history_lines(nick, &irc_target, caps, m))
+                .collect();
+            wrap_batch(caps, "chathistory", &[&irc_target], lines)
+        }
+        Err(e) => {
+            warn!(error = %e, %target, "CHATHISTORY failed");
+            vec![formatter::fail(
+                "CHATHISTORY",
+                "INVALID_TARGET",
+                &[&subcommand, target],
+                "Messages could not be retrieved",
+            )]
+        }
+    }
+}
+
+/// `CHATHISTORY TARGETS <timestamp> <timestamp> <limit>`: conversations with
+/// activity between the two timestamps, in the order the timestamps are given.
+async fn handle_targets(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    caps: &CapState,
+    args: &[String],
+) -> Vec<String> {
+    if args.len() < 3 {
+        return vec![formatter::fail(
+            "CHATHISTORY",
+            "NEED_MORE_PARAMS",
+            &["TARGETS"],
+            "Missing parameters",
+        )];
+    }
+    let (Some(HistoryAnchor::Timestamp(from)), Some(HistoryAnchor::Timestamp(to)), Some(limit)) = (
+        parse_anchor(&args[0]),
+        parse_anchor(&args[1]),
+        parse_limit(&args[2]),
+    ) else {
+        return vec![formatter::fail(
+            "CHATHISTORY",
+            "INVALID_PARAMS",
+            &["TARGETS"],
+            "Expected two timestamps and a limit",
+        )];
+    };
+
+    let (after, before) = if from <= to { (from, to) } else { (to, from) };
+    let mut targets = match engine.history_targets(session_id, after, before).await {
+        Ok(targets) => targets,
+        Err(e) => {
+            warn!(error = %e, "CHATHISTORY TARGETS failed");
+            Vec::new()
+        }
+    };
+    if from > to {
+        targets.reverse();
+    }
+    targets.truncate(limit);
+
+    let lines = targets
+        .iter()
+        .map(|t| {
+            let name = match &t.server_id {
+                Some(server_id) => to_irc_channel(engine, server_id, &t.target),
+                None => t.target.clone(),
+            };
+            formatter::chathistory_target(&name, &format_time(t.latest))
+        })
+        .collect();
+    wrap_batch(caps, "draft/chathistory-targets", &[], lines)
+}
+
+/// The last message sent to a client in each conversation, saved as its read
+/// markers when it disconnects.
+#[derive(Debug, Default)]
+pub struct ReadMarkers {
+    seen: HashMap<MarkerTarget, String>,
+}
+
+impl ReadMarkers {
+    /// Note a message that was just sent to the client.
+    pub fn record(&mut self, event: &ChatEvent) {
+        if let ChatEvent::Message {
+            id,
+            server_id,
+            target,
+            ..
+        } = event
+        {
+            let conversation = match server_id {
+                Some(server_id) if target.starts_with('#') => {
+                    MarkerTarget::Channel(server_id.clone(), target.clone())
+                }
+                _ => MarkerTarget::Direct,
+            };
+            self.seen.insert(conversation, id.to_string());
+        }
+    }
+
+    /// Save the markers for the client's next connection.
+    pub async fn save(&self, engine: &ChatEngine, session_id: SessionId, client: &str) {
+        if let Err(e) = engine
+            .save_read_markers(session_id, client, &self.seen)
+            .await
+        {
+            warn!(error = %e, %client, "failed to save read markers");
+        }
+    }
+
+    /// Where to replay a conversation from: the last message this connection
+    /// saw, or else the marker the client left last time.
+    async fn anchor(
+        &self,
+        engine: &ChatEngine,
+        session_id: SessionId,
+        client: &str,
+        conversation: &MarkerTarget,
+    ) -> Option<(HistoryAnchor, DateTime<Utc>)> {
+        match self.seen.get(conversation) {
+            Some(id) => Some((HistoryAnchor::MessageId(id.clone()), Utc::now())),
+            None => engine.read_marker(session_id, client, conversation).await,
+        }
+    }
+}
+
+/// History replayed after a JOIN for clients without `draft/chathistory`:
+/// what the client missed since its read marker, or else the latest lines.
+/// Without `server-time` the original time is prefixed to each line.
+pub async fn join_replay(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    caps: &CapState,
+    client: &str,
+    markers: &mut ReadMarkers,
+    server_id: &str,
+    channel: &str,
+) -> Vec<String> {
+    let conversation = MarkerTarget::Channel(server_id.to_string(), channel.to_string());
+    let (query, limit) = match markers
+        .anchor(engine, session_id, client, &conversation)
+        .await
+    {
+        Some((anchor, _)) => (HistoryQuery::Latest(Some(anchor)), BACKLOG_REPLAY_LINES),
+        None => (HistoryQuery::Latest(None), JOIN_REPLAY_LINES),
+    };
+    let messages = match engine
+        .fetch_history_window(session_id, server_id, channel, &query, limit)
+        .await
+    {
+        Ok(messages) => messages,
+        Err(e) => {
+            warn!(error = %e, %channel, "JOIN replay failed");
+            return Vec::new();
+        }
+    };
+    if let Some(last) = messages.last() {
+        markers.seen.insert(conversation, last.id.to_string());
+    }
+
+    let irc_channel = to_irc_channel(engine, server_id, channel);
+    messages
+        .iter()
+        .flat_map(|m| replay_lines("", &irc_channel, caps, m))
+        .collect()
+}
+
+/// Direct messages that arrived since the client's read marker, replayed
+/// after registration to clients without `draft/chathistory`. A client that
+/// has never connected before has no marker and gets nothing.
+pub async fn direct_replay(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    caps: &CapState,
+    client: &str,
+    markers: &mut ReadMarkers,
+) -> Vec<String> {
+    let Some((anchor, read_at)) = markers
+        .anchor(engine, session_id, client, &MarkerTarget::Direct)
+        .await
+    else {
+        return Vec::new();
+    };
+
+    // Stored times are whole seconds, so widen the window by a second on
+    // each side and leave the exact cut to the anchor
+    let second = Duration::seconds(1);
+    let targets = match engine
+        .history_targets(session_id, read_at - second, Utc::now() + second)
+        .await
+    {
+        Ok(targets) => targets,
+        Err(e) => {
+            warn!(error = %e, "direct message replay failed");
+            return Vec::new();
+        }
+    };
+
+    let mut messages = Vec::new();
+    for peer in targets.iter().filter(|t| t.server_id.is_none()) {
+        match engine
+            .fetch_history_window(
+                session_id,
+                DEFAULT_SERVER_ID,
+                &peer.target,
+                &HistoryQuery::Latest(Some(anchor.clone())),
+                BACKLOG_REPLAY_LINES,
+            )
+            .await
+        {
+            Ok(history) => messages.extend(history.into_iter().map(|m| (peer.target.clone(), m))),
+            Err(e) => warn!(error = %e, peer = %peer.target, "direct message replay failed"),
+        }
+    }
+    messages.sort_by_key(|(_, m)| m.timestamp);
+    if let Some((_, last)) = messages.last() {
+        markers
+            .seen
+            .insert(MarkerTarget::Direct, last.id.to_string());
+    }
+
+    messages
+        .iter()
+        .flat_map(|(peer, m)| replay_lines(nick, peer, caps, m))
+        .collect()
+}
+
+/// Render a replayed message. Without `server-time` the original time is
+/// prefixed to the text.
+fn replay_lines(
+    my_nick: &str,
+    irc_target: &str,
+    caps: &CapState,
+    message: &HistoryMessage,
+) -> Vec<String> {
+    if caps.has("server-time") {
+        return history_lines(my_nick, irc_target, caps, message);
+    }
+    let stamped = HistoryMessage {
+        content: format!(
+            "{} {}",
+            message.timestamp.format("[%H:%M:%S]"),
+            message.content
+        ),
+        ..message.clone()
+    };
+    history_lines(my_nick, irc_target, caps, &stamped)
+}
+
+/// Render one history message as tagged PRIVMSG/NOTICE lines. For DM history
+/// the lines are addressed to the reader when the other party sent them.
+fn history_lines(
+    my_nick: &str,
+    irc_target: &str,
+    caps: &CapState,
+    message: &HistoryMessage,
+) -> Vec<String> {
+    let to = if !irc_target.starts_with('#') && message.from == irc_target {
+        my_nick
+    } else {
+        irc_target
+    };
+    let reply_to = message.reply_to.as_ref().map(|r| r.id.as_str());
+    let tags = message_tags(caps, message.timestamp, &message.id.to_string(), reply_to);
+    message_lines(tags, &message.from, to, &message.content, message.kind)
+}
+
+/// Wrap lines in a batch when the client supports it, tagging each with the reference.
+fn wrap_batch(
+    caps: &CapState,
+    batch_type: &str,
+    params: &[&str],
+    lines: Vec<String>,
+) -> Vec<String> {
+    if !caps.has("batch") {
+        return lines;
+    }
+    let reference = Uuid::new_v4().simple().to_string();
+    let batch_tag = vec![("batch".to_string(), reference.clone())];
+    let mut out = Vec::with_capacity(lines.len() + 2);
+    out.push(formatter::batch_start(&reference, batch_type, params));
+    for line in lines {
+        // Merge into an existing tag section rather than adding a second one
+        match line.strip_prefix('@') {
+            Some(rest) => out.push(format!("@batch={};{}", reference, rest)),
+            None => out.push(formatter::with_tags(&batch_tag, &line)),
+        }
+    }
+    out.push(formatter::batch_end(&reference));
+    out
+}
+
+/// Parse a CHATHISTORY message reference: `msgid=<id>` or `timestamp=<RFC 3339>`.
+fn parse_anchor(reference: &str) -> Option<HistoryAnchor> {
+    if let Some(id) = reference.strip_prefix("msgid=") {
+        return (!id.is_empty()).then(|| HistoryAnchor::MessageId(id.to_string()));
+    }
+    let time = reference.strip_prefix("timestamp=")?;
+    DateTime::parse_from_rfc3339(time)
+        .ok()
+        .map(|t| HistoryAnchor::Timestamp(t.with_timezone(&Utc)))
+}
+
+/// Parse a CHATHISTORY limit, capped at CHATHISTORY_MAX_LIMIT.
+fn parse_limit(value: &str) -> Option<usize> {
+    match value.parse::<usize>() {
+        Ok(0) | Err(_) => None,
+        Ok(n) => Some(n.min(CHATHISTORY_MAX_LIMIT)),
+    }
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::pool::{create_pool, run_migrations};
+    use crate::db::queries::messages::{InsertMessageParams, insert_message};
+    use crate::db::queries::users;
+    use crate::engine::user_session::Protocol;
+
+    fn caps_with(requested: &str) -> CapState {
+        let mut caps = CapState::default();
+        let req = IrcMessage::parse(&format!("CAP REQ :{requested}")).unwrap();
+        caps.handle("*", &req, false);
+        caps
+    }
+
+    fn cmd(line: &str) -> IrcMessage {
+        IrcMessage::parse(line).unwrap()
+    }
+
+    #[test]
+    fn test_parse_anchor() {
+        assert_eq!(
+            parse_anchor("msgid=abc"),
+            Some(HistoryAnchor::MessageId("abc".into()))
+        );
+        assert!(matches!(
+            parse_anchor("timestamp=2024-05-01T12:30:00.000Z"),
+            Some(HistoryAnchor::Timestamp(_))
+        ));
+        assert_eq!(parse_anchor("msgid="), None);
+        assert_eq!(parse_anchor("timestamp=yesterday"), None);
+        assert_eq!(parse_anchor("*"), None);
+    }
+
+    #[test]
+    fn test_parse_limit() {
+        assert_eq!(parse_limit("10"), Some(10));
+        assert_eq!(parse_limit("5000"), Some(CHATHISTORY_MAX_LIMIT));
+        assert_eq!(parse_limit("0"), None);
+        assert_eq!(parse_limit("-1"), None);
+    }
+
+    #[test]
+    fn test_wrap_batch_merges_tags() {
+        let lines = vec!["@time=x :a!a@concord PRIVMSG #c :hi".to_string()];
+        let out = wrap_batch(&caps_with("batch"), "chathistory", &["#c"], lines.clone());
+        assert_eq!(out.len(), 3);
+        let reference = out[0]
+            .strip_prefix(":concord BATCH +")
+            .and_then(|rest| rest.split(' ').next())
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            out[0],
+            format!(":concord BATCH +{reference} chathistory #c")
+        );
+        assert_eq!(
+            out[1],
+            format!("@batch={reference};time=x :a!a@concord PRIVMSG #c :hi")
+        );
+        assert_eq!(out[2], format!(":concord BATCH -{reference}"));
+
+        // Without the batch cap lines pass through untouched
+        assert_eq!(
+            wrap_batch(&CapState::default(), "chathistory", &["#c"], lines.clone()),
+            lines
+        );
+    }
+
+    #[tokio::test]
+    async fn test_chathistory_errors() {
+        let engine = ChatEngine::new(None);
+        let (sid, _rx) = engine
+            .connect(None, "alice".into(), Protocol::Irc, None)
+            .unwrap();
+        let caps = CapState::default();
+
+        let reply = |line: &str| {
+            let msg = cmd(line);
+            let engine = &engine;
+            let caps = &caps;
+            async move { handle_chathistory(engine, sid, "alice", caps, &msg).await }
+        };
+
+        assert_eq!(
+            reply("CHATHISTORY").await,
+            [":concord FAIL CHATHISTORY NEED_MORE_PARAMS :Missing parameters"]
+        );
+        assert_eq!(
+            reply("CHATHISTORY FROBNICATE #c * 10").await,
+            [":concord FAIL CHATHISTORY UNKNOWN_COMMAND FROBNICATE :Unknown subcommand"]
+        );
+        assert_eq!(
+            reply("CHATHISTORY BEFORE #c 10").await,
+            [":concord FAIL CHATHISTORY NEED_MORE_PARAMS BEFORE :Missing parameters"]
+        );
+        assert_eq!(
+            reply("CHATHISTORY BEFORE #c * 10").await,
+            [":concord FAIL CHATHISTORY INVALID_PARAMS BEFORE :Invalid message reference"]
+        );
+        assert_eq!(
+            reply("CHATHISTORY LATEST #c * many").await,
+            [":concord FAIL CHATHISTORY INVALID_PARAMS LATEST :Invalid limit"]
+        );
+        assert_eq!(
+            reply("CHATHISTORY TARGETS * * 10").await,
+            [
+                ":concord FAIL CHATHISTORY INVALID_PARAMS TARGETS :Expected two timestamps and a limit"
+            ]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_chathistory_latest_in_batch() {
+        let pool = create_pool("sqlite::memory:").await.unwrap();
+        run_migrations(&pool).await.unwrap();
+        users::create_with_oauth(
+            &pool,
+            &users::CreateOAuthUser {
+                user_id: "u-owner",
+                username: "owner",
+                email: None,
+                avatar_url: None,
+                oauth_id: "oauth-owner",
+                provider: "github",
+                provider_id: "gh-owner",
+            },
+        )
+        .await
+        .unwrap();
+        let engine = ChatEngine::new(Some(pool.clone()));
+        let server_id = engine
+            .create_server("hist".into(), "u-owner".into(), None)
+            .await
+            .unwrap();
+        let (sid, _rx) = engine
+            .connect(None, "alice".into(), Protocol::Irc, None)
+            .unwrap();
+        engine.join_channel(sid, &server_id, "#general").unwrap();
+        let channel_id = engine.resolve_channel_id(&server_id, "#general").unwrap();
+        let msg_id = Uuid::new_v4().to_string();
+        insert_message(
+            &pool,
+            &InsertMessageParams {
+                id: &msg_id,
+                server_id: &server_id,
+                channel_id: &channel_id,
+                sender_id: "u-bob",
+                sender_nick: "bob",
+                content: "earlier",
+                reply_to_id: None,
+                kind: "normal",
+            },
+        )
+        .await
+        .unwrap();
+
+        let caps = caps_with("batch server-time message-tags");
+        let lines = handle_chathistory(
+            &engine,
+            sid,
+            "alice",
+            &caps,
+            &cmd("CHATHISTORY LATEST #hist/general * 10"),
+        )
+        .await;
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with(" chathistory #hist/general"));
+        assert!(lines[1].starts_with("@batch="));
+        assert!(lines[1].contains(";time="));
+        assert!(lines[1].ends_with(&format!(
+            ";msgid={msg_id} :bob!bob@concord PRIVMSG #hist/general earlier"
+        )));
+        assert!(lines[2].starts_with(":concord BATCH -"));
+
+        // Clients without the cap get the same lines replayed after JOIN, timestamped inline
+        let mut markers = ReadMarkers::default();
+        let replay = join_replay(
+            &engine,
+            sid,
+            &CapState::default(),
+            "irc",
+            &mut markers,
+            &server_id,
+            "#general",
+        )
+        .await;
+        assert_eq!(replay.len(), 1);
+        assert!(replay[0].starts_with(":bob!bob@concord PRIVMSG #hist/general :["));
+        assert!(replay[0].ends_with("] earlier"));
+
+        // Having been replayed, the line isn't replayed on the next JOIN
+        let again = join_replay(
+            &engine,
+            sid,
+            &CapState::default(),
+            "irc",
+            &mut markers,
+            &server_id,
+            "#general",
+        )
+        .await;
+        assert!(again.is_empty());
+    }
+}
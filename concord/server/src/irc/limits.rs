@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
+
+/// Counts open IRC connections per client IP, so one address can't hold
+/// thousands of them. Shared by the TCP listener and the WebSocket endpoint.
+#[derive(Clone, Default)]
+pub struct ConnectionLimiter {
+    /// Most connections per IP, or 0 for no limit.
+    max_per_ip: usize,
+    open: Arc<DashMap<String, usize>>,
+}
+
+impl ConnectionLimiter {
+    pub fn new(max_per_ip: usize) -> Self {
+        Self {
+            max_per_ip,
+            open: Arc::new(DashMap::new()),
+        }
+    }
+
+    /// Claim a connection slot for `ip`, or None when it already has as many
+    /// open as allowed. The slot is given back when the guard drops.
+    pub fn acquire(&self, ip: &str) -> Option<ConnectionGuard> {
+        let mut count = self.open.entry(ip.to_string()).or_insert(0);
+        if self.max_per_ip > 0 && *count >= self.max_per_ip {
+            return None;
+        }
+        *count += 1;
+        Some(ConnectionGuard {
+            open: self.open.clone(),
+            ip: ip.to_string(),
+        })
+    }
+
+    /// How many connections `ip` has open.
+    pub fn open_count(&self, ip: &str) -> usize {
+        self.open.get(ip).map(|count| *count).unwrap_or(0)
+    }
+}
+
+/// One open connection's slot, held for as long as the connection lives.
+pub struct ConnectionGuard {
+    open: Arc<DashMap<String, usize>>,
+    ip: String,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        if let Entry::Occupied(mut entry) = self.open.entry(self.ip.clone()) {
+            *entry.get_mut() -= 1;
+            if *entry.get() == 0 {
+                entry.remove();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_per_ip() {
+        let limiter = ConnectionLimiter::new(2);
+        let first = limiter.acquire("10.0.0.1").unwrap();
+        let _second = limiter.acquire("10.0.0.1").unwrap();
+        assert!(limiter.acquire("10.0.0.1").is_none());
+        // Other addresses have their own allowance
+        assert!(limiter.acquire("10.0.0.2").is_some());
+
+        drop(first);
+        assert_eq!(limiter.open_count("10.0.0.1"), 1);
+        assert!(limiter.acquire("10.0.0.1").is_some());
+    }
+
+    #[test]
+    fn test_slots_released_on_drop() {
+        let limiter = ConnectionLimiter::new(1);
+        drop(limiter.acquire("10.0.0.1").unwrap());
+        assert_eq!(limiter.open_count("10.0.0.1"), 0);
+        assert!(limiter.open.is_empty());
+    }
+
+    #[test]
+    fn test_zero_means_unlimited() {
+        let limiter = ConnectionLimiter::new(0);
+        let guards: Vec<_> = (0..50)
+            .filter_map(|_| limiter.acquire("10.0.0.1"))
+            .collect();
+        assert_eq!(guards.len(), 50);
+    }
+}
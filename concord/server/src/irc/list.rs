@@ -0,0 +1,265 @@
+use chrono::{DateTime, Duration, Utc};
+
+use crate::engine::chat_engine::ChatEngine;
+use crate::engine::events::{ChannelInfo, SessionId};
+
+use super::commands::to_irc_channel;
+use super::formatter;
+use super::parser::IrcMessage;
+
+/// ELIST conditions LIST understands, for RPL_ISUPPORT: creation time (C),
+/// name masks (M) and negated masks (N), topic age (T) and user count (U).
+pub const ELIST: &str = "CMNTU";
+
+/// Handle `LIST [conditions]`, listing the channels the user can see on every
+/// server they belong to. Conditions are comma-separated ELIST terms: `>N` and
+/// `<N` user counts, `C<N`/`C>N` and `T<N`/`T>N` for channels created or topics
+/// set less or more than N minutes ago, and name globs, negated with `!`.
+/// `LIST #server-name/*` lists one server.
+pub async fn handle_list(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    msg: &IrcMessage,
+) -> Vec<String> {
+    let conditions: Vec<Condition> = msg
+        .params
+        .first()
+        .into_iter()
+        .flat_map(|p| p.split(','))
+        .filter_map(Condition::parse)
+        .collect();
+    let now = Utc::now();
+
+    let mut listed: Vec<(String, ChannelInfo)> = engine
+        .list_visible_channels(session_id)
+        .await
+        .into_iter()
+        .map(|ch| (to_irc_channel(engine, &ch.server_id, &ch.name), ch))
+        .filter(|(name, ch)| matches_all(&conditions, name, ch, now))
+        .collect();
+    listed.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut replies: Vec<String> = listed
+        .iter()
+        .map(|(name, ch)| formatter::rpl_list(nick, name, ch.member_count, &list_topic(ch)))
+        .collect();
+    replies.push(formatter::rpl_listend(nick));
+    replies
+}
+
+/// One ELIST term.
+#[derive(Debug, PartialEq)]
+enum Condition {
+    Mask(String),
+    NotMask(String),
+    MoreUsersThan(usize),
+    FewerUsersThan(usize),
+    CreatedWithin(Duration),
+    CreatedBefore(Duration),
+    TopicWithin(Duration),
+    TopicBefore(Duration),
+}
+
+impl Condition {
+    /// Parse one term, or None for an empty or malformed one.
+    fn parse(term: &str) -> Option<Self> {
+        let term = term.trim();
+        if term.is_empty() {
+            return None;
+        }
+        if let Some(n) = term.strip_prefix('>') {
+            return n.parse().ok().map(Condition::MoreUsersThan);
+        }
+        if let Some(n) = term.strip_prefix('<') {
+            return n.parse().ok().map(Condition::FewerUsersThan);
+        }
+        if let Some(mask) = term.strip_prefix('!') {
+            return Some(Condition::NotMask(mask.to_string()));
+        }
+        let minutes = |n: &str| Duration::try_minutes(n.parse().ok()?);
+        let mut chars = term.chars();
+        match (
+            chars.next().map(|c| c.to_ascii_uppercase()),
+            chars.next(),
+            chars.as_str(),
+        ) {
+            (Some('C'), Some('<'), n) => minutes(n).map(Condition::CreatedWithin),
+            (Some('C'), Some('>'), n) => minutes(n).map(Condition::CreatedBefore),
+            (Some('T'), Some('<'), n) => minutes(n).map(Condition::TopicWithin),
+            (Some('T'), Some('>'), n) => minutes(n).map(Condition::TopicBefore),
+            _ => Some(Condition::Mask(term.to_string())),
+        }
+    }
+}
+
+/// Whether a channel passes every condition. With any name masks given, the
+/// channel must match at least one of them.
+fn matches_all(
+    conditions: &[Condition],
+    irc_name: &str,
+    ch: &ChannelInfo,
+    now: DateTime<Utc>,
+) -> bool {
+    let mut masks = conditions
+        .iter()
+        .filter_map(|c| match c {
+            Condition::Mask(mask) => Some(mask),
+            _ => None,
+        })
+        .peekable();
+    if masks.peek().is_some() && !masks.any(|mask| glob_matches(mask, irc_name)) {
+        return false;
+    }
+
+    let topic_age = ch.topic_set_at.map(|at| now - at);
+    conditions.iter().all(|c| match c {
+        Condition::Mask(_) => true,
+        Condition::NotMask(mask) => !glob_matches(mask, irc_name),
+        Condition::MoreUsersThan(n) => ch.member_count > *n,
+        Condition::FewerUsersThan(n) => ch.member_count < *n,
+        Condition::CreatedWithin(d) => now - ch.created_at < *d,
+        Condition::CreatedBefore(d) => now - ch.created_at > *d,
+        Condition::TopicWithin(d) => topic_age.is_some_and(|age| age < *d),
+        Condition::TopicBefore(d) => topic_age.is_some_and(|age| age > *d),
+    })
+}
+
+/// Case-insensitive glob match, where `*` matches any run of characters and
+/// `?` any one character.
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Where the last `*` was, and the text position it's currently matched up to
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, t));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// A channel's topic as LIST shows it. Threads are marked as such, and as
+/// archived once they are.
+fn list_topic(ch: &ChannelInfo) -> String {
+    let label = match (ch.thread_parent_message_id.is_some(), ch.archived) {
+        (true, true) => "[archived thread]",
+        (true, false) => "[thread]",
+        (false, true) => "[archived]",
+        (false, false) => return ch.topic.clone(),
+    };
+    format!("{label} {}", ch.topic).trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::chat_engine::DEFAULT_SERVER_ID;
+
+    fn channel(member_count: usize, age_minutes: i64, topic_minutes: Option<i64>) -> ChannelInfo {
+        let now = Utc::now();
+        ChannelInfo {
+            id: "c1".into(),
+            server_id: DEFAULT_SERVER_ID.into(),
+            name: "#general".into(),
+            topic: "Chat".into(),
+            member_count,
+            category_id: None,
+            position: 0,
+            is_private: false,
+            channel_type: "text".into(),
+            thread_parent_message_id: None,
+            archived: false,
+            created_at: now - Duration::minutes(age_minutes),
+            topic_set_at: topic_minutes.map(|m| now - Duration::minutes(m)),
+        }
+    }
+
+    fn passes(terms: &str, irc_name: &str, ch: &ChannelInfo) -> bool {
+        let conditions: Vec<Condition> = terms.split(',').filter_map(Condition::parse).collect();
+        matches_all(&conditions, irc_name, ch, Utc::now())
+    }
+
+    #[test]
+    fn test_parse_conditions() {
+        assert_eq!(Condition::parse(">5"), Some(Condition::MoreUsersThan(5)));
+        assert_eq!(Condition::parse("<5"), Some(Condition::FewerUsersThan(5)));
+        assert_eq!(
+            Condition::parse("c<60"),
+            Some(Condition::CreatedWithin(Duration::minutes(60)))
+        );
+        assert_eq!(
+            Condition::parse("T>10"),
+            Some(Condition::TopicBefore(Duration::minutes(10)))
+        );
+        assert_eq!(
+            Condition::parse("!#guild/*"),
+            Some(Condition::NotMask("#guild/*".into()))
+        );
+        assert_eq!(
+            Condition::parse("#guild/*"),
+            Some(Condition::Mask("#guild/*".into()))
+        );
+        assert_eq!(Condition::parse(">many"), None);
+        assert_eq!(Condition::parse(""), None);
+        // Too many minutes for a duration is as malformed as none at all
+        assert_eq!(Condition::parse("C<99999999999999999"), None);
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("#guild/*", "#guild/general"));
+        assert!(glob_matches("#guild/*", "#Guild/general/some-thread"));
+        assert!(!glob_matches("#guild/*", "#general"));
+        assert!(glob_matches("#gen?ral", "#general"));
+        assert!(glob_matches("*dev*", "#team/dev-chat"));
+        assert!(glob_matches("#general", "#GENERAL"));
+        assert!(!glob_matches("#general", "#general2"));
+        assert!(glob_matches("*", ""));
+    }
+
+    #[test]
+    fn test_conditions() {
+        let ch = channel(3, 120, Some(30));
+        assert!(passes("", "#general", &ch));
+        assert!(passes(">2,<4", "#general", &ch));
+        assert!(!passes(">3", "#general", &ch));
+        assert!(passes("C>60", "#general", &ch));
+        assert!(!passes("C<60", "#general", &ch));
+        assert!(passes("T<60,T>10", "#general", &ch));
+        assert!(!passes("T<10", "#general", &ch));
+        assert!(!passes("T>10", "#general", &channel(3, 120, None)));
+
+        // Any one mask will do, but every negated mask must miss
+        assert!(passes("#dev,#gen*", "#general", &ch));
+        assert!(!passes("#dev", "#general", &ch));
+        assert!(!passes("!#gen*", "#general", &ch));
+        assert!(passes("!#guild/*,>0", "#general", &ch));
+    }
+
+    #[test]
+    fn test_list_topic() {
+        let mut ch = channel(1, 0, None);
+        assert_eq!(list_topic(&ch), "Chat");
+
+        ch.channel_type = "public_thread".into();
+        ch.thread_parent_message_id = Some("m1".into());
+        assert_eq!(list_topic(&ch), "[thread] Chat");
+
+        ch.archived = true;
+        ch.topic.clear();
+        assert_eq!(list_topic(&ch), "[archived thread]");
+    }
+}
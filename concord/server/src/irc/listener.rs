@@ -1,22 +1,38 @@
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 use sqlx::SqlitePool;
-use tokio::net::TcpListener;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
 use tokio_rustls::TlsAcceptor;
+use tokio_rustls::rustls::client::danger::HandshakeSignatureValid;
+use tokio_rustls::rustls::crypto::{CryptoProvider, verify_tls12_signature, verify_tls13_signature};
+use tokio_rustls::rustls::pki_types::{CertificateDer, UnixTime};
+use tokio_rustls::rustls::server::danger::{ClientCertVerified, ClientCertVerifier};
+use tokio_rustls::rustls::{DigitallySignedStruct, DistinguishedName, SignatureScheme};
 use tokio_util::sync::CancellationToken;
 use tracing::{error, info, warn};
 
+use crate::auth::token::cert_fingerprint;
+use crate::config::IrcSection;
 use crate::engine::chat_engine::ChatEngine;
+use crate::web::rate_limit::TrustedProxies;
 
 use super::connection::handle_irc_connection;
+use super::limits::ConnectionLimiter;
+use super::proxy_protocol::read_proxy_header;
 
 /// Start the IRC TCP listener. Accepts connections and spawns a handler task for each.
 /// If a TLS acceptor is provided, connections are wrapped in TLS.
 /// Stops accepting new connections when the cancellation token is triggered.
+#[allow(clippy::too_many_arguments)]
 pub async fn start_irc_listener(
     bind_addr: &str,
     engine: Arc<ChatEngine>,
     db: SqlitePool,
+    config: Arc<IrcSection>,
+    limiter: ConnectionLimiter,
+    trusted_proxies: Arc<TrustedProxies>,
     cancel: CancellationToken,
     tls_acceptor: Option<TlsAcceptor>,
 ) {
@@ -29,6 +45,9 @@ pub async fn start_irc_listener(
     } else {
         info!("IRC listener started on {} (plaintext)", bind_addr);
     }
+    if config.proxy_protocol {
+        info!("IRC listener expects a PROXY protocol header on every connection");
+    }
 
     loop {
         tokio::select! {
@@ -39,26 +58,16 @@ pub async fn start_irc_listener(
             result = listener.accept() => {
                 match result {
                     Ok((stream, addr)) => {
-                        let engine = engine.clone();
-                        let db = db.clone();
-                        let peer = addr.to_string();
-                        if let Some(ref acceptor) = tls_acceptor {
-                            let acceptor = acceptor.clone();
-                            tokio::spawn(async move {
-                                match acceptor.accept(stream).await {
-                                    Ok(tls_stream) => {
-                                        handle_irc_connection(tls_stream, peer, engine, db).await;
-                                    }
-                                    Err(e) => {
-                                        warn!(%peer, error = %e, "TLS handshake failed");
-                                    }
-                                }
-                            });
-                        } else {
-                            tokio::spawn(async move {
-                                handle_irc_connection(stream, peer, engine, db).await;
-                            });
-                        }
+                        tokio::spawn(serve_connection(
+                            stream,
+                            addr,
+                            engine.clone(),
+                            db.clone(),
+                            config.clone(),
+                            limiter.clone(),
+                            trusted_proxies.clone(),
+                            tls_acceptor.clone(),
+                        ));
                     }
                     Err(e) => {
                         error!(error = %e, "failed to accept IRC connection");
@@ -68,3 +77,261 @@ pub async fn start_irc_listener(
         }
     }
 }
+
+/// Take an accepted connection through its PROXY header (when the listener
+/// sits behind a proxy), the per-IP connection limit and the TLS handshake,
+/// then run it. Connections from an IP that already has as many open as
+/// `limiter` allows are turned away with an ERROR. Only `trusted_proxies`
+/// may speak for a client with a PROXY header; anyone else is hung up on, as
+/// they could otherwise pick any address they like.
+#[allow(clippy::too_many_arguments)]
+async fn serve_connection(
+    mut stream: TcpStream,
+    mut addr: SocketAddr,
+    engine: Arc<ChatEngine>,
+    db: SqlitePool,
+    config: Arc<IrcSection>,
+    limiter: ConnectionLimiter,
+    trusted_proxies: Arc<TrustedProxies>,
+    tls_acceptor: Option<TlsAcceptor>,
+) {
+    if config.proxy_protocol {
+        if !trusted_proxies.contains(addr.ip()) {
+            warn!(peer = %addr, "PROXY protocol connection from an untrusted peer");
+            return;
+        }
+        let header = read_proxy_header(&mut stream);
+        match tokio::time::timeout(config.registration_timeout(), header).await {
+            Ok(Ok(Some(client))) => addr = client,
+            // The proxy connecting for itself, such as a health check
+            Ok(Ok(None)) => {}
+            Ok(Err(e)) => {
+                warn!(peer = %addr, error = %e, "bad PROXY protocol header");
+                return;
+            }
+            Err(_) => {
+                warn!(peer = %addr, "timed out waiting for PROXY protocol header");
+                return;
+            }
+        }
+    }
+
+    let Some(_guard) = limiter.acquire(&addr.ip().to_string()) else {
+        warn!(peer = %addr, "too many IRC connections from this address");
+        // A TLS client would only see garbage, so it's just hung up on
+        if tls_acceptor.is_none() {
+            let _ = stream
+                .write_all(b"ERROR :Closing Link: * (Too many connections from your IP)\r\n")
+                .await;
+        }
+        return;
+    };
+    let peer = addr.to_string();
+
+    if let Some(acceptor) = tls_acceptor {
+        match acceptor.accept(stream).await {
+            Ok(tls_stream) => {
+                // Client certificate (if any) identifies the user for SASL EXTERNAL
+                let cert_fp = tls_stream
+                    .get_ref()
+                    .1
+                    .peer_certificates()
+                    .and_then(|certs| certs.first())
+                    .map(|cert| cert_fingerprint(cert.as_ref()));
+                handle_irc_connection(tls_stream, peer, engine, db, config, cert_fp).await;
+            }
+            Err(e) => {
+                warn!(%peer, error = %e, "TLS handshake failed");
+            }
+        }
+    } else {
+        handle_irc_connection(stream, peer, engine, db, config, None).await;
+    }
+}
+
+/// TLS client certificate verifier for the IRC listener.
+///
+/// Client certificates are optional and not chained to any CA: a certificate
+/// only means something once its fingerprint is bound to a user for SASL
+/// EXTERNAL. The handshake signature is still checked, so the client must
+/// hold the private key.
+#[derive(Debug)]
+pub struct OptionalClientCertVerifier {
+    provider: Arc<CryptoProvider>,
+}
+
+impl OptionalClientCertVerifier {
+    pub fn new(provider: Arc<CryptoProvider>) -> Self {
+        Self { provider }
+    }
+}
+
+impl ClientCertVerifier for OptionalClientCertVerifier {
+    fn offer_client_auth(&self) -> bool {
+        true
+    }
+
+    fn client_auth_mandatory(&self) -> bool {
+        false
+    }
+
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _now: UnixTime,
+    ) -> Result<ClientCertVerified, tokio_rustls::rustls::Error> {
+        Ok(ClientCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    /// Accept one loopback connection into `serve_connection`, returning the
+    /// client's end. The loopback address is a trusted proxy.
+    async fn connect(config: IrcSection, limiter: ConnectionLimiter) -> TcpStream {
+        let trusted = TrustedProxies::parse(&["127.0.0.1".into()]).unwrap();
+        connect_trusting(config, limiter, trusted).await
+    }
+
+    async fn connect_trusting(
+        config: IrcSection,
+        limiter: ConnectionLimiter,
+        trusted: TrustedProxies,
+    ) -> TcpStream {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (stream, addr) = listener.accept().await.unwrap();
+        let engine = Arc::new(ChatEngine::new(None));
+        let db = SqlitePool::connect_lazy("sqlite::memory:").unwrap();
+        tokio::spawn(serve_connection(
+            stream,
+            addr,
+            engine,
+            db,
+            Arc::new(config),
+            limiter,
+            Arc::new(trusted),
+            None,
+        ));
+        client
+    }
+
+    #[tokio::test]
+    async fn test_proxy_header_gives_client_address() {
+        let config = IrcSection {
+            proxy_protocol: true,
+            ..IrcSection::default()
+        };
+        let limiter = ConnectionLimiter::new(1);
+        let mut client = connect(config, limiter.clone()).await;
+        client
+            .write_all(
+                b"PROXY TCP4 203.0.113.7 127.0.0.1 51234 6667\r\nNICK proxied\r\nUSER p 0 * :P\r\n",
+            )
+            .await
+            .unwrap();
+
+        let mut lines = BufReader::new(client).lines();
+        let welcome = lines.next_line().await.unwrap().unwrap();
+        assert!(welcome.contains(" 001 proxied "));
+        // The connection counts against the client's address, not the proxy's
+        assert_eq!(limiter.open_count("203.0.113.7"), 1);
+        assert_eq!(limiter.open_count("127.0.0.1"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_proxy_header_from_untrusted_peer_refused() {
+        let config = IrcSection {
+            proxy_protocol: true,
+            ..IrcSection::default()
+        };
+        let limiter = ConnectionLimiter::new(1);
+        let trusted = TrustedProxies::parse(&["10.0.0.1".into()]).unwrap();
+        let mut client = connect_trusting(config, limiter.clone(), trusted).await;
+        let _ = client
+            .write_all(
+                b"PROXY TCP4 203.0.113.7 127.0.0.1 51234 6667\r\nNICK spoofer\r\nUSER s 0 * :S\r\n",
+            )
+            .await;
+
+        // Hung up on without the claimed address ever being used
+        let mut lines = BufReader::new(client).lines();
+        assert!(!matches!(lines.next_line().await, Ok(Some(_))));
+        assert_eq!(limiter.open_count("203.0.113.7"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_missing_proxy_header_refused() {
+        let config = IrcSection {
+            proxy_protocol: true,
+            ..IrcSection::default()
+        };
+        let mut client = connect(config, ConnectionLimiter::new(1)).await;
+        client
+            .write_all(b"NICK direct\r\nUSER d 0 * :D\r\n")
+            .await
+            .unwrap();
+
+        // Hung up on, possibly with a reset for the unread lines
+        let mut lines = BufReader::new(client).lines();
+        assert!(!matches!(lines.next_line().await, Ok(Some(_))));
+    }
+
+    #[tokio::test]
+    async fn test_connection_limit_per_ip() {
+        let limiter = ConnectionLimiter::new(1);
+        let _held = limiter.acquire("127.0.0.1").unwrap();
+        let client = connect(IrcSection::default(), limiter).await;
+
+        let mut lines = BufReader::new(client).lines();
+        assert_eq!(
+            lines.next_line().await.unwrap().unwrap(),
+            "ERROR :Closing Link: * (Too many connections from your IP)"
+        );
+        assert!(lines.next_line().await.unwrap().is_none());
+    }
+}
@@ -1,6 +1,18 @@
+pub mod access;
+pub mod certs;
 pub mod commands;
 pub mod connection;
+pub mod edits;
 pub mod formatter;
+pub mod formatting;
+pub mod group_dm;
+pub mod history;
+pub mod limits;
+pub mod list;
 pub mod listener;
+pub mod moderation;
+pub mod monitor;
 pub mod numerics;
+pub mod oper;
 pub mod parser;
+pub mod proxy_protocol;
@@ -0,0 +1,565 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use tracing::warn;
+
+use crate::engine::chat_engine::ChatEngine;
+use crate::engine::events::{RoleInfo, SessionId};
+use crate::engine::permissions::Permissions;
+
+use super::commands::{parse_irc_channel, to_irc_channel};
+use super::formatter;
+use super::parser::IrcMessage;
+
+/// How long `MODE +q` times a member out for. Concord timeouts always expire,
+/// so an IRC quiet becomes the longest timeout moderators can hand out.
+const QUIET_DAYS: i64 = 28;
+
+/// Name of the role created for `+v` when a server has no plain speaking role yet.
+const VOICE_ROLE_NAME: &str = "Voice";
+
+/// The channel a MODE or KICK command applies to.
+struct ModeTarget<'a> {
+    server_id: &'a str,
+    channel_name: &'a str,
+    irc_channel: &'a str,
+}
+
+/// Handle `MODE <target> [modes [args...]]` and return the reply lines.
+///
+/// Channel modes map onto Concord moderation: `b` bans, `q` times out, `o` and
+/// `v` grant or revoke roles, `m` moderates the channel and `s`/`p` make it
+/// private. Without a mode string the channel's current state is reported.
+pub async fn handle_mode(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    msg: &IrcMessage,
+) -> Vec<String> {
+    let Some(target) = msg.params.first() else {
+        return vec![formatter::err_needmoreparams(nick, "MODE")];
+    };
+    if !target.starts_with('#') {
+        let modes = if engine.is_oper(session_id) {
+            "+o"
+        } else {
+            "+"
+        };
+        return vec![formatter::rpl_umodeis(nick, modes)];
+    }
+
+    let (server_id, channel_name) = parse_irc_channel(engine, target);
+    let irc_channel = to_irc_channel(engine, &server_id, &channel_name);
+    let chan = ModeTarget {
+        server_id: &server_id,
+        channel_name: &channel_name,
+        irc_channel: &irc_channel,
+    };
+
+    let Some(modes) = msg.params.get(1) else {
+        return channel_modes(engine, nick, &chan).await;
+    };
+
+    let mut args = msg.params[2..].iter();
+    let mut adding = true;
+    let mut replies = Vec::new();
+
+    for mode in modes.chars() {
+        let lines = match mode {
+            '+' => {
+                adding = true;
+                continue;
+            }
+            '-' => {
+                adding = false;
+                continue;
+            }
+            'b' => match args.next() {
+                Some(mask) => set_ban(engine, session_id, nick, &chan, adding, mask).await,
+                None => ban_list(engine, session_id, nick, &chan).await,
+            },
+            'q' => match args.next() {
+                Some(mask) => set_quiet(engine, session_id, nick, &chan, adding, mask).await,
+                None => quiet_list(engine, session_id, nick, &chan).await,
+            },
+            'o' | 'v' => match args.next() {
+                Some(target) => {
+                    set_status(engine, session_id, nick, &chan, mode, adding, target).await
+                }
+                None => vec![formatter::err_needmoreparams(nick, "MODE")],
+            },
+            'm' => set_moderated(engine, session_id, nick, &chan, adding).await,
+            's' | 'p' => {
+                match engine
+                    .set_channel_private(session_id, &server_id, &channel_name, adding)
+                    .await
+                {
+                    // The PrivacyUpdate event carries the MODE line to the channel
+                    Ok(()) => vec![],
+                    Err(e) => vec![moderation_error(nick, &chan, &e)],
+                }
+            }
+            other => vec![formatter::err_unknownmode(nick, other)],
+        };
+        replies.extend(lines);
+    }
+
+    replies
+}
+
+/// Handle `KICK <channel> <nick>[,<nick>...] [:reason]` and return the reply lines.
+///
+/// Concord kicks are server-wide, so each target leaves the server and every
+/// channel in it; the KICK lines themselves arrive as channel events.
+pub async fn handle_kick(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    msg: &IrcMessage,
+) -> Vec<String> {
+    if msg.params.len() < 2 {
+        return vec![formatter::err_needmoreparams(nick, "KICK")];
+    }
+
+    let (server_id, channel_name) = parse_irc_channel(engine, &msg.params[0]);
+    let irc_channel = to_irc_channel(engine, &server_id, &channel_name);
+    let chan = ModeTarget {
+        server_id: &server_id,
+        channel_name: &channel_name,
+        irc_channel: &irc_channel,
+    };
+    let reason = msg.params.get(2).map(|s| s.as_str());
+    let mut replies = Vec::new();
+
+    for target in msg.params[1].split(',').filter(|t| !t.is_empty()) {
+        let user_id = match member_user_id(engine, nick, &chan, target).await {
+            Ok(user_id) => user_id,
+            Err(reply) => {
+                replies.push(reply);
+                continue;
+            }
+        };
+        if let Err(e) = engine
+            .kick_member(session_id, &server_id, &user_id, reason)
+            .await
+        {
+            warn!(error = %e, %target, "KICK failed");
+            replies.push(moderation_error(nick, &chan, &e));
+        }
+    }
+
+    replies
+}
+
+/// RPL_CHANNELMODEIS and RPL_CREATIONTIME for a MODE query.
+async fn channel_modes(engine: &ChatEngine, nick: &str, chan: &ModeTarget<'_>) -> Vec<String> {
+    match engine
+        .channel_moderation(chan.server_id, chan.channel_name)
+        .await
+    {
+        Ok(state) => {
+            let mut modes = String::from("+");
+            if state.moderated {
+                modes.push('m');
+            }
+            if state.is_private {
+                modes.push('s');
+            }
+            vec![
+                formatter::rpl_channelmodeis(nick, chan.irc_channel, &modes),
+                formatter::rpl_creationtime(nick, chan.irc_channel, state.created_at.timestamp()),
+            ]
+        }
+        Err(_) => vec![formatter::err_nosuchchannel(nick, chan.irc_channel)],
+    }
+}
+
+async fn set_ban(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    chan: &ModeTarget<'_>,
+    adding: bool,
+    mask: &str,
+) -> Vec<String> {
+    let target = mask_nick(mask);
+    let Some(user_id) = engine.find_user_id_by_nick(target).await else {
+        return vec![formatter::err_nosuchnick(nick, target)];
+    };
+
+    let result = if adding {
+        engine
+            .ban_member(session_id, chan.server_id, &user_id, None, 0)
+            .await
+    } else {
+        engine
+            .unban_member(session_id, chan.server_id, &user_id)
+            .await
+    };
+
+    match result {
+        Ok(()) => {
+            let change = if adding { "+b" } else { "-b" };
+            vec![formatter::mode(
+                nick,
+                chan.irc_channel,
+                change,
+                &[&nick_mask(target)],
+            )]
+        }
+        Err(e) => {
+            warn!(error = %e, %target, "MODE b failed");
+            vec![moderation_error(nick, chan, &e)]
+        }
+    }
+}
+
+async fn ban_list(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    chan: &ModeTarget<'_>,
+) -> Vec<String> {
+    let bans = match engine.get_bans(session_id, chan.server_id).await {
+        Ok(bans) => bans,
+        Err(e) => return vec![moderation_error(nick, chan, &e)],
+    };
+
+    let mut replies = Vec::with_capacity(bans.len() + 1);
+    for ban in &bans {
+        let banned = engine.find_nick_by_user_id(&ban.user_id).await;
+        let banned_by = engine.find_nick_by_user_id(&ban.banned_by).await;
+        replies.push(formatter::rpl_banlist(
+            nick,
+            chan.irc_channel,
+            &nick_mask(&banned),
+            &banned_by,
+            unix_time(&ban.created_at),
+        ));
+    }
+    replies.push(formatter::rpl_endofbanlist(nick, chan.irc_channel));
+    replies
+}
+
+async fn set_quiet(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    chan: &ModeTarget<'_>,
+    adding: bool,
+    mask: &str,
+) -> Vec<String> {
+    let target = mask_nick(mask);
+    let user_id = match member_user_id(engine, nick, chan, target).await {
+        Ok(user_id) => user_id,
+        Err(reply) => return vec![reply],
+    };
+
+    let until = adding.then(|| {
+        (Utc::now() + Duration::days(QUIET_DAYS))
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string()
+    });
+
+    match engine
+        .timeout_member(session_id, chan.server_id, &user_id, until.as_deref(), None)
+        .await
+    {
+        Ok(()) => {
+            let change = if adding { "+q" } else { "-q" };
+            vec![formatter::mode(
+                nick,
+                chan.irc_channel,
+                change,
+                &[&nick_mask(target)],
+            )]
+        }
+        Err(e) => {
+            warn!(error = %e, %target, "MODE q failed");
+            vec![moderation_error(nick, chan, &e)]
+        }
+    }
+}
+
+/// RPL_QUIETLIST entries for members with an active timeout. Concord does not
+/// record who set a timeout or when, so entries carry the server name and the
+/// time the timeout ends.
+async fn quiet_list(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    chan: &ModeTarget<'_>,
+) -> Vec<String> {
+    let timeouts = match engine.get_timeouts(session_id, chan.server_id).await {
+        Ok(timeouts) => timeouts,
+        Err(e) => return vec![moderation_error(nick, chan, &e)],
+    };
+
+    let mut replies = Vec::with_capacity(timeouts.len() + 1);
+    for (user_id, until) in &timeouts {
+        let quieted = engine.find_nick_by_user_id(user_id).await;
+        replies.push(formatter::rpl_quietlist(
+            nick,
+            chan.irc_channel,
+            &nick_mask(&quieted),
+            formatter::server_name(),
+            unix_time(until),
+        ));
+    }
+    replies.push(formatter::rpl_endofquietlist(nick, chan.irc_channel));
+    replies
+}
+
+/// `+o`/`-o` and `+v`/`-v`: grant or revoke the role that lets a member
+/// moderate messages (operator) or speak in moderated channels (voice).
+async fn set_status(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    chan: &ModeTarget<'_>,
+    mode: char,
+    adding: bool,
+    target: &str,
+) -> Vec<String> {
+    if let Err(e) = engine
+        .require_permission(session_id, chan.server_id, None, Permissions::MANAGE_ROLES)
+        .await
+    {
+        return vec![moderation_error(nick, chan, &e)];
+    }
+
+    let user_id = match member_user_id(engine, nick, chan, target).await {
+        Ok(user_id) => user_id,
+        Err(reply) => return vec![reply],
+    };
+
+    let roles = match engine.list_roles(chan.server_id).await {
+        Ok(roles) => roles,
+        Err(e) => return vec![moderation_error(nick, chan, &e)],
+    };
+    let result = if adding {
+        let role_id = if mode == 'o' {
+            lowest_role(&roles, is_operator_role)
+                .ok_or_else(|| "No role on this server grants MANAGE_MESSAGES".to_string())
+        } else {
+            voice_role(engine, chan.server_id).await
+        };
+        match role_id {
+            Ok(role_id) => engine
+                .assign_role(chan.server_id, &user_id, &role_id)
+                .await
+                .map(|_| ()),
+            Err(e) => Err(e),
+        }
+    } else {
+        // De-opping strips every operator role; devoicing only the voice role
+        // `+v` hands out, leaving custom roles that happen to allow speaking.
+        let voice = lowest_role(&roles, is_voice_role);
+        let mut result = Ok(());
+        for role in &roles {
+            let revoke = if mode == 'o' {
+                is_operator_role(role)
+            } else {
+                voice.as_deref() == Some(role.id.as_str())
+            };
+            if !revoke {
+                continue;
+            }
+            if let Err(e) = engine.remove_role(chan.server_id, &user_id, &role.id).await {
+                result = Err(e);
+                break;
+            }
+        }
+        result
+    };
+
+    match result {
+        // The role change itself announces the resulting prefix modes to the
+        // channel (see `ChatEvent::MemberPrefixUpdate`), so there is nothing to echo.
+        Ok(()) => vec![],
+        Err(e) => {
+            warn!(error = %e, %target, %mode, "MODE status change failed");
+            vec![moderation_error(nick, chan, &e)]
+        }
+    }
+}
+
+/// `+m`/`-m`. Moderating makes sure a voice role exists first, so that `+v`
+/// members are among those still allowed to speak.
+async fn set_moderated(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    chan: &ModeTarget<'_>,
+    adding: bool,
+) -> Vec<String> {
+    if adding {
+        if let Err(e) = engine
+            .require_permission(
+                session_id,
+                chan.server_id,
+                None,
+                Permissions::MANAGE_CHANNELS,
+            )
+            .await
+        {
+            return vec![moderation_error(nick, chan, &e)];
+        }
+        if let Err(e) = voice_role(engine, chan.server_id).await {
+            return vec![moderation_error(nick, chan, &e)];
+        }
+    }
+
+    match engine
+        .set_channel_moderated(session_id, chan.server_id, chan.channel_name, adding)
+        .await
+    {
+        // The ModeratedUpdate event carries the MODE line to the channel
+        Ok(()) => vec![],
+        Err(e) => vec![moderation_error(nick, chan, &e)],
+    }
+}
+
+/// Resolve a nick to the user ID of a member of the target's server, or the
+/// error reply to send instead.
+async fn member_user_id(
+    engine: &ChatEngine,
+    nick: &str,
+    chan: &ModeTarget<'_>,
+    target: &str,
+) -> Result<String, String> {
+    let Some(user_id) = engine.find_user_id_by_nick(target).await else {
+        return Err(formatter::err_nosuchnick(nick, target));
+    };
+    if !engine.user_is_server_member(chan.server_id, &user_id) {
+        return Err(formatter::err_usernotinchannel(
+            nick,
+            target,
+            chan.irc_channel,
+        ));
+    }
+    Ok(user_id)
+}
+
+/// A role `+o` grants: it may manage messages but is not a full administrator.
+fn is_operator_role(role: &RoleInfo) -> bool {
+    let perms = Permissions::from_bits_truncate(role.permissions as u64);
+    !role.is_default
+        && perms.contains(Permissions::MANAGE_MESSAGES)
+        && !perms.contains(Permissions::ADMINISTRATOR)
+}
+
+/// A role `+v` grants: it may send messages but not manage them.
+fn is_voice_role(role: &RoleInfo) -> bool {
+    let perms = Permissions::from_bits_truncate(role.permissions as u64);
+    !role.is_default
+        && perms.contains(Permissions::SEND_MESSAGES)
+        && !perms.contains(Permissions::MANAGE_MESSAGES)
+}
+
+fn lowest_role(roles: &[RoleInfo], matches: fn(&RoleInfo) -> bool) -> Option<String> {
+    roles
+        .iter()
+        .filter(|r| matches(r))
+        .min_by_key(|r| r.position)
+        .map(|r| r.id.clone())
+}
+
+/// The server's voice role, created on first use.
+async fn voice_role(engine: &ChatEngine, server_id: &str) -> Result<String, String> {
+    let roles = engine.list_roles(server_id).await?;
+    if let Some(role_id) = lowest_role(&roles, is_voice_role) {
+        return Ok(role_id);
+    }
+    let role = engine
+        .create_role(
+            server_id,
+            VOICE_ROLE_NAME,
+            None,
+            Permissions::SEND_MESSAGES.bits() as i64,
+        )
+        .await?;
+    Ok(role.id)
+}
+
+/// Permission failures become ERR_CHANOPRIVSNEEDED; anything else is explained
+/// in a server NOTICE.
+fn moderation_error(nick: &str, chan: &ModeTarget<'_>, error: &str) -> String {
+    if error == "AUTH_REQUIRED" || error.starts_with("FORBIDDEN") {
+        formatter::err_chanoprivsneeded(nick, chan.irc_channel)
+    } else {
+        formatter::server_notice(nick, error)
+    }
+}
+
+/// The nick a `nick!user@host` mask refers to. Concord bans users, not hosts,
+/// so the user and host parts are ignored.
+fn mask_nick(mask: &str) -> &str {
+    mask.split('!').next().unwrap_or(mask)
+}
+
+fn nick_mask(nick: &str) -> String {
+    format!("{nick}!*@*")
+}
+
+/// Unix time of a stored "YYYY-MM-DD HH:MM:SS" timestamp (0 if unparseable).
+fn unix_time(value: &str) -> i64 {
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .map(|dt| dt.and_utc().timestamp())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn role(name: &str, position: i32, permissions: Permissions, is_default: bool) -> RoleInfo {
+        RoleInfo {
+            id: name.to_lowercase(),
+            server_id: "s1".into(),
+            name: name.into(),
+            color: None,
+            icon_url: None,
+            position,
+            permissions: permissions.bits() as i64,
+            is_default,
+        }
+    }
+
+    #[test]
+    fn test_mask_nick() {
+        assert_eq!(mask_nick("troll!*@*"), "troll");
+        assert_eq!(mask_nick("troll"), "troll");
+        assert_eq!(nick_mask("troll"), "troll!*@*");
+    }
+
+    #[test]
+    fn test_unix_time() {
+        assert_eq!(unix_time("2024-01-01 00:00:00"), 1704067200);
+        assert_eq!(unix_time("garbage"), 0);
+    }
+
+    #[test]
+    fn test_status_roles() {
+        use crate::engine::permissions::{DEFAULT_ADMIN, DEFAULT_EVERYONE, DEFAULT_MODERATOR};
+
+        let roles = vec![
+            role("Everyone", 0, DEFAULT_EVERYONE, true),
+            role("Moderator", 1, DEFAULT_MODERATOR, false),
+            role("Admin", 2, DEFAULT_ADMIN, false),
+            role("Owner", 3, Permissions::all(), false),
+        ];
+        assert_eq!(
+            lowest_role(&roles, is_operator_role),
+            Some("moderator".into())
+        );
+        // Owner holds ADMINISTRATOR, so -o never strips it
+        assert!(!is_operator_role(&roles[3]));
+        // No plain speaking role yet; +v creates one
+        assert_eq!(lowest_role(&roles, is_voice_role), None);
+        assert!(is_voice_role(&role(
+            "Voice",
+            4,
+            Permissions::SEND_MESSAGES,
+            false
+        )));
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::HashSet;
+
+use crate::engine::chat_engine::{ChatEngine, MONITOR_LIMIT};
+use crate::engine::events::SessionId;
+
+use super::formatter;
+use super::parser::IrcMessage;
+
+/// Room for targets in one MONITOR reply line, leaving space for the rest of it.
+const TARGETS_LINE_BUDGET: usize = 400;
+
+/// Handle `MONITOR + targets`, `MONITOR - targets`, `MONITOR C`, `MONITOR L`
+/// and `MONITOR S`. Changes after `+` arrive as `MonitorStatus` events.
+pub fn handle_monitor(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    msg: &IrcMessage,
+) -> Vec<String> {
+    let Some(subcommand) = msg.params.first() else {
+        return vec![formatter::err_needmoreparams(nick, "MONITOR")];
+    };
+    // Each nick once, compared with the `ascii` casemapping
+    let mut seen = HashSet::new();
+    let targets: Vec<String> = msg
+        .params
+        .get(1)
+        .into_iter()
+        .flat_map(|t| t.split(','))
+        .map(str::trim)
+        .filter(|t| !t.is_empty() && seen.insert(t.to_ascii_lowercase()))
+        .map(String::from)
+        .collect();
+
+    match subcommand.to_ascii_uppercase().as_str() {
+        "+" => {
+            if targets.is_empty() {
+                return vec![formatter::err_needmoreparams(nick, "MONITOR")];
+            }
+            let rejected = engine.monitor_add(session_id, &targets);
+            let added: Vec<String> = targets
+                .into_iter()
+                .filter(|t| !rejected.contains(t))
+                .collect();
+            let mut replies = status_lines(engine, nick, &added);
+            if !rejected.is_empty() {
+                replies.push(formatter::err_monlistfull(nick, MONITOR_LIMIT, &rejected));
+            }
+            replies
+        }
+        "-" => {
+            engine.monitor_remove(session_id, &targets);
+            vec![]
+        }
+        "C" => {
+            engine.monitor_clear(session_id);
+            vec![]
+        }
+        "L" => {
+            let watched = engine.monitor_list(session_id);
+            let mut replies: Vec<String> = line_groups(&watched)
+                .into_iter()
+                .map(|group| formatter::rpl_monlist(nick, group))
+                .collect();
+            replies.push(formatter::rpl_endofmonlist(nick));
+            replies
+        }
+        "S" => status_lines(engine, nick, &engine.monitor_list(session_id)),
+        _ => vec![],
+    }
+}
+
+/// RPL_MONONLINE and RPL_MONOFFLINE lines for the given nicknames.
+fn status_lines(engine: &ChatEngine, nick: &str, targets: &[String]) -> Vec<String> {
+    let (online, offline): (Vec<String>, Vec<String>) = targets
+        .iter()
+        .cloned()
+        .partition(|t| engine.is_nick_online(t));
+
+    let mut replies: Vec<String> = line_groups(&online)
+        .into_iter()
+        .map(|group| formatter::rpl_mononline(nick, group))
+        .collect();
+    replies.extend(
+        line_groups(&offline)
+            .into_iter()
+            .map(|group| formatter::rpl_monoffline(nick, group)),
+    );
+    replies
+}
+
+/// Split targets into groups small enough for one reply line, even once
+/// RPL_MONONLINE expands each into `nick!nick@concord`.
+fn line_groups(targets: &[String]) -> Vec<&[String]> {
+    let mut groups = Vec::new();
+    let (mut start, mut used) = (0, 0);
+    for (i, target) in targets.iter().enumerate() {
+        let cost = target.len() * 2 + formatter::server_name().len() + 3;
+        if i > start && used + cost > TARGETS_LINE_BUDGET {
+            groups.push(&targets[start..i]);
+            (start, used) = (i, 0);
+        }
+        used += cost;
+    }
+    if start < targets.len() {
+        groups.push(&targets[start..]);
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::events::ChatEvent;
+
+    fn monitor(engine: &ChatEngine, session_id: SessionId, line: &str) -> Vec<String> {
+        handle_monitor(engine, session_id, "me", &IrcMessage::parse(line).unwrap())
+    }
+
+    #[test]
+    fn test_line_groups() {
+        assert!(line_groups(&[]).is_empty());
+
+        let short: Vec<String> = (0..5).map(|i| format!("n{i}")).collect();
+        assert_eq!(line_groups(&short).len(), 1);
+
+        let long: Vec<String> = (0..20).map(|i| format!("{i:032}")).collect();
+        let groups = line_groups(&long);
+        assert!(groups.len() > 1);
+        assert_eq!(groups.iter().map(|g| g.len()).sum::<usize>(), 20);
+        for group in groups {
+            assert!(formatter::rpl_mononline("me", group).len() < 512);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_monitor_subcommands() {
+        let engine = ChatEngine::new(None);
+        let (me, _rx) = engine
+            .connect(
+                None,
+                "me".into(),
+                crate::engine::user_session::Protocol::Irc,
+                None,
+            )
+            .unwrap();
+        let (_alice, _rx_alice) = engine
+            .connect(
+                None,
+                "alice".into(),
+                crate::engine::user_session::Protocol::Irc,
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(
+            monitor(&engine, me, "MONITOR + alice,bob"),
+            vec![
+                ":concord 730 me alice!alice@concord".to_string(),
+                ":concord 731 me bob".to_string(),
+            ]
+        );
+        assert_eq!(
+            monitor(&engine, me, "MONITOR L"),
+            vec![
+                ":concord 732 me alice,bob".to_string(),
+                ":concord 733 me :End of MONITOR list".to_string(),
+            ]
+        );
+
+        assert!(monitor(&engine, me, "MONITOR - bob").is_empty());
+        assert_eq!(
+            monitor(&engine, me, "MONITOR S"),
+            vec![":concord 730 me alice!alice@concord".to_string()]
+        );
+
+        assert!(monitor(&engine, me, "MONITOR C").is_empty());
+        assert_eq!(monitor(&engine, me, "MONITOR L").len(), 1);
+        assert!(monitor(&engine, me, "MONITOR +")[0].contains(" 461 "));
+    }
+
+    #[tokio::test]
+    async fn test_monitor_ignores_nick_case() {
+        let engine = ChatEngine::new(None);
+        let (me, mut rx) = engine
+            .connect(
+                None,
+                "me".into(),
+                crate::engine::user_session::Protocol::Irc,
+                None,
+            )
+            .unwrap();
+        assert_eq!(
+            monitor(&engine, me, "MONITOR + Alice,ALICE"),
+            vec![":concord 731 me Alice".to_string()]
+        );
+        assert_eq!(engine.monitor_list(me), ["alice"]);
+
+        let (_alice, _rx_alice) = engine
+            .connect(
+                None,
+                "alice".into(),
+                crate::engine::user_session::Protocol::Irc,
+                None,
+            )
+            .unwrap();
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(ChatEvent::MonitorStatus { online: true, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_monitor_limit() {
+        let engine = ChatEngine::new(None);
+        let (me, _rx) = engine
+            .connect(
+                None,
+                "me".into(),
+                crate::engine::user_session::Protocol::Irc,
+                None,
+            )
+            .unwrap();
+        let many: Vec<String> = (0..MONITOR_LIMIT + 2).map(|i| format!("n{i}")).collect();
+
+        let replies = monitor(&engine, me, &format!("MONITOR + {}", many.join(",")));
+        assert_eq!(
+            replies.last().unwrap(),
+            &format!(
+                ":concord 734 me {MONITOR_LIMIT} n{},n{} :Monitor list is full.",
+                MONITOR_LIMIT,
+                MONITOR_LIMIT + 1
+            )
+        );
+        assert_eq!(engine.monitor_list(me).len(), MONITOR_LIMIT);
+    }
+}
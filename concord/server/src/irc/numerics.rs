@@ -4,6 +4,7 @@ pub const RPL_WELCOME: &str = "001";
 pub const RPL_YOURHOST: &str = "002";
 pub const RPL_CREATED: &str = "003";
 pub const RPL_MYINFO: &str = "004";
+pub const RPL_ISUPPORT: &str = "005";
 
 // Channel operations
 pub const RPL_TOPIC: &str = "332";
@@ -11,17 +12,61 @@ pub const RPL_NOTOPIC: &str = "331";
 pub const RPL_NAMREPLY: &str = "353";
 pub const RPL_ENDOFNAMES: &str = "366";
 
+// Channel modes
+pub const RPL_UMODEIS: &str = "221";
+pub const RPL_CHANNELMODEIS: &str = "324";
+pub const RPL_CREATIONTIME: &str = "329";
+pub const RPL_BANLIST: &str = "367";
+pub const RPL_ENDOFBANLIST: &str = "368";
+pub const RPL_QUIETLIST: &str = "728";
+pub const RPL_ENDOFQUIETLIST: &str = "729";
+
+// Operators
+pub const RPL_WHOISOPERATOR: &str = "313";
+pub const RPL_YOUREOPER: &str = "381";
+pub const RPL_ENDOFSTATS: &str = "219";
+pub const RPL_STATSUPTIME: &str = "242";
+pub const RPL_STATSDEBUG: &str = "249";
+
+// Invites and knocks
+pub const RPL_INVITING: &str = "341";
+pub const RPL_KNOCK: &str = "710";
+pub const RPL_KNOCKDLVR: &str = "711";
+
 // LIST
 pub const RPL_LIST: &str = "322";
 pub const RPL_LISTEND: &str = "323";
 
 // WHO / WHOIS
 pub const RPL_WHOREPLY: &str = "352";
+pub const RPL_WHOSPCRPL: &str = "354";
 pub const RPL_ENDOFWHO: &str = "315";
 pub const RPL_WHOISUSER: &str = "311";
 pub const RPL_WHOISSERVER: &str = "312";
 pub const RPL_ENDOFWHOIS: &str = "318";
 pub const RPL_WHOISCHANNELS: &str = "319";
+pub const RPL_WHOISACCOUNT: &str = "330";
+
+// USERHOST / ISON
+pub const RPL_USERHOST: &str = "302";
+pub const RPL_ISON: &str = "303";
+
+// MONITOR
+pub const RPL_MONONLINE: &str = "730";
+pub const RPL_MONOFFLINE: &str = "731";
+pub const RPL_MONLIST: &str = "732";
+pub const RPL_ENDOFMONLIST: &str = "733";
+pub const ERR_MONLISTFULL: &str = "734";
+
+// AWAY
+pub const RPL_AWAY: &str = "301";
+pub const RPL_UNAWAY: &str = "305";
+pub const RPL_NOWAWAY: &str = "306";
+
+// LUSERS
+pub const RPL_LUSERCLIENT: &str = "251";
+pub const RPL_LUSERCHANNELS: &str = "254";
+pub const RPL_LUSERME: &str = "255";
 
 // MOTD
 pub const RPL_MOTDSTART: &str = "375";
@@ -29,15 +74,38 @@ pub const RPL_MOTD: &str = "372";
 pub const RPL_ENDOFMOTD: &str = "376";
 pub const ERR_NOMOTD: &str = "422";
 
+// SASL
+pub const RPL_LOGGEDIN: &str = "900";
+pub const RPL_SASLSUCCESS: &str = "903";
+pub const ERR_SASLFAIL: &str = "904";
+pub const ERR_SASLTOOLONG: &str = "905";
+pub const ERR_SASLABORTED: &str = "906";
+pub const ERR_SASLALREADY: &str = "907";
+pub const RPL_SASLMECHS: &str = "908";
+
 // Errors
 pub const ERR_NOSUCHNICK: &str = "401";
+pub const ERR_NOSUCHSERVER: &str = "402";
 pub const ERR_NOSUCHCHANNEL: &str = "403";
 pub const ERR_CANNOTSENDTOCHAN: &str = "404";
+pub const ERR_TOOMANYCHANNELS: &str = "405";
+pub const ERR_INVALIDCAPCMD: &str = "410";
 pub const ERR_UNKNOWNCOMMAND: &str = "421";
 pub const ERR_NONICKNAMEGIVEN: &str = "431";
 pub const ERR_NICKNAMEINUSE: &str = "433";
+pub const ERR_USERNOTINCHANNEL: &str = "441";
 pub const ERR_NOTONCHANNEL: &str = "442";
+pub const ERR_USERONCHANNEL: &str = "443";
 pub const ERR_NOTREGISTERED: &str = "451";
 pub const ERR_NEEDMOREPARAMS: &str = "461";
 pub const ERR_ALREADYREGISTERED: &str = "462";
 pub const ERR_PASSWDMISMATCH: &str = "464";
+pub const ERR_UNKNOWNMODE: &str = "472";
+pub const ERR_INVITEONLYCHAN: &str = "473";
+pub const ERR_BADCHANNELKEY: &str = "475";
+pub const ERR_NOPRIVILEGES: &str = "481";
+pub const ERR_CHANOPRIVSNEEDED: &str = "482";
+pub const ERR_NOOPERHOST: &str = "491";
+pub const ERR_TOOMANYKNOCK: &str = "712";
+pub const ERR_CHANOPEN: &str = "713";
+pub const ERR_KNOCKONCHAN: &str = "714";
@@ -0,0 +1,212 @@
+use tracing::warn;
+
+use crate::engine::chat_engine::ChatEngine;
+use crate::engine::events::SessionId;
+
+use super::access::parse_channel;
+use super::connection::validate_irc_pass;
+use super::formatter;
+use super::parser::IrcMessage;
+
+/// Handle `OPER <account> <token>`. System admins become IRC operators by
+/// naming their own account and one of its IRC tokens.
+pub async fn handle_oper(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    msg: &IrcMessage,
+) -> Vec<String> {
+    if msg.params.len() < 2 {
+        return vec![formatter::err_needmoreparams(nick, "OPER")];
+    }
+    let (Some(db), Some(session)) = (engine.db(), engine.get_session(session_id)) else {
+        return vec![formatter::err_nooperhost(nick)];
+    };
+    let Some(user_id) = session.user_id.as_deref() else {
+        return vec![formatter::err_nooperhost(nick)];
+    };
+
+    match validate_irc_pass(db, &msg.params[1], &msg.params[0]).await {
+        Ok(Some(uid)) if uid == user_id => {}
+        Ok(_) => return vec![formatter::err_passwdmismatch(nick)],
+        Err(e) => {
+            warn!(error = %e, "OPER token validation error");
+            return vec![formatter::err_passwdmismatch(nick)];
+        }
+    }
+
+    match engine.oper(session_id).await {
+        Ok(()) => vec![
+            formatter::rpl_youreoper(nick),
+            formatter::mode(nick, nick, "+o", &[]),
+        ],
+        Err(e) => {
+            warn!(error = %e, %nick, "OPER refused");
+            vec![formatter::err_nooperhost(nick)]
+        }
+    }
+}
+
+/// Handle `KILL <nick> [reason]`, disconnecting all of the user's clients.
+pub fn handle_kill(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    msg: &IrcMessage,
+) -> Vec<String> {
+    if !engine.is_oper(session_id) {
+        return vec![formatter::err_noprivileges(nick)];
+    }
+    let Some(target) = msg.params.first() else {
+        return vec![formatter::err_needmoreparams(nick, "KILL")];
+    };
+    let reason = msg.params.get(1).map(String::as_str).unwrap_or("No reason");
+
+    match engine.kill(session_id, target, reason) {
+        Ok(()) => vec![],
+        Err(e) => {
+            warn!(error = %e, %target, "KILL failed");
+            vec![formatter::err_nosuchnick(nick, target)]
+        }
+    }
+}
+
+/// Handle `WALLOPS <text>` (to operators) and `GLOBOPS <text>` (to everyone).
+pub fn handle_wallops(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    msg: &IrcMessage,
+) -> Vec<String> {
+    if !engine.is_oper(session_id) {
+        return vec![formatter::err_noprivileges(nick)];
+    }
+    let Some(text) = msg.params.first().filter(|t| !t.is_empty()) else {
+        return vec![formatter::err_needmoreparams(nick, &msg.command)];
+    };
+
+    let result = if msg.command == "GLOBOPS" {
+        engine.global_notice(session_id, text)
+    } else {
+        engine.wallops(session_id, text)
+    };
+    match result {
+        Ok(()) => vec![],
+        Err(e) => vec![formatter::server_notice(nick, &e)],
+    }
+}
+
+/// Handle `STATS [query]`. `u` reports uptime; anything else the session,
+/// channel and message counters.
+pub fn handle_stats(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    msg: &IrcMessage,
+) -> Vec<String> {
+    if !engine.is_oper(session_id) {
+        return vec![formatter::err_noprivileges(nick)];
+    }
+    let query = msg.params.first().map(String::as_str).unwrap_or("*");
+    let stats = engine.stats();
+
+    let mut replies = if query == "u" {
+        vec![formatter::rpl_statsuptime(nick, stats.uptime_secs)]
+    } else {
+        vec![
+            formatter::rpl_statsdebug(
+                nick,
+                query,
+                &format!(
+                    "Sessions: {} IRC, {} web, {} detached",
+                    stats.irc_sessions, stats.web_sessions, stats.detached_sessions
+                ),
+            ),
+            formatter::rpl_statsdebug(
+                nick,
+                query,
+                &format!(
+                    "Channels: {} across {} servers",
+                    stats.channels, stats.servers
+                ),
+            ),
+            formatter::rpl_statsdebug(
+                nick,
+                query,
+                &format!(
+                    "Messages: {} in the last minute",
+                    stats.messages_last_minute
+                ),
+            ),
+        ]
+    };
+    replies.push(formatter::rpl_endofstats(nick, query));
+    replies
+}
+
+/// Handle `SAJOIN <nick> <channel>` and `SAPART <nick> <channel> [reason]`,
+/// moving another user in or out of a channel.
+pub fn handle_sajoin_sapart(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    msg: &IrcMessage,
+) -> Vec<String> {
+    if !engine.is_oper(session_id) {
+        return vec![formatter::err_noprivileges(nick)];
+    }
+    if msg.params.len() < 2 {
+        return vec![formatter::err_needmoreparams(nick, &msg.command)];
+    }
+    let target = &msg.params[0];
+    let channel = &msg.params[1];
+    if engine.get_session_by_nick(target).is_none() {
+        return vec![formatter::err_nosuchnick(nick, target)];
+    }
+    let Some((server_id, channel_name)) = parse_channel(engine, channel) else {
+        return vec![formatter::err_nosuchchannel(nick, channel)];
+    };
+
+    let result = if msg.command == "SAJOIN" {
+        engine.sajoin(session_id, target, &server_id, &channel_name)
+    } else {
+        let reason = msg.params.get(2).cloned();
+        engine.sapart(session_id, target, &server_id, &channel_name, reason)
+    };
+    match result {
+        Ok(()) => vec![],
+        Err(e) => {
+            warn!(error = %e, %target, %channel, command = %msg.command, "forced join/part failed");
+            vec![formatter::server_notice(nick, &e)]
+        }
+    }
+}
+
+/// Handle `DELSERVER <server-name>`, deleting a whole server.
+pub async fn handle_delserver(
+    engine: &ChatEngine,
+    session_id: SessionId,
+    nick: &str,
+    msg: &IrcMessage,
+) -> Vec<String> {
+    if !engine.is_oper(session_id) {
+        return vec![formatter::err_noprivileges(nick)];
+    }
+    let Some(name) = msg.params.first() else {
+        return vec![formatter::err_needmoreparams(nick, "DELSERVER")];
+    };
+    let Some(server_id) = engine.find_server_by_name(name) else {
+        return vec![formatter::err_nosuchserver(nick, name)];
+    };
+
+    match engine.delete_server(&server_id).await {
+        Ok(()) => vec![formatter::server_notice(
+            nick,
+            &format!("Server \"{name}\" deleted"),
+        )],
+        Err(e) => {
+            warn!(error = %e, %name, "DELSERVER failed");
+            vec![formatter::server_notice(nick, &e)]
+        }
+    }
+}
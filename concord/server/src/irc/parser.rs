@@ -1,13 +1,16 @@
-/// An IRC protocol message per RFC 2812.
+/// An IRC protocol message per RFC 2812, with IRCv3 message tags.
 ///
-/// Wire format: `[:prefix] COMMAND [params...] [:trailing]\r\n`
+/// Wire format: `[@tags] [:prefix] COMMAND [params...] [:trailing]\r\n`
 ///
 /// Examples:
 ///   `:nick!user@host PRIVMSG #channel :Hello world\r\n`
+///   `@time=2025-01-01T12:00:00.000Z :nick!user@host JOIN #general\r\n`
 ///   `NICK alice\r\n`
 ///   `JOIN #general\r\n`
 #[derive(Debug, Clone, PartialEq)]
 pub struct IrcMessage {
+    /// IRCv3 message tags as (key, unescaped value). Valueless tags have an empty value.
+    pub tags: Vec<(String, String)>,
     pub prefix: Option<String>,
     pub command: String,
     pub params: Vec<String>,
@@ -24,6 +27,18 @@ impl IrcMessage {
 
         let mut remaining = line;
         let mut prefix = None;
+        let mut tags = Vec::new();
+
+        // Parse optional IRCv3 tags
+        if let Some(rest) = remaining.strip_prefix('@') {
+            match rest.find(' ') {
+                Some(idx) => {
+                    tags = parse_tags(&rest[..idx]);
+                    remaining = rest[idx..].trim_start();
+                }
+                None => return Err(ParseError::MissingCommand),
+            }
+        }
 
         // Parse optional prefix
         if remaining.starts_with(':') {
@@ -76,6 +91,7 @@ impl IrcMessage {
         }
 
         Ok(IrcMessage {
+            tags,
             prefix,
             command,
             params,
@@ -86,6 +102,12 @@ impl IrcMessage {
     pub fn format(&self) -> String {
         let mut out = String::with_capacity(512);
 
+        if !self.tags.is_empty() {
+            out.push('@');
+            out.push_str(&format_tags(&self.tags));
+            out.push(' ');
+        }
+
         if let Some(ref prefix) = self.prefix {
             out.push(':');
             out.push_str(prefix);
@@ -111,11 +133,83 @@ impl IrcMessage {
     /// Create a server reply with the given prefix.
     pub fn server_reply(server_name: &str, command: &str, params: Vec<String>) -> Self {
         IrcMessage {
+            tags: Vec::new(),
             prefix: Some(server_name.to_string()),
             command: command.to_string(),
             params,
         }
     }
+
+    /// Look up a tag value by key. Valueless tags return `Some("")`.
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Parse the tag section (without the leading `@`) into (key, value) pairs.
+fn parse_tags(raw: &str) -> Vec<(String, String)> {
+    raw.split(';')
+        .filter(|t| !t.is_empty())
+        .map(|t| match t.split_once('=') {
+            Some((k, v)) => (k.to_string(), unescape_tag_value(v)),
+            None => (t.to_string(), String::new()),
+        })
+        .collect()
+}
+
+/// Serialize tags to wire form (without the leading `@`).
+pub fn format_tags(tags: &[(String, String)]) -> String {
+    tags.iter()
+        .map(|(k, v)| {
+            if v.is_empty() {
+                k.clone()
+            } else {
+                format!("{}={}", k, escape_tag_value(v))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+/// Escape a tag value: `;` `space` `\` CR LF become `\:` `\s` `\\` `\r` `\n`.
+fn escape_tag_value(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            ';' => out.push_str("\\:"),
+            ' ' => out.push_str("\\s"),
+            '\\' => out.push_str("\\\\"),
+            '\r' => out.push_str("\\r"),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Reverse of `escape_tag_value`. Unknown escapes drop the backslash and a
+/// trailing lone backslash is discarded, per the message-tags spec.
+fn unescape_tag_value(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(':') => out.push(';'),
+            Some('s') => out.push(' '),
+            Some('r') => out.push('\r'),
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out
 }
 
 #[derive(Debug, PartialEq)]
@@ -212,6 +306,7 @@ mod tests {
     #[test]
     fn test_format_simple() {
         let msg = IrcMessage {
+            tags: Vec::new(),
             prefix: None,
             command: "NICK".into(),
             params: vec!["alice".into()],
@@ -222,6 +317,7 @@ mod tests {
     #[test]
     fn test_format_with_prefix_and_trailing() {
         let msg = IrcMessage {
+            tags: Vec::new(),
             prefix: Some("server".into()),
             command: "PRIVMSG".into(),
             params: vec!["#general".into(), "Hello world".into()],
@@ -232,6 +328,7 @@ mod tests {
     #[test]
     fn test_format_numeric() {
         let msg = IrcMessage {
+            tags: Vec::new(),
             prefix: Some("concord".into()),
             command: "001".into(),
             params: vec!["alice".into(), "Welcome to Concord!".into()],
@@ -260,6 +357,59 @@ mod tests {
         assert_eq!(msg.params, vec!["#channel", "+o", "alice"]);
     }
 
+    // ── IRCv3 message tags ──
+
+    #[test]
+    fn test_parse_tags() {
+        let msg =
+            IrcMessage::parse("@time=2025-01-01T00:00:00.000Z;+typing=active :alice PRIVMSG #a :hi")
+                .unwrap();
+        assert_eq!(msg.tag("time"), Some("2025-01-01T00:00:00.000Z"));
+        assert_eq!(msg.tag("+typing"), Some("active"));
+        assert_eq!(msg.prefix, Some("alice".into()));
+        assert_eq!(msg.command, "PRIVMSG");
+        assert_eq!(msg.params, vec!["#a", "hi"]);
+    }
+
+    #[test]
+    fn test_parse_valueless_tag() {
+        let msg = IrcMessage::parse("@+draft/flag;msgid=abc TAGMSG #a").unwrap();
+        assert_eq!(msg.tag("+draft/flag"), Some(""));
+        assert_eq!(msg.tag("msgid"), Some("abc"));
+        assert_eq!(msg.tag("missing"), None);
+    }
+
+    #[test]
+    fn test_parse_tag_unescaping() {
+        let msg = IrcMessage::parse("@k=a\\:b\\sc\\\\d\\xe\\ PING x").unwrap();
+        assert_eq!(msg.tag("k"), Some("a;b c\\dxe"));
+    }
+
+    #[test]
+    fn test_parse_tags_without_command() {
+        assert_eq!(IrcMessage::parse("@k=v"), Err(ParseError::MissingCommand));
+    }
+
+    #[test]
+    fn test_format_tags_roundtrip() {
+        let msg = IrcMessage {
+            tags: vec![
+                ("time".into(), "2025-01-01T00:00:00.000Z".into()),
+                ("+note".into(), "a; b".into()),
+                ("+flag".into(), String::new()),
+            ],
+            prefix: Some("alice".into()),
+            command: "PRIVMSG".into(),
+            params: vec!["#a".into(), "hello there".into()],
+        };
+        let wire = msg.format();
+        assert_eq!(
+            wire,
+            "@time=2025-01-01T00:00:00.000Z;+note=a\\:\\sb;+flag :alice PRIVMSG #a :hello there"
+        );
+        assert_eq!(IrcMessage::parse(&wire).unwrap(), msg);
+    }
+
     // ── Additional IRC command type tests ──
 
     #[test]
@@ -532,6 +682,7 @@ mod tests {
     #[test]
     fn test_format_empty_last_param_gets_colon() {
         let msg = IrcMessage {
+            tags: Vec::new(),
             prefix: None,
             command: "PRIVMSG".into(),
             params: vec!["#test".into(), "".into()],
@@ -542,6 +693,7 @@ mod tests {
     #[test]
     fn test_format_no_params() {
         let msg = IrcMessage {
+            tags: Vec::new(),
             prefix: None,
             command: "QUIT".into(),
             params: vec![],
@@ -552,6 +704,7 @@ mod tests {
     #[test]
     fn test_format_single_param_no_spaces() {
         let msg = IrcMessage {
+            tags: Vec::new(),
             prefix: None,
             command: "NICK".into(),
             params: vec!["alice".into()],
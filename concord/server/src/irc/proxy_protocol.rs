@@ -0,0 +1,221 @@
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Signature opening a PROXY protocol v2 header.
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+/// Longest PROXY protocol v1 header, CRLF included.
+const V1_MAX_LENGTH: usize = 107;
+
+/// Read the HAProxy PROXY protocol header (v1 or v2) a proxy sends ahead of
+/// the client's own bytes, and return the client address it carries. None
+/// when the proxy is connecting on its own behalf (v1 `UNKNOWN`, v2 `LOCAL`)
+/// or the address isn't TCP over IPv4 or IPv6. Only the header is consumed.
+pub async fn read_proxy_header<R: AsyncRead + Unpin>(
+    stream: &mut R,
+) -> io::Result<Option<SocketAddr>> {
+    // Every header is at least 12 bytes long, so this never reads past one
+    let mut start = [0u8; 12];
+    stream.read_exact(&mut start).await?;
+    if start == V2_SIGNATURE {
+        read_v2(stream).await
+    } else if start.starts_with(b"PROXY ") {
+        read_v1(stream, &start).await
+    } else {
+        Err(invalid("missing PROXY protocol header"))
+    }
+}
+
+/// The rest of a v1 header, a text line such as
+/// `PROXY TCP4 203.0.113.7 10.0.0.1 51234 6667`.
+async fn read_v1<R: AsyncRead + Unpin>(
+    stream: &mut R,
+    start: &[u8],
+) -> io::Result<Option<SocketAddr>> {
+    let mut line = start.to_vec();
+    while !line.ends_with(b"\r\n") {
+        if line.len() >= V1_MAX_LENGTH {
+            return Err(invalid("PROXY v1 header too long"));
+        }
+        line.push(stream.read_u8().await?);
+    }
+    let line = std::str::from_utf8(&line[..line.len() - 2])
+        .map_err(|_| invalid("PROXY v1 header is not ASCII"))?;
+    parse_v1(line)
+}
+
+fn parse_v1(line: &str) -> io::Result<Option<SocketAddr>> {
+    let fields: Vec<&str> = line.split(' ').collect();
+    match fields.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => Ok(None),
+        [
+            "PROXY",
+            family @ ("TCP4" | "TCP6"),
+            source,
+            _dest,
+            source_port,
+            _dest_port,
+        ] => {
+            let ip: IpAddr = source
+                .parse()
+                .map_err(|_| invalid("bad PROXY v1 source address"))?;
+            if ip.is_ipv4() != (*family == "TCP4") {
+                return Err(invalid("PROXY v1 address doesn't match its family"));
+            }
+            let port: u16 = source_port
+                .parse()
+                .map_err(|_| invalid("bad PROXY v1 source port"))?;
+            Ok(Some(SocketAddr::new(ip, port)))
+        }
+        _ => Err(invalid("malformed PROXY v1 header")),
+    }
+}
+
+/// The rest of a v2 header: version and command, address family, then the
+/// length of the addresses (and any TLVs, which are skipped).
+async fn read_v2<R: AsyncRead + Unpin>(stream: &mut R) -> io::Result<Option<SocketAddr>> {
+    let mut head = [0u8; 4];
+    stream.read_exact(&mut head).await?;
+    let [version_command, family, len_hi, len_lo] = head;
+    if version_command >> 4 != 2 {
+        return Err(invalid("unsupported PROXY protocol version"));
+    }
+    let mut body = vec![0u8; u16::from_be_bytes([len_hi, len_lo]) as usize];
+    stream.read_exact(&mut body).await?;
+    parse_v2(version_command & 0x0f, family, &body)
+}
+
+fn parse_v2(command: u8, family: u8, body: &[u8]) -> io::Result<Option<SocketAddr>> {
+    match command {
+        0x0 => return Ok(None), // LOCAL: health checks and the like
+        0x1 => {}
+        _ => return Err(invalid("unknown PROXY v2 command")),
+    }
+    let short = || invalid("PROXY v2 addresses cut short");
+    match family >> 4 {
+        // IPv4: source and destination addresses, then their ports
+        0x1 => {
+            let b = body.get(..12).ok_or_else(short)?;
+            let ip = Ipv4Addr::new(b[0], b[1], b[2], b[3]);
+            Ok(Some(SocketAddr::new(
+                ip.into(),
+                u16::from_be_bytes([b[8], b[9]]),
+            )))
+        }
+        // IPv6, laid out the same way
+        0x2 => {
+            let b = body.get(..36).ok_or_else(short)?;
+            let octets: [u8; 16] = b[..16].try_into().map_err(|_| short())?;
+            let ip = Ipv6Addr::from(octets);
+            Ok(Some(SocketAddr::new(
+                ip.into(),
+                u16::from_be_bytes([b[32], b[33]]),
+            )))
+        }
+        // Unspecified or a Unix socket: no IP address to use
+        _ => Ok(None),
+    }
+}
+
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn read(mut bytes: &[u8]) -> (io::Result<Option<SocketAddr>>, Vec<u8>) {
+        let result = read_proxy_header(&mut bytes).await;
+        (result, bytes.to_vec())
+    }
+
+    fn v2_header(command: u8, family: u8, addresses: &[u8]) -> Vec<u8> {
+        let mut header = V2_SIGNATURE.to_vec();
+        header.push(0x20 | command);
+        header.push(family);
+        header.extend((addresses.len() as u16).to_be_bytes());
+        header.extend(addresses);
+        header
+    }
+
+    #[tokio::test]
+    async fn test_v1_header() {
+        let (addr, rest) =
+            read(b"PROXY TCP4 203.0.113.7 10.0.0.1 51234 6667\r\nNICK alice\r\n").await;
+        assert_eq!(addr.unwrap(), Some("203.0.113.7:51234".parse().unwrap()));
+        // The client's own lines are left to read
+        assert_eq!(rest, b"NICK alice\r\n");
+
+        let (addr, _) = read(b"PROXY TCP6 2001:db8::7 2001:db8::1 51234 6667\r\n").await;
+        assert_eq!(addr.unwrap(), Some("[2001:db8::7]:51234".parse().unwrap()));
+
+        let (addr, _) = read(b"PROXY UNKNOWN\r\n").await;
+        assert_eq!(addr.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_v1_header_rejected() {
+        assert!(
+            read(b"PROXY TCP4 nonsense 10.0.0.1 1 2\r\n")
+                .await
+                .0
+                .is_err()
+        );
+        assert!(
+            read(b"PROXY TCP4 2001:db8::7 10.0.0.1 1 2\r\n")
+                .await
+                .0
+                .is_err()
+        );
+        assert!(read(b"PROXY TCP4 203.0.113.7\r\n").await.0.is_err());
+        assert!(
+            read(&[b"PROXY ".as_slice(), &[b'x'; 200]].concat())
+                .await
+                .0
+                .is_err()
+        );
+        assert!(
+            read(b"NICK alice\r\nUSER alice 0 * :A\r\n")
+                .await
+                .0
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_v2_header() {
+        let mut ipv4 = vec![203, 0, 113, 7, 10, 0, 0, 1];
+        ipv4.extend(51234u16.to_be_bytes());
+        ipv4.extend(6667u16.to_be_bytes());
+        let mut bytes = v2_header(0x1, 0x11, &ipv4);
+        bytes.extend(b"NICK alice\r\n");
+        let (addr, rest) = read(&bytes).await;
+        assert_eq!(addr.unwrap(), Some("203.0.113.7:51234".parse().unwrap()));
+        assert_eq!(rest, b"NICK alice\r\n");
+
+        let source: Ipv6Addr = "2001:db8::7".parse().unwrap();
+        let mut ipv6 = source.octets().to_vec();
+        ipv6.extend([0; 16]);
+        ipv6.extend(51234u16.to_be_bytes());
+        ipv6.extend(6667u16.to_be_bytes());
+        // A trailing TLV is skipped along with the addresses
+        ipv6.extend([0x04, 0x00, 0x01, 0xff]);
+        let (addr, rest) = read(&v2_header(0x1, 0x21, &ipv6)).await;
+        assert_eq!(addr.unwrap(), Some("[2001:db8::7]:51234".parse().unwrap()));
+        assert!(rest.is_empty());
+
+        let (addr, _) = read(&v2_header(0x0, 0x00, &[])).await;
+        assert_eq!(addr.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_v2_header_rejected() {
+        assert!(read(&v2_header(0x1, 0x11, &[203, 0, 113])).await.0.is_err());
+        assert!(read(&v2_header(0x2, 0x11, &[0; 12])).await.0.is_err());
+        let mut wrong_version = v2_header(0x1, 0x11, &[0; 12]);
+        wrong_version[12] = 0x11;
+        assert!(read(&wrong_version).await.0.is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 use tokio_util::sync::CancellationToken;
@@ -7,9 +8,12 @@ use tracing_subscriber::EnvFilter;
 use concord_server::config::ServerConfig;
 use concord_server::db::pool::{create_pool, run_migrations};
 use concord_server::engine::chat_engine::ChatEngine;
-use concord_server::irc::listener::start_irc_listener;
+use concord_server::irc::formatter;
+use concord_server::irc::limits::ConnectionLimiter;
+use concord_server::irc::listener::{OptionalClientCertVerifier, start_irc_listener};
 use concord_server::web::app_state::AppState;
 use concord_server::web::atproto::AtprotoOAuth;
+use concord_server::web::rate_limit::TrustedProxies;
 use concord_server::web::router::build_router;
 
 #[tokio::main]
@@ -70,8 +74,15 @@ async fn main() {
         }
     }
 
+    // How IRC clients see this server
+    formatter::set_identity(&config.irc.server_name, &config.irc.network_name);
+
     // Create the shared chat engine with database
-    let engine = Arc::new(ChatEngine::new(Some(pool.clone())));
+    let engine = Arc::new(
+        ChatEngine::new(Some(pool.clone()))
+            .with_motd(config.server.motd.clone())
+            .with_irc_channel_limit(config.irc.max_channels_per_user),
+    );
 
     // Load persisted servers and channels into memory
     engine
@@ -106,13 +117,33 @@ async fn main() {
         _ => None,
     };
 
-    // Start IRC listener
+    let trusted_proxies = Arc::new(
+        TrustedProxies::parse(&config.server.trusted_proxies)
+            .unwrap_or_else(|e| panic!("Failed to load trusted_proxies: {e}")),
+    );
+
+    // Start IRC listener. Its per-IP connection limit also covers IRC over WebSocket.
+    let irc_config = Arc::new(config.irc.clone());
+    let irc_limiter = ConnectionLimiter::new(config.irc.max_connections_per_ip);
     let irc_engine = engine.clone();
     let irc_pool = pool.clone();
     let irc_addr = config.server.irc_address.clone();
     let irc_cancel = cancel.clone();
+    let listener_config = irc_config.clone();
+    let listener_limiter = irc_limiter.clone();
+    let listener_proxies = trusted_proxies.clone();
     tokio::spawn(async move {
-        start_irc_listener(&irc_addr, irc_engine, irc_pool, irc_cancel, irc_tls_acceptor).await;
+        start_irc_listener(
+            &irc_addr,
+            irc_engine,
+            irc_pool,
+            listener_config,
+            listener_limiter,
+            listener_proxies,
+            irc_cancel,
+            irc_tls_acceptor,
+        )
+        .await;
     });
 
     let max_file_size = config.storage.max_file_size_mb * 1024 * 1024;
@@ -126,6 +157,9 @@ async fn main() {
         auth_config,
         atproto,
         max_file_size,
+        irc: irc_config,
+        irc_limiter,
+        trusted_proxies,
     });
 
     let app = build_router(app_state);
@@ -141,7 +175,11 @@ async fn main() {
 
     // Serve with graceful shutdown on Ctrl+C
     let shutdown_cancel = cancel.clone();
-    axum::serve(listener, app)
+    // Peer addresses let the rate limiters tell trusted proxies from clients
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
         .with_graceful_shutdown(async move {
             tokio::signal::ctrl_c()
                 .await
@@ -176,8 +214,13 @@ fn load_irc_tls_config(
     let key = private_key(&mut BufReader::new(key_file))?
         .ok_or("No private key found in key file")?;
 
-    let config = ServerConfig::builder()
-        .with_no_client_auth()
+    // Request (but don't require) client certificates for SASL EXTERNAL
+    let builder = ServerConfig::builder();
+    let verifier = Arc::new(OptionalClientCertVerifier::new(
+        builder.crypto_provider().clone(),
+    ));
+    let config = builder
+        .with_client_cert_verifier(verifier)
         .with_single_cert(certs, key)?;
 
     Ok(tokio_rustls::TlsAcceptor::from(Arc::new(config)))
@@ -3,9 +3,12 @@ use std::sync::Arc;
 use sqlx::SqlitePool;
 
 use crate::auth::config::AuthConfig;
+use crate::config::IrcSection;
 use crate::engine::chat_engine::ChatEngine;
+use crate::irc::limits::ConnectionLimiter;
 
 use super::atproto::AtprotoOAuth;
+use super::rate_limit::TrustedProxies;
 
 /// Shared application state available to all HTTP/WebSocket handlers.
 pub struct AppState {
@@ -14,4 +17,10 @@ pub struct AppState {
     pub auth_config: AuthConfig,
     pub atproto: AtprotoOAuth,
     pub max_file_size: u64,
+    /// IRC settings, for IRC-over-WebSocket connections.
+    pub irc: Arc<IrcSection>,
+    /// Per-IP IRC connection counts, shared with the TCP listener.
+    pub irc_limiter: ConnectionLimiter,
+    /// Reverse proxies whose forwarding headers give the client's address.
+    pub trusted_proxies: Arc<TrustedProxies>,
 }
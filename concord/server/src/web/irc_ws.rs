@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+use axum::extract::ws::Message;
+use axum::extract::{State, WebSocketUpgrade};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use futures_util::{SinkExt, StreamExt, future, stream};
+
+use crate::irc::connection::handle_connection;
+
+use super::app_state::AppState;
+use super::rate_limit::ClientIp;
+
+/// IRCv3 WebSocket subprotocol carrying each line as a text message.
+pub const TEXT_PROTOCOL: &str = "text.ircv3.net";
+/// IRCv3 WebSocket subprotocol carrying each line as a binary message.
+pub const BINARY_PROTOCOL: &str = "binary.ircv3.net";
+
+/// GET /irc — IRC over WebSocket for browser clients such as Kiwi IRC and
+/// gamja. Each WebSocket message is one IRC line, and the connection runs the
+/// same state machine as the TCP listener. Clients that don't ask for a
+/// subprotocol are spoken to in text messages. These connections count towards
+/// the same per-IP limit as the TCP listener's.
+pub async fn irc_ws_upgrade(
+    State(state): State<Arc<AppState>>,
+    ClientIp(ip): ClientIp,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let Some(guard) = state.irc_limiter.acquire(&ip) else {
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            "Too many IRC connections from your IP.",
+        )
+            .into_response();
+    };
+    let ws = ws
+        .protocols([TEXT_PROTOCOL, BINARY_PROTOCOL])
+        .max_message_size(state.irc.max_line_length);
+    let binary = ws
+        .selected_protocol()
+        .is_some_and(|p| p.as_bytes() == BINARY_PROTOCOL.as_bytes());
+    let peer = format!("{ip} (websocket)");
+    let engine = state.engine.clone();
+    let db = state.db.clone();
+    let config = state.irc.clone();
+
+    ws.on_upgrade(move |socket| async move {
+        let _guard = guard;
+        let (sender, receiver) = socket.split();
+        let lines = receiver
+            .take_while(|msg| future::ready(!matches!(msg, Ok(Message::Close(_)))))
+            .flat_map(|msg| stream::iter(message_lines(msg)));
+        let sink = sender.with(move |line: String| {
+            future::ready(Ok::<_, axum::Error>(line_message(line, binary)))
+        });
+
+        // Browsers can't present client certificates, so there's no SASL EXTERNAL here.
+        handle_connection(lines, sink, peer, engine, db, config, None).await;
+    })
+    .into_response()
+}
+
+/// Inbound IRC lines in a WebSocket message. Clients should send one line per
+/// message without a terminator, but a stray CRLF or several lines are accepted.
+fn message_lines(msg: Result<Message, axum::Error>) -> Vec<std::io::Result<String>> {
+    let text = match msg {
+        Ok(Message::Text(text)) => text.to_string(),
+        Ok(Message::Binary(data)) => String::from_utf8_lossy(&data).into_owned(),
+        Ok(_) => return vec![],
+        Err(e) => return vec![Err(std::io::Error::other(e))],
+    };
+    text.lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| Ok(line.to_string()))
+        .collect()
+}
+
+/// An outbound IRC line as a WebSocket message of the negotiated kind.
+fn line_message(line: String, binary: bool) -> Message {
+    if binary {
+        Message::Binary(line.into())
+    } else {
+        Message::Text(line.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(msg: Message) -> Vec<String> {
+        message_lines(Ok(msg))
+            .into_iter()
+            .map(|line| line.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_message_lines() {
+        assert_eq!(
+            lines(Message::Text("NICK alice".into())),
+            vec!["NICK alice"]
+        );
+        assert_eq!(
+            lines(Message::Text("NICK alice\r\nUSER a 0 * :A\r\n".into())),
+            vec!["NICK alice", "USER a 0 * :A"]
+        );
+        assert_eq!(
+            lines(Message::Binary(
+                b"PRIVMSG #a :caf\xc3\xa9\r\n".to_vec().into()
+            )),
+            vec!["PRIVMSG #a :café"]
+        );
+        assert!(lines(Message::Ping(Vec::new().into())).is_empty());
+        assert!(lines(Message::Text("\r\n".into())).is_empty());
+    }
+
+    #[test]
+    fn test_line_message_matches_subprotocol() {
+        assert_eq!(
+            line_message("PING :x".into(), false),
+            Message::Text("PING :x".into())
+        );
+        assert_eq!(
+            line_message("PING :x".into(), true),
+            Message::Binary(b"PING :x".to_vec().into())
+        );
+    }
+}
@@ -1,6 +1,7 @@
 pub mod app_state;
 pub mod atproto;
 pub mod auth_middleware;
+pub mod irc_ws;
 pub mod oauth;
 pub mod pds_client;
 pub mod rate_limit;
@@ -1,8 +1,11 @@
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 
 use axum::body::Body;
-use axum::extract::Request;
-use axum::http::StatusCode;
+use axum::extract::{ConnectInfo, FromRequestParts, Request};
+use axum::http::request::Parts;
+use axum::http::{Extensions, HeaderMap, StatusCode};
 use axum::middleware::Next;
 use axum::response::{IntoResponse, Response};
 
@@ -31,32 +34,170 @@ impl Default for ApiRateLimiters {
     }
 }
 
-/// Extract client IP from request headers or connection info.
-fn client_ip(req: &Request<Body>) -> String {
-    // Check X-Forwarded-For first (for reverse proxies / ngrok)
-    if let Some(forwarded) = req.headers().get("x-forwarded-for")
-        && let Ok(val) = forwarded.to_str()
-        && let Some(first) = val.split(',').next()
-    {
-        return first.trim().to_string();
+/// Reverse proxies trusted to report the client's address, as IP addresses
+/// and CIDR ranges (`[server] trusted_proxies`).
+#[derive(Clone, Debug, Default)]
+pub struct TrustedProxies {
+    ranges: Vec<(IpAddr, u8)>,
+}
+
+impl TrustedProxies {
+    /// Parse bare addresses (`127.0.0.1`) and ranges (`10.0.0.0/8`, `fd00::/8`).
+    pub fn parse(entries: &[String]) -> Result<Self, String> {
+        let ranges = entries
+            .iter()
+            .map(|entry| {
+                let (addr, prefix) = match entry.split_once('/') {
+                    Some((addr, prefix)) => (addr, Some(prefix)),
+                    None => (entry.as_str(), None),
+                };
+                let addr: IpAddr = addr
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("Invalid trusted proxy address: {entry}"))?;
+                let bits = if addr.is_ipv4() { 32 } else { 128 };
+                let prefix = match prefix {
+                    Some(p) => p
+                        .trim()
+                        .parse()
+                        .ok()
+                        .filter(|p| *p <= bits)
+                        .ok_or_else(|| format!("Invalid trusted proxy prefix: {entry}"))?,
+                    None => bits,
+                };
+                Ok((addr, prefix))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self { ranges })
+    }
+
+    /// Whether `ip` is one of the trusted proxies.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.ranges.iter().any(|&(net, prefix)| match (ip, net) {
+            (IpAddr::V4(ip), IpAddr::V4(net)) => {
+                same_prefix(u32::from(ip).into(), u32::from(net).into(), prefix, 32)
+            }
+            (IpAddr::V6(ip), IpAddr::V6(net)) => same_prefix(ip.into(), net.into(), prefix, 128),
+            _ => false,
+        })
+    }
+}
+
+/// Whether two `bits`-wide addresses share their first `prefix` bits.
+fn same_prefix(a: u128, b: u128, prefix: u8, bits: u32) -> bool {
+    let shift = bits - u32::from(prefix);
+    shift == 128 || a >> shift == b >> shift
+}
+
+/// The client's address for a request from `peer`. A trusted proxy's
+/// forwarding headers (`Forwarded`, else `X-Forwarded-For`, else `X-Real-IP`)
+/// are walked back from the nearest hop to the first address that isn't
+/// itself a trusted proxy. Anyone else's are ignored, as they're trivially
+/// forged, and the peer is the client.
+pub fn client_ip(headers: &HeaderMap, peer: Option<IpAddr>, trusted: &TrustedProxies) -> String {
+    // Without a peer address, all such requests share one limit
+    let Some(peer) = peer else {
+        return "unknown".to_string();
+    };
+    let mut client = peer.to_canonical();
+    if trusted.contains(client) {
+        for hop in forwarded_chain(headers).into_iter().rev() {
+            // Past an obfuscated or unknown hop, nothing can be believed
+            let Some(hop) = hop else { break };
+            client = hop.to_canonical();
+            if !trusted.contains(client) {
+                break;
+            }
+        }
+    }
+    client.to_string()
+}
+
+/// The client's address for a request, from the peer address axum recorded
+/// (see `into_make_service_with_connect_info`) and the router's trusted proxies.
+fn request_client_ip(headers: &HeaderMap, extensions: &Extensions) -> String {
+    let peer = extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    match extensions.get::<Arc<TrustedProxies>>() {
+        Some(trusted) => client_ip(headers, peer, trusted),
+        None => client_ip(headers, peer, &TrustedProxies::default()),
     }
+}
+
+/// Extractor for the requesting client's address, as [`client_ip`] finds it.
+pub struct ClientIp(pub String);
 
-    // Check X-Real-IP
-    if let Some(real_ip) = req.headers().get("x-real-ip")
-        && let Ok(val) = real_ip.to_str()
-    {
-        return val.trim().to_string();
+impl<S: Send + Sync> FromRequestParts<S> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(ClientIp(request_client_ip(
+            &parts.headers,
+            &parts.extensions,
+        )))
     }
+}
 
-    // Fallback: use a generic key (all connections share the limit)
-    "unknown".to_string()
+/// Addresses in the forwarding headers, client first. Entries that aren't an
+/// IP address (`unknown`, obfuscated identifiers) are None.
+fn forwarded_chain(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    let forwarded = header_list(headers, "forwarded");
+    if !forwarded.is_empty() {
+        return forwarded
+            .into_iter()
+            .map(|element| {
+                element
+                    .split(';')
+                    .find_map(|pair| {
+                        let (key, value) = pair.split_once('=')?;
+                        key.trim().eq_ignore_ascii_case("for").then_some(value)
+                    })
+                    .and_then(parse_node)
+            })
+            .collect();
+    }
+    let forwarded_for = header_list(headers, "x-forwarded-for");
+    if !forwarded_for.is_empty() {
+        return forwarded_for.into_iter().map(parse_node).collect();
+    }
+    header_list(headers, "x-real-ip")
+        .into_iter()
+        .map(parse_node)
+        .collect()
+}
+
+/// The comma-separated entries of every instance of a header, in order.
+fn header_list<'a>(headers: &'a HeaderMap, name: &str) -> Vec<&'a str> {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .collect()
+}
+
+/// An address from a forwarding header: `192.0.2.1`, `192.0.2.1:4711`,
+/// `2001:db8::1` or `"[2001:db8::1]:4711"`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Ok(ip) = node.parse() {
+        return Some(ip);
+    }
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split(']').next()?.parse().ok();
+    }
+    node.parse::<SocketAddr>().ok().map(|addr| addr.ip())
 }
 
 /// Middleware for auth endpoint rate limiting.
 pub async fn auth_rate_limit(req: Request<Body>, next: Next) -> Response {
     let limiters = req.extensions().get::<Arc<ApiRateLimiters>>();
     if let Some(limiters) = limiters {
-        let ip = client_ip(&req);
+        let ip = request_client_ip(req.headers(), req.extensions());
         if !limiters.auth.check(&ip) {
             return (
                 StatusCode::TOO_MANY_REQUESTS,
@@ -72,7 +213,7 @@ pub async fn auth_rate_limit(req: Request<Body>, next: Next) -> Response {
 pub async fn api_rate_limit(req: Request<Body>, next: Next) -> Response {
     let limiters = req.extensions().get::<Arc<ApiRateLimiters>>();
     if let Some(limiters) = limiters {
-        let ip = client_ip(&req);
+        let ip = request_client_ip(req.headers(), req.extensions());
         if !limiters.api.check(&ip) {
             return (
                 StatusCode::TOO_MANY_REQUESTS,
@@ -88,7 +229,7 @@ pub async fn api_rate_limit(req: Request<Body>, next: Next) -> Response {
 pub async fn ws_rate_limit(req: Request<Body>, next: Next) -> Response {
     let limiters = req.extensions().get::<Arc<ApiRateLimiters>>();
     if let Some(limiters) = limiters {
-        let ip = client_ip(&req);
+        let ip = request_client_ip(req.headers(), req.extensions());
         if !limiters.ws.check(&ip) {
             return (
                 StatusCode::TOO_MANY_REQUESTS,
@@ -99,3 +240,100 @@ pub async fn ws_rate_limit(req: Request<Body>, next: Next) -> Response {
     }
     next.run(req).await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trusted(entries: &[&str]) -> TrustedProxies {
+        let entries: Vec<String> = entries.iter().map(|e| e.to_string()).collect();
+        TrustedProxies::parse(&entries).unwrap()
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    fn ip(addr: &str) -> Option<IpAddr> {
+        Some(addr.parse().unwrap())
+    }
+
+    #[test]
+    fn test_trusted_proxies() {
+        let proxies = trusted(&["127.0.0.1", "10.0.0.0/8", "fd00::/8"]);
+        assert!(proxies.contains("127.0.0.1".parse().unwrap()));
+        assert!(!proxies.contains("127.0.0.2".parse().unwrap()));
+        assert!(proxies.contains("10.200.3.4".parse().unwrap()));
+        assert!(proxies.contains("fd12::1".parse().unwrap()));
+        assert!(!proxies.contains("2001:db8::1".parse().unwrap()));
+        // IPv4-mapped IPv6 addresses match their IPv4 ranges
+        assert!(proxies.contains("::ffff:10.0.0.1".parse().unwrap()));
+        assert!(trusted(&["0.0.0.0/0"]).contains("203.0.113.7".parse().unwrap()));
+        assert!(trusted(&["::/0"]).contains("2001:db8::1".parse().unwrap()));
+
+        for bad in ["proxy.local", "10.0.0.0/33", "10.0.0.0/x"] {
+            assert!(TrustedProxies::parse(&[bad.to_string()]).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_untrusted_peer_headers_ignored() {
+        let proxies = trusted(&["10.0.0.1"]);
+        let forged = headers(&[("x-forwarded-for", "1.2.3.4"), ("forwarded", "for=1.2.3.4")]);
+        assert_eq!(
+            client_ip(&forged, ip("203.0.113.7"), &proxies),
+            "203.0.113.7"
+        );
+        assert_eq!(client_ip(&forged, None, &proxies), "unknown");
+    }
+
+    #[test]
+    fn test_forwarded_for_through_trusted_proxies() {
+        let proxies = trusted(&["10.0.0.0/8"]);
+        let peer = ip("10.0.0.1");
+
+        // The nearest untrusted hop wins over whatever the client claimed
+        let chain = headers(&[("x-forwarded-for", "1.2.3.4, 203.0.113.7, 10.0.0.2")]);
+        assert_eq!(client_ip(&chain, peer, &proxies), "203.0.113.7");
+
+        // Repeated headers read as one list
+        let split = headers(&[
+            ("x-forwarded-for", "203.0.113.7"),
+            ("x-forwarded-for", "10.0.0.2"),
+        ]);
+        assert_eq!(client_ip(&split, peer, &proxies), "203.0.113.7");
+
+        let real_ip = headers(&[("x-real-ip", "203.0.113.7")]);
+        assert_eq!(client_ip(&real_ip, peer, &proxies), "203.0.113.7");
+
+        // With no headers, the proxy itself is the client
+        assert_eq!(client_ip(&HeaderMap::new(), peer, &proxies), "10.0.0.1");
+    }
+
+    #[test]
+    fn test_forwarded_header() {
+        let proxies = trusted(&["10.0.0.0/8"]);
+        let peer = ip("10.0.0.1");
+
+        let forwarded = headers(&[(
+            "forwarded",
+            "for=1.2.3.4, for=\"[2001:db8:cafe::17]:4711\";proto=https, For=10.0.0.2",
+        )]);
+        assert_eq!(client_ip(&forwarded, peer, &proxies), "2001:db8:cafe::17");
+
+        // Forwarded is preferred to X-Forwarded-For
+        let both = headers(&[
+            ("forwarded", "for=203.0.113.7:51234"),
+            ("x-forwarded-for", "1.2.3.4"),
+        ]);
+        assert_eq!(client_ip(&both, peer, &proxies), "203.0.113.7");
+
+        // An obfuscated hop stops the walk at the last address known
+        let hidden = headers(&[("forwarded", "for=1.2.3.4, for=_hidden, for=10.0.0.2")]);
+        assert_eq!(client_ip(&hidden, peer, &proxies), "10.0.0.2");
+    }
+}
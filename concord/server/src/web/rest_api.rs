@@ -12,7 +12,7 @@ use uuid::Uuid;
 
 use crate::auth::token::{generate_irc_token, hash_irc_token, verify_irc_token};
 use crate::db::queries::{attachments, bots, community, emoji, invites, roles, servers, users};
-use crate::engine::events::HistoryMessage;
+use crate::engine::events::{HistoryMessage, MentionInfo};
 use crate::engine::permissions::{Permissions, compute_effective_permissions};
 use sqlx;
 
@@ -442,6 +442,138 @@ pub async fn get_me(State(state): State<Arc<AppState>>, auth: AuthUser) -> impl
     }
 }
 
+/// GET /api/dms — list the current user's direct message conversations,
+/// most recently active first, with unread counts.
+pub async fn list_dm_conversations(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUser,
+) -> impl IntoResponse {
+    match state.engine.list_dm_conversations(&auth.user_id).await {
+        Ok(conversations) => Json(conversations).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to list DM conversations");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
+// ── Mentions inbox ──────────────────────────────────────
+
+#[derive(Deserialize)]
+pub struct MentionListParams {
+    /// ID of the oldest mention already fetched
+    pub before: Option<String>,
+    pub limit: Option<i64>,
+    #[serde(default)]
+    pub unread_only: bool,
+}
+
+#[derive(Serialize)]
+pub struct MentionListResponse {
+    pub mentions: Vec<MentionInfo>,
+    pub has_more: bool,
+}
+
+/// GET /api/mentions — the current user's mentions, newest first.
+pub async fn list_mentions(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUser,
+    Query(params): Query<MentionListParams>,
+) -> impl IntoResponse {
+    let limit = params.limit.unwrap_or(50).clamp(1, 200) as usize;
+    match state
+        .engine
+        .list_mentions(
+            &auth.user_id,
+            params.before.as_deref(),
+            limit,
+            params.unread_only,
+        )
+        .await
+    {
+        Ok((mentions, has_more)) => Json(MentionListResponse { mentions, has_more }).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to list mentions");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
+/// POST /api/mentions/:id/read — mark a mention as read.
+pub async fn mark_mention_read(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUser,
+    Path(mention_id): Path<String>,
+) -> impl IntoResponse {
+    match state
+        .engine
+        .mark_mention_read(&auth.user_id, &mention_id)
+        .await
+    {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) if e.starts_with("No such mention") => {
+            (StatusCode::NOT_FOUND, "Mention not found").into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to mark mention read");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct MarkMentionsReadParams {
+    pub server_id: Option<String>,
+}
+
+/// POST /api/mentions/read — mark all mentions as read, or only those in
+/// `?server_id=`.
+pub async fn mark_all_mentions_read(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUser,
+    Query(params): Query<MarkMentionsReadParams>,
+) -> impl IntoResponse {
+    match state
+        .engine
+        .mark_all_mentions_read(&auth.user_id, params.server_id.as_deref())
+        .await
+    {
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to mark mentions read");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
+// ── Message edit history ────────────────────────────────
+
+/// GET /api/messages/:id/history — every version of a message, oldest first.
+/// Visible to its author and to those who can manage messages in its channel.
+pub async fn get_message_edit_history(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUser,
+    Path(message_id): Path<String>,
+) -> impl IntoResponse {
+    match state
+        .engine
+        .message_edit_history(&auth.user_id, &message_id)
+        .await
+    {
+        Ok(revisions) => Json(revisions).into_response(),
+        Err(e) if e == "Message not found" => {
+            (StatusCode::NOT_FOUND, "Message not found").into_response()
+        }
+        Err(e) if e.starts_with("FORBIDDEN") => {
+            (StatusCode::FORBIDDEN, "Insufficient permissions").into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to fetch message edit history");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
 // ── User profile lookup (public) ──────────────────────────
 
 #[derive(Serialize)]
@@ -576,6 +708,100 @@ pub async fn delete_irc_token(
     }
 }
 
+// ── IRC client certificates (SASL EXTERNAL) ─────────────────
+
+#[derive(Serialize)]
+pub struct IrcCertInfo {
+    pub id: String,
+    pub fingerprint: String,
+    pub label: Option<String>,
+    pub last_used: Option<String>,
+    pub created_at: String,
+}
+
+/// GET /api/irc-certs — list the current user's bound certificate fingerprints.
+pub async fn list_irc_certs(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUser,
+) -> impl IntoResponse {
+    match users::list_irc_certs(&state.db, &auth.user_id).await {
+        Ok(rows) => {
+            let certs: Vec<IrcCertInfo> = rows
+                .into_iter()
+                .map(|(id, fingerprint, label, last_used, created_at)| IrcCertInfo {
+                    id,
+                    fingerprint,
+                    label,
+                    last_used,
+                    created_at,
+                })
+                .collect();
+            Json(certs).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to list IRC certificates");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
+/// DELETE /api/irc-certs/:id — unbind a certificate fingerprint.
+pub async fn delete_irc_cert(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUser,
+    Path(cert_id): Path<String>,
+) -> impl IntoResponse {
+    match users::delete_irc_cert(&state.db, &cert_id, &auth.user_id).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "Certificate not found").into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to delete IRC certificate");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
+// ── IRC settings ──────────────────────────────────────────
+
+#[derive(Serialize, Deserialize)]
+pub struct IrcSettings {
+    /// Stay connected, and keep receiving messages, while no client is.
+    pub always_on: bool,
+}
+
+/// GET /api/irc-settings — the current user's IRC options.
+pub async fn get_irc_settings(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUser,
+) -> impl IntoResponse {
+    match state.engine.always_on(&auth.user_id).await {
+        Ok(always_on) => Json(IrcSettings { always_on }).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to fetch IRC settings");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
+/// PUT /api/irc-settings — change the current user's IRC options.
+pub async fn update_irc_settings(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUser,
+    Json(body): Json<IrcSettings>,
+) -> impl IntoResponse {
+    match state
+        .engine
+        .set_always_on(&auth.user_id, body.always_on)
+        .await
+    {
+        Ok(()) => Json(body).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to update IRC settings");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
 // ── File upload endpoints ─────────────────────────────────
 
 #[derive(Serialize)]
@@ -1408,6 +1634,24 @@ mod tests {
         assert!(json["last_used"].is_null());
     }
 
+    // ── IrcCertInfo serialization ──
+
+    #[test]
+    fn test_irc_cert_info_serialize() {
+        let info = IrcCertInfo {
+            id: "c1".into(),
+            fingerprint: "ab".repeat(32),
+            label: Some("laptop".into()),
+            last_used: None,
+            created_at: "2025-01-01".into(),
+        };
+        let json = serde_json::to_value(&info).unwrap();
+        assert_eq!(json["id"], "c1");
+        assert_eq!(json["fingerprint"], "ab".repeat(32));
+        assert_eq!(json["label"], "laptop");
+        assert!(json["last_used"].is_null());
+    }
+
     // ── UploadResponse serialization ──
 
     #[test]
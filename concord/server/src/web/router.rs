@@ -8,7 +8,7 @@ use tower_http::services::{ServeDir, ServeFile};
 
 use super::app_state::AppState;
 use super::rate_limit::{ApiRateLimiters, api_rate_limit, auth_rate_limit, ws_rate_limit};
-use super::{atproto, oauth, rest_api, ws_handler};
+use super::{atproto, irc_ws, oauth, rest_api, ws_handler};
 
 /// Build the axum router with all HTTP and WebSocket routes.
 pub fn build_router(state: Arc<AppState>) -> Router {
@@ -56,9 +56,10 @@ pub fn build_router(state: Arc<AppState>) -> Router {
         .route("/api/auth/logout", axum::routing::post(oauth::logout))
         .layer(axum::middleware::from_fn(auth_rate_limit));
 
-    // WebSocket — connection rate limit
+    // WebSocket (web client and IRC) — connection rate limit
     let ws_routes = Router::new()
         .route("/ws", axum::routing::get(ws_handler::ws_upgrade))
+        .route("/irc", axum::routing::get(irc_ws::irc_ws_upgrade))
         .layer(axum::middleware::from_fn(ws_rate_limit));
 
     // All other API routes — general rate limit
@@ -110,6 +111,23 @@ pub fn build_router(state: Arc<AppState>) -> Router {
         )
         // Authenticated user endpoints
         .route("/api/me", axum::routing::get(rest_api::get_me))
+        .route(
+            "/api/dms",
+            axum::routing::get(rest_api::list_dm_conversations),
+        )
+        .route("/api/mentions", axum::routing::get(rest_api::list_mentions))
+        .route(
+            "/api/mentions/read",
+            axum::routing::post(rest_api::mark_all_mentions_read),
+        )
+        .route(
+            "/api/mentions/{id}/read",
+            axum::routing::post(rest_api::mark_mention_read),
+        )
+        .route(
+            "/api/messages/{id}/history",
+            axum::routing::get(rest_api::get_message_edit_history),
+        )
         .route(
             "/api/tokens",
             axum::routing::get(rest_api::list_irc_tokens).post(rest_api::create_irc_token),
@@ -118,6 +136,18 @@ pub fn build_router(state: Arc<AppState>) -> Router {
             "/api/tokens/{id}",
             axum::routing::delete(rest_api::delete_irc_token),
         )
+        .route(
+            "/api/irc-certs",
+            axum::routing::get(rest_api::list_irc_certs),
+        )
+        .route(
+            "/api/irc-certs/{id}",
+            axum::routing::delete(rest_api::delete_irc_cert),
+        )
+        .route(
+            "/api/irc-settings",
+            axum::routing::get(rest_api::get_irc_settings).put(rest_api::update_irc_settings),
+        )
         // File upload/download
         .route(
             "/api/uploads",
@@ -172,7 +202,8 @@ pub fn build_router(state: Arc<AppState>) -> Router {
         // Static files with SPA fallback — unmatched routes serve index.html
         .fallback_service(ServeDir::new("static").fallback(ServeFile::new("static/index.html")))
         .layer(cors)
-        // Inject rate limiters into all request extensions
+        // Inject rate limiters and trusted proxies into all request extensions
         .layer(axum::Extension(rate_limiters))
+        .layer(axum::Extension(state.trusted_proxies.clone()))
         .with_state(state)
 }
@@ -11,7 +11,7 @@ use tracing::{error, info, warn};
 use crate::auth::token::validate_session_token;
 use crate::db::queries::users;
 use crate::engine::chat_engine::{ChatEngine, DEFAULT_SERVER_ID};
-use crate::engine::events::ChatEvent;
+use crate::engine::events::{ChatEvent, MessageKind};
 use crate::engine::permissions::Permissions;
 use crate::engine::user_session::Protocol;
 
@@ -28,6 +28,9 @@ enum ClientMessage {
         content: String,
         reply_to: Option<String>,
         attachment_ids: Option<Vec<String>>,
+        /// `action` for `/me`, `notice` for a notice; ordinary text by default.
+        #[serde(default)]
+        kind: MessageKind,
     },
     JoinChannel {
         #[serde(default = "default_server_id")]
@@ -100,6 +103,9 @@ enum ClientMessage {
         message_id: String,
         content: String,
     },
+    GetEditHistory {
+        message_id: String,
+    },
     DeleteMessage {
         message_id: String,
     },
@@ -126,6 +132,31 @@ enum ClientMessage {
         #[serde(default = "default_server_id")]
         server_id: String,
     },
+    // ── Group DMs ──
+    // Messages, history and read markers use the `&<conversation_id>` target.
+    CreateGroupDm {
+        nicknames: Vec<String>,
+        name: Option<String>,
+    },
+    AddGroupDmParticipant {
+        conversation_id: String,
+        nickname: String,
+    },
+    RemoveGroupDmParticipant {
+        conversation_id: String,
+        nickname: String,
+    },
+    RenameGroupDm {
+        conversation_id: String,
+        name: Option<String>,
+    },
+    LeaveGroupDm {
+        conversation_id: String,
+    },
+    TransferGroupDmOwnership {
+        conversation_id: String,
+        nickname: String,
+    },
     // ── Roles ──
     ListRoles {
         server_id: String,
@@ -259,6 +290,21 @@ enum ClientMessage {
         message_id: String,
     },
     ListBookmarks,
+    // ── Mentions inbox ──
+    ListMentions {
+        /// ID of the oldest mention already fetched
+        before: Option<String>,
+        limit: Option<i64>,
+        #[serde(default)]
+        unread_only: bool,
+    },
+    MarkMentionRead {
+        mention_id: String,
+    },
+    /// Mark every mention read, or only those in `server_id`.
+    MarkAllMentionsRead {
+        server_id: Option<String>,
+    },
     // ── Phase 6: Moderation ──
     KickMember {
         server_id: String,
@@ -392,6 +438,13 @@ enum ClientMessage {
     GetCommunitySettings {
         server_id: String,
     },
+    SetServerMotd {
+        server_id: String,
+        motd: Option<String>,
+    },
+    GetServerMotd {
+        server_id: String,
+    },
     DiscoverServers {
         category: Option<String>,
     },
@@ -555,9 +608,17 @@ pub async fn ws_upgrade(
         None
     };
 
+    // The account name IRC clients are shown for this user
+    let account = match &user_id {
+        Some(id) => users::get_account_name(&state.db, id).await.ok().flatten(),
+        None => None,
+    };
+
     let engine = state.engine.clone();
     ws.max_message_size(64 * 1024) // 64 KB max WS message
-        .on_upgrade(move |socket| handle_ws_connection(socket, engine, user_id, nickname, avatar_url))
+        .on_upgrade(move |socket| {
+            handle_ws_connection(socket, engine, user_id, nickname, avatar_url, account)
+        })
         .into_response()
 }
 
@@ -567,6 +628,7 @@ async fn handle_ws_connection(
     user_id: Option<String>,
     nickname: String,
     avatar_url: Option<String>,
+    account: Option<String>,
 ) {
     let (session_id, mut event_rx) =
         match engine.connect(user_id, nickname.clone(), Protocol::WebSocket, avatar_url) {
@@ -576,6 +638,9 @@ async fn handle_ws_connection(
                 return;
             }
         };
+    if let Some(account) = account {
+        engine.set_account(session_id, account);
+    }
 
     let (mut ws_sender, mut ws_receiver) = socket.split();
 
@@ -638,13 +703,15 @@ async fn handle_client_message(
             content,
             reply_to,
             attachment_ids,
-        } => engine.send_message(
+            kind,
+        } => engine.send_message_as(
             session_id,
             &server_id,
             &channel,
             &content,
             reply_to.as_deref(),
             attachment_ids.as_deref(),
+            kind,
         ),
         ClientMessage::JoinChannel { server_id, channel } => {
             engine.join_channel(session_id, &server_id, &channel)
@@ -665,19 +732,32 @@ async fn handle_client_message(
             before,
             limit,
         } => {
+            let limit = limit.unwrap_or(50).min(200);
+            // A target that isn't a channel is the other side of a DM or a group DM
+            let is_dm = !channel.starts_with('#');
             // Verify the user is a member of this server
-            let is_member = engine
-                .get_session(session_id)
-                .and_then(|s| s.user_id.as_ref().map(|uid| engine.user_is_server_member(&server_id, uid)))
-                .unwrap_or(false);
+            let is_member = is_dm
+                || engine
+                    .get_session(session_id)
+                    .and_then(|s| {
+                        s.user_id
+                            .as_ref()
+                            .map(|uid| engine.user_is_server_member(&server_id, uid))
+                    })
+                    .unwrap_or(false);
             if !is_member {
                 Err("You are not a member of this server".into())
             } else {
-                let limit = limit.unwrap_or(50).min(200);
-                match engine
-                    .fetch_history(&server_id, &channel, before.as_deref(), limit)
-                    .await
-                {
+                let history = if is_dm {
+                    engine
+                        .fetch_dm_history(session_id, &channel, before.as_deref(), limit)
+                        .await
+                } else {
+                    engine
+                        .fetch_history(&server_id, &channel, before.as_deref(), limit)
+                        .await
+                };
+                match history {
                     Ok((messages, has_more)) => {
                         if let Some(session) = engine.get_session(session_id) {
                             let _ = session.send(ChatEvent::History {
@@ -999,6 +1079,23 @@ async fn handle_client_message(
             message_id,
             content,
         } => engine.edit_message(session_id, &message_id, &content).await,
+        ClientMessage::GetEditHistory { message_id } => {
+            match engine.get_session(session_id) {
+                Some(session) => match session.user_id.as_deref() {
+                    Some(user_id) => engine
+                        .message_edit_history(user_id, &message_id)
+                        .await
+                        .map(|revisions| {
+                            let _ = session.send(ChatEvent::MessageEditHistory {
+                                message_id,
+                                revisions,
+                            });
+                        }),
+                    None => Err("AUTH_REQUIRED".into()),
+                },
+                None => Err("Session not found".into()),
+            }
+        }
         ClientMessage::DeleteMessage { message_id } => {
             engine.delete_message(session_id, &message_id).await
         }
@@ -1018,9 +1115,13 @@ async fn handle_client_message(
             channel,
             message_id,
         } => {
-            engine
-                .mark_read(session_id, &server_id, &channel, &message_id)
-                .await
+            if channel.starts_with('#') {
+                engine
+                    .mark_read(session_id, &server_id, &channel, &message_id)
+                    .await
+            } else {
+                engine.mark_dm_read(session_id, &channel, &message_id).await
+            }
         }
         ClientMessage::GetUnreadCounts { server_id } => {
             match engine.get_unread_counts(session_id, &server_id).await {
@@ -1033,6 +1134,46 @@ async fn handle_client_message(
                 Err(e) => Err(e),
             }
         }
+        // ── Group DMs ──
+        ClientMessage::CreateGroupDm { nicknames, name } => engine
+            .create_group_dm(session_id, &nicknames, name.as_deref())
+            .await
+            .map(|_| ()),
+        ClientMessage::AddGroupDmParticipant {
+            conversation_id,
+            nickname,
+        } => {
+            engine
+                .add_group_dm_participant(session_id, &conversation_id, &nickname)
+                .await
+        }
+        ClientMessage::RemoveGroupDmParticipant {
+            conversation_id,
+            nickname,
+        } => {
+            engine
+                .remove_group_dm_participant(session_id, &conversation_id, &nickname)
+                .await
+        }
+        ClientMessage::RenameGroupDm {
+            conversation_id,
+            name,
+        } => {
+            engine
+                .rename_group_dm(session_id, &conversation_id, name.as_deref())
+                .await
+        }
+        ClientMessage::LeaveGroupDm { conversation_id } => {
+            engine.leave_group_dm(session_id, &conversation_id).await
+        }
+        ClientMessage::TransferGroupDmOwnership {
+            conversation_id,
+            nickname,
+        } => {
+            engine
+                .transfer_group_dm_ownership(session_id, &conversation_id, &nickname)
+                .await
+        }
         // ── Roles ──
         ClientMessage::ListRoles { server_id } => match engine.list_roles(&server_id).await {
             Ok(roles) => {
@@ -1510,6 +1651,41 @@ async fn handle_client_message(
             engine.remove_bookmark(session_id, &message_id).await
         }
         ClientMessage::ListBookmarks => engine.list_bookmarks(session_id).await,
+        // ── Mentions inbox ──
+        ClientMessage::ListMentions {
+            before,
+            limit,
+            unread_only,
+        } => {
+            let limit = limit.unwrap_or(50).clamp(1, 200) as usize;
+            match engine.get_session(session_id) {
+                Some(session) => match session.user_id.as_deref() {
+                    Some(user_id) => engine
+                        .list_mentions(user_id, before.as_deref(), limit, unread_only)
+                        .await
+                        .map(|(mentions, has_more)| {
+                            let _ = session.send(ChatEvent::MentionList { mentions, has_more });
+                        }),
+                    None => Err("AUTH_REQUIRED".into()),
+                },
+                None => Err("Session not found".into()),
+            }
+        }
+        ClientMessage::MarkMentionRead { mention_id } => {
+            match engine.get_session(session_id).and_then(|s| s.user_id.clone()) {
+                Some(user_id) => engine.mark_mention_read(&user_id, &mention_id).await,
+                None => Err("AUTH_REQUIRED".into()),
+            }
+        }
+        ClientMessage::MarkAllMentionsRead { server_id } => {
+            match engine.get_session(session_id).and_then(|s| s.user_id.clone()) {
+                Some(user_id) => engine
+                    .mark_all_mentions_read(&user_id, server_id.as_deref())
+                    .await
+                    .map(|_| ()),
+                None => Err("AUTH_REQUIRED".into()),
+            }
+        }
         // ── Phase 6: Moderation ──
         ClientMessage::KickMember {
             server_id,
@@ -1763,6 +1939,14 @@ async fn handle_client_message(
         ClientMessage::GetCommunitySettings { server_id } => {
             engine.get_community_settings(session_id, &server_id).await
         }
+        ClientMessage::SetServerMotd { server_id, motd } => {
+            engine
+                .set_server_motd(session_id, &server_id, motd.as_deref())
+                .await
+        }
+        ClientMessage::GetServerMotd { server_id } => {
+            engine.get_server_motd(session_id, &server_id).await
+        }
         ClientMessage::DiscoverServers { category } => {
             engine
                 .discover_servers(session_id, category.as_deref())
@@ -2008,17 +2192,31 @@ mod tests {
                 content,
                 reply_to,
                 attachment_ids,
+                kind,
             } => {
                 assert_eq!(server_id, DEFAULT_SERVER_ID);
                 assert_eq!(channel, "#general");
                 assert_eq!(content, "Hello world");
                 assert!(reply_to.is_none());
                 assert!(attachment_ids.is_none());
+                assert_eq!(kind, MessageKind::Normal);
             }
             _ => panic!("Expected SendMessage"),
         }
     }
 
+    #[test]
+    fn test_send_message_action() {
+        let msg: ClientMessage = parse_msg(
+            r##"{"type": "send_message", "channel": "#general", "content": "waves", "kind": "action"}"##,
+        )
+        .unwrap();
+        match msg {
+            ClientMessage::SendMessage { kind, .. } => assert_eq!(kind, MessageKind::Action),
+            _ => panic!("Expected SendMessage"),
+        }
+    }
+
     #[test]
     fn test_send_message_with_reply_and_attachments() {
         let msg: ClientMessage = parse_msg(
@@ -2334,6 +2532,15 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_get_edit_history() {
+        let msg: ClientMessage =
+            parse_msg(r##"{"type": "get_edit_history", "message_id": "msg-1"}"##).unwrap();
+        assert!(
+            matches!(msg, ClientMessage::GetEditHistory { message_id } if message_id == "msg-1")
+        );
+    }
+
     #[test]
     fn test_delete_message() {
         let msg: ClientMessage = parse_msg(
@@ -2423,6 +2630,46 @@ mod tests {
         }
     }
 
+    // ── Group DMs ──
+
+    #[test]
+    fn test_create_group_dm() {
+        let msg: ClientMessage =
+            parse_msg(r#"{"type":"create_group_dm","nicknames":["bob","carol"],"name":"weekend"}"#)
+                .unwrap();
+        match msg {
+            ClientMessage::CreateGroupDm { nicknames, name } => {
+                assert_eq!(nicknames, vec!["bob", "carol"]);
+                assert_eq!(name.as_deref(), Some("weekend"));
+            }
+            _ => panic!("Expected CreateGroupDm"),
+        }
+    }
+
+    #[test]
+    fn test_group_dm_membership_messages() {
+        let msg: ClientMessage = parse_msg(
+            r#"{"type":"transfer_group_dm_ownership","conversation_id":"g1","nickname":"bob"}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            msg,
+            ClientMessage::TransferGroupDmOwnership { conversation_id, nickname }
+                if conversation_id == "g1" && nickname == "bob"
+        ));
+        let msg: ClientMessage =
+            parse_msg(r#"{"type":"leave_group_dm","conversation_id":"g1"}"#).unwrap();
+        assert!(
+            matches!(msg, ClientMessage::LeaveGroupDm { conversation_id } if conversation_id == "g1")
+        );
+        let msg: ClientMessage =
+            parse_msg(r#"{"type":"rename_group_dm","conversation_id":"g1"}"#).unwrap();
+        assert!(matches!(
+            msg,
+            ClientMessage::RenameGroupDm { name: None, .. }
+        ));
+    }
+
     // ── Roles ──
 
     #[test]
@@ -2614,6 +2861,50 @@ mod tests {
         assert!(matches!(msg, ClientMessage::ListBookmarks));
     }
 
+    // ── Mentions inbox ──
+
+    #[test]
+    fn test_list_mentions() {
+        let msg: ClientMessage = parse_msg(
+            r##"{"type": "list_mentions", "before": "mn-9", "unread_only": true}"##,
+        )
+        .unwrap();
+        match msg {
+            ClientMessage::ListMentions {
+                before,
+                limit,
+                unread_only,
+            } => {
+                assert_eq!(before.as_deref(), Some("mn-9"));
+                assert_eq!(limit, None);
+                assert!(unread_only);
+            }
+            _ => panic!("Expected ListMentions"),
+        }
+        let msg: ClientMessage = parse_msg(r##"{"type": "list_mentions"}"##).unwrap();
+        assert!(matches!(
+            msg,
+            ClientMessage::ListMentions {
+                unread_only: false,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_mark_mentions_read() {
+        let msg: ClientMessage =
+            parse_msg(r##"{"type": "mark_mention_read", "mention_id": "mn-1"}"##).unwrap();
+        assert!(
+            matches!(msg, ClientMessage::MarkMentionRead { mention_id } if mention_id == "mn-1")
+        );
+        let msg: ClientMessage = parse_msg(r##"{"type": "mark_all_mentions_read"}"##).unwrap();
+        assert!(matches!(
+            msg,
+            ClientMessage::MarkAllMentionsRead { server_id: None }
+        ));
+    }
+
     // ── Phase 6: Moderation ──
 
     #[test]
@@ -3182,6 +3473,20 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_set_server_motd() {
+        let msg: ClientMessage =
+            parse_msg(r##"{"type": "set_server_motd", "server_id": "srv-1", "motd": "Hello"}"##)
+                .unwrap();
+        match msg {
+            ClientMessage::SetServerMotd { server_id, motd } => {
+                assert_eq!(server_id, "srv-1");
+                assert_eq!(motd, Some("Hello".into()));
+            }
+            _ => panic!("Expected SetServerMotd"),
+        }
+    }
+
     #[test]
     fn test_follow_channel() {
         let msg: ClientMessage = parse_msg(
@@ -1,4 +1,4 @@
-import type { AttachmentInfo, AuthStatus, ChannelInfo, CreateTokenResponse, HistoryResponse, IrcToken, PublicUserProfile, ServerInfo, UserProfile } from './types';
+import type { AttachmentInfo, AuthStatus, ChannelInfo, CreateTokenResponse, HistoryResponse, IrcSettings, IrcToken, PublicUserProfile, ServerInfo, UserProfile } from './types';
 
 const BASE = '/api';
 
@@ -70,6 +70,14 @@ export const createToken = (label?: string) =>
 export const deleteToken = (id: string) =>
   request<void>(`/tokens/${encodeURIComponent(id)}`, { method: 'DELETE' });
 
+// IRC settings
+export const getIrcSettings = () => request<IrcSettings>('/irc-settings');
+export const updateIrcSettings = (settings: IrcSettings) =>
+  request<IrcSettings>('/irc-settings', {
+    method: 'PUT',
+    body: JSON.stringify(settings),
+  });
+
 // Admin
 export const adminListServers = () => request<ServerInfo[]>('/admin/servers');
 export const adminDeleteServer = (id: string) =>
@@ -33,6 +33,8 @@ export interface ChannelInfo {
   channel_type: string;
   thread_parent_message_id?: string | null;
   archived: boolean;
+  created_at: string;
+  topic_set_at?: string | null;
   slowmode_seconds: number;
   is_nsfw: boolean;
 }
@@ -44,6 +46,7 @@ export interface MemberInfo {
   custom_status?: string | null;
   status_emoji?: string | null;
   user_id?: string | null;
+  prefixes?: string;
 }
 
 export interface ReplyInfo {
@@ -84,13 +87,54 @@ export interface HistoryMessage {
   reactions?: ReactionGroup[] | null;
   attachments?: AttachmentInfo[] | null;
   embeds?: EmbedInfo[] | null;
+  kind?: MessageKind;
 }
 
+/** `action` is a `/me` message; `notice` is an IRC NOTICE. */
+export type MessageKind = 'normal' | 'action' | 'notice' | 'system';
+
 export interface UnreadCount {
   channel_name: string;
   count: number;
 }
 
+export interface DmParticipant {
+  user_id: string;
+  /** Live nickname if connected, otherwise username. */
+  nickname: string;
+  avatar_url?: string;
+}
+
+export interface DmConversationInfo {
+  id: string;
+  /** Group conversations are messaged at `&<id>`. */
+  is_group: boolean;
+  name?: string;
+  owner_id?: string;
+  /** Everyone in the conversation except you. */
+  participants: DmParticipant[];
+  last_message_at: string | null;
+  unread_count: number;
+}
+
+export interface GroupDmInfo {
+  id: string;
+  /** `&<id>`: the channel to send messages, fetch history and mark read with. */
+  target: string;
+  name?: string;
+  owner_id: string;
+  /** Everyone in the group, you included. */
+  participants: DmParticipant[];
+}
+
+export type GroupDmChange =
+  | { kind: 'created'; by: string }
+  | { kind: 'added'; by: string; nickname: string }
+  | { kind: 'removed'; by: string; nickname: string }
+  | { kind: 'left'; nickname: string }
+  | { kind: 'renamed'; by: string; name: string | null }
+  | { kind: 'owner_changed'; by: string; nickname: string };
+
 export interface RoleInfo {
   id: string;
   server_id: string;
@@ -196,6 +240,25 @@ export interface BookmarkInfo {
   created_at: string;
 }
 
+export interface MessageRevisionInfo {
+  content: string;
+  timestamp: string;
+}
+
+export type MentionKind = 'user' | 'role' | 'everyone';
+
+export interface MentionInfo {
+  id: string;
+  message_id: string;
+  server_id: string;
+  channel: string;
+  from: string;
+  content: string;
+  kind: MentionKind;
+  timestamp: string;
+  read: boolean;
+}
+
 export interface AuditLogEntry {
   id: string;
   actor_id: string;
@@ -427,6 +490,10 @@ export interface IrcToken {
   created_at: string;
 }
 
+export interface IrcSettings {
+  always_on: boolean;
+}
+
 export interface CreateTokenResponse {
   id: string;
   token: string;
@@ -437,13 +504,15 @@ export interface CreateTokenResponse {
 
 // Server → Client events
 export type ServerEvent =
-  | { type: 'message'; id: string; server_id?: string; from: string; target: string; content: string; timestamp: string; avatar_url?: string; reply_to?: ReplyInfo | null; attachments?: AttachmentInfo[] | null }
-  | { type: 'message_edit'; id: string; server_id: string; channel: string; content: string; edited_at: string }
-  | { type: 'message_delete'; id: string; server_id: string; channel: string }
+  | { type: 'message'; id: string; server_id?: string; from: string; target: string; content: string; timestamp: string; avatar_url?: string; reply_to?: ReplyInfo | null; attachments?: AttachmentInfo[] | null; kind?: MessageKind }
+  | { type: 'message_edit'; id: string; server_id: string; channel: string; from?: string; content: string; edited_at: string }
+  | { type: 'message_delete'; id: string; server_id: string; channel: string; deleted_by?: string }
+  | { type: 'message_edit_history'; message_id: string; revisions: MessageRevisionInfo[] }
   | { type: 'message_embed'; message_id: string; server_id: string; channel: string; embeds: EmbedInfo[] }
   | { type: 'reaction_add'; message_id: string; server_id: string; channel: string; user_id: string; nickname: string; emoji: string }
   | { type: 'reaction_remove'; message_id: string; server_id: string; channel: string; user_id: string; nickname: string; emoji: string }
   | { type: 'typing_start'; server_id: string; channel: string; nickname: string }
+  | { type: 'typing_stop'; server_id: string; channel: string; nickname: string; paused?: boolean }
   | { type: 'join'; nickname: string; server_id: string; channel: string; avatar_url?: string }
   | { type: 'part'; nickname: string; server_id: string; channel: string; reason?: string }
   | { type: 'quit'; nickname: string; reason?: string }
@@ -460,6 +529,7 @@ export type ServerEvent =
   | { type: 'role_update'; server_id: string; role: RoleInfo }
   | { type: 'role_delete'; server_id: string; role_id: string }
   | { type: 'member_role_update'; server_id: string; user_id: string; role_ids: string[] }
+  | { type: 'member_prefix_update'; server_id: string; channel: string; nickname: string; added: string; removed: string }
   | { type: 'category_list'; server_id: string; categories: CategoryInfo[] }
   | { type: 'category_update'; server_id: string; category: CategoryInfo }
   | { type: 'category_delete'; server_id: string; category_id: string }
@@ -488,6 +558,17 @@ export type ServerEvent =
   | { type: 'member_timeout'; server_id: string; user_id: string; timeout_until?: string | null }
   | { type: 'slow_mode_update'; server_id: string; channel: string; seconds: number }
   | { type: 'nsfw_update'; server_id: string; channel: string; is_nsfw: boolean }
+  | { type: 'privacy_update'; server_id: string; channel: string; is_private: boolean }
+  | { type: 'moderated_update'; server_id: string; channel: string; moderated: boolean }
+  | { type: 'channel_kick'; server_id: string; channel: string; nickname: string; kicked_by: string; reason?: string | null }
+  | { type: 'channel_invite'; server_id: string; channel: string; invited_by: string; code?: string | null }
+  | { type: 'channel_knock'; server_id: string; channel: string; nickname: string; message?: string | null }
+  | { type: 'wallops'; from: string; message: string }
+  | { type: 'killed'; killed_by: string; reason: string }
+  | { type: 'monitor_status'; nickname: string; online: boolean }
+  | { type: 'group_dm_update'; conversation: GroupDmInfo; change: GroupDmChange }
+  | { type: 'mention'; mention: MentionInfo }
+  | { type: 'mention_list'; mentions: MentionInfo[]; has_more: boolean }
   | { type: 'bulk_message_delete'; server_id: string; channel: string; message_ids: string[] }
   | { type: 'audit_log_entries'; server_id: string; entries: AuditLogEntry[] }
   | { type: 'ban_list'; server_id: string; bans: BanInfo[] }
@@ -502,6 +583,7 @@ export type ServerEvent =
   | { type: 'event_delete'; server_id: string; event_id: string }
   | { type: 'event_rsvp_list'; event_id: string; rsvps: RsvpInfo[] }
   | { type: 'server_community'; community: ServerCommunityInfo }
+  | { type: 'server_motd'; server_id: string; motd: string | null }
   | { type: 'discover_servers'; servers: ServerCommunityInfo[] }
   | { type: 'channel_follow_list'; channel_id: string; follows: ChannelFollowInfo[] }
   | { type: 'channel_follow_create'; follow: ChannelFollowInfo }
@@ -524,8 +606,9 @@ export type ServerEvent =
 
 // Client → Server commands
 export type ClientCommand =
-  | { type: 'send_message'; server_id: string; channel: string; content: string; reply_to?: string; attachment_ids?: string[] }
+  | { type: 'send_message'; server_id: string; channel: string; content: string; reply_to?: string; attachment_ids?: string[]; kind?: MessageKind }
   | { type: 'edit_message'; message_id: string; content: string }
+  | { type: 'get_edit_history'; message_id: string }
   | { type: 'delete_message'; message_id: string }
   | { type: 'add_reaction'; message_id: string; emoji: string }
   | { type: 'remove_reaction'; message_id: string; emoji: string }
@@ -546,6 +629,12 @@ export type ClientCommand =
   | { type: 'update_member_role'; server_id: string; user_id: string; role: string }
   | { type: 'mark_read'; server_id: string; channel: string; message_id: string }
   | { type: 'get_unread_counts'; server_id: string }
+  | { type: 'create_group_dm'; nicknames: string[]; name?: string }
+  | { type: 'add_group_dm_participant'; conversation_id: string; nickname: string }
+  | { type: 'remove_group_dm_participant'; conversation_id: string; nickname: string }
+  | { type: 'rename_group_dm'; conversation_id: string; name?: string }
+  | { type: 'leave_group_dm'; conversation_id: string }
+  | { type: 'transfer_group_dm_ownership'; conversation_id: string; nickname: string }
   | { type: 'list_roles'; server_id: string }
   | { type: 'create_role'; server_id: string; name: string; color?: string; permissions?: number; position?: number }
   | { type: 'update_role'; server_id: string; role_id: string; name?: string; color?: string; permissions?: number; position?: number }
@@ -573,6 +662,9 @@ export type ClientCommand =
   | { type: 'add_bookmark'; message_id: string; note?: string }
   | { type: 'remove_bookmark'; message_id: string }
   | { type: 'list_bookmarks' }
+  | { type: 'list_mentions'; before?: string; limit?: number; unread_only?: boolean }
+  | { type: 'mark_mention_read'; mention_id: string }
+  | { type: 'mark_all_mentions_read'; server_id?: string }
   | { type: 'kick_member'; server_id: string; user_id: string; reason?: string }
   | { type: 'ban_member'; server_id: string; user_id: string; reason?: string; delete_message_days?: number }
   | { type: 'unban_member'; server_id: string; user_id: string }
@@ -599,6 +691,8 @@ export type ClientCommand =
   | { type: 'list_rsvps'; event_id: string }
   | { type: 'update_community_settings'; server_id: string; description?: string; is_discoverable: boolean; welcome_message?: string; rules_text?: string; category?: string }
   | { type: 'get_community_settings'; server_id: string }
+  | { type: 'set_server_motd'; server_id: string; motd: string | null }
+  | { type: 'get_server_motd'; server_id: string }
   | { type: 'discover_servers'; category?: string }
   | { type: 'accept_rules'; server_id: string }
   | { type: 'set_announcement_channel'; server_id: string; channel: string; is_announcement: boolean }
@@ -13,9 +13,11 @@ export function SettingsPage() {
   const [newTokenLabel, setNewTokenLabel] = useState('');
   const [newToken, setNewToken] = useState<string | null>(null);
   const [loading, setLoading] = useState(false);
+  const [alwaysOn, setAlwaysOn] = useState(false);
 
   useEffect(() => {
     api.getTokens().then(setTokens).catch(console.error);
+    api.getIrcSettings().then((s) => setAlwaysOn(s.always_on)).catch(console.error);
   }, []);
 
   const handleCreateToken = async () => {
@@ -41,6 +43,15 @@ export function SettingsPage() {
     }
   };
 
+  const handleToggleAlwaysOn = async () => {
+    try {
+      const updated = await api.updateIrcSettings({ always_on: !alwaysOn });
+      setAlwaysOn(updated.always_on);
+    } catch (e) {
+      console.error('Failed to update IRC settings:', e);
+    }
+  };
+
   const handleLogout = async () => {
     await logout();
     setShowSettings(false);
@@ -129,6 +140,22 @@ export function SettingsPage() {
             </button>
           </div>
 
+          <label className="mb-3 flex items-start gap-3 rounded-md bg-bg-tertiary p-3">
+            <input
+              type="checkbox"
+              checked={alwaysOn}
+              onChange={handleToggleAlwaysOn}
+              className="mt-1"
+            />
+            <span>
+              <span className="block text-sm font-medium text-text-primary">Always on</span>
+              <span className="block text-xs text-text-muted">
+                Stay in your channels while no IRC client is connected, and get what you missed
+                when one reconnects.
+              </span>
+            </span>
+          </label>
+
           {tokens.length > 0 && (
             <div className="space-y-2">
               {tokens.map((t) => (
@@ -114,7 +114,13 @@ export function MessageInput() {
       setUploading(false);
     }
 
-    sendMessage(activeServer, activeChannel, trimmed || '\u200B', attachments);
+    // `/me waves` sends an action, shown as "* nick waves" here and on IRC
+    const action = trimmed.match(/^\/me\s+([\s\S]+)/);
+    if (action) {
+      sendMessage(activeServer, activeChannel, action[1], attachments, 'action');
+    } else {
+      sendMessage(activeServer, activeChannel, trimmed || '\u200B', attachments);
+    }
     setText('');
     setPendingFiles([]);
     setMentionQuery(null);
@@ -246,6 +246,14 @@ function MessageItem({ message }: { message: HistoryMessage }) {
               Enter to save, Escape to cancel
             </div>
           </div>
+        ) : message.kind === 'action' ? (
+          <div className="italic text-text-secondary">
+            * {message.from} <FormattedMessage content={message.content} />
+          </div>
+        ) : message.kind === 'notice' ? (
+          <div className="text-text-muted">
+            <FormattedMessage content={message.content} />
+          </div>
         ) : (
           <FormattedMessage content={message.content} />
         )}
@@ -1,5 +1,5 @@
 import { create } from 'zustand';
-import type { AttachmentInfo, AuditLogEntry, AutomodRuleInfo, BanInfo, BookmarkInfo, BotTokenInfo, CategoryInfo, ChannelInfo, ChannelPositionInfo, EventInfo, ForumTagInfo, HistoryMessage, InviteInfo, MemberInfo, OAuth2AppInfo, PinnedMessageInfo, PresenceInfo, ReplyInfo, RoleInfo, SearchResultMessage, ServerCommunityInfo, ServerEvent, ServerInfo, SlashCommandInfo, TemplateInfo, ThreadInfo, UserProfileInfo, WebhookInfo } from '../api/types';
+import type { AttachmentInfo, AuditLogEntry, AutomodRuleInfo, BanInfo, BookmarkInfo, BotTokenInfo, CategoryInfo, ChannelInfo, ChannelPositionInfo, EventInfo, ForumTagInfo, HistoryMessage, InviteInfo, MemberInfo, MessageKind, OAuth2AppInfo, PinnedMessageInfo, PresenceInfo, ReplyInfo, RoleInfo, SearchResultMessage, ServerCommunityInfo, ServerEvent, ServerInfo, SlashCommandInfo, TemplateInfo, ThreadInfo, UserProfileInfo, WebhookInfo } from '../api/types';
 import { listServerEmoji, createServerEmoji, deleteServerEmoji } from '../api/client';
 import { channelKey } from '../api/types';
 import { WebSocketManager } from '../api/websocket';
@@ -27,6 +27,7 @@ const EMPTY_BOOKMARKS: BookmarkInfo[] = [];
 const EMPTY_INVITES: Record<string, InviteInfo[]> = {};
 const EMPTY_EVENTS: Record<string, EventInfo[]> = {};
 const EMPTY_COMMUNITY: Record<string, ServerCommunityInfo> = {};
+const EMPTY_MOTDS: Record<string, string | null> = {};
 const EMPTY_DISCOVER: ServerCommunityInfo[] = [];
 const EMPTY_TEMPLATES: Record<string, TemplateInfo[]> = {};
 const EMPTY_WEBHOOKS: Record<string, WebhookInfo[]> = {};
@@ -84,6 +85,8 @@ interface ChatState {
   serverEvents: Record<string, EventInfo[]>;
   /** server_id -> community settings */
   communitySettings: Record<string, ServerCommunityInfo>;
+  /** server_id -> the server's own IRC message of the day */
+  serverMotds: Record<string, string | null>;
   /** Discoverable servers */
   discoverableServers: ServerCommunityInfo[];
   /** server_id -> templates */
@@ -101,7 +104,7 @@ interface ChatState {
   connect: (nickname: string) => void;
   disconnect: () => void;
   handleEvent: (event: ServerEvent) => void;
-  sendMessage: (serverId: string, channel: string, content: string, attachments?: AttachmentInfo[]) => void;
+  sendMessage: (serverId: string, channel: string, content: string, attachments?: AttachmentInfo[], kind?: MessageKind) => void;
   editMessage: (messageId: string, content: string) => void;
   deleteMessage: (messageId: string) => void;
   addReaction: (messageId: string, emoji: string) => void;
@@ -183,6 +186,8 @@ interface ChatState {
   listRsvps: (eventId: string) => void;
   updateCommunitySettings: (serverId: string, settings: { description?: string; isDiscoverable: boolean; welcomeMessage?: string; rulesText?: string; category?: string }) => void;
   getCommunitySettings: (serverId: string) => void;
+  setServerMotd: (serverId: string, motd: string | null) => void;
+  getServerMotd: (serverId: string) => void;
   discoverServers: (category?: string) => void;
   acceptRules: (serverId: string) => void;
   setAnnouncementChannel: (serverId: string, channel: string, isAnnouncement: boolean) => void;
@@ -250,6 +255,7 @@ export const useChatStore = create<ChatState>((set, get) => ({
   invites: EMPTY_INVITES,
   serverEvents: EMPTY_EVENTS,
   communitySettings: EMPTY_COMMUNITY,
+  serverMotds: EMPTY_MOTDS,
   discoverableServers: EMPTY_DISCOVER,
   templates: EMPTY_TEMPLATES,
   webhooks: EMPTY_WEBHOOKS,
@@ -315,6 +321,7 @@ export const useChatStore = create<ChatState>((set, get) => ({
       invites: EMPTY_INVITES,
       serverEvents: EMPTY_EVENTS,
       communitySettings: EMPTY_COMMUNITY,
+      serverMotds: EMPTY_MOTDS,
       discoverableServers: EMPTY_DISCOVER,
       templates: EMPTY_TEMPLATES,
       webhooks: EMPTY_WEBHOOKS,
@@ -336,6 +343,7 @@ export const useChatStore = create<ChatState>((set, get) => ({
           timestamp: event.timestamp,
           reply_to: event.reply_to,
           attachments: event.attachments,
+          kind: event.kind,
         };
         set((s) => {
           // Increment unread count for messages from others
@@ -462,6 +470,18 @@ export const useChatStore = create<ChatState>((set, get) => ({
         break;
       }
 
+      case 'typing_stop': {
+        const key = channelKey(event.server_id, event.channel);
+        set((s) => {
+          const current = s.typingUsers[key] || [];
+          if (!current.includes(event.nickname)) return s;
+          return {
+            typingUsers: { ...s.typingUsers, [key]: current.filter((n) => n !== event.nickname) },
+          };
+        });
+        break;
+      }
+
       case 'join': {
         const key = channelKey(event.server_id, event.channel);
         const memberInfo: MemberInfo = { nickname: event.nickname, avatar_url: event.avatar_url };
@@ -892,6 +912,46 @@ export const useChatStore = create<ChatState>((set, get) => ({
         });
         break;
       }
+      case 'privacy_update': {
+        const e = event as Extract<ServerEvent, { type: 'privacy_update' }>;
+        const channels = get().channels[e.server_id] ?? [];
+        set({
+          channels: {
+            ...get().channels,
+            [e.server_id]: channels.map(ch =>
+              ch.name === e.channel ? { ...ch, is_private: e.is_private } : ch
+            ),
+          },
+        });
+        break;
+      }
+      case 'channel_kick': {
+        const e = event as Extract<ServerEvent, { type: 'channel_kick' }>;
+        const key = channelKey(e.server_id, e.channel);
+        set((s) => ({
+          members: {
+            ...s.members,
+            [key]: (s.members[key] || []).filter((m) => m.nickname !== e.nickname),
+          },
+        }));
+        break;
+      }
+      case 'member_prefix_update': {
+        const e = event as Extract<ServerEvent, { type: 'member_prefix_update' }>;
+        const key = channelKey(e.server_id, e.channel);
+        set((s) => ({
+          members: {
+            ...s.members,
+            [key]: (s.members[key] || []).map((m) => {
+              if (m.nickname !== e.nickname) return m;
+              const kept = (m.prefixes ?? '').split('').filter((p) => !e.removed.includes(p));
+              const prefixes = '~&@%+'.split('').filter((p) => kept.includes(p) || e.added.includes(p));
+              return { ...m, prefixes: prefixes.join('') };
+            }),
+          },
+        }));
+        break;
+      }
       case 'bulk_message_delete': {
         const e = event as Extract<ServerEvent, { type: 'bulk_message_delete' }>;
         const key = channelKey(e.server_id, e.channel);
@@ -992,6 +1052,9 @@ export const useChatStore = create<ChatState>((set, get) => ({
       case 'server_community':
         set({ communitySettings: { ...get().communitySettings, [event.community.server_id]: event.community } });
         break;
+      case 'server_motd':
+        set({ serverMotds: { ...get().serverMotds, [event.server_id]: event.motd } });
+        break;
       case 'discover_servers':
         set({ discoverableServers: event.servers });
         break;
@@ -1075,7 +1138,7 @@ export const useChatStore = create<ChatState>((set, get) => ({
     }
   },
 
-  sendMessage: (serverId, channel, content, attachments) => {
+  sendMessage: (serverId, channel, content, attachments, kind) => {
     const { ws, nickname, replyingTo } = get();
     if (!ws || !nickname) return;
 
@@ -1089,6 +1152,7 @@ export const useChatStore = create<ChatState>((set, get) => ({
       timestamp: new Date().toISOString(),
       reply_to: replyingTo,
       attachments: attachments || null,
+      kind,
     };
     set((s) => ({
       messages: {
@@ -1105,6 +1169,7 @@ export const useChatStore = create<ChatState>((set, get) => ({
       content,
       reply_to: replyingTo?.id,
       attachment_ids: attachments?.map((a) => a.id),
+      kind,
     });
   },
 
@@ -1431,6 +1496,12 @@ export const useChatStore = create<ChatState>((set, get) => ({
   getCommunitySettings: (serverId) => {
     get().ws?.send({ type: 'get_community_settings', server_id: serverId });
   },
+  setServerMotd: (serverId, motd) => {
+    get().ws?.send({ type: 'set_server_motd', server_id: serverId, motd });
+  },
+  getServerMotd: (serverId) => {
+    get().ws?.send({ type: 'get_server_motd', server_id: serverId });
+  },
   discoverServers: (category) => {
     get().ws?.send({ type: 'discover_servers', category });
   },